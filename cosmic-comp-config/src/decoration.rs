@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DecorationMode {
+    /// The compositor draws the titlebar
+    ServerSide,
+    /// The client decorates itself
+    ClientSide,
+}
+
+/// Forces the decoration mode of matching windows, whatever the client requests
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DecorationRule {
+    /// Regular expression the whole app id has to match, matches any window if unset
+    pub app_id: Option<String>,
+    /// Only matches tiled (or floating) windows, if set
+    pub tiled: Option<bool>,
+    pub mode: DecorationMode,
+}
@@ -27,12 +27,17 @@ pub struct InputConfig {
     pub scroll_config: Option<ScrollConfig>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub tap_config: Option<TapConfig>,
+    /// Name of the output absolute positions (touch, tablets) are mapped to
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub map_to_output: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct AccelConfig {
-    #[serde(with = "AccelProfileDef")]
+    /// Acceleration profile, falls back to the profile of the default config if unset
+    #[serde(with = "AccelProfileDef", default)]
     pub profile: Option<AccelProfile>,
+    #[serde(default)]
     pub speed: f64,
 }
 
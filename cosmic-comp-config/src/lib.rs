@@ -2,7 +2,9 @@
 
 use serde::{Deserialize, Serialize};
 
+pub mod decoration;
 pub mod input;
+pub mod night_light;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct XkbConfig {
@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum NightLightSchedule {
+    /// Active whenever night light is enabled
+    Manual,
+    /// Active between sunset and sunrise at the given location
+    SunsetToSunrise { latitude: f64, longitude: f64 },
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct NightLightConfig {
+    pub enabled: bool,
+    /// Color temperature in Kelvin while night light is active
+    pub temperature: u32,
+    pub schedule: NightLightSchedule,
+}
+
+impl Default for NightLightConfig {
+    fn default() -> Self {
+        NightLightConfig {
+            enabled: false,
+            temperature: 4000,
+            schedule: NightLightSchedule::Manual,
+        }
+    }
+}
@@ -2,4 +2,10 @@ grow-window = Grow
 shrink-window = Shrink
 swap-windows = Swap Windows
 stack-windows = Stack Windows
-unknown-keybinding = <unset>
\ No newline at end of file
+unknown-keybinding = <unset>
+window-menu-maximize = Maximize
+window-menu-unmaximize = Unmaximize
+window-menu-shade = Shade
+window-menu-unshade = Unshade
+window-menu-move-to-workspace = Move to Workspace {$num}
+window-menu-close = Close
\ No newline at end of file
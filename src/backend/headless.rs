@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    backend::render,
+    config::{OutputConfig, VirtualOutputConfig},
+    shell::Shell,
+    state::{BackendData, Common},
+    utils::prelude::*,
+    wayland::protocols::screencopy::{BufferParams, Session as ScreencopySession},
+};
+use anyhow::{Context, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        drm::DrmNode,
+        egl::{EGLContext, EGLDevice, EGLDisplay},
+        renderer::{
+            damage::{OutputDamageTracker, RenderOutputResult},
+            gles::GlesRenderbuffer,
+            glow::GlowRenderer,
+            ImportDma, ImportEgl, Offscreen,
+        },
+    },
+    input::Seat,
+    output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+    reexports::{
+        calloop::{
+            ping,
+            timer::{TimeoutAction, Timer},
+            EventLoop, LoopHandle, RegistrationToken,
+        },
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::DisplayHandle,
+    },
+    utils::{Size, Transform},
+    wayland::dmabuf::DmabufFeedbackBuilder,
+};
+use std::{cell::RefCell, time::Duration};
+use tracing::{error, info, warn};
+
+#[cfg(feature = "debug")]
+use crate::state::Fps;
+
+use super::render::{init_shaders, CursorMode};
+
+#[derive(Debug)]
+pub struct HeadlessState {
+    _egl: EGLDisplay,
+    pub renderer: GlowRenderer,
+    surfaces: Vec<Surface>,
+    /// Used to name new outputs, so names are never reused
+    next_id: usize,
+}
+
+/// A virtual output without any display, rendered offscreen at the pace of its refresh rate
+#[derive(Debug)]
+pub struct Surface {
+    output: Output,
+    buffer: Option<GlesRenderbuffer>,
+    damage_tracker: OutputDamageTracker,
+    screencopy: Vec<(ScreencopySession, BufferParams)>,
+    render: ping::Ping,
+    token: RegistrationToken,
+    dirty: bool,
+    pending: bool,
+    #[cfg(feature = "debug")]
+    fps: Fps,
+}
+
+impl HeadlessState {
+    pub fn add_output(
+        &mut self,
+        handle: &LoopHandle<'static, State>,
+        config: VirtualOutputConfig,
+        position: (i32, i32),
+    ) -> Result<Output> {
+        let name = format!("HEADLESS-{}", self.next_id);
+        self.next_id += 1;
+        let props = PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "COSMIC".to_string(),
+            model: name.clone(),
+        };
+        let mode = Mode {
+            size: config.size.into(),
+            refresh: config.refresh as i32,
+        };
+        let output = Output::new(name, props);
+        output.add_mode(mode);
+        output.set_preferred(mode);
+        output.change_current_state(
+            Some(mode),
+            Some(Transform::Normal),
+            Some(Scale::Fractional(config.scale)),
+            Some(position.into()),
+        );
+        output.user_data().insert_if_missing(|| {
+            RefCell::new(OutputConfig {
+                mode: (config.size, Some(config.refresh)),
+                scale: config.scale,
+                position,
+                ..Default::default()
+            })
+        });
+
+        let output_ref = output.clone();
+        let (ping, source) =
+            ping::make_ping().with_context(|| "Failed to create output event loop source")?;
+        let token = handle
+            .insert_source(source, move |_, _, state| {
+                let headless_state = state.backend.headless();
+                if let Some(surface) = headless_state
+                    .surfaces
+                    .iter_mut()
+                    .find(|s| s.output == output_ref)
+                {
+                    if let Err(err) =
+                        surface.render_output(&mut headless_state.renderer, &mut state.common)
+                    {
+                        error!(?err, "Error rendering.");
+                    }
+                    surface.dirty = false;
+                    surface.pending = true;
+                    surface.schedule_frame_done(&state.common.event_loop_handle);
+                }
+            })
+            .with_context(|| "Failed to add output to event loop")?;
+
+        self.surfaces.push(Surface {
+            output: output.clone(),
+            buffer: None,
+            damage_tracker: OutputDamageTracker::from_output(&output),
+            screencopy: Vec::new(),
+            render: ping.clone(),
+            token,
+            dirty: false,
+            pending: true,
+            #[cfg(feature = "debug")]
+            fps: Fps::new(&mut self.renderer),
+        });
+
+        // schedule first render
+        ping.ping();
+        Ok(output)
+    }
+
+    /// Removes the surface of the output, returns `false` if it isn't a virtual output
+    pub fn remove_output(&mut self, handle: &LoopHandle<'static, State>, output: &Output) -> bool {
+        let Some(idx) = self.surfaces.iter().position(|s| s.output == *output) else {
+            return false;
+        };
+        let surface = self.surfaces.remove(idx);
+        handle.remove(surface.token);
+        true
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.surfaces.iter().map(|s| &s.output)
+    }
+
+    pub fn schedule_render(
+        &mut self,
+        output: &Output,
+        screencopy: Option<Vec<(ScreencopySession, BufferParams)>>,
+    ) {
+        if let Some(surface) = self.surfaces.iter_mut().find(|s| s.output == *output) {
+            surface.dirty = true;
+            if let Some(sessions) = screencopy {
+                surface.screencopy.extend(sessions);
+            }
+            if !surface.pending {
+                surface.render.ping();
+            }
+        }
+    }
+
+    pub fn apply_config_for_output(
+        &mut self,
+        output: &Output,
+        seats: impl Iterator<Item = Seat<State>>,
+        shell: &mut Shell,
+        test_only: bool,
+    ) -> Result<(), anyhow::Error> {
+        let Some(surface) = self.surfaces.iter_mut().find(|s| s.output == *output) else {
+            return Err(anyhow::anyhow!("Unknown output"));
+        };
+        let config = output
+            .user_data()
+            .get::<RefCell<OutputConfig>>()
+            .unwrap()
+            .borrow();
+        if test_only {
+            return Ok(());
+        }
+
+        if !config.enabled {
+            shell.remove_output(output, seats);
+            surface.dirty = false;
+            return Ok(());
+        }
+
+        // virtual outputs support any mode
+        let mode = Mode {
+            size: config.mode_size(),
+            refresh: config.mode_refresh() as i32,
+        };
+        if output.current_mode() != Some(mode) {
+            if let Some(current) = output.current_mode() {
+                output.delete_mode(current);
+            }
+            output.add_mode(mode);
+            output.set_preferred(mode);
+            surface.buffer = None;
+        }
+        shell.add_output(output);
+        surface.dirty = true;
+        if !surface.pending {
+            surface.render.ping();
+        }
+        Ok(())
+    }
+}
+
+impl Surface {
+    pub fn render_output(&mut self, renderer: &mut GlowRenderer, state: &mut Common) -> Result<()> {
+        let size = self
+            .output
+            .current_mode()
+            .with_context(|| "Output has no mode")?
+            .size;
+        let (buffer, age) = match self.buffer.as_ref() {
+            // the content of the last frame stays in the buffer
+            Some(buffer) => (buffer.clone(), 1),
+            None => {
+                let buffer = Offscreen::<GlesRenderbuffer>::create_buffer(
+                    renderer,
+                    Fourcc::Abgr8888,
+                    Size::from((size.w, size.h)),
+                )
+                .with_context(|| "Failed to allocate buffer")?;
+                self.buffer = Some(buffer.clone());
+                (buffer, 0)
+            }
+        };
+
+        match render::render_output::<_, _, GlesRenderbuffer, _>(
+            None,
+            renderer,
+            buffer.clone(),
+            &mut self.damage_tracker,
+            age,
+            state,
+            &self.output,
+            CursorMode::All,
+            if !self.screencopy.is_empty() {
+                Some((buffer, &self.screencopy))
+            } else {
+                None
+            },
+            #[cfg(not(feature = "debug"))]
+            None,
+            #[cfg(feature = "debug")]
+            Some(&mut self.fps),
+            None,
+        ) {
+            Ok(RenderOutputResult { damage, states, .. }) => {
+                self.screencopy.clear();
+                #[cfg(feature = "debug")]
+                self.fps.displayed();
+                state.send_frames(&self.output, &states, |_| None);
+                if damage.is_some() {
+                    let mut output_presentation_feedback =
+                        state.take_presentation_feedback(&self.output, &states);
+                    output_presentation_feedback.presented(
+                        state.clock.now(),
+                        self.frame_duration(),
+                        0,
+                        wp_presentation_feedback::Kind::empty(),
+                    )
+                }
+            }
+            Err(err) => {
+                for (session, params) in self.screencopy.drain(..) {
+                    state.still_pending(session, params)
+                }
+                self.buffer = None;
+                anyhow::bail!("Rendering failed: {}", err);
+            }
+        };
+
+        Ok(())
+    }
+
+    fn frame_duration(&self) -> Duration {
+        self.output
+            .current_mode()
+            .filter(|mode| mode.refresh > 0)
+            .map(|mode| Duration::from_secs_f64(1_000.0 / mode.refresh as f64))
+            .unwrap_or(Duration::from_millis(16))
+    }
+
+    /// There is no vblank to wait for, so the next frame is allowed after the refresh interval
+    fn schedule_frame_done(&self, handle: &LoopHandle<'static, State>) {
+        let output = self.output.clone();
+        if let Err(err) = handle.insert_source(
+            Timer::from_duration(self.frame_duration()),
+            move |_, _, state| {
+                if let Some(surface) = state
+                    .backend
+                    .headless()
+                    .surfaces
+                    .iter_mut()
+                    .find(|s| s.output == output)
+                {
+                    if surface.dirty {
+                        surface.render.ping();
+                    } else {
+                        surface.pending = false;
+                    }
+                }
+                TimeoutAction::Drop
+            },
+        ) {
+            warn!(?err, "Failed to schedule frame for headless output.");
+        }
+    }
+}
+
+pub fn init_backend(
+    dh: &DisplayHandle,
+    _event_loop: &mut EventLoop<State>,
+    state: &mut State,
+) -> Result<()> {
+    // any device will do, but prefer actual gpus over software rendering
+    let devices = EGLDevice::enumerate()
+        .with_context(|| "Failed to enumerate EGL devices")?
+        .collect::<Vec<_>>();
+    let device = devices
+        .iter()
+        .find(|device| device.try_get_render_node().ok().flatten().is_some())
+        .or_else(|| devices.first())
+        .cloned()
+        .with_context(|| "Failed to find any EGLDevice")?;
+    let render_node = device.try_get_render_node().ok().flatten();
+    // Initialize EGL
+    let egl = EGLDisplay::new(device).with_context(|| "Failed to create EGL display")?;
+    // Create the OpenGL context
+    let context = EGLContext::new(&egl).with_context(|| "Failed to create EGL context")?;
+    // Create a renderer
+    let mut renderer =
+        unsafe { GlowRenderer::new(context) }.with_context(|| "Failed to initialize renderer")?;
+
+    init_shaders(&mut renderer).expect("Failed to initialize renderer");
+    init_egl_client_side(dh, state, render_node.as_ref(), &mut renderer)?;
+
+    state.backend = BackendData::Headless(HeadlessState {
+        _egl: egl,
+        renderer,
+        surfaces: Vec::new(),
+        next_id: 0,
+    });
+
+    // virtual outputs are placed next to each other
+    let mut outputs = Vec::new();
+    let mut x = 0;
+    for config in state.common.config.static_conf.headless.outputs.clone() {
+        let output = state
+            .backend
+            .headless()
+            .add_output(&state.common.event_loop_handle, config, (x, 0))
+            .with_context(|| "Failed to create wl_output")?;
+        x += output.geometry().size.w;
+        outputs.push(output);
+    }
+    state
+        .common
+        .output_configuration_state
+        .add_heads(outputs.iter());
+    for output in &outputs {
+        state.common.shell.add_output(output);
+    }
+    let seats = state.common.seats().cloned().collect::<Vec<_>>();
+    state.common.config.read_outputs(
+        &mut state.common.output_configuration_state,
+        &mut state.backend,
+        &mut state.common.shell,
+        seats.iter().cloned(),
+        &state.common.event_loop_handle,
+    );
+    state.launch_xwayland(render_node);
+
+    Ok(())
+}
+
+fn init_egl_client_side<R>(
+    dh: &DisplayHandle,
+    state: &mut State,
+    render_node: Option<&DrmNode>,
+    renderer: &mut R,
+) -> Result<()>
+where
+    R: ImportEgl + ImportDma,
+{
+    if let Err(err) = renderer.bind_wl_display(dh) {
+        warn!(
+            ?err,
+            "Unable to initialize bind display to EGL. Some older clients may not work correctly."
+        )
+    }
+
+    let dmabuf_formats = renderer.dmabuf_formats().collect::<Vec<_>>();
+    match render_node {
+        Some(node) => {
+            let default_feedback = DmabufFeedbackBuilder::new(node.dev_id(), dmabuf_formats)
+                .build()
+                .unwrap();
+            state
+                .common
+                .dmabuf_state
+                .create_global_with_default_feedback::<State>(dh, &default_feedback);
+        }
+        None => {
+            warn!("Failed to query render node, dmabuf protocol will only advertise v3");
+            state
+                .common
+                .dmabuf_state
+                .create_global::<State>(dh, dmabuf_formats);
+        }
+    }
+
+    info!("EGL hardware-acceleration enabled.");
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::config::ColorFilter;
+
+pub type Matrix = [f64; 9];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+/// Conversion between RGB and the LMS color space of the cones (Viénot et al.)
+#[rustfmt::skip]
+const RGB_TO_LMS: Matrix = [
+    17.8824, 43.5161, 4.11935,
+    3.45565, 27.1554, 3.86714,
+    0.0299566, 0.184309, 1.46709,
+];
+#[rustfmt::skip]
+const LMS_TO_RGB: Matrix = [
+    0.0809444479, -0.130504409, 0.116721066,
+    -0.0102485335, 0.0540193266, -0.113614708,
+    -0.000365296938, -0.00412161469, 0.693511405,
+];
+
+/// Shifts the colors lost to a deficiency towards ones, that are still distinguishable
+const ERROR_SHIFT: Matrix = [0.0, 0.0, 0.0, 0.7, 1.0, 0.0, 0.7, 0.0, 1.0];
+
+/// Color transformation matrix (row-major) applied by the crtc for the given filter,
+/// or `None` if the filter needs none.
+///
+/// Inversion is done with the gamma tables instead.
+pub fn color_matrix(filter: ColorFilter) -> Option<Matrix> {
+    // how the missing cones are reconstructed from the remaining ones
+    let simulation: Matrix = match filter {
+        ColorFilter::None | ColorFilter::Invert => return None,
+        ColorFilter::Grayscale => {
+            // Rec. 709 luminance
+            let luma = [0.2126, 0.7152, 0.0722];
+            return Some([
+                luma[0], luma[1], luma[2], luma[0], luma[1], luma[2], luma[0], luma[1], luma[2],
+            ]);
+        }
+        ColorFilter::Protanopia => [0.0, 2.02344, -2.52581, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        ColorFilter::Deuteranopia => [1.0, 0.0, 0.0, 0.494207, 0.0, 1.24827, 0.0, 0.0, 1.0],
+        ColorFilter::Tritanopia => [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -0.395913, 0.801109, 0.0],
+    };
+
+    // daltonize: add the error between the original and the simulated colors back in a visible way
+    let simulated = mul(&LMS_TO_RGB, &mul(&simulation, &RGB_TO_LMS));
+    let error = sub(&IDENTITY, &simulated);
+    Some(add(&IDENTITY, &mul(&ERROR_SHIFT, &error)))
+}
+
+/// Color transformation matrix of a crtc, applying the filter before the gamut mapping
+pub fn combine(filter: Option<Matrix>, gamut: Option<Matrix>) -> Option<Matrix> {
+    match (filter, gamut) {
+        (Some(filter), Some(gamut)) => Some(mul(&gamut, &filter)),
+        (filter, gamut) => filter.or(gamut),
+    }
+}
+
+pub fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| {
+        let (row, col) = (i / 3, i % 3);
+        (0..3).map(|k| a[row * 3 + k] * b[k * 3 + col]).sum()
+    })
+}
+
+fn add(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| a[i] + b[i])
+}
+
+fn sub(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+/// Inverse of a matrix, if it is not singular
+pub fn invert(m: &Matrix) -> Option<Matrix> {
+    // cofactors, transposed
+    let adjugate = [
+        m[4] * m[8] - m[5] * m[7],
+        m[2] * m[7] - m[1] * m[8],
+        m[1] * m[5] - m[2] * m[4],
+        m[5] * m[6] - m[3] * m[8],
+        m[0] * m[8] - m[2] * m[6],
+        m[2] * m[3] - m[0] * m[5],
+        m[3] * m[7] - m[4] * m[6],
+        m[1] * m[6] - m[0] * m[7],
+        m[0] * m[4] - m[1] * m[3],
+    ];
+    let determinant = m[0] * adjugate[0] + m[1] * adjugate[3] + m[2] * adjugate[6];
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(adjugate.map(|x| x / determinant))
+}
@@ -1,15 +1,25 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use anyhow::{anyhow, Result};
-use smithay::reexports::drm::control::{
-    atomic::AtomicModeReq,
-    connector::{self, State as ConnectorState},
-    crtc,
-    dumbbuffer::DumbBuffer,
-    property, AtomicCommitFlags, Device as ControlDevice, Mode, ModeFlags, PlaneType,
-    ResourceHandle,
+use smithay::{
+    backend::drm::DrmDeviceFd,
+    reexports::drm::control::{
+        atomic::AtomicModeReq,
+        connector::{self, State as ConnectorState},
+        crtc,
+        dumbbuffer::DumbBuffer,
+        property, AtomicCommitFlags, Device as ControlDevice, Mode, ModeFlags, PlaneType,
+        ResourceHandle,
+    },
 };
-use std::{collections::HashMap, ops::Range};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    os::unix::io::{AsFd, AsRawFd},
+};
+
+use super::gamma::GammaRamp;
+use crate::config::Modeline;
 
 pub fn display_configuration(
     device: &mut impl ControlDevice,
@@ -159,6 +169,7 @@ pub fn interface_name(device: &impl ControlDevice, connector: connector::Handle)
 pub struct EdidInfo {
     pub model: String,
     pub manufacturer: String,
+    pub serial: Option<String>,
 }
 
 pub fn edid_info(device: &impl ControlDevice, connector: connector::Handle) -> Result<EdidInfo> {
@@ -168,6 +179,7 @@ pub fn edid_info(device: &impl ControlDevice, connector: connector::Handle) -> R
     let edid_info = device.get_property(edid_prop)?;
     let mut manufacturer = "Unknown".into();
     let mut model = "Unknown".into();
+    let mut serial = None;
     let props = device.get_properties(connector)?;
     let (ids, vals) = props.as_props_and_values();
     for (&id, &val) in ids.iter().zip(vals.iter()) {
@@ -191,6 +203,18 @@ pub fn edid_info(device: &impl ControlDevice, connector: connector::Handle) -> R
                     } else {
                         format!("{}", edid.product.product_code)
                     };
+                    serial = edid
+                        .descriptors
+                        .0
+                        .iter()
+                        .find_map(|x| match x {
+                            MonitorDescriptor::SerialNumber(serial) => Some(serial.clone()),
+                            _ => None,
+                        })
+                        .or_else(|| {
+                            (edid.product.serial_number != 0)
+                                .then(|| edid.product.serial_number.to_string())
+                        });
                 }
             }
             break;
@@ -200,6 +224,7 @@ pub fn edid_info(device: &impl ControlDevice, connector: connector::Handle) -> R
     Ok(EdidInfo {
         model,
         manufacturer,
+        serial,
     })
 }
 
@@ -327,6 +352,137 @@ pub fn calculate_refresh_rate(mode: Mode) -> u32 {
     refresh as u32
 }
 
+const DRM_MODE_FLAG_PHSYNC: u32 = 1 << 0;
+const DRM_MODE_FLAG_NHSYNC: u32 = 1 << 1;
+const DRM_MODE_FLAG_PVSYNC: u32 = 1 << 2;
+const DRM_MODE_FLAG_NVSYNC: u32 = 1 << 3;
+const DRM_MODE_TYPE_USERDEF: u32 = 1 << 5;
+
+/// Builds a mode from custom timings, checking that they are sane
+pub fn mode_from_modeline(modeline: &Modeline) -> Result<Mode> {
+    let Modeline {
+        clock,
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+        hsync_positive,
+        vsync_positive,
+    } = *modeline;
+
+    if clock == 0
+        || hdisplay == 0
+        || vdisplay == 0
+        || !(hdisplay <= hsync_start && hsync_start < hsync_end && hsync_end <= htotal)
+        || !(vdisplay <= vsync_start && vsync_start < vsync_end && vsync_end <= vtotal)
+    {
+        return Err(anyhow!("Invalid modeline: {:?}", modeline));
+    }
+
+    let mut name = [0; 32];
+    for (dst, src) in name
+        .iter_mut()
+        .zip(format!("{}x{}", hdisplay, vdisplay).bytes())
+    {
+        *dst = src as _;
+    }
+
+    let info = drm_ffi::drm_mode_modeinfo {
+        clock,
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        hskew: 0,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+        vscan: 0,
+        vrefresh: (modeline.refresh() + 500) / 1000,
+        flags: if hsync_positive {
+            DRM_MODE_FLAG_PHSYNC
+        } else {
+            DRM_MODE_FLAG_NHSYNC
+        } | if vsync_positive {
+            DRM_MODE_FLAG_PVSYNC
+        } else {
+            DRM_MODE_FLAG_NVSYNC
+        },
+        type_: DRM_MODE_TYPE_USERDEF,
+        name,
+    };
+    Ok(Mode::from(info))
+}
+
+/// Generates VESA CVT timings for a mode not advertised by the display,
+/// following the same algorithm as the `cvt` utility.
+pub fn cvt_modeline(width: u16, height: u16, refresh: u32) -> Modeline {
+    const CELL_GRAN: f64 = 8.0;
+    const MIN_V_PORCH: f64 = 3.0;
+    const MIN_V_BPORCH: f64 = 6.0;
+    const MIN_VSYNC_BP: f64 = 550.0;
+    const HSYNC_PERCENTAGE: f64 = 8.0;
+    const C_PRIME: f64 = 30.0;
+    const M_PRIME: f64 = 300.0;
+    const CLOCK_STEP: f64 = 250.0;
+
+    let vfield_rate = refresh as f64 / 1000.0;
+    let hdisplay = width as f64 - (width as f64 % CELL_GRAN);
+    let vdisplay = height as f64;
+
+    // the vsync width encodes the aspect ratio
+    let (w, h) = (width as u32, height as u32);
+    let vsync = if w * 3 == h * 4 {
+        4.0
+    } else if w * 9 == h * 16 {
+        5.0
+    } else if w * 10 == h * 16 {
+        6.0
+    } else if w * 4 == h * 5 || w * 9 == h * 15 {
+        7.0
+    } else {
+        10.0
+    };
+
+    // horizontal period estimate in microseconds
+    let hperiod = (1_000_000.0 / vfield_rate - MIN_VSYNC_BP) / (vdisplay + MIN_V_PORCH);
+    let vsync_bp = ((MIN_VSYNC_BP / hperiod).floor() + 1.0).max(vsync + MIN_V_BPORCH);
+    let vtotal = vdisplay + vsync_bp + MIN_V_PORCH;
+
+    let hblank_percentage = (C_PRIME - M_PRIME * hperiod / 1000.0).max(20.0);
+    let mut hblank = hdisplay * hblank_percentage / (100.0 - hblank_percentage);
+    hblank -= hblank % (2.0 * CELL_GRAN);
+    let htotal = hdisplay + hblank;
+
+    let hsync_end = hdisplay + hblank / 2.0;
+    let mut hsync = htotal * HSYNC_PERCENTAGE / 100.0;
+    hsync -= hsync % CELL_GRAN;
+    let hsync_start = hsync_end - hsync;
+
+    let mut clock = htotal * 1000.0 / hperiod;
+    clock -= clock % CLOCK_STEP;
+
+    let vsync_start = vdisplay + MIN_V_PORCH;
+    Modeline {
+        clock: clock as u32,
+        hdisplay: hdisplay as u16,
+        hsync_start: hsync_start as u16,
+        hsync_end: hsync_end as u16,
+        htotal: htotal as u16,
+        vdisplay: vdisplay as u16,
+        vsync_start: vsync_start as u16,
+        vsync_end: (vsync_start + vsync) as u16,
+        vtotal: vtotal as u16,
+        hsync_positive: false,
+        vsync_positive: true,
+    }
+}
+
 pub fn supports_vrr(dev: &impl ControlDevice, conn: connector::Handle) -> Result<bool> {
     get_property_val(dev, conn, "vrr_capable").map(|(val_type, val)| {
         match val_type.convert_value(val) {
@@ -385,6 +541,144 @@ pub fn get_max_bpc(
     Ok(Some((value, range)))
 }
 
+pub fn set_gamma(dev: &impl ControlDevice, crtc: crtc::Handle, ramp: &GammaRamp) -> Result<()> {
+    dev.set_gamma(crtc, &ramp.red, &ramp.green, &ramp.blue)
+        .map_err(Into::<anyhow::Error>::into)
+}
+
+/// Size of the degamma table of a crtc, if it supports one
+pub fn degamma_size(dev: &impl ControlDevice, crtc: crtc::Handle) -> Option<usize> {
+    let (val_type, val) = get_property_val(dev, crtc, "DEGAMMA_LUT_SIZE").ok()?;
+    match val_type.convert_value(val) {
+        property::Value::UnsignedRange(size) if size > 0 => Some(size as usize),
+        _ => None,
+    }
+}
+
+/// Sets the degamma table of a crtc, applied before the color transformation matrix,
+/// or resets it if `None`.
+///
+/// `blob` holds the property blob set previously, which is destroyed once replaced.
+pub fn set_degamma(
+    dev: &impl ControlDevice,
+    crtc: crtc::Handle,
+    ramp: Option<&GammaRamp>,
+    blob: &mut Option<u64>,
+) -> Result<()> {
+    let prop = match get_prop(dev, crtc, "DEGAMMA_LUT") {
+        Ok(prop) => prop,
+        Err(_) if ramp.is_none() => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let new_blob = match ramp {
+        Some(ramp) => {
+            // struct drm_color_lut
+            let mut data = ramp
+                .red
+                .iter()
+                .zip(ramp.green.iter())
+                .zip(ramp.blue.iter())
+                .flat_map(|((red, green), blue)| [*red, *green, *blue, 0])
+                .flat_map(u16::to_ne_bytes)
+                .collect::<Vec<u8>>();
+            let blob = drm_ffi::mode::create_property_blob(dev.as_fd().as_raw_fd(), &mut data)?;
+            Some(blob.blob_id.into())
+        }
+        None => None,
+    };
+    replace_blob(dev, crtc, prop, new_blob, blob)
+}
+
+/// Sets the color transformation matrix (row-major) of a crtc, or resets it if `None`.
+///
+/// `blob` holds the property blob set previously, which is destroyed once replaced.
+pub fn set_ctm(
+    dev: &impl ControlDevice,
+    crtc: crtc::Handle,
+    matrix: Option<[f64; 9]>,
+    blob: &mut Option<u64>,
+) -> Result<()> {
+    let prop = match get_prop(dev, crtc, "CTM") {
+        Ok(prop) => prop,
+        // nothing to reset
+        Err(_) if matrix.is_none() => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let new_blob = match matrix {
+        Some(matrix) => {
+            let ctm = drm_ffi::drm_color_ctm {
+                // S31.32 sign-magnitude fixed point
+                matrix: matrix.map(|x| {
+                    let magnitude = (x.abs() * (1u64 << 32) as f64) as u64;
+                    if x < 0.0 {
+                        magnitude | (1 << 63)
+                    } else {
+                        magnitude
+                    }
+                }),
+            };
+            match dev.create_property_blob(&ctm)? {
+                property::Value::Blob(id) => Some(id),
+                value => return Err(anyhow!("CTM blob has unexpected value {:?}", value)),
+            }
+        }
+        None => None,
+    };
+    replace_blob(dev, crtc, prop, new_blob, blob)
+}
+
+/// Property blobs of the degamma table and color transformation matrix set on a crtc,
+/// destroyed once dropped
+#[derive(Debug)]
+pub struct ColorBlobs {
+    fd: DrmDeviceFd,
+    pub degamma: Option<u64>,
+    pub ctm: Option<u64>,
+}
+
+impl ColorBlobs {
+    pub fn new(fd: DrmDeviceFd) -> ColorBlobs {
+        ColorBlobs {
+            fd,
+            degamma: None,
+            ctm: None,
+        }
+    }
+}
+
+impl Drop for ColorBlobs {
+    fn drop(&mut self) {
+        for blob in [self.degamma.take(), self.ctm.take()].into_iter().flatten() {
+            let _ = self.fd.destroy_property_blob(blob);
+        }
+    }
+}
+
+/// Sets a blob property of a crtc, destroying the blob it replaces,
+/// or the new one if it could not be set.
+fn replace_blob(
+    dev: &impl ControlDevice,
+    crtc: crtc::Handle,
+    prop: property::Handle,
+    new_blob: Option<u64>,
+    blob: &mut Option<u64>,
+) -> Result<()> {
+    if let Err(err) = dev.set_property(
+        crtc,
+        prop,
+        property::Value::Blob(new_blob.unwrap_or(0)).into(),
+    ) {
+        if let Some(new_blob) = new_blob {
+            let _ = dev.destroy_property_blob(new_blob);
+        }
+        return Err(err.into());
+    }
+    if let Some(old_blob) = std::mem::replace(blob, new_blob) {
+        let _ = dev.destroy_property_blob(old_blob);
+    }
+    Ok(())
+}
+
 pub fn set_max_bpc(dev: &impl ControlDevice, conn: connector::Handle, bpc: u32) -> Result<u32> {
     let (_, range) =
         get_max_bpc(dev, conn)?.ok_or(anyhow!("max bpc does not exist for connector"))?;
@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+
+use super::color_filter::{self, Matrix};
+
+/// Conversion from linear sRGB to the profile connection space (XYZ, D50)
+#[rustfmt::skip]
+const SRGB_TO_XYZ_D50: Matrix = [
+    0.4360747, 0.3850649, 0.1430804,
+    0.2225045, 0.7168786, 0.0606169,
+    0.0139322, 0.0971045, 0.7141733,
+];
+
+/// Per-channel lookup tables as used by the legacy crtc gamma api
+#[derive(Debug, Clone, PartialEq)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    pub fn identity(size: usize) -> GammaRamp {
+        let channel = (0..size)
+            .map(|i| (i as f64 / (size.max(2) - 1) as f64 * u16::MAX as f64).round() as u16)
+            .collect::<Vec<_>>();
+        GammaRamp {
+            red: channel.clone(),
+            green: channel.clone(),
+            blue: channel,
+        }
+    }
+
+    /// Decodes sRGB into linear light, as the degamma table in front of the color matrix
+    pub fn srgb_eotf(size: usize) -> GammaRamp {
+        let channel = (0..size)
+            .map(|i| {
+                let x = i as f64 / (size.max(2) - 1) as f64;
+                let linear = if x <= 0.04045 {
+                    x / 12.92
+                } else {
+                    ((x + 0.055) / 1.055).powf(2.4)
+                };
+                (linear * u16::MAX as f64).round() as u16
+            })
+            .collect::<Vec<_>>();
+        GammaRamp {
+            red: channel.clone(),
+            green: channel.clone(),
+            blue: channel,
+        }
+    }
+
+    /// Reverses the ramps, inverting all colors
+    pub fn inverted(mut self) -> GammaRamp {
+        self.red.reverse();
+        self.green.reverse();
+        self.blue.reverse();
+        self
+    }
+
+    /// Scales each channel by the given factor, e.g. to alter the whitepoint
+    pub fn scaled(&self, (red, green, blue): (f64, f64, f64)) -> GammaRamp {
+        let scale = |channel: &[u16], factor: f64| {
+            channel
+                .iter()
+                .map(|x| (*x as f64 * factor).round() as u16)
+                .collect()
+        };
+        GammaRamp {
+            red: scale(&self.red, red),
+            green: scale(&self.green, green),
+            blue: scale(&self.blue, blue),
+        }
+    }
+}
+
+/// The parts of an ICC profile, that can be applied by a crtc
+#[derive(Debug, Clone)]
+pub struct IccProfile {
+    /// Calibration curves (`vcgt` tag)
+    vcgt: Option<[Vec<f64>; 3]>,
+    /// Primaries (`rXYZ`, `gXYZ`, `bXYZ`) and tone response curves of a matrix/TRC profile
+    colorimetry: Option<(Matrix, [Curve; 3])>,
+}
+
+impl IccProfile {
+    pub fn load(path: impl AsRef<Path>) -> Result<IccProfile> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read ICC profile {}", path.display()))?;
+        IccProfile::parse(&data).with_context(|| format!("Invalid ICC profile {}", path.display()))
+    }
+
+    fn parse(data: &[u8]) -> Result<IccProfile> {
+        if data.get(36..40) != Some(b"acsp") {
+            bail!("Missing ICC signature");
+        }
+
+        let vcgt = find_tag(data, b"vcgt")?
+            .map(|offset| vcgt_curves(data, offset))
+            .transpose()?;
+        let colorimetry = match (
+            find_tag(data, b"rXYZ")?,
+            find_tag(data, b"gXYZ")?,
+            find_tag(data, b"bXYZ")?,
+            find_tag(data, b"rTRC")?,
+            find_tag(data, b"gTRC")?,
+            find_tag(data, b"bTRC")?,
+        ) {
+            (Some(r), Some(g), Some(b), Some(r_trc), Some(g_trc), Some(b_trc)) => {
+                let [r, g, b] = [read_xyz(data, r)?, read_xyz(data, g)?, read_xyz(data, b)?];
+                // the primaries are the columns
+                let primaries = [r[0], g[0], b[0], r[1], g[1], b[1], r[2], g[2], b[2]];
+                let curves = [
+                    Curve::parse(data, r_trc)?,
+                    Curve::parse(data, g_trc)?,
+                    Curve::parse(data, b_trc)?,
+                ];
+                Some((primaries, curves))
+            }
+            _ => None,
+        };
+
+        if vcgt.is_none() && colorimetry.is_none() {
+            bail!("Profile contains neither calibration curves (vcgt) nor primaries");
+        }
+        Ok(IccProfile { vcgt, colorimetry })
+    }
+
+    /// Matrix mapping linear sRGB into linear display colors,
+    /// if the profile describes the primaries of the display.
+    pub fn gamut(&self) -> Option<Matrix> {
+        let (primaries, _) = self.colorimetry.as_ref()?;
+        let to_display = color_filter::invert(primaries)?;
+        Some(color_filter::mul(&to_display, &SRGB_TO_XYZ_D50))
+    }
+
+    /// Gamma ramp of the given size, applying the calibration curves.
+    ///
+    /// If `linear` is set, the input is in linear light (after `gamut`),
+    /// which is encoded with the tone response of the display first.
+    pub fn gamma(&self, size: usize, linear: bool) -> GammaRamp {
+        let channel = |idx: usize| {
+            (0..size)
+                .map(|i| {
+                    let mut x = i as f64 / (size.max(2) - 1) as f64;
+                    if let Some((_, curves)) = self.colorimetry.as_ref().filter(|_| linear) {
+                        x = curves[idx].inverse(x);
+                    }
+                    if let Some(vcgt) = self.vcgt.as_ref() {
+                        x = interpolate(&vcgt[idx], x);
+                    }
+                    (x.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+                })
+                .collect()
+        };
+        GammaRamp {
+            red: channel(0),
+            green: channel(1),
+            blue: channel(2),
+        }
+    }
+}
+
+/// Tone response curve of a color channel, from encoded values to linear light
+#[derive(Debug, Clone)]
+enum Curve {
+    Gamma(f64),
+    Table(Vec<f64>),
+    /// Function type and parameters (g, a, b, c, d, e, f) of a `para` curve
+    Parametric(u16, [f64; 7]),
+}
+
+impl Curve {
+    fn parse(data: &[u8], offset: usize) -> Result<Curve> {
+        match data.get(offset..offset + 4) {
+            Some(b"curv") => {
+                let count = read_u32(data, offset + 8)? as usize;
+                match count {
+                    0 => Ok(Curve::Gamma(1.0)),
+                    // u8Fixed8
+                    1 => Ok(Curve::Gamma(read_u16(data, offset + 12)? as f64 / 256.0)),
+                    _ => (0..count)
+                        .map(|i| Ok(read_u16(data, offset + 12 + i * 2)? as f64 / u16::MAX as f64))
+                        .collect::<Result<Vec<_>>>()
+                        .map(Curve::Table),
+                }
+            }
+            Some(b"para") => {
+                let function = read_u16(data, offset + 8)?;
+                let count = match function {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    x => bail!("Unknown parametric curve type {}", x),
+                };
+                let mut params = [0.0; 7];
+                for (i, param) in params.iter_mut().enumerate().take(count) {
+                    *param = read_s15_fixed16(data, offset + 12 + i * 4)?;
+                }
+                Ok(Curve::Parametric(function, params))
+            }
+            _ => bail!("Unsupported tone response curve"),
+        }
+    }
+
+    fn eval(&self, x: f64) -> f64 {
+        match self {
+            Curve::Gamma(gamma) => x.powf(*gamma),
+            Curve::Table(table) => interpolate(table, x),
+            Curve::Parametric(function, [g, a, b, c, d, e, f]) => match function {
+                0 => x.powf(*g),
+                1 if x >= -b / a => (a * x + b).powf(*g),
+                1 => 0.0,
+                2 if x >= -b / a => (a * x + b).powf(*g) + c,
+                2 => *c,
+                3 if x >= *d => (a * x + b).powf(*g),
+                3 => c * x,
+                _ if x >= *d => (a * x + b).powf(*g) + e,
+                _ => c * x + f,
+            },
+        }
+    }
+
+    /// Encodes linear light, assuming the curve is monotonic
+    fn inverse(&self, y: f64) -> f64 {
+        if let Curve::Gamma(gamma) = self {
+            return y.powf(1.0 / gamma);
+        }
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..24 {
+            let mid = (low + high) / 2.0;
+            if self.eval(mid) < y {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2.0
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("Truncated profile"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("Truncated profile"))
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> Result<f64> {
+    Ok(read_u32(data, offset)? as i32 as f64 / 65536.0)
+}
+
+/// Offset of the tag with the given signature, if the profile contains it
+fn find_tag(data: &[u8], signature: &[u8; 4]) -> Result<Option<usize>> {
+    let tag_count = read_u32(data, 128)? as usize;
+    (0..tag_count)
+        .map(|i| 132 + i * 12)
+        .find(|entry| data.get(*entry..*entry + 4) == Some(signature.as_slice()))
+        .map(|entry| read_u32(data, entry + 4).map(|offset| offset as usize))
+        .transpose()
+}
+
+fn read_xyz(data: &[u8], offset: usize) -> Result<[f64; 3]> {
+    if data.get(offset..offset + 4) != Some(b"XYZ ") {
+        bail!("Malformed XYZ tag");
+    }
+    Ok([
+        read_s15_fixed16(data, offset + 8)?,
+        read_s15_fixed16(data, offset + 12)?,
+        read_s15_fixed16(data, offset + 16)?,
+    ])
+}
+
+/// Parses the `vcgt` tag into normalized curves for red, green and blue
+fn vcgt_curves(data: &[u8], offset: usize) -> Result<[Vec<f64>; 3]> {
+    if data.get(offset..offset + 4) != Some(b"vcgt") {
+        bail!("Malformed vcgt tag");
+    }
+    match read_u32(data, offset + 8)? {
+        // table
+        0 => {
+            let channels = read_u16(data, offset + 12)? as usize;
+            let entries = read_u16(data, offset + 14)? as usize;
+            let entry_size = read_u16(data, offset + 16)? as usize;
+            let data_offset = offset + 18;
+            if entries < 2 {
+                bail!("vcgt table is too small");
+            }
+
+            let read_entry = |idx: usize| -> Result<f64> {
+                let pos = data_offset + idx * entry_size;
+                match entry_size {
+                    1 => data
+                        .get(pos)
+                        .map(|x| *x as f64 / u8::MAX as f64)
+                        .ok_or_else(|| anyhow!("Truncated profile")),
+                    2 => Ok(read_u16(data, pos)? as f64 / u16::MAX as f64),
+                    x => bail!("Unsupported vcgt entry size {}", x),
+                }
+            };
+            let channel = |channel: usize| -> Result<Vec<f64>> {
+                // single channel tables apply to all colors
+                let channel = if channels == 1 { 0 } else { channel };
+                (0..entries)
+                    .map(|i| read_entry(channel * entries + i))
+                    .collect()
+            };
+            Ok([channel(0)?, channel(1)?, channel(2)?])
+        }
+        // formula
+        1 => {
+            let channel = |channel: usize| -> Result<Vec<f64>> {
+                let base = offset + 12 + channel * 12;
+                let gamma = read_s15_fixed16(data, base)?;
+                let min = read_s15_fixed16(data, base + 4)?;
+                let max = read_s15_fixed16(data, base + 8)?;
+                Ok((0..256)
+                    .map(|i| min + (max - min) * (i as f64 / 255.0).powf(gamma))
+                    .collect())
+            };
+            Ok([channel(0)?, channel(1)?, channel(2)?])
+        }
+        x => bail!("Unknown vcgt type {}", x),
+    }
+}
+
+/// Looks up `x` in 0..=1 in a curve of at least two evenly spaced points
+fn interpolate(curve: &[f64], x: f64) -> f64 {
+    let pos = x.clamp(0.0, 1.0) * (curve.len() - 1) as f64;
+    let idx = (pos.floor() as usize).min(curve.len() - 2);
+    let frac = pos - idx as f64;
+    curve[idx] * (1.0 - frac) + curve[idx + 1] * frac
+}
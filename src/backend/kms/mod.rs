@@ -3,14 +3,20 @@
 #[cfg(feature = "debug")]
 use crate::backend::render::element::AsGlowRenderer;
 use crate::{
-    backend::render::{workspace_elements, CLEAR_COLOR},
-    config::OutputConfig,
-    shell::Shell,
+    backend::render::{workspace_elements, zoomed_elements, CLEAR_COLOR},
+    config::{
+        AdaptiveSync, ColorDepth, ColorFilter, FrameScheduling, InternalOutput, OutputConfig,
+        OutputSerial, RenderDeviceConfig,
+    },
+    shell::{zoom::zoom_level, Shell},
     state::{BackendData, ClientState, Common, Fps, SurfaceDmabufFeedback},
     utils::prelude::*,
     wayland::{
         handlers::screencopy::{render_session, UserdataExt},
-        protocols::screencopy::{BufferParams, Session as ScreencopySession},
+        protocols::{
+            content_type::{surface_content_type, ContentType},
+            screencopy::{BufferParams, Session as ScreencopySession},
+        },
     },
 };
 
@@ -35,7 +41,7 @@ use smithay::{
             buffer_dimensions,
             damage::{Error as RenderError, RenderOutputResult},
             element::Element,
-            gles::{GlesRenderbuffer, GlesTexture},
+            gles::{ffi, GlesRenderbuffer, GlesRenderer, GlesTexture},
             glow::GlowRenderer,
             multigpu::{gbm::GbmGlesBackend, Error as MultiError, GpuManager},
             sync::SyncPoint,
@@ -78,22 +84,35 @@ use smithay::{
 use tracing::{error, info, trace, warn};
 
 use std::{
+    borrow::BorrowMut,
     cell::RefCell,
     collections::{HashMap, HashSet},
     ffi::CStr,
     fmt,
     os::unix::io::FromRawFd,
-    path::PathBuf,
-    time::Duration,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
+mod color_filter;
 mod drm_helpers;
+mod gamma;
+mod night_light;
 mod socket;
+use night_light::NightLight;
 use socket::*;
 
-use super::render::{init_shaders, CursorMode, GlMultiRenderer};
+use super::render::{damage, init_shaders, CursorMode, GlMultiRenderer};
 // for now we assume we need at least 3ms
 const MIN_RENDER_TIME: Duration = Duration::from_millis(3);
+// added to the render time estimate for every missed vblank
+const RENDER_MARGIN_STEP: Duration = Duration::from_millis(1);
+// removed from the margin again for every frame, that met its deadline
+const RENDER_MARGIN_DECAY: Duration = Duration::from_micros(50);
+// maximum difference in mHz for a connector mode to match the configured refresh rate
+const MODE_REFRESH_TOLERANCE: i32 = 1000;
+// consecutive failed frames, after which the renderer is checked for a lost context
+const MAX_RENDER_FAILURES: u32 = 3;
 
 #[derive(Debug)]
 pub struct KmsState {
@@ -101,7 +120,13 @@ pub struct KmsState {
     pub input_devices: HashMap<String, input::Device>,
     pub api: GpuManager<GbmGlesBackend<GlowRenderer>>,
     pub primary: DrmNode,
+    /// Gpus configured to render the outputs of the given connectors
+    pub output_render_nodes: HashMap<String, DrmNode>,
     session: LibSeatSession,
+    pub night_light: NightLight,
+    night_light_token: Option<RegistrationToken>,
+    /// Gpus, whose renderer is going to be set up again
+    recovering_renderers: HashSet<DrmNode>,
     _tokens: Vec<RegistrationToken>,
 }
 
@@ -146,13 +171,39 @@ pub struct Surface {
     output: Output,
     refresh_rate: u32,
     vrr: bool,
+    vrr_mode: AdaptiveSync,
+    /// Whether a fullscreen window was shown in the last frame
+    fullscreen: bool,
+    /// Content type of the fullscreen window, if any
+    content_type: ContentType,
     scheduled: bool,
     pending: bool,
     dirty: bool,
     last_animation_state: bool,
     render_timer_token: Option<RegistrationToken>,
+    last_vblank: Option<Instant>,
+    /// Extra time reserved for rendering, grown whenever a deadline was missed
+    render_margin: Duration,
     fps: Fps,
+    damage_overlay: damage::DamageOverlay,
     feedback: HashMap<DrmNode, SurfaceDmabufFeedback>,
+    /// Gamma ramp after calibration, before night light is applied
+    base_gamma: Option<gamma::GammaRamp>,
+    /// Gamma ramp set by a client, overriding calibration and night light
+    client_gamma: Option<gamma::GammaRamp>,
+    /// Degamma table and matrix mapping sRGB into the gamut of the display,
+    /// if the crtc and the ICC profile support it
+    gamut: Option<(gamma::GammaRamp, color_filter::Matrix)>,
+    /// Color transformation matrix of the color filter
+    filter_matrix: Option<color_filter::Matrix>,
+    /// Property blobs of the degamma table and color transformation matrix currently set
+    color_blobs: drm_helpers::ColorBlobs,
+    /// Max bpc and color depth the compositor was set up with
+    color_setup: (Option<u32>, ColorDepth),
+    /// Frames that failed to render in a row
+    render_failures: u32,
+    /// Turned off while staying mapped, the crtc stays disabled until it is turned on again
+    powered_off: bool,
 }
 
 pub type GbmDrmCompositor = DrmCompositor<
@@ -217,6 +268,16 @@ pub fn init_backend(
         .and_then(|x| DrmNode::from_path(x).ok())
     {
         path
+    } else if let Some(node) = state
+        .common
+        .config
+        .static_conf
+        .render_devices
+        .primary
+        .as_deref()
+        .and_then(render_node_from_path)
+    {
+        node
     } else {
         let primary_node = primary_gpu(session.seat())
             .ok()
@@ -375,10 +436,18 @@ pub fn init_backend(
             udev_event_source,
         ],
         primary,
+        output_render_nodes: output_render_nodes(&state.common.config.static_conf.render_devices),
         session,
+        night_light: NightLight::new(state.common.config.night_light.clone()),
+        night_light_token: None,
+        recovering_renderers: HashSet::new(),
         devices: HashMap::new(),
         input_devices: HashMap::new(),
     });
+    state
+        .backend
+        .kms()
+        .schedule_night_light(&state.common.event_loop_handle);
 
     // Create relative pointer global
     RelativePointerManagerState::new::<State>(&dh);
@@ -439,6 +508,9 @@ impl State {
             .with_context(|| format!("Failed to initialize drm device for: {}", path.display()))?;
         let drm_node = DrmNode::from_dev_id(dev)?;
         let supports_atomic = drm.is_atomic();
+        if !supports_atomic {
+            info!("Using legacy modesetting for {}.", path.display());
+        }
 
         let gbm = GbmDevice::new(fd)
             .with_context(|| format!("Failed to initialize GBM device for {}", path.display()))?;
@@ -479,21 +551,24 @@ impl State {
                         {
                             if let Some(surface) = device.surfaces.get_mut(&crtc) {
                                 trace!(?crtc, "VBlank");
+                                surface.last_vblank = Some(Instant::now());
                                 #[cfg(feature = "debug")]
                                 surface.fps.displayed();
 
                                 match surface.surface.as_mut().map(|x| x.frame_submitted()) {
                                     Some(Ok(feedback)) => {
                                         if let Some(mut feedback) = feedback.flatten() {
+                                            // read the sequence before taking the metadata,
+                                            // otherwise clients are always told it is 0
+                                            let seq = metadata
+                                                .as_ref()
+                                                .map(|metadata| metadata.sequence)
+                                                .unwrap_or(0);
                                             let submit_time =
                                                 match metadata.take().map(|data| data.time) {
                                                     Some(DrmEventTime::Monotonic(tp)) => Some(tp),
                                                     _ => None,
                                                 };
-                                            let seq = metadata
-                                                .as_ref()
-                                                .map(|metadata| metadata.sequence)
-                                                .unwrap_or(0);
 
                                             let (clock, flags) = if let Some(tp) = submit_time {
                                                 (
@@ -532,14 +607,18 @@ impl State {
                                             &mut surface.last_animation_state,
                                             animations_going,
                                         ) != animations_going;
-                                        (surface.dirty || animations_going || animation_diff).then(
-                                            || {
-                                                (
-                                                    surface.output.clone(),
-                                                    surface.fps.avg_rendertime(5),
-                                                )
-                                            },
-                                        )
+                                        // fading damage highlights need further frames as well
+                                        (surface.dirty
+                                            || animations_going
+                                            || animation_diff
+                                            || surface.damage_overlay.is_fading())
+                                        .then(|| {
+                                            (
+                                                surface.output.clone(),
+                                                surface.fps.avg_rendertime(5)
+                                                    + surface.render_margin,
+                                            )
+                                        })
                                     }
                                     Some(Err(err)) => {
                                         warn!(?err, "Failed to submit frame.");
@@ -565,11 +644,16 @@ impl State {
                             }
 
                             let estimated_rendertime =
-                                std::cmp::max(avg_rendertime, MIN_RENDER_TIME);
+                                match state.common.config.static_conf.frame_scheduling {
+                                    FrameScheduling::Immediate => None,
+                                    FrameScheduling::Latency => {
+                                        Some(std::cmp::max(avg_rendertime, MIN_RENDER_TIME))
+                                    }
+                                };
                             if let Err(err) = state.backend.kms().schedule_render(
                                 &state.common.event_loop_handle,
                                 &output,
-                                Some(estimated_rendertime),
+                                estimated_rendertime,
                                 scheduled_sessions,
                             ) {
                                 warn!(?err, "Failed to schedule render.");
@@ -670,11 +754,13 @@ impl State {
                     let drm_helpers::EdidInfo {
                         model,
                         manufacturer,
+                        ..
                     } = match drm_helpers::edid_info(&device.drm, conn) {
                         Ok(info) => info,
                         Err(_) => drm_helpers::EdidInfo {
                             model: "Unknown".into(),
                             manufacturer: "Unknown".into(),
+                            serial: None,
                         },
                     };
 
@@ -728,6 +814,81 @@ impl State {
         Ok(())
     }
 
+    /// Sets up the renderer of a gpu anew, after its context was lost, e.g. by a gpu reset.
+    ///
+    /// Client buffers are imported into the new context again on the next frame,
+    /// and the outputs rendered by it get new swapchains.
+    fn recover_renderer(&mut self, render_node: DrmNode) {
+        let backend = self.backend.kms();
+        backend.recovering_renderers.remove(&render_node);
+        let Some((drm_node, gbm)) = backend
+            .devices
+            .iter()
+            .find(|(_, device)| device.render_node == render_node)
+            .map(|(drm_node, device)| (*drm_node, device.gbm.clone()))
+        else {
+            return;
+        };
+        warn!(
+            "Renderer of {} stopped working, setting it up again.",
+            render_node
+        );
+
+        if let Err(err) = reset_renderer(&mut backend.api, render_node, gbm) {
+            error!(
+                ?err,
+                "Failed to recover renderer, setting up {} again.", drm_node
+            );
+            if let Err(err) = self.reset_device(drm_node) {
+                error!(?err, "Failed to set up {} again.", drm_node);
+            }
+            return;
+        }
+
+        let loop_handle = self.common.event_loop_handle.clone();
+        let outputs = backend
+            .devices
+            .values_mut()
+            .filter(|device| device.render_node == render_node)
+            .flat_map(|device| device.surfaces.values_mut())
+            .filter_map(|surface| {
+                if let Some(token) = surface.render_timer_token.take() {
+                    loop_handle.remove(token);
+                }
+                surface.scheduled = false;
+                surface.pending = false;
+                surface.render_failures = 0;
+                // outputs turned off stay off
+                surface.surface.take().map(|_| surface.output.clone())
+            })
+            .collect::<Vec<_>>();
+
+        let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        for output in outputs {
+            if let Err(err) = self.backend.kms().apply_config_for_output(
+                &output,
+                seats.iter().cloned(),
+                &mut self.common.shell,
+                false,
+                &loop_handle,
+            ) {
+                warn!(?err, "Failed to set up output {} again.", output.name());
+            }
+            self.backend.schedule_render(&loop_handle, &output, None);
+        }
+    }
+
+    /// Removes a device and probes it again from scratch
+    fn reset_device(&mut self, drm_node: DrmNode) -> Result<()> {
+        let path = drm_node
+            .dev_path()
+            .with_context(|| format!("No device path for {}", drm_node))?;
+        let dh = self.common.display_handle.clone();
+        let dev = drm_node.dev_id();
+        self.device_removed(dev, &dh)?;
+        self.device_added(dev, path, &dh, true)
+    }
+
     fn init_vulkan(&mut self, drm_node: DrmNode, render_node: DrmNode) {
         if let Ok(instance) = Instance::new(Version::VERSION_1_2, None) {
             if let Some(physical_device) =
@@ -821,11 +982,13 @@ impl State {
                         let drm_helpers::EdidInfo {
                             model,
                             manufacturer,
+                            ..
                         } = match drm_helpers::edid_info(&device.drm, conn) {
                             Ok(info) => info,
                             Err(_) => drm_helpers::EdidInfo {
                                 model: "Unknown".into(),
                                 manufacturer: "Unknown".into(),
+                                serial: None,
                             },
                         };
 
@@ -870,6 +1033,9 @@ impl State {
         self.common
             .output_configuration_state
             .remove_heads(outputs_removed.iter());
+        for output in &outputs_removed {
+            self.common.gamma_control_state.output_removed(output);
+        }
         self.common
             .output_configuration_state
             .add_heads(outputs_added.iter());
@@ -923,6 +1089,9 @@ impl State {
         self.common
             .output_configuration_state
             .remove_heads(outputs_removed.iter());
+        for output in &outputs_removed {
+            self.common.gamma_control_state.output_removed(output);
+        }
 
         let seats = self.common.seats().cloned().collect::<Vec<_>>();
         if self.backend.kms().session.is_active() {
@@ -946,6 +1115,124 @@ impl State {
     }
 }
 
+/// Replaces the renderer of a gpu with a new one, in a fresh context
+/// Whether the context of the renderer of a gpu was lost, e.g. by a gpu reset,
+/// instead of just failing to render some frames
+fn context_lost(api: &mut GpuManager<GbmGlesBackend<GlowRenderer>>, render_node: &DrmNode) -> bool {
+    let Ok(mut renderer) = api.single_renderer(render_node) else {
+        return true;
+    };
+    let renderer: &mut GlowRenderer = renderer.as_mut();
+    // a lost context can't be made current anymore, or reports the reset
+    BorrowMut::<GlesRenderer>::borrow_mut(renderer)
+        .with_context(|gl| unsafe { gl.GetGraphicsResetStatus() })
+        .map_or(true, |status| status != ffi::NO_ERROR)
+}
+
+fn reset_renderer(
+    api: &mut GpuManager<GbmGlesBackend<GlowRenderer>>,
+    render_node: DrmNode,
+    gbm: GbmDevice<DrmDeviceFd>,
+) -> Result<()> {
+    api.as_mut().remove_node(&render_node);
+    api.as_mut()
+        .add_node(render_node, gbm)
+        .with_context(|| format!("Failed to initialize renderer for device: {}", render_node))?;
+    let mut renderer = api.single_renderer(&render_node)?;
+    init_shaders(&mut renderer).with_context(|| "Failed to compile shaders")?;
+    Ok(())
+}
+
+/// Loads the calibration of a crtc from the ICC profile of its output:
+/// the gamma ramp, that night light is applied on top of, and the gamut mapping.
+fn load_calibration(
+    drm: &DrmDevice,
+    crtc: crtc::Handle,
+    output_config: &OutputConfig,
+) -> (
+    Option<gamma::GammaRamp>,
+    Option<(gamma::GammaRamp, color_filter::Matrix)>,
+) {
+    let size = drm
+        .get_crtc(crtc)
+        .map(|info| info.gamma_length() as usize)
+        .unwrap_or(0);
+    if size == 0 {
+        return (None, None);
+    }
+    let profile =
+        output_config
+            .icc_profile
+            .as_ref()
+            .and_then(|path| match gamma::IccProfile::load(path) {
+                Ok(profile) => Some(profile),
+                Err(err) => {
+                    warn!(?err, "Failed to apply ICC profile {}.", path.display());
+                    None
+                }
+            });
+    let gamut = profile
+        .as_ref()
+        .and_then(|profile| profile.gamut())
+        .and_then(|matrix| {
+            let degamma = gamma::GammaRamp::srgb_eotf(drm_helpers::degamma_size(drm, crtc)?);
+            Some((degamma, matrix))
+        });
+
+    let ramp = match profile {
+        Some(profile) => profile.gamma(size, gamut.is_some()),
+        None => gamma::GammaRamp::identity(size),
+    };
+    let ramp = if output_config.color_filter == ColorFilter::Invert {
+        ramp.inverted()
+    } else {
+        ramp
+    };
+    (Some(ramp), gamut)
+}
+
+/// Sets the degamma table and color transformation matrix of a crtc.
+///
+/// Gamma ramps of clients expect sRGB encoded colors, so the gamut mapping is skipped for them.
+fn apply_color_transform(drm: &DrmDevice, crtc: crtc::Handle, surface: &mut Surface) -> Result<()> {
+    let gamut = surface
+        .gamut
+        .as_ref()
+        .filter(|_| surface.client_gamma.is_none());
+    let blobs = &mut surface.color_blobs;
+    drm_helpers::set_degamma(
+        drm,
+        crtc,
+        gamut.map(|(degamma, _)| degamma),
+        &mut blobs.degamma,
+    )?;
+    drm_helpers::set_ctm(
+        drm,
+        crtc,
+        color_filter::combine(surface.filter_matrix, gamut.map(|(_, matrix)| *matrix)),
+        &mut blobs.ctm,
+    )
+}
+
+fn apply_gamma(
+    drm: &DrmDevice,
+    crtc: crtc::Handle,
+    surface: &Surface,
+    temperature: f64,
+) -> Result<()> {
+    if let Some(ramp) = surface.client_gamma.as_ref() {
+        drm_helpers::set_gamma(drm, crtc, ramp)
+    } else if let Some(base) = surface.base_gamma.as_ref() {
+        drm_helpers::set_gamma(
+            drm,
+            crtc,
+            &base.scaled(night_light::whitepoint(temperature)),
+        )
+    } else {
+        Ok(())
+    }
+}
+
 pub struct OutputChanges {
     pub added: Vec<(crtc::Handle, connector::Handle)>,
     pub removed: Vec<crtc::Handle>,
@@ -1026,6 +1313,19 @@ impl Device {
                     .unwrap_or_else(|_| String::from("Unknown")),
             },
         );
+        if let Some(serial) = edid_info.ok().and_then(|info| info.serial) {
+            output
+                .user_data()
+                .insert_if_missing(|| OutputSerial(serial));
+        }
+        if matches!(
+            conn_info.interface(),
+            connector::Interface::EmbeddedDisplayPort
+                | connector::Interface::LVDS
+                | connector::Interface::DSI
+        ) {
+            output.user_data().insert_if_missing(|| InternalOutput);
+        }
         for mode in conn_info.modes() {
             let refresh_rate = drm_helpers::calculate_refresh_rate(*mode);
             let mode = OutputMode {
@@ -1057,14 +1357,32 @@ impl Device {
             surface: None,
             connector: conn,
             vrr,
+            vrr_mode: if vrr {
+                AdaptiveSync::Enabled
+            } else {
+                AdaptiveSync::Disabled
+            },
+            fullscreen: false,
+            content_type: ContentType::None,
             refresh_rate,
             scheduled: false,
             pending: false,
             dirty: false,
             last_animation_state: false,
             render_timer_token: None,
+            last_vblank: None,
+            render_margin: Duration::ZERO,
             fps: Fps::new(renderer.as_mut()),
+            damage_overlay: damage::DamageOverlay::default(),
             feedback: HashMap::new(),
+            base_gamma: None,
+            client_gamma: None,
+            gamut: None,
+            filter_matrix: None,
+            color_blobs: drm_helpers::ColorBlobs::new(self.drm.device_fd().clone()),
+            color_setup: (None, ColorDepth::Auto),
+            render_failures: 0,
+            powered_off: false,
         };
         self.surfaces.insert(crtc, data);
 
@@ -1085,6 +1403,44 @@ pub fn source_node_for_surface(w: &WlSurface, dh: &DisplayHandle) -> Option<DrmN
     None
 }
 
+/// Formats to try for the framebuffers of an output, most preferred first
+fn color_formats(depth: ColorDepth) -> &'static [Fourcc] {
+    match depth {
+        ColorDepth::Auto => &[
+            Fourcc::Abgr2101010,
+            Fourcc::Argb2101010,
+            Fourcc::Abgr8888,
+            Fourcc::Argb8888,
+        ],
+        ColorDepth::Eight => &[Fourcc::Abgr8888, Fourcc::Argb8888],
+        ColorDepth::Ten => &[Fourcc::Abgr2101010, Fourcc::Argb2101010],
+    }
+}
+
+/// Render node of the gpu the drm node at the given path belongs to
+fn render_node_from_path(path: &Path) -> Option<DrmNode> {
+    let node = match DrmNode::from_path(path) {
+        Ok(node) => node,
+        Err(err) => {
+            warn!(?err, "Unknown render device {}.", path.display());
+            return None;
+        }
+    };
+    Some(
+        node.node_with_type(NodeType::Render)
+            .and_then(Result::ok)
+            .unwrap_or(node),
+    )
+}
+
+fn output_render_nodes(config: &RenderDeviceConfig) -> HashMap<String, DrmNode> {
+    config
+        .outputs
+        .iter()
+        .filter_map(|(name, path)| Some((name.clone(), render_node_from_path(path)?)))
+        .collect()
+}
+
 fn render_node_for_output(
     dh: &DisplayHandle,
     output: &Output,
@@ -1175,6 +1531,22 @@ fn get_surface_dmabuf_feedback(
 }
 
 impl Surface {
+    /// Adapts the safety margin of delayed rendering, depending on whether
+    /// the frame was ready before the next vblank.
+    fn update_render_margin(&mut self) {
+        let Some(last_vblank) = self.last_vblank else {
+            return;
+        };
+        let frame_time = Duration::from_secs_f64(1000.0 / self.refresh_rate as f64);
+        if last_vblank.elapsed() > frame_time {
+            self.render_margin = (self.render_margin + RENDER_MARGIN_STEP).min(frame_time / 2);
+            self.fps.missed_vblank();
+            trace!(margin = ?self.render_margin, "Missed render deadline");
+        } else {
+            self.render_margin = self.render_margin.saturating_sub(RENDER_MARGIN_DECAY);
+        }
+    }
+
     pub fn render_output(
         &mut self,
         api: &mut GpuManager<GbmGlesBackend<GlowRenderer>>,
@@ -1198,9 +1570,13 @@ impl Surface {
             Some((render_node, allocator)) => (
                 render_node,
                 api.renderer(&render_node, &target_node, allocator, compositor.format())
-                    .unwrap(),
+                    .with_context(|| "Failed to get renderer")?,
+            ),
+            None => (
+                target_node,
+                api.single_renderer(&target_node)
+                    .with_context(|| "Failed to get renderer")?,
             ),
-            None => (target_node, api.single_renderer(&target_node).unwrap()),
         };
 
         self.fps.start();
@@ -1213,13 +1589,37 @@ impl Surface {
         }
 
         let (previous_workspace, workspace) = state.shell.workspaces.active(&self.output);
+        let fullscreen_window = workspace.get_fullscreen(&self.output);
+        let fullscreen = fullscreen_window.is_some();
+        let fullscreen_surface = fullscreen_window.and_then(|window| window.surface().wl_surface());
+        self.content_type = fullscreen_surface
+            .as_ref()
+            .map_or(ContentType::None, surface_content_type);
+        if fullscreen != self.fullscreen {
+            self.fullscreen = fullscreen;
+            if self.vrr_mode == AdaptiveSync::Fullscreen {
+                match drm_helpers::set_vrr(
+                    compositor.surface(),
+                    compositor.crtc(),
+                    self.connector,
+                    fullscreen,
+                ) {
+                    Ok(vrr) => self.vrr = vrr,
+                    Err(err) => warn!(
+                        ?err,
+                        "Failed to toggle VRR on output {}.",
+                        self.output.name()
+                    ),
+                }
+            }
+        }
         let (previous_idx, idx) = state.shell.workspaces.active_num(&self.output);
         let previous_workspace = previous_workspace
             .zip(previous_idx)
-            .map(|((w, start), idx)| (w.handle, idx, start));
+            .map(|((w, delta), idx)| (w.handle, idx, delta));
         let workspace = (workspace.handle, idx);
 
-        let elements = workspace_elements(
+        let mut elements = workspace_elements(
             Some(&render_node),
             &mut renderer,
             state,
@@ -1233,10 +1633,22 @@ impl Surface {
         .map_err(|err| {
             anyhow::format_err!("Failed to accumulate elements for rendering: {:?}", err)
         })?;
+        damage::visualize_damage(
+            &renderer,
+            &self.output,
+            &mut self.damage_overlay,
+            state.visualize_damage,
+            &mut elements,
+        );
+        let zoom = zoom_level(&self.output);
+        let elements = zoomed_elements(&mut renderer, state, &self.output, zoom, elements);
         self.fps.elements();
 
-        let res =
-            compositor.render_frame::<_, _, GlesTexture>(&mut renderer, &elements, CLEAR_COLOR);
+        let res = {
+            #[cfg(feature = "debug")]
+            puffin::profile_scope!("render_frame");
+            compositor.render_frame::<_, _, GlesTexture>(&mut renderer, &elements, CLEAR_COLOR)
+        };
         self.fps.render();
 
         match res {
@@ -1247,7 +1659,12 @@ impl Surface {
                     None
                 };
 
-                match compositor.queue_frame(feedback) {
+                let res = {
+                    #[cfg(feature = "debug")]
+                    puffin::profile_scope!("queue_frame");
+                    compositor.queue_frame(feedback)
+                };
+                match res {
                     Ok(()) | Err(FrameError::EmptyFrame) => {}
                     Err(err) => {
                         return Err(err).with_context(|| "Failed to submit result for display")
@@ -1362,6 +1779,17 @@ impl Surface {
             }
         }
 
+        #[cfg(feature = "debug")]
+        {
+            if let Some(rd) = self.fps.rd.as_mut() {
+                rd.end_frame_capture(
+                    renderer.glow_renderer().egl_context().get_context_handle(),
+                    std::ptr::null(),
+                );
+            }
+            puffin::GlobalProfiler::lock().new_frame();
+        }
+
         Ok(())
     }
 }
@@ -1371,6 +1799,120 @@ impl KmsState {
         self.session.change_vt(num).map_err(Into::into)
     }
 
+    /// (Re-)starts the timer transitioning the color temperature of all outputs,
+    /// e.g. after the night light config changed.
+    pub fn schedule_night_light(&mut self, loop_handle: &LoopHandle<'_, State>) {
+        if let Some(token) = self.night_light_token.take() {
+            loop_handle.remove(token);
+        }
+        match loop_handle.insert_source(Timer::immediate(), |_, _, state| {
+            let kms = state.backend.kms();
+            let (changed, next) = kms.night_light.step();
+            if changed {
+                kms.apply_color_temperature();
+            }
+            TimeoutAction::ToDuration(next)
+        }) {
+            Ok(token) => self.night_light_token = Some(token),
+            Err(err) => error!(?err, "Failed to schedule night light."),
+        }
+    }
+
+    fn apply_color_temperature(&mut self) {
+        let temperature = self.night_light.temperature();
+        for device in self.devices.values() {
+            for (crtc, surface) in device.surfaces.iter() {
+                if surface.surface.is_none() || surface.client_gamma.is_some() {
+                    continue;
+                }
+                if let Err(err) = apply_gamma(&device.drm, *crtc, surface, temperature) {
+                    warn!(
+                        ?err,
+                        "Failed to set gamma of output {}.",
+                        surface.output.name()
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn gamma_size(&self, output: &Output) -> Option<u32> {
+        self.devices.values().find_map(|device| {
+            let (crtc, _) = device
+                .surfaces
+                .iter()
+                .find(|(_, s)| s.output == *output && s.surface.is_some())?;
+            let size = device.drm.get_crtc(*crtc).ok()?.gamma_length();
+            (size > 0).then_some(size)
+        })
+    }
+
+    /// Sets the gamma ramps requested by a client, or restores the default ramps if `None`
+    pub fn set_client_gamma(
+        &mut self,
+        output: &Output,
+        gamma: Option<[Vec<u16>; 3]>,
+    ) -> Result<()> {
+        let temperature = self.night_light.temperature();
+        let (device, crtc, surface) = self
+            .devices
+            .values_mut()
+            .find_map(|device| {
+                let (crtc, surface) = device
+                    .surfaces
+                    .iter_mut()
+                    .find(|(_, s)| s.output == *output)?;
+                Some((&device.drm, *crtc, surface))
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown output"))?;
+
+        surface.client_gamma =
+            gamma.map(|[red, green, blue]| gamma::GammaRamp { red, green, blue });
+        if surface.surface.is_some() {
+            apply_gamma(device, crtc, surface, temperature)?;
+            apply_color_transform(device, crtc, surface)?;
+        }
+        Ok(())
+    }
+
+    /// Turns an output off by dropping its compositor, which disables the crtc,
+    /// or on again by setting it up anew.
+    pub fn set_output_power(
+        &mut self,
+        output: &Output,
+        on: bool,
+        shell: &mut Shell,
+        seats: impl Iterator<Item = Seat<State>>,
+        loop_handle: &LoopHandle<'_, State>,
+    ) -> Result<(), anyhow::Error> {
+        let Some(surface) = self
+            .devices
+            .values_mut()
+            .flat_map(|device| device.surfaces.values_mut())
+            .find(|surface| surface.output == *output)
+        else {
+            return Ok(());
+        };
+
+        if on {
+            if !surface.powered_off {
+                return Ok(());
+            }
+            surface.powered_off = false;
+            self.apply_config_for_output(output, seats, shell, false, loop_handle)
+        } else {
+            surface.powered_off = true;
+            if let Some(token) = surface.render_timer_token.take() {
+                loop_handle.remove(token);
+            }
+            surface.surface = None;
+            surface.scheduled = false;
+            surface.pending = false;
+            surface.dirty = false;
+            Ok(())
+        }
+    }
+
     pub fn apply_config_for_output(
         &mut self,
         output: &Output,
@@ -1409,39 +1951,74 @@ impl KmsState {
                 let drm = &mut device.drm;
                 let conn = surface.connector;
                 let conn_info = drm.get_connector(conn, false)?;
-                let mode = conn_info
-                    .modes()
-                    .iter()
-                    // match the size
-                    .filter(|mode| {
-                        let (x, y) = mode.size();
-                        Size::from((x as i32, y as i32)) == output_config.mode_size()
-                    })
-                    // and then select the closest refresh rate (e.g. to match 59.98 as 60)
-                    .min_by_key(|mode| {
-                        let refresh_rate = drm_helpers::calculate_refresh_rate(**mode);
-                        (output_config.mode.1.unwrap() as i32 - refresh_rate as i32).abs()
-                    })
-                    .ok_or(anyhow::anyhow!("Unknown mode"))?;
+                let mode = match output_config.modeline {
+                    Some(modeline) => drm_helpers::mode_from_modeline(&modeline)?,
+                    None => {
+                        let refresh = output_config.mode_refresh();
+                        conn_info
+                            .modes()
+                            .iter()
+                            // match the size
+                            .filter(|mode| {
+                                let (x, y) = mode.size();
+                                Size::from((x as i32, y as i32)) == output_config.mode_size()
+                            })
+                            // and then select the closest refresh rate (e.g. to match 59.98 as 60)
+                            .map(|mode| {
+                                let refresh_rate = drm_helpers::calculate_refresh_rate(*mode);
+                                (*mode, (refresh as i32 - refresh_rate as i32).abs())
+                            })
+                            .filter(|(_, diff)| *diff <= MODE_REFRESH_TOLERANCE)
+                            .min_by_key(|(_, diff)| *diff)
+                            .map(|(mode, _)| Ok(mode))
+                            // not advertised by the connector, generate cvt timings instead
+                            .unwrap_or_else(|| {
+                                let (w, h) = output_config.mode.0;
+                                drm_helpers::mode_from_modeline(&drm_helpers::cvt_modeline(
+                                    w as u16, h as u16, refresh,
+                                ))
+                            })?
+                    }
+                };
+                // custom timings beyond what the connector can drive fail the configuration
+                let max_clock = conn_info.modes().iter().map(|mode| mode.clock()).max();
+                if !conn_info.modes().contains(&mode)
+                    && max_clock.map_or(false, |max_clock| mode.clock() > max_clock)
+                {
+                    anyhow::bail!(
+                        "Mode {:?} exceeds the highest pixel clock of the connector ({} kHz)",
+                        mode,
+                        max_clock.unwrap_or_default(),
+                    );
+                }
 
-                if !test_only {
+                if !test_only && !surface.powered_off {
+                    surface.vrr_mode = output_config.adaptive_sync();
+                    let vrr = match surface.vrr_mode {
+                        AdaptiveSync::Disabled => false,
+                        AdaptiveSync::Enabled => true,
+                        AdaptiveSync::Fullscreen => surface.fullscreen,
+                    };
+                    // the bit depth is only negotiated, when the compositor is set up
+                    let color_setup = (output_config.max_bpc, output_config.color_depth);
+                    if surface.surface.is_some() && surface.color_setup != color_setup {
+                        surface.surface = None;
+                        surface.pending = false;
+                    }
                     let res = if let Some(compositor) = surface.surface.as_mut() {
-                        if output_config.vrr != surface.vrr {
-                            surface.vrr = drm_helpers::set_vrr(
-                                drm,
-                                *crtc,
-                                conn_info.handle(),
-                                output_config.vrr,
-                            )?;
+                        if vrr != surface.vrr {
+                            surface.vrr =
+                                drm_helpers::set_vrr(drm, *crtc, conn_info.handle(), vrr)?;
                         }
                         compositor
-                            .use_mode(*mode)
+                            .use_mode(mode)
                             .context("Failed to apply new mode")?;
+                        surface.refresh_rate = drm_helpers::calculate_refresh_rate(mode);
                         false
                     } else {
-                        surface.vrr = drm_helpers::set_vrr(drm, *crtc, conn, output_config.vrr)
-                            .unwrap_or(false);
-                        if let Some(bpc) = output_config.max_bpc {
+                        surface.vrr = drm_helpers::set_vrr(drm, *crtc, conn, vrr).unwrap_or(false);
+                        if let Some(bpc) = output_config.max_bpc.or(output_config.color_depth.bpc())
+                        {
                             if let Err(err) = drm_helpers::set_max_bpc(drm, conn, bpc) {
                                 warn!(
                                     ?bpc,
@@ -1451,9 +2028,9 @@ impl KmsState {
                                 );
                             }
                         }
-                        surface.refresh_rate = drm_helpers::calculate_refresh_rate(*mode);
+                        surface.refresh_rate = drm_helpers::calculate_refresh_rate(mode);
 
-                        let drm_surface = drm.create_surface(*crtc, *mode, &[conn])?;
+                        let drm_surface = drm.create_surface(*crtc, mode, &[conn])?;
                         let driver = drm
                             .get_driver()
                             .with_context(|| "Failed to query drm driver")?;
@@ -1467,6 +2044,11 @@ impl KmsState {
                         {
                             planes.overlay = vec![];
                         }
+                        // the legacy api can only flip the primary plane, the cursor gets composited
+                        if !device.supports_atomic {
+                            planes.overlay = vec![];
+                            planes.cursor = None;
+                        }
 
                         let target = DrmCompositor::new(
                             &surface.output,
@@ -1477,12 +2059,7 @@ impl KmsState {
                                 GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
                             ),
                             device.gbm.clone(),
-                            &[
-                                Fourcc::Abgr2101010,
-                                Fourcc::Argb2101010,
-                                Fourcc::Abgr8888,
-                                Fourcc::Argb8888,
-                            ],
+                            color_formats(output_config.color_depth),
                             device.formats.clone(),
                             drm.cursor_size(),
                             Some(device.gbm.clone()),
@@ -1495,11 +2072,31 @@ impl KmsState {
                             )
                         })?;
                         surface.surface = Some(target);
+                        surface.color_setup = color_setup;
                         true
                     };
+                    (surface.base_gamma, surface.gamut) =
+                        load_calibration(drm, *crtc, &output_config);
+                    surface.filter_matrix = color_filter::color_matrix(output_config.color_filter);
+                    if let Err(err) =
+                        apply_gamma(drm, *crtc, surface, self.night_light.temperature())
+                    {
+                        warn!(?err, "Failed to set gamma of output {}.", output.name());
+                    }
+                    if let Err(err) = apply_color_transform(drm, *crtc, surface) {
+                        warn!(
+                            ?err,
+                            "Failed to set color filter of output {}.",
+                            output.name()
+                        );
+                    }
                     shell.add_output(output);
                     res
                 } else {
+                    // a powered off output is set up with the new config, once it is turned on
+                    if !test_only {
+                        shell.add_output(output);
+                    }
                     false
                 }
             }
@@ -1545,7 +2142,12 @@ impl KmsState {
         target: DrmNode,
         shell: &Shell,
     ) {
-        let render = render_node_for_output(dh, &output, target, &shell);
+        let render = self
+            .output_render_nodes
+            .get(&output.name())
+            .copied()
+            .filter(|node| self.devices.values().any(|dev| dev.render_node == *node))
+            .unwrap_or_else(|| render_node_for_output(dh, &output, target, &shell));
         if let Err(err) = self.api.early_import(
             if let Some(client) = dh.get_client(surface.id()).ok() {
                 if let Some(normal_client) = client.get_data::<ClientState>() {
@@ -1613,8 +2215,14 @@ impl KmsState {
                 if let Some(token) = surface.render_timer_token.take() {
                     loop_handle.remove(token);
                 }
+                // keep a steady refresh rate on the desktop, as VRR panels may flicker otherwise.
+                // Photos don't benefit from low latency, so they keep the steady refresh rate.
+                let delayed = !((surface.vrr
+                    && surface.fullscreen
+                    && surface.content_type != ContentType::Photo)
+                    || estimated_rendertime.is_none());
                 surface.render_timer_token = Some(loop_handle.insert_source(
-                    if surface.vrr || estimated_rendertime.is_none() {
+                    if !delayed {
                         Timer::immediate()
                     } else {
                         Timer::from_duration(
@@ -1632,12 +2240,23 @@ impl KmsState {
 
                         if let Some(surface) = target_device.surfaces.get_mut(&crtc) {
                             let target_node = target_device.render_node;
-                            let render_node = render_node_for_output(
-                                &state.common.display_handle,
-                                &surface.output,
-                                target_node,
-                                &state.common.shell,
-                            );
+                            // a gpu assigned in the config wins over the one the clients use
+                            let render_node = backend
+                                .output_render_nodes
+                                .get(&surface.output.name())
+                                .copied()
+                                .filter(|node| {
+                                    *node == target_node
+                                        || other.iter().any(|(_, dev)| dev.render_node == *node)
+                                })
+                                .unwrap_or_else(|| {
+                                    render_node_for_output(
+                                        &state.common.display_handle,
+                                        &surface.output,
+                                        target_node,
+                                        &state.common.shell,
+                                    )
+                                });
                             let common = &mut state.common;
 
                             let result = if render_node != target_node {
@@ -1669,6 +2288,10 @@ impl KmsState {
                             match result {
                                 Ok(_) => {
                                     trace!(?crtc, "Frame pending");
+                                    if delayed {
+                                        surface.update_render_margin();
+                                    }
+                                    surface.render_failures = 0;
                                     surface.dirty = false;
                                     surface.pending = true;
                                     surface.scheduled = false;
@@ -1678,9 +2301,32 @@ impl KmsState {
                                 Err(err) => {
                                     if backend.session.is_active() {
                                         error!(?err, "Error rendering.");
-                                        return TimeoutAction::ToDuration(Duration::from_secs_f64(
-                                            (1000.0 / surface.refresh_rate as f64) - 0.003,
-                                        ));
+                                        surface.render_failures += 1;
+                                        if surface.render_failures < MAX_RENDER_FAILURES {
+                                            return TimeoutAction::ToDuration(
+                                                Duration::from_secs_f64(
+                                                    (1000.0 / surface.refresh_rate as f64) - 0.003,
+                                                ),
+                                            );
+                                        }
+                                        surface.render_failures = 0;
+                                        if !context_lost(&mut backend.api, &render_node) {
+                                            return TimeoutAction::ToDuration(
+                                                Duration::from_secs_f64(
+                                                    (1000.0 / surface.refresh_rate as f64) - 0.003,
+                                                ),
+                                            );
+                                        }
+                                        // the gpu was reset, the next frame waits for
+                                        // a new renderer instead
+                                        surface.scheduled = false;
+                                        surface.render_timer_token = None;
+                                        // other outputs of the gpu are set up again with it
+                                        if backend.recovering_renderers.insert(render_node) {
+                                            common.event_loop_handle.insert_idle(move |state| {
+                                                state.recover_renderer(render_node);
+                                            });
+                                        }
                                     }
                                 }
                             };
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic_comp_config::night_light::{NightLightConfig, NightLightSchedule};
+use std::{
+    f64::consts::PI,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Color temperature of the unfiltered display
+pub const NEUTRAL_TEMPERATURE: f64 = 6500.0;
+/// Duration of the scheduled transitions after sunset and before sunrise in seconds
+const SCHEDULE_TRANSITION: f64 = 30.0 * 60.0;
+/// Speed of the transition after toggling or changing the temperature in Kelvin per second
+const FADE_SPEED: f64 = 2000.0;
+const FADE_INTERVAL: Duration = Duration::from_millis(50);
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct NightLight {
+    pub config: NightLightConfig,
+    current: f64,
+}
+
+impl NightLight {
+    pub fn new(config: NightLightConfig) -> NightLight {
+        NightLight {
+            config,
+            current: NEUTRAL_TEMPERATURE,
+        }
+    }
+
+    /// Color temperature currently applied in Kelvin
+    pub fn temperature(&self) -> f64 {
+        self.current
+    }
+
+    /// Moves the current temperature towards the scheduled one.
+    ///
+    /// Returns if the temperature changed and when to step again.
+    pub fn step(&mut self) -> (bool, Duration) {
+        let target = self.target_temperature(unix_now());
+        let diff = target - self.current;
+        let max_step = FADE_SPEED * FADE_INTERVAL.as_secs_f64();
+
+        if diff.abs() < 1.0 {
+            (false, SCHEDULE_INTERVAL)
+        } else if diff.abs() <= max_step {
+            self.current = target;
+            (true, SCHEDULE_INTERVAL)
+        } else {
+            self.current += max_step.copysign(diff);
+            (true, FADE_INTERVAL)
+        }
+    }
+
+    fn target_temperature(&self, now: f64) -> f64 {
+        if !self.config.enabled {
+            return NEUTRAL_TEMPERATURE;
+        }
+
+        let factor = match self.config.schedule {
+            NightLightSchedule::Manual => 1.0,
+            NightLightSchedule::SunsetToSunrise {
+                latitude,
+                longitude,
+            } => night_factor(now, latitude, longitude),
+        };
+        NEUTRAL_TEMPERATURE + (self.config.temperature as f64 - NEUTRAL_TEMPERATURE) * factor
+    }
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// How far into the night the given time is, from 0 (day) to 1 (night),
+/// with linear transitions after sunset and before sunrise.
+fn night_factor(now: f64, latitude: f64, longitude: f64) -> f64 {
+    let day = ((now / 86400.0 + 2440587.5) - 2451545.0 + 0.0008).round();
+    // the night may have started on the previous day (in UTC)
+    [day - 1.0, day]
+        .into_iter()
+        .filter_map(|day| {
+            let (_, sunset) = sun_times(day, latitude, longitude);
+            let (sunrise, _) = sun_times(day + 1.0, latitude, longitude);
+            (sunset..=sunrise).contains(&now).then(|| {
+                ((now - sunset) / SCHEDULE_TRANSITION)
+                    .min((sunrise - now) / SCHEDULE_TRANSITION)
+                    .min(1.0)
+            })
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Sunrise and sunset as unix timestamps for the given day since J2000
+fn sun_times(day: f64, latitude: f64, longitude: f64) -> (f64, f64) {
+    let rad = PI / 180.0;
+    let mean_solar_time = day - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let center = 1.9148 * (anomaly * rad).sin()
+        + 0.0200 * (2.0 * anomaly * rad).sin()
+        + 0.0003 * (3.0 * anomaly * rad).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = 2451545.0 + mean_solar_time + 0.0053 * (anomaly * rad).sin()
+        - 0.0069 * (2.0 * ecliptic_longitude * rad).sin();
+    let declination = ((ecliptic_longitude * rad).sin() * (23.4397 * rad).sin()).asin();
+    let cos_hour_angle = ((-0.833 * rad).sin() - (latitude * rad).sin() * declination.sin())
+        / ((latitude * rad).cos() * declination.cos());
+    // clamped for polar night and midnight sun
+    let hour_angle = cos_hour_angle.clamp(-1.0, 1.0).acos() / rad;
+
+    let to_unix = |julian: f64| (julian - 2440587.5) * 86400.0;
+    (
+        to_unix(transit - hour_angle / 360.0),
+        to_unix(transit + hour_angle / 360.0),
+    )
+}
+
+/// Relative intensity of red, green and blue for a given color temperature
+pub fn whitepoint(temperature: f64) -> (f64, f64, f64) {
+    fn approximate(temperature: f64) -> (f64, f64, f64) {
+        let t = temperature / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698727446 * (t - 60.0).powf(-0.1332047592)
+        };
+        let green = if t <= 66.0 {
+            99.4708025861 * t.ln() - 161.1195681661
+        } else {
+            288.1221695283 * (t - 60.0).powf(-0.0755148492)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.5177312231 * (t - 10.0).ln() - 305.0447927307
+        };
+        (
+            (red / 255.0).clamp(0.0, 1.0),
+            (green / 255.0).clamp(0.0, 1.0),
+            (blue / 255.0).clamp(0.0, 1.0),
+        )
+    }
+
+    // normalize, so the neutral temperature doesn't alter colors
+    let (r, g, b) = approximate(temperature);
+    let (nr, ng, nb) = approximate(NEUTRAL_TEMPERATURE);
+    ((r / nr).min(1.0), (g / ng).min(1.0), (b / nb).min(1.0))
+}
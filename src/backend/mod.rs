@@ -1,12 +1,19 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::state::State;
+use crate::{
+    state::{BackendData, State},
+    utils::prelude::*,
+};
 use anyhow::{Context, Result};
-use smithay::reexports::{calloop::EventLoop, wayland_server::DisplayHandle};
+use smithay::{
+    output::Output,
+    reexports::{calloop::EventLoop, wayland_server::DisplayHandle},
+};
 use tracing::{info, warn};
 
 pub mod render;
 
+pub mod headless;
 pub mod kms;
 pub mod winit;
 pub mod x11;
@@ -22,6 +29,7 @@ pub fn init_backend_auto(
         Ok(x) if x == "x11" => x11::init_backend(dh, event_loop, state),
         Ok(x) if x == "winit" => winit::init_backend(dh, event_loop, state),
         Ok(x) if x == "kms" => kms::init_backend(dh, event_loop, state),
+        Ok(x) if x == "headless" => headless::init_backend(dh, event_loop, state),
         Ok(_) => unimplemented!("There is no backend with this identifier"),
         Err(_) => {
             if std::env::var_os("DISPLAY").is_some()
@@ -47,15 +55,124 @@ pub fn init_backend_auto(
             .shell
             .outputs()
             .next()
+            .cloned()
             .with_context(|| "Backend initialized without output")?;
         let initial_seat = crate::input::add_seat(
             dh,
             &mut state.common.seat_state,
-            output,
+            &output,
             &state.common.config,
             "seat-0".into(),
         );
         state.common.add_seat(initial_seat);
+
+        for seat_conf in state.common.config.static_conf.seats.clone() {
+            let output = seat_conf
+                .output
+                .as_ref()
+                .and_then(|name| {
+                    state
+                        .common
+                        .shell
+                        .outputs()
+                        .find(|output| &output.name() == name)
+                })
+                .cloned()
+                .unwrap_or_else(|| output.clone());
+            let seat = crate::input::add_seat(
+                dh,
+                &mut state.common.seat_state,
+                &output,
+                &state.common.config,
+                seat_conf.name,
+            );
+            state.common.add_seat(seat);
+        }
     }
     res
 }
+
+impl State {
+    /// Adds a virtual output right of all others, sized like the first configured one.
+    ///
+    /// On the headless backend this is an offscreen output, nested on X11 a new host window.
+    pub fn add_virtual_output(&mut self) {
+        let x = self
+            .common
+            .shell
+            .outputs()
+            .map(|output| output.geometry().loc.x + output.geometry().size.w)
+            .max()
+            .unwrap_or(0);
+        let static_conf = &self.common.config.static_conf;
+        let handle = &self.common.event_loop_handle;
+        let result = match &mut self.backend {
+            BackendData::Headless(headless) => {
+                let config = static_conf.headless.outputs.first().copied();
+                headless.add_output(handle, config.unwrap_or_default(), (x, 0))
+            }
+            BackendData::X11(x11) => {
+                let config = static_conf.nested.outputs.first().copied();
+                x11.add_window(handle.clone(), config.unwrap_or_default(), (x, 0))
+            }
+            _ => {
+                info!("Virtual outputs can only be added on the headless or x11 backend.");
+                return;
+            }
+        };
+        let output = match result {
+            Ok(output) => output,
+            Err(err) => {
+                warn!(?err, "Failed to add virtual output.");
+                return;
+            }
+        };
+        info!("Added virtual output {}.", output.name());
+
+        self.common
+            .output_configuration_state
+            .add_heads(std::iter::once(&output));
+        self.common.shell.add_output(&output);
+        let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        self.common.config.read_outputs(
+            &mut self.common.output_configuration_state,
+            &mut self.backend,
+            &mut self.common.shell,
+            seats.into_iter(),
+            &self.common.event_loop_handle,
+        );
+    }
+
+    /// Removes the given virtual output, as if it was unplugged. The last output is kept.
+    pub fn remove_virtual_output(&mut self, output: &Output) {
+        let removed = match &mut self.backend {
+            BackendData::Headless(headless) if headless.outputs().count() > 1 => {
+                headless.remove_output(&self.common.event_loop_handle, output)
+            }
+            BackendData::X11(x11) if x11.outputs().count() > 1 => x11.remove_window(output),
+            BackendData::Headless(_) | BackendData::X11(_) => {
+                info!("Not removing the last virtual output.");
+                return;
+            }
+            _ => {
+                info!("Virtual outputs can only be removed on the headless or x11 backend.");
+                return;
+            }
+        };
+        if removed {
+            info!("Removed virtual output {}.", output.name());
+            self.output_unplugged(output);
+        }
+    }
+
+    /// Cleans up after an output of a nested or virtual backend went away
+    pub fn output_unplugged(&mut self, output: &Output) {
+        self.common
+            .output_configuration_state
+            .remove_heads(std::iter::once(output));
+        self.common.gamma_control_state.output_removed(output);
+        let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        self.common.shell.remove_output(output, seats.into_iter());
+        self.common.output_configuration_state.update();
+    }
+}
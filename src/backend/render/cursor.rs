@@ -6,15 +6,15 @@ use smithay::{
         allocator::Fourcc,
         renderer::{
             element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
                 surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
-                texture::{TextureBuffer, TextureRenderElement},
                 Kind,
             },
             ImportAll, ImportMem, Renderer,
         },
     },
     input::{
-        pointer::{CursorImageAttributes, CursorImageStatus},
+        pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus},
         Seat,
     },
     reexports::wayland_server::protocol::wl_surface,
@@ -22,14 +22,7 @@ use smithay::{
     utils::{IsAlive, Logical, Monotonic, Point, Scale, Time, Transform},
     wayland::compositor::{get_role, with_states},
 };
-use std::{
-    any::{Any, TypeId},
-    cell::RefCell,
-    collections::HashMap,
-    io::Read,
-    sync::Mutex,
-    time::Duration,
-};
+use std::{cell::RefCell, collections::HashMap, io::Read, sync::Mutex, time::Duration};
 use tracing::warn;
 use xcursor::{
     parser::{parse_xcursor, Image},
@@ -44,6 +37,7 @@ pub enum CursorShape {
     ColResize,
     RowResize,
     Grab,
+    Crosshair,
 }
 
 impl ToString for CursorShape {
@@ -53,6 +47,7 @@ impl ToString for CursorShape {
             CursorShape::ColResize => "col-resize",
             CursorShape::RowResize => "row-resize",
             CursorShape::Grab => "grabbing",
+            CursorShape::Crosshair => "crosshair",
         }
         .to_string()
     }
@@ -66,8 +61,29 @@ pub struct Cursor {
 
 impl Cursor {
     pub fn load(theme: &CursorTheme, shape: CursorShape, size: u32) -> Cursor {
-        let icons = load_icon(&theme, shape)
-            .map_err(|err| warn!(?err, "Unable to load xcursor, using fallback cursor"))
+        Cursor::load_names(theme, &[&shape.to_string()], size)
+    }
+
+    /// Loads a cursor requested by a client, trying the alternative names of themes as well
+    pub fn load_named(theme: &CursorTheme, icon: CursorIcon, size: u32) -> Cursor {
+        let names = std::iter::once(icon.name())
+            .chain(icon.alt_names().iter().copied())
+            .collect::<Vec<_>>();
+        Cursor::load_names(theme, &names, size)
+    }
+
+    fn load_names(theme: &CursorTheme, names: &[&str], size: u32) -> Cursor {
+        let icons = names
+            .iter()
+            .find_map(|name| load_icon(theme, name).ok())
+            .ok_or(Error::NoDefaultCursor)
+            .map_err(|err| {
+                warn!(
+                    ?err,
+                    ?names,
+                    "Unable to load xcursor, using fallback cursor"
+                )
+            })
             .unwrap_or_else(|_| {
                 vec![Image {
                     size: 32,
@@ -126,10 +142,8 @@ enum Error {
     Parse,
 }
 
-fn load_icon(theme: &CursorTheme, shape: CursorShape) -> Result<Vec<Image>, Error> {
-    let icon_path = theme
-        .load_icon(&shape.to_string())
-        .ok_or(Error::NoDefaultCursor)?;
+fn load_icon(theme: &CursorTheme, name: &str) -> Result<Vec<Image>, Error> {
+    let icon_path = theme.load_icon(name).ok_or(Error::NoDefaultCursor)?;
     let mut cursor_file = std::fs::File::open(&icon_path)?;
     let mut cursor_data = Vec::new();
     cursor_file.read_to_end(&mut cursor_data)?;
@@ -137,8 +151,8 @@ fn load_icon(theme: &CursorTheme, shape: CursorShape) -> Result<Vec<Image>, Erro
 }
 
 render_elements! {
-    pub CursorRenderElement<R> where R: ImportAll;
-    Static=TextureRenderElement<<R as Renderer>::TextureId>,
+    pub CursorRenderElement<R> where R: ImportAll + ImportMem;
+    Static=MemoryRenderBufferRenderElement<R>,
     Surface=WaylandSurfaceRenderElement<R>,
 }
 
@@ -149,7 +163,7 @@ pub fn draw_surface_cursor<R>(
     scale: impl Into<Scale<f64>>,
 ) -> Vec<CursorRenderElement<R>>
 where
-    R: Renderer + ImportAll,
+    R: Renderer + ImportAll + ImportMem,
     <R as Renderer>::TextureId: 'static,
 {
     let mut position = location.into();
@@ -182,7 +196,7 @@ pub fn draw_dnd_icon<R>(
     scale: impl Into<Scale<f64>>,
 ) -> Vec<CursorRenderElement<R>>
 where
-    R: Renderer + ImportAll,
+    R: Renderer + ImportAll + ImportMem,
     <R as Renderer>::TextureId: 'static,
 {
     #[cfg(feature = "debug")]
@@ -208,17 +222,42 @@ where
 pub struct CursorState {
     current_cursor: RefCell<CursorShape>,
     pub cursors: HashMap<CursorShape, Cursor>,
+    /// Cursors requested by clients through cursor-shape-v1, loaded on first use
+    named_cursors: RefCell<HashMap<CursorIcon, Cursor>>,
+    theme: CursorTheme,
+    theme_name: String,
+    size: u32,
     current_image: RefCell<Option<Image>>,
-    image_cache: RefCell<HashMap<(TypeId, usize), Vec<(Image, Box<dyn Any + 'static>)>>>,
+    // memory buffers are renderer independent and can be put on cursor planes as is
+    image_cache: RefCell<Vec<(Image, MemoryRenderBuffer)>>,
 }
 
 impl CursorState {
+    /// Name and size of the cursor theme
+    pub fn theme(&self) -> (&str, u32) {
+        (&self.theme_name, self.size)
+    }
+
     pub fn set_shape(&self, shape: CursorShape) {
         *self.current_cursor.borrow_mut() = shape;
     }
+
+    /// Image of a named cursor, a shape set by the compositor for a grab takes precedence
+    pub fn named_image(&self, icon: CursorIcon, scale: u32, millis: u32) -> Image {
+        let shape = *self.current_cursor.borrow();
+        if shape != CursorShape::Default || icon == CursorIcon::Default {
+            return self.cursors.get(&shape).unwrap().get_image(scale, millis);
+        }
+        self.named_cursors
+            .borrow_mut()
+            .entry(icon)
+            .or_insert_with(|| Cursor::load_named(&self.theme, icon, self.size))
+            .get_image(scale, millis)
+    }
 }
 
-pub fn load_cursor_theme() -> (CursorTheme, u32) {
+/// Name and size of the cursor theme configured for the session
+fn cursor_theme_config() -> (String, u32) {
     let name = std::env::var("XCURSOR_THEME")
         .ok()
         .unwrap_or_else(|| "default".into());
@@ -226,12 +265,18 @@ pub fn load_cursor_theme() -> (CursorTheme, u32) {
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(24);
+    (name, size)
+}
+
+pub fn load_cursor_theme() -> (CursorTheme, u32) {
+    let (name, size) = cursor_theme_config();
     (CursorTheme::load(&name), size)
 }
 
 impl Default for CursorState {
     fn default() -> CursorState {
-        let (theme, size) = load_cursor_theme();
+        let (theme_name, size) = cursor_theme_config();
+        let theme = CursorTheme::load(&theme_name);
         CursorState {
             current_cursor: RefCell::new(CursorShape::Default),
             cursors: {
@@ -252,14 +297,66 @@ impl Default for CursorState {
                     CursorShape::Grab,
                     Cursor::load(&theme, CursorShape::Grab, size),
                 );
+                map.insert(
+                    CursorShape::Crosshair,
+                    Cursor::load(&theme, CursorShape::Crosshair, size),
+                );
                 map
             },
+            named_cursors: RefCell::new(HashMap::new()),
+            theme,
+            theme_name,
+            size,
             current_image: RefCell::new(None),
-            image_cache: RefCell::new(HashMap::new()),
+            image_cache: RefCell::new(Vec::new()),
         }
     }
 }
 
+/// Hotspot of the cursor image currently shown for the seat, `None` if it is hidden
+pub fn cursor_hotspot(
+    seat: &Seat<State>,
+    scale: Scale<f64>,
+    time: Time<Monotonic>,
+) -> Option<Point<f64, Logical>> {
+    let cursor_status = seat
+        .user_data()
+        .get::<RefCell<CursorImageStatus>>()
+        .map(|cell| cell.borrow().clone())
+        .filter(|status| !matches!(status, CursorImageStatus::Surface(surface) if !surface.alive()))
+        .unwrap_or(CursorImageStatus::default_named());
+
+    match cursor_status {
+        CursorImageStatus::Surface(ref surface) => {
+            let hotspot = with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<CursorImageAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .hotspot
+            });
+            Some(hotspot.to_f64())
+        }
+        CursorImageStatus::Named(icon) => {
+            let integer_scale = scale.x.max(scale.y).ceil() as u32;
+            let state = seat.user_data().get::<CursorState>().unwrap();
+            let frame = state.named_image(
+                icon,
+                integer_scale,
+                Into::<Duration>::into(time).as_millis() as u32,
+            );
+            Some(
+                Point::<i32, Logical>::from((frame.xhot as i32, frame.yhot as i32))
+                    .to_f64()
+                    .downscale(integer_scale as f64),
+            )
+        }
+        CursorImageStatus::Hidden => None,
+    }
+}
+
 pub fn draw_cursor<R>(
     renderer: &mut R,
     seat: &Seat<State>,
@@ -292,65 +389,58 @@ where
 
     if let CursorImageStatus::Surface(ref wl_surface) = cursor_status {
         return draw_surface_cursor(renderer, wl_surface, location.to_i32_round(), scale);
-    // TODO: Handle other named cursors
-    } else if draw_default && CursorImageStatus::default_named() == cursor_status {
+    } else if let CursorImageStatus::Named(icon) = cursor_status {
+        if !draw_default && icon == CursorIcon::Default {
+            return Vec::new();
+        }
         let integer_scale = scale.x.max(scale.y).ceil() as u32;
 
         let seat_userdata = seat.user_data();
         let state = seat_userdata.get::<CursorState>().unwrap();
-        let frame = state
-            .cursors
-            .get(&*state.current_cursor.borrow())
-            .unwrap()
-            .get_image(
-                integer_scale,
-                Into::<Duration>::into(time).as_millis() as u32,
-            );
-
-        let mut cache = state.image_cache.borrow_mut();
-        let pointer_images = cache
-            .entry((TypeId::of::<TextureBuffer<R::TextureId>>(), renderer.id()))
-            .or_default();
+        let frame = state.named_image(
+            icon,
+            integer_scale,
+            Into::<Duration>::into(time).as_millis() as u32,
+        );
 
-        let maybe_image = pointer_images
-            .iter()
-            .find_map(|(image, texture)| if image == &frame { Some(texture) } else { None })
-            .and_then(|texture| texture.downcast_ref::<TextureBuffer<R::TextureId>>());
-        let pointer_image = match maybe_image {
-            Some(image) => image,
+        let mut pointer_images = state.image_cache.borrow_mut();
+        let pointer_image = match pointer_images.iter().position(|(image, _)| image == &frame) {
+            Some(idx) => pointer_images[idx].1.clone(),
             None => {
-                let texture = TextureBuffer::from_memory(
-                    renderer,
+                let buffer = MemoryRenderBuffer::from_slice(
                     &frame.pixels_rgba,
                     Fourcc::Abgr8888,
                     (frame.width as i32, frame.height as i32),
-                    false,
                     integer_scale as i32,
                     Transform::Normal,
                     None,
-                )
-                .expect("Failed to import cursor bitmap");
-                pointer_images.push((frame.clone(), Box::new(texture.clone())));
-                pointer_images
-                    .last()
-                    .and_then(|(_, i)| i.downcast_ref::<TextureBuffer<R::TextureId>>())
-                    .unwrap()
+                );
+                pointer_images.push((frame.clone(), buffer.clone()));
+                buffer
             }
         };
 
-        let hotspot = Point::<i32, Logical>::from((frame.xhot as i32, frame.yhot as i32)).to_f64();
+        // the hotspot is given in pixels of the (scaled) image
+        let hotspot = Point::<i32, Logical>::from((frame.xhot as i32, frame.yhot as i32))
+            .to_f64()
+            .downscale(integer_scale as f64);
         *state.current_image.borrow_mut() = Some(frame);
 
-        return vec![CursorRenderElement::Static(
-            TextureRenderElement::from_texture_buffer(
-                (location - hotspot).to_physical(scale),
-                pointer_image,
-                None,
-                None,
-                None,
-                Kind::Cursor,
-            ),
-        )];
+        return match MemoryRenderBufferRenderElement::from_buffer(
+            renderer,
+            (location - hotspot).to_physical(scale),
+            &pointer_image,
+            None,
+            None,
+            None,
+            Kind::Cursor,
+        ) {
+            Ok(element) => vec![CursorRenderElement::Static(element)],
+            Err(err) => {
+                warn!(?err, "Failed to import cursor bitmap");
+                Vec::new()
+            }
+        };
     } else {
         Vec::new()
     }
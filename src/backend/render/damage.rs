@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::renderer::{
+        damage::OutputDamageTracker,
+        element::{Element, Kind, RenderElement},
+        gles::{element::PixelShaderElement, Uniform},
+        ImportAll, ImportMem, Renderer,
+    },
+    output::Output,
+    utils::{Physical, Rectangle},
+};
+
+use super::{
+    element::{AsGlowRenderer, CosmicElement},
+    BackdropShader,
+};
+use crate::shell::CosmicMappedRenderElement;
+
+pub static DAMAGE_COLOR: [f32; 3] = [0.937, 0.161, 0.380];
+const DAMAGE_ALPHA: f32 = 0.3;
+/// How long damaged regions stay highlighted
+const HIGHLIGHT_DURATION: Duration = Duration::from_millis(500);
+
+/// Damage of the most recent frames presented on an output.
+///
+/// Owned by the backend surface of the output, so captures of it don't add to the history.
+#[derive(Debug, Default)]
+pub struct DamageOverlay {
+    tracker: Option<OutputDamageTracker>,
+    frames: VecDeque<(Instant, Vec<Rectangle<i32, Physical>>)>,
+}
+
+impl DamageOverlay {
+    /// Whether highlights are still fading out, so the output has to render again
+    pub fn is_fading(&self) -> bool {
+        !self.frames.is_empty()
+    }
+}
+
+/// Puts highlights of the regions damaged during the last frames on top of the given elements,
+/// if `enabled`, otherwise forgets about the damage seen so far.
+///
+/// The highlights fade out with age and add up where damage overlaps,
+/// so frequently redrawn regions stand out.
+pub fn visualize_damage<R>(
+    renderer: &R,
+    output: &Output,
+    overlay: &mut DamageOverlay,
+    enabled: bool,
+    elements: &mut Vec<CosmicElement<R>>,
+) where
+    R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+    <R as Renderer>::TextureId: 'static,
+    CosmicMappedRenderElement<R>: RenderElement<R>,
+{
+    if !enabled {
+        *overlay = DamageOverlay::default();
+        return;
+    }
+
+    let highlights = damage_elements(renderer, output, overlay, elements);
+    elements.splice(
+        0..0,
+        highlights
+            .into_iter()
+            .map(|elem| CosmicElement::from(CosmicMappedRenderElement::from(elem))),
+    );
+}
+
+fn damage_elements<R, E>(
+    renderer: &R,
+    output: &Output,
+    history: &mut DamageOverlay,
+    elements: &[E],
+) -> Vec<PixelShaderElement>
+where
+    R: AsGlowRenderer,
+    E: Element,
+{
+    let tracker = history
+        .tracker
+        .get_or_insert_with(|| OutputDamageTracker::from_output(output));
+
+    // the highlights are not tracked here, so they don't cause damage themselves
+    let now = Instant::now();
+    if let Ok((Some(damage), _)) = tracker.damage_output(1, elements) {
+        history.frames.push_back((now, damage));
+    }
+    history
+        .frames
+        .retain(|(time, _)| now.duration_since(*time) < HIGHLIGHT_DURATION);
+
+    let scale = output.current_scale().fractional_scale();
+    let shader = BackdropShader::get(renderer);
+    history
+        .frames
+        .iter()
+        .flat_map(|(time, damage)| {
+            let age = now.duration_since(*time).as_secs_f32() / HIGHLIGHT_DURATION.as_secs_f32();
+            let alpha = DAMAGE_ALPHA * (1.0 - age);
+            let shader = shader.clone();
+            damage.iter().map(move |rect| {
+                PixelShaderElement::new(
+                    shader.clone(),
+                    rect.to_f64().to_logical(scale).to_i32_round(),
+                    None,
+                    alpha,
+                    vec![
+                        Uniform::new(
+                            "color",
+                            [
+                                DAMAGE_COLOR[0] * alpha,
+                                DAMAGE_COLOR[1] * alpha,
+                                DAMAGE_COLOR[2] * alpha,
+                            ],
+                        ),
+                        Uniform::new("radius", 0.0f32),
+                    ],
+                    Kind::Unspecified,
+                )
+            })
+        })
+        .collect()
+}
@@ -1,16 +1,30 @@
-use crate::shell::{CosmicMappedRenderElement, WorkspaceRenderElement};
+use crate::{
+    shell::{CosmicMappedRenderElement, WorkspaceRenderElement},
+    wayland::protocols::alpha_modifier::alpha_multiplier,
+};
 
 use smithay::{
     backend::renderer::{
         element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            surface::WaylandSurfaceRenderElement,
             utils::{Relocate, RelocateRenderElement},
-            Element, RenderElement, UnderlyingStorage,
+            Element, Kind, RenderElement, UnderlyingStorage,
         },
         glow::{GlowFrame, GlowRenderer},
+        utils::RendererSurfaceStateUserData,
         Frame, ImportAll, ImportMem, Renderer,
     },
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    render_elements,
     utils::{Buffer as BufferCoords, Physical, Point, Rectangle, Scale},
+    wayland::{
+        compositor::{with_surface_tree_downward, TraversalAction},
+        single_pixel_buffer::get_single_pixel_buffer,
+    },
 };
+use std::cell::RefCell;
+use tracing::warn;
 
 #[cfg(feature = "debug")]
 use smithay::backend::renderer::{element::texture::TextureRenderElement, gles::GlesTexture};
@@ -307,3 +321,92 @@ impl<'renderer, 'alloc, 'frame> AsGlowFrame<'frame> for GlMultiFrame<'renderer,
         self.as_mut()
     }
 }
+
+// a surface, or one with a single-pixel buffer drawn as a solid-color quad
+render_elements! {
+    pub SurfaceRenderElement<R> where R: ImportAll;
+    Wayland=WaylandSurfaceRenderElement<R>,
+    SolidColor=SolidColorRenderElement,
+}
+
+/// Like smithay's `render_elements_from_surface_tree`, but multiplies the alpha of every
+/// surface with the factor set through wp_alpha_modifier_v1.
+///
+/// Surfaces with a single-pixel buffer are drawn as solid-color quads, without a texture.
+pub fn render_elements_from_surface_tree<R, E>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    location: impl Into<Point<i32, Physical>>,
+    scale: impl Into<Scale<f64>>,
+    alpha: f32,
+    kind: Kind,
+) -> Vec<E>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+    E: From<SurfaceRenderElement<R>>,
+{
+    let location = location.into().to_f64();
+    let scale = scale.into();
+    let mut elements = Vec::new();
+
+    with_surface_tree_downward(
+        surface,
+        location,
+        |_, states, location| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>();
+            match data.and_then(|data| data.borrow().view()) {
+                Some(view) => {
+                    TraversalAction::DoChildren(*location + view.offset.to_f64().to_physical(scale))
+                }
+                None => TraversalAction::SkipChildren,
+            }
+        },
+        |surface, states, location| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>();
+            let Some(view) = data.and_then(|data| data.borrow().view()) else {
+                return;
+            };
+            let location = *location + view.offset.to_f64().to_physical(scale);
+            // the multiplier only applies to the surface itself, not its subsurfaces
+            let alpha = alpha * alpha_multiplier(states);
+
+            let single_pixel = data.and_then(|data| {
+                let data = data.borrow();
+                let buffer = get_single_pixel_buffer(data.buffer()?).ok()?;
+                Some(buffer.rgba32f())
+            });
+            if let Some(color) = single_pixel {
+                states
+                    .data_map
+                    .insert_if_missing(|| RefCell::new(SolidColorBuffer::new((0, 0), color)));
+                let mut buffer = states
+                    .data_map
+                    .get::<RefCell<SolidColorBuffer>>()
+                    .unwrap()
+                    .borrow_mut();
+                buffer.update(view.dst, color);
+                let element = SolidColorRenderElement::from_buffer(
+                    &buffer,
+                    location.to_i32_round(),
+                    scale,
+                    alpha,
+                    kind,
+                );
+                elements.push(E::from(SurfaceRenderElement::from(element)));
+                return;
+            }
+
+            match WaylandSurfaceRenderElement::from_surface(
+                renderer, surface, states, location, alpha, kind,
+            ) {
+                Ok(Some(element)) => elements.push(E::from(SurfaceRenderElement::from(element))),
+                Ok(None) => {} // surface is not mapped
+                Err(err) => warn!(?err, "Failed to import surface."),
+            }
+        },
+        |_, _, _| true,
+    );
+
+    elements
+}
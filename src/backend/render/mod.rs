@@ -5,18 +5,21 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     sync::Weak,
-    time::Instant,
 };
 
 #[cfg(feature = "debug")]
 use crate::debug::{fps_ui, profiler_ui};
 use crate::{
-    config::WorkspaceLayout,
+    config::{ScaleFilter, ShadowConfig, Wallpaper, WorkspaceLayout},
     shell::{
-        focus::target::WindowGroup, grabs::SeatMoveGrabState, layout::tiling::ANIMATION_DURATION,
-        CosmicMapped, CosmicMappedRenderElement, OverviewMode, Trigger, WorkspaceRenderElement,
+        element::{error_indicator::ERROR_INDICATOR_SIZE, window::CosmicWindowRenderElement},
+        focus::target::WindowGroup,
+        grabs::{SeatMenuGrabState, SeatMoveGrabState, SeatScreenshotGrabState},
+        zoom::{zoom_elements, zoom_level},
+        CosmicMapped, CosmicMappedRenderElement, OverviewMode, Trigger, WorkspaceDelta,
+        WorkspaceRenderElement,
     },
-    state::{Common, Fps},
+    state::{Common, Fps, State},
     utils::prelude::{OutputExt, SeatExt},
     wayland::{
         handlers::{
@@ -33,7 +36,6 @@ use crate::{
 };
 
 use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::FailureReason;
-use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
     backend::{
         allocator::dmabuf::Dmabuf,
@@ -42,13 +44,12 @@ use smithay::{
             buffer_dimensions,
             damage::{Error as RenderError, OutputDamageTracker, RenderOutputResult},
             element::{
-                surface::render_elements_from_surface_tree,
-                utils::{Relocate, RelocateRenderElement},
-                Element, Id, Kind, RenderElement,
+                utils::{Relocate, RelocateRenderElement, RescaleRenderElement},
+                AsRenderElements, Element, Id, Kind, RenderElement,
             },
             gles::{
-                element::PixelShaderElement, GlesError, GlesPixelProgram, GlesRenderer, Uniform,
-                UniformName, UniformType,
+                element::PixelShaderElement, GlesError, GlesPixelProgram, GlesRenderbuffer,
+                GlesRenderer, Uniform, UniformName, UniformType,
             },
             glow::GlowRenderer,
             multigpu::{gbm::GbmGlesBackend, Error as MultiError, MultiFrame, MultiRenderer},
@@ -56,8 +57,9 @@ use smithay::{
             Bind, Blit, ExportMem, ImportAll, ImportMem, Offscreen, Renderer, TextureFilter,
         },
     },
-    desktop::{layer_map_for_output, PopupManager},
+    desktop::{layer_map_for_output, space::SpaceElement, PopupManager},
     output::{Output, OutputNoMode},
+    reexports::calloop::LoopHandle,
     utils::{IsAlive, Logical, Point, Rectangle, Scale},
     wayland::{
         dmabuf::get_dmabuf,
@@ -69,8 +71,13 @@ use tracing::warn;
 
 pub mod cursor;
 use self::cursor::CursorRenderElement;
+pub mod damage;
+use self::damage::DamageOverlay;
 pub mod element;
-use self::element::{AsGlowRenderer, CosmicElement};
+use self::element::{render_elements_from_surface_tree, AsGlowRenderer, CosmicElement};
+pub mod screenshot;
+pub mod wallpaper;
+use self::wallpaper::wallpaper_element;
 
 pub type GlMultiRenderer<'a, 'b> =
     MultiRenderer<'a, 'a, 'b, GbmGlesBackend<GlowRenderer>, GbmGlesBackend<GlowRenderer>>;
@@ -79,12 +86,16 @@ pub type GlMultiFrame<'a, 'b, 'frame> =
 pub type GlMultiError = MultiError<GbmGlesBackend<GlowRenderer>, GbmGlesBackend<GlowRenderer>>;
 
 pub static CLEAR_COLOR: [f32; 4] = [0.153, 0.161, 0.165, 1.0];
+/// Shown while the session is locked, where no lock surface covers the output
+pub static LOCK_COLOR: [f32; 3] = [0.0, 0.0, 0.0];
 pub static GROUP_COLOR: [f32; 3] = [0.788, 0.788, 0.788];
 pub static ACTIVE_GROUP_COLOR: [f32; 3] = [0.58, 0.922, 0.922];
 pub static FOCUS_INDICATOR_COLOR: [f32; 3] = [0.580, 0.921, 0.921];
+pub static BELL_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
 
 pub static OUTLINE_SHADER: &str = include_str!("./shaders/rounded_outline.frag");
 pub static RECTANGLE_SHADER: &str = include_str!("./shaders/rounded_rectangle.frag");
+pub static SHADOW_SHADER: &str = include_str!("./shaders/shadow.frag");
 
 pub struct IndicatorShader(pub GlesPixelProgram);
 
@@ -95,6 +106,8 @@ pub enum Usage {
     MoveGrabIndicator,
     FocusIndicator,
     PotentialGroupIndicator,
+    Shadow,
+    MoveGrabShadow,
 }
 
 #[derive(Clone)]
@@ -164,6 +177,7 @@ impl IndicatorShader {
         thickness: u8,
         scale: f64,
         alpha: f32,
+        color: [f32; 3],
     ) -> PixelShaderElement {
         let t = thickness as i32;
         element_geo.loc -= (t, t).into();
@@ -177,7 +191,7 @@ impl IndicatorShader {
             thickness * 2,
             alpha,
             scale,
-            FOCUS_INDICATOR_COLOR,
+            color,
         )
     }
 
@@ -249,6 +263,12 @@ impl IndicatorShader {
 
 pub struct BackdropShader(pub GlesPixelProgram);
 
+/// Element id of the backdrop of an output, while the session is locked
+struct LockBackdropId(Id);
+
+/// Element id of the visual bell of an output
+struct VisualBellId(Id);
+
 #[derive(PartialEq)]
 struct BackdropSettings {
     radius: f32,
@@ -327,6 +347,91 @@ impl BackdropShader {
     }
 }
 
+pub struct ShadowShader(pub GlesPixelProgram);
+
+/// Radius of the corners of the shape casting the shadow, matching the window headers
+const SHADOW_CORNER_RADIUS: f32 = 8.0;
+
+#[derive(PartialEq)]
+struct ShadowSettings {
+    blur: f32,
+    alpha: f32,
+}
+type ShadowCache = RefCell<HashMap<Key, (ShadowSettings, PixelShaderElement)>>;
+
+impl ShadowShader {
+    pub fn get<R: AsGlowRenderer>(renderer: &R) -> GlesPixelProgram {
+        Borrow::<GlesRenderer>::borrow(renderer.glow_renderer())
+            .egl_context()
+            .user_data()
+            .get::<ShadowShader>()
+            .expect("Custom Shaders not initialized")
+            .0
+            .clone()
+    }
+
+    /// Shadow cast by an element with the given geometry
+    pub fn element<R: AsGlowRenderer>(
+        renderer: &R,
+        key: impl Into<Key>,
+        element_geo: Rectangle<i32, Logical>,
+        config: &ShadowConfig,
+        scale: f64,
+        alpha: f32,
+    ) -> PixelShaderElement {
+        let r = config.radius as i32;
+        let geo = Rectangle::from_loc_and_size(
+            element_geo.loc + Point::from(config.offset) - Point::from((r, r)),
+            element_geo.size + (r * 2, r * 2).into(),
+        );
+        let settings = ShadowSettings {
+            blur: (config.radius as f64 * scale) as f32,
+            alpha: config.opacity * alpha,
+        };
+
+        let user_data = Borrow::<GlesRenderer>::borrow(renderer.glow_renderer())
+            .egl_context()
+            .user_data();
+
+        user_data.insert_if_missing(|| ShadowCache::new(HashMap::new()));
+        let mut cache = user_data.get::<ShadowCache>().unwrap().borrow_mut();
+        cache.retain(|k, _| match k {
+            Key::Static(_) => true,
+            Key::Group(a) => a.upgrade().is_some(),
+            Key::Window(_, w) => w.alive(),
+        });
+
+        let key = key.into();
+        if cache
+            .get(&key)
+            .filter(|(old_settings, _)| &settings == old_settings)
+            .is_none()
+        {
+            let shader = Self::get(renderer);
+
+            let elem = PixelShaderElement::new(
+                shader,
+                geo,
+                None,
+                settings.alpha,
+                vec![
+                    Uniform::new("color", [0.0f32, 0.0, 0.0]),
+                    Uniform::new("radius", SHADOW_CORNER_RADIUS * scale as f32),
+                    Uniform::new("blur", settings.blur),
+                ],
+                Kind::Unspecified,
+            );
+            cache.insert(key.clone(), (settings, elem));
+        }
+
+        let elem = &mut cache.get_mut(&key).unwrap().1;
+        if elem.geometry(1.0.into()).to_logical(1) != geo {
+            elem.resize(geo, None);
+        }
+        elem.clone()
+    }
+}
+
 pub fn init_shaders<R: AsGlowRenderer>(renderer: &mut R) -> Result<(), GlesError> {
     let glow_renderer = renderer.glow_renderer_mut();
     let gles_renderer: &mut GlesRenderer = glow_renderer.borrow_mut();
@@ -346,6 +451,14 @@ pub fn init_shaders<R: AsGlowRenderer>(renderer: &mut R) -> Result<(), GlesError
             UniformName::new("radius", UniformType::_1f),
         ],
     )?;
+    let shadow_shader = gles_renderer.compile_custom_pixel_shader(
+        SHADOW_SHADER,
+        &[
+            UniformName::new("color", UniformType::_3f),
+            UniformName::new("radius", UniformType::_1f),
+            UniformName::new("blur", UniformType::_1f),
+        ],
+    )?;
 
     let egl_context = gles_renderer.egl_context();
     egl_context
@@ -354,6 +467,9 @@ pub fn init_shaders<R: AsGlowRenderer>(renderer: &mut R) -> Result<(), GlesError
     egl_context
         .user_data()
         .insert_if_missing(|| BackdropShader(rectangle_shader));
+    egl_context
+        .user_data()
+        .insert_if_missing(|| ShadowShader(shadow_shader));
 
     Ok(())
 }
@@ -419,10 +535,40 @@ where
             .unwrap()
             .borrow()
             .as_ref()
-            .map(|state| state.render::<E, R>(renderer, seat, output))
+            .map(|grab_state| {
+                grab_state.render::<E, R>(
+                    renderer,
+                    seat,
+                    output,
+                    state.config.static_conf.borders.focused,
+                    &state.config.static_conf.animations,
+                )
+            })
         {
             elements.extend(grab_elements);
         }
+
+        if let Some(menu_elements) = seat
+            .user_data()
+            .get::<SeatMenuGrabState>()
+            .unwrap()
+            .borrow()
+            .as_ref()
+            .map(|state| state.render::<E, R>(renderer, output))
+        {
+            elements.extend(menu_elements);
+        }
+
+        if let Some(screenshot_elements) = seat
+            .user_data()
+            .get::<SeatScreenshotGrabState>()
+            .unwrap()
+            .borrow()
+            .as_ref()
+            .map(|state| state.render::<E, R>(renderer, output))
+        {
+            elements.extend(screenshot_elements);
+        }
     }
 
     elements
@@ -433,7 +579,7 @@ pub fn workspace_elements<R>(
     renderer: &mut R,
     state: &mut Common,
     output: &Output,
-    previous: Option<(WorkspaceHandle, usize, Instant)>,
+    previous: Option<(WorkspaceHandle, usize, WorkspaceDelta)>,
     current: (WorkspaceHandle, usize),
     cursor_mode: CursorMode,
     _fps: &mut Option<&mut Fps>,
@@ -451,6 +597,102 @@ where
 
     let mut elements = cursor_elements(renderer, state, output, cursor_mode);
 
+    // nothing but the lock surface is shown while the session is locked. Outputs without one,
+    // because they were just plugged in or the locker crashed, stay blacked out.
+    if let Some(session_lock) = &state.session_lock {
+        let scale = output.current_scale().fractional_scale();
+        if let Some(lock_surface) = session_lock
+            .surfaces
+            .get(output)
+            .filter(|lock_surface| lock_surface.wl_surface().alive())
+        {
+            elements.extend(
+                render_elements_from_surface_tree::<_, WorkspaceRenderElement<R>>(
+                    renderer,
+                    lock_surface.wl_surface(),
+                    (0, 0),
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into_iter()
+                .map(Into::into),
+            );
+        }
+
+        output
+            .user_data()
+            .insert_if_missing(|| LockBackdropId(Id::new()));
+        let id = output
+            .user_data()
+            .get::<LockBackdropId>()
+            .unwrap()
+            .0
+            .clone();
+        elements.push(
+            CosmicMappedRenderElement::from(BackdropShader::element(
+                renderer,
+                id,
+                Rectangle::from_loc_and_size((0, 0), output.geometry().size),
+                0.,
+                1.0,
+                LOCK_COLOR,
+            ))
+            .into(),
+        );
+        return Ok(elements);
+    }
+
+    // labels identifying the output are above everything, but the cursor
+    if let Some(indicator) = state.shell.identify_indicator(output) {
+        let scale = output.current_scale().fractional_scale();
+        indicator.output_enter(output, output.geometry());
+        elements.extend(
+            indicator
+                .render_elements::<CosmicWindowRenderElement<R>>(
+                    renderer,
+                    Point::<i32, Logical>::from((32, 32)).to_physical_precise_round(scale),
+                    scale.into(),
+                    1.0,
+                )
+                .into_iter()
+                .map(CosmicMappedRenderElement::from)
+                .map(Into::into),
+        );
+    }
+
+    // errors are centered at the top, also above everything but the cursor
+    if let Some(indicator) = state.shell.error_indicator(output) {
+        let scale = output.current_scale().fractional_scale();
+        let x = (output.geometry().size.w - ERROR_INDICATOR_SIZE.0).max(0) / 2;
+        indicator.output_enter(output, output.geometry());
+        elements.extend(
+            indicator
+                .render_elements::<CosmicWindowRenderElement<R>>(
+                    renderer,
+                    Point::<i32, Logical>::from((x, 32)).to_physical_precise_round(scale),
+                    scale.into(),
+                    1.0,
+                )
+                .into_iter()
+                .map(CosmicMappedRenderElement::from)
+                .map(Into::into),
+        );
+    }
+
+    if let Some(area) = state.shell.visual_bell(output) {
+        output
+            .user_data()
+            .insert_if_missing(|| VisualBellId(Id::new()));
+        let id = output.user_data().get::<VisualBellId>().unwrap().0.clone();
+        elements.push(
+            CosmicMappedRenderElement::from(BackdropShader::element(
+                renderer, id, area, 0., 0.4, BELL_COLOR,
+            ))
+            .into(),
+        );
+    }
+
     #[cfg(feature = "debug")]
     {
         let output_geo = output.geometry();
@@ -512,7 +754,12 @@ where
         overview.1.map(|indicator| (indicator, swap_tree)),
     );
 
-    let last_active_seat = state.last_active_seat().clone();
+    // indicate the focus of the seat currently using this output, if any
+    let last_active_seat = std::iter::once(state.last_active_seat())
+        .chain(state.seats())
+        .find(|seat| &seat.active_output() == output)
+        .unwrap_or_else(|| state.last_active_seat())
+        .clone();
     let move_active = last_active_seat
         .user_data()
         .get::<SeatMoveGrabState>()
@@ -549,8 +796,14 @@ where
         Vec::new()
     };
 
+    let wallpaper = state
+        .config
+        .static_conf
+        .wallpaper
+        .for_output(&output.name());
+
     let offset = match previous.as_ref() {
-        Some((previous, previous_idx, start)) => {
+        Some((previous, previous_idx, delta)) => {
             let layout = state.config.static_conf.workspace_layout;
 
             let workspace = state
@@ -560,11 +813,7 @@ where
             let has_fullscreen = workspace.fullscreen.contains_key(output);
             let is_active_space = workspace.outputs().any(|o| o == &active_output);
 
-            let percentage = {
-                let percentage = Instant::now().duration_since(*start).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
-                ease(EaseInOutCubic, 0.0, 1.0, percentage)
-            };
+            let percentage = delta.percentage();
             let offset = Point::<i32, Logical>::from(match (layout, *previous_idx < current.1) {
                 (WorkspaceLayout::Vertical, true) => {
                     (0, (-output_size.h as f32 * percentage).round() as i32)
@@ -590,6 +839,8 @@ where
                     overview.clone(),
                     resize_indicator.clone(),
                     state.config.static_conf.active_hint,
+                    &state.config.static_conf.borders,
+                    &state.config.static_conf.shadows,
                 )
                 .map_err(|_| OutputNoMode)?;
             elements.extend(p_elements.into_iter().map(|p_element| {
@@ -608,8 +859,13 @@ where
             }));
 
             if !has_fullscreen {
-                let (w_elements, p_elements) =
-                    background_layer_elements(renderer, output, exclude_workspace_overview);
+                let (w_elements, p_elements) = background_layer_elements(
+                    renderer,
+                    &state.event_loop_handle,
+                    output,
+                    wallpaper,
+                    exclude_workspace_overview,
+                );
                 elements.extend(p_elements.into_iter().map(|p_element| {
                     CosmicElement::Workspace(RelocateRenderElement::from_element(
                         p_element,
@@ -633,7 +889,17 @@ where
                 (WorkspaceLayout::Horizontal, false) => (-(output_size.w - offset.y), 0),
             })
         }
-        None => (0, 0).into(),
+        None => {
+            let overscroll = state.shell.workspaces.overscroll(output);
+            Point::<i32, Logical>::from(match state.config.static_conf.workspace_layout {
+                WorkspaceLayout::Vertical => {
+                    (0, (-output_size.h as f64 * overscroll).round() as i32)
+                }
+                WorkspaceLayout::Horizontal => {
+                    ((-output_size.w as f64 * overscroll).round() as i32, 0)
+                }
+            })
+        }
     };
 
     let is_active_space = workspace.outputs().any(|o| o == &active_output);
@@ -648,6 +914,8 @@ where
             overview,
             resize_indicator,
             state.config.static_conf.active_hint,
+            &state.config.static_conf.borders,
+            &state.config.static_conf.shadows,
         )
         .map_err(|_| OutputNoMode)?;
     elements.extend(p_elements.into_iter().map(|p_element| {
@@ -666,8 +934,13 @@ where
     }));
 
     if has_fullscreen.is_none() {
-        let (w_elements, p_elements) =
-            background_layer_elements(renderer, output, exclude_workspace_overview);
+        let (w_elements, p_elements) = background_layer_elements(
+            renderer,
+            &state.event_loop_handle,
+            output,
+            wallpaper,
+            exclude_workspace_overview,
+        );
 
         elements.extend(p_elements.into_iter().map(|p_element| {
             CosmicElement::Workspace(RelocateRenderElement::from_element(
@@ -761,7 +1034,9 @@ where
 // bottom and background layer surfaces
 pub fn background_layer_elements<R>(
     renderer: &mut R,
+    loop_handle: &LoopHandle<'static, State>,
     output: &Output,
+    wallpaper: Option<&Wallpaper>,
     exclude_workspace_overview: bool,
 ) -> (
     Vec<WorkspaceRenderElement<R>>,
@@ -784,9 +1059,61 @@ where
     );
     layer_elements.extend(more.0);
     popup_elements.extend(more.1);
+    if let Some(wallpaper) = wallpaper {
+        layer_elements
+            .extend(wallpaper_element(renderer, loop_handle, output, wallpaper).map(Into::into));
+    }
     (layer_elements, popup_elements)
 }
 
+/// Filter requested by the force-scaled windows visible on an output
+fn force_scale_filter(state: &Common, output: &Output) -> TextureFilter {
+    let workspace = state.shell.active_space(output);
+    let nearest = workspace
+        .windows()
+        .chain(
+            workspace
+                .get_fullscreen(output)
+                .map(|window| window.surface()),
+        )
+        .filter_map(|window| window.force_scaling())
+        .any(|scaling| scaling.filter == ScaleFilter::Nearest);
+    if nearest {
+        TextureFilter::Nearest
+    } else {
+        TextureFilter::Linear
+    }
+}
+
+/// Magnifies the elements of an output by the given level around the pointer
+pub fn zoomed_elements<R, E>(
+    renderer: &mut R,
+    state: &Common,
+    output: &Output,
+    level: f64,
+    elements: Vec<E>,
+) -> Vec<RescaleRenderElement<E>>
+where
+    R: Renderer,
+    E: Element,
+{
+    let filter = if level > 1.0 {
+        state.config.static_conf.zoom.filter.into()
+    } else {
+        force_scale_filter(state, output)
+    };
+    if let Err(err) = renderer.upscale_filter(filter) {
+        warn!(?err, "Failed to set upscale filter.");
+    }
+
+    let pointer = state
+        .last_active_seat()
+        .get_pointer()
+        .unwrap()
+        .current_location();
+    zoom_elements(output, level, pointer, elements)
+}
+
 pub fn render_output<R, Target, OffTarget, Source>(
     gpu: Option<&DrmNode>,
     renderer: &mut R,
@@ -798,6 +1125,7 @@ pub fn render_output<R, Target, OffTarget, Source>(
     cursor_mode: CursorMode,
     screencopy: Option<(Source, &[(ScreencopySession, BufferParams)])>,
     fps: Option<&mut Fps>,
+    damage_overlay: Option<&mut DamageOverlay>,
 ) -> Result<RenderOutputResult, RenderError<R>>
 where
     R: Renderer
@@ -807,6 +1135,7 @@ where
         + Bind<Dmabuf>
         + Bind<Target>
         + Offscreen<OffTarget>
+        + Offscreen<GlesRenderbuffer>
         + Blit<Source>
         + AsGlowRenderer,
     <R as Renderer>::TextureId: Clone + 'static,
@@ -816,17 +1145,11 @@ where
     WorkspaceRenderElement<R>: RenderElement<R>,
     Source: Clone,
 {
-    // TODO: if session locked, render that. surface.
-    if let Some(session_lock) = &state.session_lock {
-        if let Some(surface) = session_lock.surfaces.get(output) {
-        }
-    }
-
     let (previous_workspace, workspace) = state.shell.workspaces.active(output);
     let (previous_idx, idx) = state.shell.workspaces.active_num(output);
     let previous_workspace = previous_workspace
         .zip(previous_idx)
-        .map(|((w, start), idx)| (w.handle, idx, start));
+        .map(|((w, delta), idx)| (w.handle, idx, delta));
     let workspace = (workspace.handle, idx);
 
     let result = render_workspace(
@@ -842,6 +1165,7 @@ where
         cursor_mode,
         screencopy,
         fps,
+        damage_overlay,
         false,
     );
 
@@ -856,11 +1180,12 @@ pub fn render_workspace<R, Target, OffTarget, Source>(
     age: usize,
     state: &mut Common,
     output: &Output,
-    previous: Option<(WorkspaceHandle, usize, Instant)>,
+    previous: Option<(WorkspaceHandle, usize, WorkspaceDelta)>,
     current: (WorkspaceHandle, usize),
     mut cursor_mode: CursorMode,
     screencopy: Option<(Source, &[(ScreencopySession, BufferParams)])>,
     mut fps: Option<&mut Fps>,
+    damage_overlay: Option<&mut DamageOverlay>,
     exclude_workspace_overview: bool,
 ) -> Result<RenderOutputResult, RenderError<R>>
 where
@@ -871,6 +1196,7 @@ where
         + Bind<Dmabuf>
         + Bind<Target>
         + Offscreen<OffTarget>
+        + Offscreen<GlesRenderbuffer>
         + Blit<Source>
         + AsGlowRenderer,
     <R as Renderer>::TextureId: Clone + 'static,
@@ -909,7 +1235,7 @@ where
         cursor_mode = CursorMode::All;
     };
 
-    let elements: Vec<CosmicElement<R>> = workspace_elements(
+    let mut elements: Vec<CosmicElement<R>> = workspace_elements(
         gpu,
         renderer,
         state,
@@ -920,6 +1246,22 @@ where
         &mut fps,
         exclude_workspace_overview,
     )?;
+    if let Some(overlay) = damage_overlay {
+        damage::visualize_damage(
+            renderer,
+            output,
+            overlay,
+            state.visualize_damage,
+            &mut elements,
+        );
+    }
+    // workspace captures are not magnified
+    let zoom = if exclude_workspace_overview {
+        1.0
+    } else {
+        zoom_level(output)
+    };
+    let elements = zoomed_elements(renderer, state, output, zoom, elements);
     if let Some(fps) = fps.as_mut() {
         fps.elements();
     }
@@ -952,9 +1294,9 @@ where
                                             with_buffer_contents(buffer, |_, _, data| shm_format_to_fourcc(data.format))
                                                 .map_err(|_| OutputNoMode)? // eh, we have to do some error
                                                 .expect("We should be able to convert all hardcoded shm screencopy formats");
-                                let render_buffer = renderer
-                                    .create_buffer(format, size)
-                                    .map_err(RenderError::Rendering)?;
+                                let render_buffer =
+                                    Offscreen::<OffTarget>::create_buffer(renderer, format, size)
+                                        .map_err(RenderError::Rendering)?;
                                 renderer
                                     .bind(render_buffer)
                                     .map_err(RenderError::Rendering)?;
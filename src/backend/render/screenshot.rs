@@ -0,0 +1,406 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use smithay::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, Fourcc},
+        drm::DrmNode,
+        renderer::{
+            damage::{Error as RenderError, OutputDamageTracker},
+            element::{AsRenderElements, RenderElement},
+            gles::{GlesError, GlesRenderbuffer},
+            Bind, Blit, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
+        },
+    },
+    input::Seat,
+    output::Output,
+    reexports::calloop::channel,
+    utils::{Buffer as BufferCoords, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+    wayland::selection::{data_device::set_data_device_selection, SelectionTarget},
+};
+use tracing::{info, warn};
+
+use super::{
+    element::{AsGlowRenderer, CosmicElement, SurfaceRenderElement},
+    render_output, CursorMode,
+};
+use crate::{
+    shell::{CosmicMappedRenderElement, CosmicSurface, WorkspaceRenderElement},
+    state::{BackendData, Common, State},
+    utils::prelude::OutputExt,
+    wayland::handlers::selection::SelectionData,
+};
+
+pub const PNG_MIME_TYPE: &str = "image/png";
+
+/// Rgba pixels read back from the renderer
+#[derive(Debug)]
+pub struct RgbaImage {
+    pub size: Size<i32, Physical>,
+    pub pixels: Vec<u8>,
+}
+
+/// Captures the given region of an output, or all of it, and saves the image
+/// and/or offers it on the clipboard of the seat, as configured.
+///
+/// The region is in global coordinates and cut to the part covering the output.
+/// Encoding and saving happen on their own thread, the image is offered on the
+/// clipboard once it is encoded.
+pub fn take_screenshot(
+    state: &mut State,
+    seat: &Seat<State>,
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) {
+    let config = state.common.config.static_conf.screenshots.clone();
+    let directory = config.save.then(|| config.directory()).flatten();
+    if config.save && directory.is_none() {
+        warn!("No directory to save screenshots to, set one in the config.");
+    }
+    if !config.clipboard && directory.is_none() {
+        return;
+    }
+
+    let (size, pixels) = match capture_rgba(state, output, region) {
+        Ok(Some(image)) => image,
+        Ok(None) => return,
+        Err(err) => {
+            warn!(?err, "Failed to take screenshot of {}.", output.name());
+            return;
+        }
+    };
+
+    let (sender, receiver) = channel::channel::<Arc<Vec<u8>>>();
+    if config.clipboard {
+        let seat = seat.clone();
+        // the source is removed with the sender, after the thread is done
+        if let Err(err) =
+            state
+                .common
+                .event_loop_handle
+                .insert_source(receiver, move |event, _, state| {
+                    let channel::Event::Msg(image) = event else {
+                        return;
+                    };
+                    set_data_device_selection(
+                        &state.common.display_handle,
+                        &seat,
+                        vec![PNG_MIME_TYPE.to_string()],
+                        SelectionData::Screenshot(image),
+                    );
+                    state.offer_selection_to_xwayland(
+                        &seat,
+                        SelectionTarget::Clipboard,
+                        Some(vec![PNG_MIME_TYPE.to_string()]),
+                    );
+                })
+        {
+            warn!(?err, "Failed to offer screenshot on the clipboard.");
+        }
+    }
+
+    std::thread::spawn(move || {
+        let image = match encode_png(size, &pixels) {
+            Ok(image) => Arc::new(image),
+            Err(err) => {
+                warn!(?err, "Failed to encode screenshot.");
+                return;
+            }
+        };
+        if config.clipboard {
+            let _ = sender.send(image.clone());
+        }
+        if let Some(directory) = directory {
+            match save_png(&directory, &image) {
+                Ok(path) => info!("Saved screenshot to {}.", path.display()),
+                Err(err) => warn!(?err, "Failed to save screenshot."),
+            }
+        }
+    });
+}
+
+/// Renders an output, e.g. for the source picker of a screencast
+pub fn output_thumbnail(state: &mut State, output: &Output) -> Result<RgbaImage> {
+    let (size, pixels) =
+        capture_rgba(state, output, None)?.ok_or_else(|| anyhow!("Output has no mode"))?;
+    Ok(RgbaImage { size, pixels })
+}
+
+/// Renders a window fitting into `max_size`, even if it is covered or on another workspace
+pub fn window_thumbnail(
+    state: &mut State,
+    window: &CosmicSurface,
+    max_size: u32,
+) -> Result<RgbaImage> {
+    let geometry = window.geometry();
+    if geometry.size.w <= 0 || geometry.size.h <= 0 {
+        return Err(anyhow!("Window has no size"));
+    }
+    let size = fit_size(
+        Size::from((geometry.size.w, geometry.size.h)),
+        max_size as f64,
+    );
+    let scale = size.w as f64 / geometry.size.w as f64;
+
+    let pixels = match &mut state.backend {
+        BackendData::Kms(kms) => {
+            let mut renderer = kms.api.single_renderer(&kms.primary)?;
+            capture_window(&mut renderer, window, size, scale).map_err(|err| match err {
+                RenderError::OutputNoMode(x) => anyhow::Error::from(x),
+                RenderError::Rendering(x) => x.into(),
+            })?
+        }
+        BackendData::Winit(winit) => capture_window(winit.backend.renderer(), window, size, scale)?,
+        BackendData::X11(x11) => capture_window(&mut x11.renderer, window, size, scale)?,
+        BackendData::Headless(headless) => {
+            capture_window(&mut headless.renderer, window, size, scale)?
+        }
+        _ => return Err(anyhow!("No renderer available")),
+    };
+    Ok(RgbaImage { size, pixels })
+}
+
+/// Encodes a thumbnail as png, scaled down to fit into `max_size`.
+///
+/// Slow for large images, so better not called on the event loop.
+pub fn encode_thumbnail(image: RgbaImage, max_size: u32) -> Result<Vec<u8>> {
+    let target = fit_size(image.size, max_size as f64);
+    if target == image.size {
+        return encode_png(image.size, &image.pixels);
+    }
+    encode_png(target, &downscale(&image.pixels, image.size, target))
+}
+
+/// Captures the given region of an output, or all of it, as rgba pixels
+fn capture_rgba(
+    state: &mut State,
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) -> Result<Option<(Size<i32, Physical>, Vec<u8>)>> {
+    let scale = output.current_scale().fractional_scale();
+    let output_geo = output.geometry();
+    let Some(region) = region.map_or(Some(output_geo), |region| region.intersection(output_geo))
+    else {
+        return Ok(None);
+    };
+    let size = output_geo.size.to_f64().to_physical(scale).to_i32_round();
+    let Some(rect) = Rectangle::<i32, Logical>::from_loc_and_size(
+        region.loc - output_geo.loc,
+        region.size,
+    )
+    .to_f64()
+    .to_physical(scale)
+    .to_i32_round()
+    .intersection(Rectangle::from_loc_and_size((0, 0), size))
+    .filter(|rect| rect.size.w > 0 && rect.size.h > 0) else {
+        return Ok(None);
+    };
+
+    let common = &mut state.common;
+    let mut pixels = match &mut state.backend {
+        BackendData::Kms(kms) => {
+            let node = kms.target_node_for_output(output).unwrap_or(kms.primary);
+            let mut renderer = kms.api.single_renderer(&node)?;
+            capture(Some(&node), &mut renderer, common, output, size, rect).map_err(
+                |err| match err {
+                    RenderError::OutputNoMode(x) => anyhow::Error::from(x),
+                    RenderError::Rendering(x) => x.into(),
+                },
+            )?
+        }
+        BackendData::Winit(winit) => {
+            capture(None, winit.backend.renderer(), common, output, size, rect)?
+        }
+        BackendData::X11(x11) => capture(None, &mut x11.renderer, common, output, size, rect)?,
+        BackendData::Headless(headless) => {
+            capture(None, &mut headless.renderer, common, output, size, rect)?
+        }
+        _ => return Err(anyhow!("No renderer available")),
+    };
+
+    // what is behind transparent regions is of no interest
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+
+    Ok(Some((rect.size, pixels)))
+}
+
+fn encode_png(size: Size<i32, Physical>, pixels: &[u8]) -> Result<Vec<u8>> {
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, size.w as u32, size.h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(image)
+}
+
+/// Largest size with the aspect ratio of `size`, that fits into a square of `max_size`
+fn fit_size(size: Size<i32, Physical>, max_size: f64) -> Size<i32, Physical> {
+    let factor = (max_size / size.w.max(size.h) as f64).min(1.0);
+    Size::from((
+        ((size.w as f64 * factor).round() as i32).max(1),
+        ((size.h as f64 * factor).round() as i32).max(1),
+    ))
+}
+
+/// Scales rgba pixels down by averaging the pixels covered by each target pixel
+fn downscale(pixels: &[u8], size: Size<i32, Physical>, target: Size<i32, Physical>) -> Vec<u8> {
+    let (w, h) = (size.w as usize, size.h as usize);
+    let (tw, th) = (target.w as usize, target.h as usize);
+    let mut result = Vec::with_capacity(tw * th * 4);
+    for ty in 0..th {
+        let (y0, y1) = (ty * h / th, ((ty + 1) * h / th).max(ty * h / th + 1));
+        for tx in 0..tw {
+            let (x0, x1) = (tx * w / tw, ((tx + 1) * w / tw).max(tx * w / tw + 1));
+            let mut sum = [0u32; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = &pixels[(y * w + x) * 4..][..4];
+                    for (sum, value) in sum.iter_mut().zip(pixel) {
+                        *sum += *value as u32;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            result.extend(sum.map(|sum| (sum / count) as u8));
+        }
+    }
+    result
+}
+
+/// Renders a window offscreen at the given scale and reads it back as rgba
+fn capture_window<R>(
+    renderer: &mut R,
+    window: &CosmicSurface,
+    size: Size<i32, Physical>,
+    scale: f64,
+) -> Result<Vec<u8>, RenderError<R>>
+where
+    R: Renderer + ImportAll + ExportMem + Offscreen<GlesRenderbuffer>,
+    <R as Renderer>::TextureId: Clone + 'static,
+{
+    let buffer = Offscreen::<GlesRenderbuffer>::create_buffer(
+        renderer,
+        Fourcc::Abgr8888,
+        Size::<i32, BufferCoords>::from((size.w, size.h)),
+    )
+    .map_err(RenderError::Rendering)?;
+    renderer.bind(buffer).map_err(RenderError::Rendering)?;
+
+    let geometry = window.geometry();
+    let elements = AsRenderElements::<R>::render_elements::<SurfaceRenderElement<R>>(
+        window,
+        renderer,
+        Point::<i32, Logical>::from((-geometry.loc.x, -geometry.loc.y))
+            .to_physical_precise_round(scale),
+        Scale::from(scale),
+        1.0,
+    );
+    let mut damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+    damage_tracker.render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 0.0])?;
+
+    let mapping = renderer
+        .copy_framebuffer(
+            Rectangle::<i32, BufferCoords>::from_loc_and_size((0, 0), (size.w, size.h)),
+            Fourcc::Abgr8888,
+        )
+        .map_err(RenderError::Rendering)?;
+    let data = renderer
+        .map_texture(&mapping)
+        .map_err(RenderError::Rendering)?;
+    Ok(data.to_vec())
+}
+
+/// Renders the whole output offscreen, without any cursor, and reads back the given rect as rgba
+fn capture<R>(
+    node: Option<&DrmNode>,
+    renderer: &mut R,
+    common: &mut Common,
+    output: &Output,
+    size: Size<i32, Physical>,
+    rect: Rectangle<i32, Physical>,
+) -> Result<Vec<u8>, RenderError<R>>
+where
+    R: Renderer
+        + ImportAll
+        + ImportMem
+        + ExportMem
+        + Bind<Dmabuf>
+        + Offscreen<GlesRenderbuffer>
+        + Blit<Dmabuf>
+        + AsGlowRenderer,
+    <R as Renderer>::TextureId: Clone + 'static,
+    <R as Renderer>::Error: From<GlesError>,
+    CosmicElement<R>: RenderElement<R>,
+    CosmicMappedRenderElement<R>: RenderElement<R>,
+    WorkspaceRenderElement<R>: RenderElement<R>,
+{
+    let scale = output.current_scale().fractional_scale();
+    let buffer = Offscreen::<GlesRenderbuffer>::create_buffer(
+        renderer,
+        Fourcc::Abgr8888,
+        Size::<i32, BufferCoords>::from((size.w, size.h)),
+    )
+    .map_err(RenderError::Rendering)?;
+    // a fresh tracker without transform, so the full frame is drawn upright
+    let mut damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+    render_output::<_, _, GlesRenderbuffer, Dmabuf>(
+        node,
+        renderer,
+        buffer,
+        &mut damage_tracker,
+        0,
+        common,
+        output,
+        CursorMode::None,
+        None,
+        None,
+        None,
+    )?;
+
+    let mapping = renderer
+        .copy_framebuffer(
+            Rectangle::<i32, BufferCoords>::from_loc_and_size(
+                (rect.loc.x, rect.loc.y),
+                (rect.size.w, rect.size.h),
+            ),
+            Fourcc::Abgr8888,
+        )
+        .map_err(RenderError::Rendering)?;
+    let data = renderer
+        .map_texture(&mapping)
+        .map_err(RenderError::Rendering)?;
+    Ok(data.to_vec())
+}
+
+fn save_png(directory: &Path, image: &[u8]) -> Result<PathBuf> {
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create {}", directory.display()))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let mut path = directory.join(format!("screenshot-{}.png", timestamp));
+    let mut counter = 1;
+    while path.exists() {
+        path = directory.join(format!("screenshot-{}-{}.png", timestamp, counter));
+        counter += 1;
+    }
+
+    let mut file = std::fs::File::create(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(image)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    channel,
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use once_cell::sync::Lazy;
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                Kind,
+            },
+            ImportMem, Renderer,
+        },
+    },
+    output::Output,
+    utils::{Physical, Point, Rectangle, Size, Transform},
+};
+use tiny_skia::{
+    Color, FilterQuality, Paint, Pattern, Pixmap, PixmapPaint, Rect, SpreadMode,
+    Transform as SkiaTransform,
+};
+use tracing::warn;
+
+use crate::{
+    config::{Wallpaper, WallpaperFit, WallpaperSource},
+    state::State,
+    utils::prelude::OutputExt,
+};
+
+/// Reference point all slideshows count their intervals from, so they switch in sync
+static SLIDESHOW_EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Wallpaper currently shown on an output
+struct WallpaperState {
+    wallpaper: Wallpaper,
+    size: Size<i32, Physical>,
+    /// Images of a slideshow, listed when it was configured
+    images: Vec<PathBuf>,
+    image: Option<PathBuf>,
+    /// Last rendered wallpaper, shown until the current one is ready
+    buffer: Option<MemoryRenderBuffer>,
+}
+type WallpaperCell = RefCell<Option<WallpaperState>>;
+
+/// Element drawing the given wallpaper over the whole output.
+///
+/// The image is only decoded and fit to the output again, when the wallpaper,
+/// the size of the output or the image of a slideshow changes. That happens on
+/// its own thread, the output is redrawn once it is done.
+pub fn wallpaper_element<R>(
+    renderer: &mut R,
+    loop_handle: &LoopHandle<'static, State>,
+    output: &Output,
+    wallpaper: &Wallpaper,
+) -> Option<MemoryRenderBufferRenderElement<R>>
+where
+    R: Renderer + ImportMem,
+    <R as Renderer>::TextureId: 'static,
+{
+    let scale = output.current_scale().fractional_scale();
+    let geometry = output.geometry();
+    let size = geometry.size.to_f64().to_physical(scale).to_i32_round();
+    if size.w <= 0 || size.h <= 0 {
+        return None;
+    }
+
+    let user_data = output.user_data();
+    user_data.insert_if_missing(|| WallpaperCell::new(None));
+    let mut state = user_data.get::<WallpaperCell>().unwrap().borrow_mut();
+
+    match state.as_mut() {
+        Some(state) if &state.wallpaper == wallpaper && state.size == size => {
+            let image = current_image(wallpaper, &state.images);
+            if image != state.image {
+                state.image = image;
+                load_wallpaper(loop_handle, output, state);
+            }
+        }
+        _ => {
+            let images = match &wallpaper.source {
+                WallpaperSource::Slideshow { directory, .. } => slideshow_images(directory),
+                _ => Vec::new(),
+            };
+            let image = current_image(wallpaper, &images);
+            let buffer = state.take().and_then(|state| state.buffer);
+            let state = state.insert(WallpaperState {
+                wallpaper: wallpaper.clone(),
+                size,
+                images,
+                image,
+                buffer,
+            });
+            load_wallpaper(loop_handle, output, state);
+        }
+    }
+    let buffer = state.as_ref().and_then(|state| state.buffer.as_ref())?;
+
+    match MemoryRenderBufferRenderElement::from_buffer(
+        renderer,
+        Point::<f64, Physical>::from((0.0, 0.0)),
+        buffer,
+        None,
+        None,
+        Some(geometry.size),
+        Kind::Unspecified,
+    ) {
+        Ok(element) => Some(element),
+        Err(err) => {
+            warn!(?err, "Failed to import wallpaper.");
+            None
+        }
+    }
+}
+
+/// Redraws outputs with a slideshow, whenever it moves on to the next image
+pub fn init_slideshow_timer(handle: &LoopHandle<'static, State>) {
+    if let Err(err) = handle.insert_source(Timer::immediate(), |_, _, state| {
+        let mut next = Duration::from_secs(60);
+        let elapsed = Instant::now().duration_since(*SLIDESHOW_EPOCH).as_secs();
+        let outputs = state.common.shell.outputs().cloned().collect::<Vec<_>>();
+        for output in outputs {
+            let Some(Wallpaper {
+                source: WallpaperSource::Slideshow { interval, .. },
+                ..
+            }) = state
+                .common
+                .config
+                .static_conf
+                .wallpaper
+                .for_output(&output.name())
+            else {
+                continue;
+            };
+            let interval = (*interval).max(1);
+            next = next.min(Duration::from_secs(interval - elapsed % interval));
+            state
+                .backend
+                .schedule_render(&state.common.event_loop_handle, &output, None);
+        }
+        TimeoutAction::ToDuration(next)
+    }) {
+        warn!(?err, "Failed to start wallpaper slideshow timer.");
+    }
+}
+
+/// Renders the wallpaper of an output on its own thread, the buffer of the output
+/// is replaced once it is done, unless the wallpaper changed again in the meantime
+fn load_wallpaper(
+    loop_handle: &LoopHandle<'static, State>,
+    output: &Output,
+    state: &WallpaperState,
+) {
+    let (sender, receiver) = channel::channel();
+    let (wallpaper, image, size) = (state.wallpaper.clone(), state.image.clone(), state.size);
+    let key = (wallpaper.clone(), image.clone(), size);
+    let output = output.clone();
+    // the source is removed with the sender, after the thread is done
+    if let Err(err) = loop_handle.insert_source(receiver, move |event, _, state| {
+        let channel::Event::Msg(pixmap) = event else {
+            return;
+        };
+        let Some(cell) = output.user_data().get::<WallpaperCell>() else {
+            return;
+        };
+        if let Some(current) = cell.borrow_mut().as_mut().filter(|current| {
+            (&current.wallpaper, &current.image, current.size) == (&key.0, &key.1, key.2)
+        }) {
+            current.buffer = Some(wallpaper_buffer(&pixmap));
+        } else {
+            return;
+        }
+        state
+            .backend
+            .schedule_render(&state.common.event_loop_handle, &output, None);
+    }) {
+        warn!(?err, "Failed to load wallpaper.");
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let _ = sender.send(render_wallpaper(&wallpaper, image.as_deref(), size));
+    });
+}
+
+fn slideshow_images(directory: &Path) -> Vec<PathBuf> {
+    let mut images = match std::fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("png"))
+            })
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            warn!(
+                ?err,
+                "Failed to list wallpaper slideshow {}.",
+                directory.display()
+            );
+            Vec::new()
+        }
+    };
+    images.sort();
+    images
+}
+
+fn current_image(wallpaper: &Wallpaper, images: &[PathBuf]) -> Option<PathBuf> {
+    match &wallpaper.source {
+        WallpaperSource::Color(_) => None,
+        WallpaperSource::Image(path) => Some(path.clone()),
+        WallpaperSource::Slideshow { interval, .. } => {
+            if images.is_empty() {
+                return None;
+            }
+            let elapsed = Instant::now().duration_since(*SLIDESHOW_EPOCH).as_secs();
+            let idx = (elapsed / (*interval).max(1)) as usize % images.len();
+            Some(images[idx].clone())
+        }
+    }
+}
+
+fn color([r, g, b]: [f32; 3]) -> Color {
+    Color::from_rgba(r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), 1.0).unwrap()
+}
+
+fn render_wallpaper(
+    wallpaper: &Wallpaper,
+    image: Option<&Path>,
+    size: Size<i32, Physical>,
+) -> Pixmap {
+    let mut pixmap = Pixmap::new(size.w as u32, size.h as u32).unwrap();
+    match &wallpaper.source {
+        WallpaperSource::Color(rgb) => pixmap.fill(color(*rgb)),
+        _ => pixmap.fill(color(wallpaper.background)),
+    }
+
+    if let Some(path) = image {
+        match Pixmap::load_png(path) {
+            Ok(image) => draw_image(&mut pixmap, &image, wallpaper.fit),
+            Err(err) => warn!(?err, "Failed to load wallpaper {}.", path.display()),
+        }
+    }
+    pixmap
+}
+
+fn wallpaper_buffer(pixmap: &Pixmap) -> MemoryRenderBuffer {
+    let (w, h) = (pixmap.width() as i32, pixmap.height() as i32);
+    MemoryRenderBuffer::from_slice(
+        pixmap.data(),
+        Fourcc::Abgr8888,
+        (w, h),
+        1,
+        Transform::Normal,
+        Some(vec![Rectangle::from_loc_and_size((0, 0), (w, h))]),
+    )
+}
+
+fn draw_image(target: &mut Pixmap, image: &Pixmap, fit: WallpaperFit) {
+    let (width, height) = (target.width() as f32, target.height() as f32);
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+
+    if fit == WallpaperFit::Tile {
+        let paint = Paint {
+            shader: Pattern::new(
+                image.as_ref(),
+                SpreadMode::Repeat,
+                FilterQuality::Nearest,
+                1.0,
+                SkiaTransform::identity(),
+            ),
+            ..Default::default()
+        };
+        let rect = Rect::from_xywh(0.0, 0.0, width, height).unwrap();
+        target.fill_rect(rect, &paint, SkiaTransform::identity(), None);
+        return;
+    }
+
+    let (scale_x, scale_y) = match fit {
+        WallpaperFit::Zoom => {
+            let scale = (width / image_width).max(height / image_height);
+            (scale, scale)
+        }
+        WallpaperFit::Fit => {
+            let scale = (width / image_width).min(height / image_height);
+            (scale, scale)
+        }
+        WallpaperFit::Stretch => (width / image_width, height / image_height),
+        WallpaperFit::Center | WallpaperFit::Tile => (1.0, 1.0),
+    };
+    // centered on the output
+    let transform = SkiaTransform::from_row(
+        scale_x,
+        0.0,
+        0.0,
+        scale_y,
+        (width - image_width * scale_x) / 2.0,
+        (height - image_height * scale_y) / 2.0,
+    );
+    target.draw_pixmap(
+        0,
+        0,
+        image.as_ref(),
+        &PixmapPaint {
+            quality: FilterQuality::Bicubic,
+            ..Default::default()
+        },
+        transform,
+        None,
+    );
+}
@@ -2,7 +2,7 @@
 
 use crate::{
     backend::render,
-    config::OutputConfig,
+    config::{ColorFilter, OutputConfig},
     input::Devices,
     state::{BackendData, Common},
     utils::prelude::*,
@@ -20,7 +20,6 @@ use smithay::{
         },
         winit::{self, WinitEvent, WinitGraphicsBackend, WinitVirtualDevice},
     },
-    desktop::layer_map_for_output,
     output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
     reexports::{
         calloop::{ping, EventLoop},
@@ -36,7 +35,7 @@ use tracing::{error, info, warn};
 #[cfg(feature = "debug")]
 use crate::state::Fps;
 
-use super::render::{init_shaders, CursorMode};
+use super::render::{damage::DamageOverlay, init_shaders, CursorMode};
 
 #[derive(Debug)]
 pub struct WinitState {
@@ -44,6 +43,7 @@ pub struct WinitState {
     pub backend: WinitGraphicsBackend<GlowRenderer>,
     output: Output,
     damage_tracker: OutputDamageTracker,
+    damage_overlay: DamageOverlay,
     screencopy: Vec<(ScreencopySession, BufferParams)>,
     #[cfg(feature = "debug")]
     fps: Fps,
@@ -75,6 +75,7 @@ impl WinitState {
             None,
             #[cfg(feature = "debug")]
             Some(&mut self.fps),
+            Some(&mut self.damage_overlay),
         ) {
             Ok(RenderOutputResult { damage, states, .. }) => {
                 self.backend
@@ -125,7 +126,7 @@ impl WinitState {
             .get::<RefCell<OutputConfig>>()
             .unwrap()
             .borrow_mut();
-        if dbg!(config.mode.0) != dbg!((size.physical_size.w as i32, size.physical_size.h as i32)) {
+        if config.mode.0 != (size.physical_size.w as i32, size.physical_size.h as i32) {
             if !test_only {
                 config.mode = (
                     (size.physical_size.w as i32, size.physical_size.h as i32),
@@ -133,6 +134,11 @@ impl WinitState {
                 );
             }
             Err(anyhow::anyhow!("Cannot set window size"))
+        } else if config.color_filter != ColorFilter::None {
+            if !test_only {
+                config.color_filter = ColorFilter::None;
+            }
+            Err(anyhow::anyhow!("Color filters are not supported"))
         } else {
             Ok(())
         }
@@ -236,6 +242,7 @@ pub fn init_backend(
         backend,
         output: output.clone(),
         damage_tracker: OutputDamageTracker::from_output(&output),
+        damage_overlay: DamageOverlay::default(),
         screencopy: Vec::new(),
         #[cfg(feature = "debug")]
         fps,
@@ -329,12 +336,15 @@ impl State {
                 }
             }
             WinitEvent::Resized { size, .. } => {
-                let winit_state = self.backend.winit();
-                let output = &winit_state.output;
+                // the host window is the output, so its size becomes the new mode
+                let output = self.backend.winit().output.clone();
                 let mode = Mode {
                     size,
                     refresh: 60_000,
                 };
+                if output.current_mode() == Some(mode) {
+                    return;
+                }
 
                 {
                     let mut config = output
@@ -342,14 +352,15 @@ impl State {
                         .get::<RefCell<OutputConfig>>()
                         .unwrap()
                         .borrow_mut();
-                    config.mode.0 = size.into();
+                    config.mode = (size.into(), None);
+                }
+                if let Some(current) = output.current_mode() {
+                    output.delete_mode(current);
                 }
-                output.delete_mode(output.current_mode().unwrap());
                 output.set_preferred(mode);
                 output.change_current_state(Some(mode), None, None, None);
-                layer_map_for_output(output).arrange();
                 self.common.output_configuration_state.update();
-                self.common.shell.refresh_outputs();
+                self.common.shell.output_resized(&output);
                 render_ping.ping();
             }
             WinitEvent::Refresh => render_ping.ping(),
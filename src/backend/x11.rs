@@ -2,7 +2,7 @@
 
 use crate::{
     backend::render,
-    config::OutputConfig,
+    config::{ColorFilter, OutputConfig, VirtualOutputConfig},
     input::Devices,
     state::{BackendData, Common},
     utils::prelude::*,
@@ -28,7 +28,6 @@ use smithay::{
         vulkan::{version::Version, Instance, PhysicalDevice},
         x11::{Window, WindowBuilder, X11Backend, X11Event, X11Handle, X11Input, X11Surface},
     },
-    desktop::layer_map_for_output,
     output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
     reexports::{
         calloop::{ping, EventLoop, LoopHandle},
@@ -45,7 +44,7 @@ use tracing::{debug, error, info, warn};
 #[cfg(feature = "debug")]
 use crate::state::Fps;
 
-use super::render::init_shaders;
+use super::render::{damage::DamageOverlay, init_shaders};
 
 #[derive(Debug)]
 enum Allocator {
@@ -59,13 +58,23 @@ pub struct X11State {
     _egl: EGLDisplay,
     pub renderer: GlowRenderer,
     surfaces: Vec<Surface>,
+    next_id: usize,
     handle: X11Handle,
 }
 
 impl X11State {
-    pub fn add_window(&mut self, handle: LoopHandle<'_, State>) -> Result<Output> {
+    /// Opens a new host window acting as an output of the given initial size and scale
+    pub fn add_window(
+        &mut self,
+        handle: LoopHandle<'_, State>,
+        config: VirtualOutputConfig,
+        position: (i32, i32),
+    ) -> Result<Output> {
+        let name = format!("X11-{}", self.next_id);
+        let title = format!("COSMIC ({})", name);
         let window = WindowBuilder::new()
-            .title("COSMIC")
+            .title(&title)
+            .size((config.size.0.max(1) as u16, config.size.1.max(1) as u16).into())
             .build(&self.handle)
             .with_context(|| "Failed to create window")?;
         let fourcc = window.format();
@@ -92,7 +101,7 @@ impl X11State {
                 .with_context(|| "Failed to create surface")?,
         };
 
-        let name = format!("X11-{}", self.surfaces.len());
+        self.next_id += 1;
         let size = window.size();
         let props = PhysicalProperties {
             size: (0, 0).into(),
@@ -110,12 +119,14 @@ impl X11State {
         output.change_current_state(
             Some(mode),
             Some(Transform::Normal),
-            Some(Scale::Integer(1)),
-            Some((0, 0).into()),
+            Some(Scale::Fractional(config.scale)),
+            Some(position.into()),
         );
         output.user_data().insert_if_missing(|| {
             RefCell::new(OutputConfig {
                 mode: ((size.w as i32, size.h as i32), None),
+                scale: config.scale,
+                position,
                 ..Default::default()
             })
         });
@@ -146,6 +157,7 @@ impl X11State {
             window,
             surface,
             damage_tracker: OutputDamageTracker::from_output(&output),
+            damage_overlay: DamageOverlay::default(),
             output: output.clone(),
             render: ping.clone(),
             dirty: false,
@@ -160,6 +172,20 @@ impl X11State {
         Ok(output)
     }
 
+    /// Closes the host window of the given output
+    pub fn remove_window(&mut self, output: &Output) -> bool {
+        let Some(idx) = self.surfaces.iter().position(|s| s.output == *output) else {
+            return false;
+        };
+        let surface = self.surfaces.remove(idx);
+        surface.window.unmap();
+        true
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.surfaces.iter().map(|s| &s.output)
+    }
+
     pub fn schedule_render(
         &mut self,
         output: &Output,
@@ -200,6 +226,11 @@ impl X11State {
                 config.mode = ((size.w as i32, size.h as i32), None);
             }
             Err(anyhow::anyhow!("Cannot set window size"))
+        } else if config.color_filter != ColorFilter::None {
+            if !test_only {
+                config.color_filter = ColorFilter::None;
+            }
+            Err(anyhow::anyhow!("Color filters are not supported"))
         } else {
             Ok(())
         }
@@ -210,6 +241,7 @@ impl X11State {
 pub struct Surface {
     window: Window,
     damage_tracker: OutputDamageTracker,
+    damage_overlay: DamageOverlay,
     screencopy: Vec<(ScreencopySession, BufferParams)>,
     surface: X11Surface,
     output: Output,
@@ -244,6 +276,7 @@ impl Surface {
             None,
             #[cfg(feature = "debug")]
             Some(&mut self.fps),
+            Some(&mut self.damage_overlay),
         ) {
             Ok(RenderOutputResult { damage, states, .. }) => {
                 self.screencopy.clear();
@@ -367,18 +400,28 @@ pub fn init_backend(
         _egl: egl,
         renderer,
         surfaces: Vec::new(),
+        next_id: 0,
     });
 
-    let output = state
-        .backend
-        .x11()
-        .add_window(event_loop.handle())
-        .with_context(|| "Failed to create wl_output")?;
+    // every configured output gets its own window, placed next to each other
+    let mut outputs = Vec::new();
+    let mut x = 0;
+    for config in state.common.config.static_conf.nested.outputs.clone() {
+        let output = state
+            .backend
+            .x11()
+            .add_window(event_loop.handle(), config, (x, 0))
+            .with_context(|| "Failed to create wl_output")?;
+        x += output.geometry().size.w;
+        outputs.push(output);
+    }
     state
         .common
         .output_configuration_state
-        .add_heads(std::iter::once(&output));
-    state.common.shell.add_output(&output);
+        .add_heads(outputs.iter());
+    for output in &outputs {
+        state.common.shell.add_output(output);
+    }
     let seats = state.common.seats().cloned().collect::<Vec<_>>();
     state.common.config.read_outputs(
         &mut state.common.output_configuration_state,
@@ -393,28 +436,18 @@ pub fn init_backend(
         .handle()
         .insert_source(backend, move |event, _, state| match event {
             X11Event::CloseRequested { window_id } => {
-                // TODO: drain_filter
-                let mut outputs_removed = Vec::new();
-                for surface in state
+                // closing a window unplugs its output
+                let outputs_removed = state
                     .backend
                     .x11()
                     .surfaces
                     .iter()
                     .filter(|s| s.window.id() == window_id)
-                {
-                    surface.window.unmap();
-                    outputs_removed.push(surface.output.clone());
-                }
-                state
-                    .backend
-                    .x11()
-                    .surfaces
-                    .retain(|s| s.window.id() != window_id);
+                    .map(|s| s.output.clone())
+                    .collect::<Vec<_>>();
                 for output in outputs_removed.into_iter() {
-                    state
-                        .common
-                        .shell
-                        .remove_output(&output, seats.iter().cloned());
+                    state.backend.x11().remove_window(&output);
+                    state.output_unplugged(&output);
                 }
             }
             X11Event::Resized {
@@ -446,9 +479,8 @@ pub fn init_backend(
                     output.delete_mode(output.current_mode().unwrap());
                     output.change_current_state(Some(mode), None, None, None);
                     output.set_preferred(mode);
-                    layer_map_for_output(output).arrange();
                     state.common.output_configuration_state.update();
-                    state.common.shell.refresh_outputs();
+                    state.common.shell.output_resized(output);
                     surface.dirty = true;
                     if !surface.pending {
                         surface.render.ping();
@@ -463,7 +495,7 @@ pub fn init_backend(
                     .iter_mut()
                     .find(|s| s.window.id() == window_id)
                 {
-                    if surface.dirty {
+                    if surface.dirty || surface.damage_overlay.is_fading() {
                         surface.render.ping();
                     } else {
                         surface.pending = false;
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum GestureKind {
+    Swipe,
+    Pinch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum GestureDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Fingers moving towards each other
+    In,
+    /// Fingers moving away from each other
+    Out,
+}
+
+/// Description of a touchpad gesture that might be
+/// handled by the compositor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GesturePattern {
+    pub kind: GestureKind,
+    /// Number of fingers involved in the gesture
+    pub fingers: u32,
+    pub direction: GestureDirection,
+}
@@ -79,6 +79,7 @@ pub fn for_device(device: &InputDevice) -> InputConfig {
         } else {
             None
         },
+        map_to_output: None,
     }
 }
 
@@ -142,12 +143,14 @@ pub fn update_device(
             device.name(),
         );
     }
-    if let Some((accel, is_default)) = config!(|x| x.acceleration.as_ref()) {
-        if let Some(profile) = accel.profile {
-            if let Err(err) = device.config_accel_set_profile(profile) {
-                config_set_error(device, "acceleration profile", profile, err, is_default);
-            }
+    // the profile is looked up separately, so a device config only adjusting
+    // the speed keeps the profile of the default config
+    if let Some((profile, is_default)) = config!(|x| x.acceleration.as_ref()?.profile) {
+        if let Err(err) = device.config_accel_set_profile(profile) {
+            config_set_error(device, "acceleration profile", profile, err, is_default);
         }
+    }
+    if let Some((accel, is_default)) = config!(|x| x.acceleration.as_ref()) {
         if let Err(err) = device.config_accel_set_speed(accel.speed) {
             config_set_error(device, "acceleration speed", accel.speed, err, is_default);
         }
@@ -131,8 +131,25 @@ impl ToString for KeyPattern {
 pub enum Action {
     Terminate,
     Debug,
+    DebugDamage,
+    /// Adds a virtual output, on the headless or nested x11 backend
+    DebugAddOutput,
+    /// Removes the active virtual output, on the headless or nested x11 backend
+    DebugRemoveOutput,
     Close,
 
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ToggleColorFilter(super::ColorFilter),
+    Screenshot(super::ScreenshotTarget),
+    /// Turns all outputs off, until the next input
+    PowerOffOutputs,
+    /// Shows the name and model of every output for a few seconds
+    IdentifyOutputs,
+    /// Keeps the session from going idle, until toggled again
+    ToggleIdleInhibit,
+
     Workspace(u8),
     NextWorkspace,
     PreviousWorkspace,
@@ -169,6 +186,8 @@ pub enum Action {
     _ResizingInternal(ResizeDirection, ResizeEdge, KeyState),
     Maximize,
     Spawn(String),
+    #[serde(skip)]
+    _BreakGrabs,
 }
 
 fn insert_binding(
@@ -5,11 +5,12 @@ use crate::{
     state::{BackendData, State},
     wayland::protocols::output_configuration::OutputConfigurationState,
 };
+use anyhow::Context;
 use cosmic_config::ConfigGet;
 use serde::{Deserialize, Serialize};
 use smithay::input::Seat;
 pub use smithay::{
-    backend::input::KeyState,
+    backend::{input::KeyState, renderer::TextureFilter},
     input::keyboard::{keysyms as KeySyms, Keysym, ModifiersState},
     output::{Mode, Output},
     reexports::{
@@ -21,15 +22,28 @@ pub use smithay::{
     },
     utils::{Logical, Physical, Point, Size, Transform},
 };
-use std::{cell::RefCell, collections::HashMap, fs::OpenOptions, path::PathBuf};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
 use tracing::{debug, error, info, warn};
 
+mod gestures;
+pub use gestures::{GestureDirection, GestureKind, GesturePattern};
 mod input_config;
 mod key_bindings;
 pub use key_bindings::{Action, KeyModifier, KeyModifiers, KeyPattern};
+mod reload;
 mod types;
 pub use self::types::*;
-use cosmic_comp_config::{input::InputConfig, XkbConfig};
+use cosmic_comp_config::{
+    decoration::{DecorationMode, DecorationRule},
+    input::InputConfig,
+    night_light::NightLightConfig,
+    XkbConfig,
+};
 
 #[derive(Debug)]
 pub struct Config {
@@ -40,6 +54,10 @@ pub struct Config {
     pub input_default: InputConfig,
     pub input_touchpad: InputConfig,
     pub input_devices: HashMap<String, InputConfig>,
+    pub night_light: NightLightConfig,
+    pub decoration_rules: DecorationRules,
+    /// Why the static config couldn't be loaded on startup, until it is reported
+    static_error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,6 +72,62 @@ pub struct StaticConfig {
     pub active_hint: u8,
     #[serde(default = "default_gaps")]
     pub gaps: (u8, u8),
+    #[serde(default)]
+    pub titlebar_actions: TitlebarActions,
+    #[serde(default)]
+    pub scroll_actions: ScrollActions,
+    #[serde(default)]
+    pub seats: Vec<SeatConfig>,
+    #[serde(default)]
+    pub switches: SwitchConfig,
+    #[serde(default)]
+    pub idle: IdleConfig,
+    #[serde(default)]
+    pub gestures: HashMap<GesturePattern, key_bindings::Action>,
+    /// Key combination that forcefully releases all input grabs
+    #[serde(default = "default_break_grabs_binding")]
+    pub break_grabs_binding: KeyPattern,
+    #[serde(default)]
+    pub frame_scheduling: FrameScheduling,
+    #[serde(default)]
+    pub activation_policy: ActivationPolicy,
+    #[serde(default)]
+    pub zoom: ZoomConfig,
+    #[serde(default)]
+    pub shadows: ShadowConfig,
+    #[serde(default)]
+    pub borders: BorderConfig,
+    #[serde(default)]
+    pub animations: AnimationConfig,
+    #[serde(default)]
+    pub wallpaper: WallpaperConfig,
+    #[serde(default)]
+    pub screenshots: ScreenshotConfig,
+    #[serde(default)]
+    pub render_devices: RenderDeviceConfig,
+    #[serde(default)]
+    pub headless: HeadlessConfig,
+    #[serde(default)]
+    pub nested: NestedConfig,
+    #[serde(default)]
+    pub security_context: SecurityContextConfig,
+    /// Let clipboard managers observe and set the selections without keyboard focus
+    #[serde(default = "default_enabled")]
+    pub data_control: bool,
+    #[serde(default)]
+    pub window_rules: Vec<WindowRule>,
+    #[serde(default)]
+    pub bell: BellConfig,
+    /// Start Xwayland to run X11 clients
+    #[serde(default = "default_enabled")]
+    pub xwayland: bool,
+    /// Let X11 clients render at the output scale, instead of scaling them up.
+    /// Override-redirect windows aren't scaled, so this is off by default.
+    #[serde(default)]
+    pub xwayland_hidpi: bool,
+    /// X11 window classes, that may grab the keyboard including the compositor shortcuts
+    #[serde(default)]
+    pub xwayland_keyboard_grab: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -68,27 +142,681 @@ pub enum WorkspaceLayout {
     Horizontal,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TitlebarAction {
+    None,
+    Maximize,
+    Lower,
+    Close,
+    WindowMenu,
+    Shade,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct TitlebarActions {
+    pub double_click: TitlebarAction,
+    pub middle_click: TitlebarAction,
+    pub right_click: TitlebarAction,
+}
+
+impl Default for TitlebarActions {
+    fn default() -> Self {
+        TitlebarActions {
+            double_click: TitlebarAction::Maximize,
+            middle_click: TitlebarAction::Lower,
+            right_click: TitlebarAction::WindowMenu,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAction {
+    None,
+    CycleWorkspaces,
+    CycleTabs,
+    Opacity,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScrollActions {
+    /// Modifiers to hold for the scroll actions, none for plain scrolling
+    #[serde(deserialize_with = "deserialize_KeyModifiers")]
+    pub modifiers: KeyModifiers,
+    pub titlebar: ScrollAction,
+    pub gaps: ScrollAction,
+    pub background: ScrollAction,
+}
+
+impl Default for ScrollActions {
+    fn default() -> Self {
+        ScrollActions {
+            modifiers: KeyModifiers::default(),
+            titlebar: ScrollAction::None,
+            gaps: ScrollAction::None,
+            background: ScrollAction::None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LidAction {
+    None,
+    DisableOutput,
+    Lock,
+    Suspend,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct SwitchConfig {
+    /// What to do when the lid is closed. Internal outputs are always re-enabled on open
+    pub lid_close: LidAction,
+    /// Maximize newly opened floating windows while in tablet mode
+    pub tablet_mode_maximize: bool,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        SwitchConfig {
+            lid_close: LidAction::DisableOutput,
+            tablet_mode_maximize: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Seconds without input after which all outputs are turned off, `None` to never
+    pub screen_off: Option<u64>,
+    /// App ids, whose idle inhibitors have no effect
+    pub ignore_inhibitors: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FrameScheduling {
+    /// Render right after the previous frame was displayed
+    Immediate,
+    /// Delay rendering as close to the next vblank as the predicted render time allows,
+    /// to reduce input latency
+    #[default]
+    Latency,
+}
+
+/// Which xdg-activation requests may move the focus to another window
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ActivationPolicy {
+    /// Any token is honored
+    Always,
+    /// Only tokens of the focused client, requested in response to user input
+    #[default]
+    Interaction,
+    /// Windows requesting activation are only marked urgent
+    Never,
+}
+
+/// Filter used to upscale textures
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFilter {
+    Linear,
+    Nearest,
+}
+
+impl From<ScaleFilter> for TextureFilter {
+    fn from(filter: ScaleFilter) -> Self {
+        match filter {
+            ScaleFilter::Linear => TextureFilter::Linear,
+            ScaleFilter::Nearest => TextureFilter::Nearest,
+        }
+    }
+}
+
+/// Scale the content of an X11 window is shown at, instead of the scale of its output
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum X11Scale {
+    /// The client already renders at the scale of its output, show its pixels unscaled
+    HiDpi,
+    /// Scale the content of the client by this factor, e.g. 2.0 for tiny legacy apps
+    Factor(f64),
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ZoomConfig {
+    /// Highest magnification level
+    pub max_level: f64,
+    /// Factor the magnification changes by with every step
+    pub step: f64,
+    /// Modifiers to hold for zooming with the scroll wheel, none disables scroll zooming
+    #[serde(deserialize_with = "deserialize_KeyModifiers")]
+    pub scroll_modifiers: KeyModifiers,
+    /// Filter used to upscale the magnified content
+    pub filter: ScaleFilter,
+}
+
+impl Default for ZoomConfig {
+    fn default() -> Self {
+        ZoomConfig {
+            max_level: 8.0,
+            step: 1.25,
+            scroll_modifiers: KeyModifier::Super.into(),
+            filter: ScaleFilter::Linear,
+        }
+    }
+}
+
+/// Shadows drawn behind server-side decorated floating windows
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ShadowConfig {
+    pub enabled: bool,
+    /// Distance the shadow fades out over
+    pub radius: u8,
+    /// Offset of the shadow relative to the window
+    pub offset: (i32, i32),
+    pub opacity: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        ShadowConfig {
+            enabled: true,
+            radius: 24,
+            offset: (0, 8),
+            opacity: 0.4,
+        }
+    }
+}
+
+/// Borders drawn around tiled and floating windows, `active_hint` sets the width of the focused one
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BorderConfig {
+    /// Width of the border of windows without focus, 0 draws none
+    pub width: u8,
+    pub focused: [f32; 3],
+    pub unfocused: [f32; 3],
+    /// Color of windows requesting attention, until they are focused
+    pub urgent: [f32; 3],
+}
+
+impl Default for BorderConfig {
+    fn default() -> Self {
+        BorderConfig {
+            width: 0,
+            focused: [0.580, 0.921, 0.921],
+            unfocused: [0.388, 0.388, 0.388],
+            urgent: [0.984, 0.722, 0.424],
+        }
+    }
+}
+
+impl BorderConfig {
+    /// Width and color of the border of a window, urgent windows are as wide as the focused one.
+    /// `width` overrides the width of the unfocused border, e.g. by a window rule.
+    pub fn for_window(
+        &self,
+        focused: bool,
+        urgent: bool,
+        width: Option<u8>,
+        active_hint: u8,
+    ) -> (u8, [f32; 3]) {
+        let width = width.unwrap_or(self.width);
+        if focused {
+            (active_hint, self.focused)
+        } else if urgent {
+            (active_hint.max(width), self.urgent)
+        } else {
+            (width, self.unfocused)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationCurve {
+    Linear,
+    EaseInCubic,
+    EaseOutCubic,
+    EaseInOutCubic,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationSettings {
+    /// Duration in milliseconds
+    pub duration: u64,
+    pub curve: AnimationCurve,
+}
+
+impl AnimationSettings {
+    const fn new(duration: u64, curve: AnimationCurve) -> AnimationSettings {
+        AnimationSettings { duration, curve }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct AnimationConfig {
+    /// Skips all animations
+    pub reduced_motion: bool,
+    pub workspace_switch: AnimationSettings,
+    pub overview: AnimationSettings,
+    pub fullscreen: AnimationSettings,
+    /// Rearranging tiled windows
+    pub tiling: AnimationSettings,
+    /// Rescaling of windows picked up by a move grab
+    pub window_move: AnimationSettings,
+    /// Rearranging the tabs of a stack
+    pub tabs: AnimationSettings,
+    pub tab_scroll: AnimationSettings,
+    /// Floating windows appearing.
+    ///
+    /// There is no counterpart for closing windows, as their content is gone once the client
+    /// destroys them, and tiled windows use the `tiling` settings for both.
+    pub window_open: AnimationSettings,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        AnimationConfig {
+            reduced_motion: false,
+            workspace_switch: AnimationSettings::new(200, AnimationCurve::EaseInOutCubic),
+            overview: AnimationSettings::new(200, AnimationCurve::EaseInOutCubic),
+            fullscreen: AnimationSettings::new(200, AnimationCurve::EaseInOutCubic),
+            tiling: AnimationSettings::new(200, AnimationCurve::EaseInOutCubic),
+            window_move: AnimationSettings::new(150, AnimationCurve::Linear),
+            tabs: AnimationSettings::new(150, AnimationCurve::EaseOutCubic),
+            tab_scroll: AnimationSettings::new(200, AnimationCurve::EaseInOutCubic),
+            window_open: AnimationSettings::new(200, AnimationCurve::EaseOutCubic),
+        }
+    }
+}
+
+/// Where the pixels of a wallpaper come from
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum WallpaperSource {
+    /// Solid rgb color
+    Color([f32; 3]),
+    /// Path of a png image
+    Image(PathBuf),
+    /// Cycles through the png images of a directory
+    Slideshow {
+        directory: PathBuf,
+        /// Seconds until the next image is shown
+        interval: u64,
+    },
+}
+
+/// How an image is fit to the size of an output
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WallpaperFit {
+    /// Scales the image to cover the output, cutting off what doesn't fit
+    #[default]
+    Zoom,
+    /// Scales the image to fit into the output, filling the rest with the background color
+    Fit,
+    /// Scales the image to the size of the output, ignoring its aspect ratio
+    Stretch,
+    /// Shows the image unscaled in the middle of the output
+    Center,
+    /// Repeats the unscaled image to cover the output
+    Tile,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Wallpaper {
+    pub source: WallpaperSource,
+    #[serde(default)]
+    pub fit: WallpaperFit,
+    /// Color behind images, that don't cover the whole output
+    #[serde(default)]
+    pub background: [f32; 3],
+}
+
+/// Background rendered by the compositor below all layer surfaces
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WallpaperConfig {
+    /// Wallpaper of outputs without their own, none leaves the background to clients
+    pub default: Option<Wallpaper>,
+    /// Wallpapers of specific outputs by connector name
+    pub outputs: HashMap<String, Wallpaper>,
+}
+
+impl Default for WallpaperConfig {
+    fn default() -> Self {
+        WallpaperConfig {
+            default: Some(Wallpaper {
+                source: WallpaperSource::Color([0.1, 0.1, 0.1]),
+                fit: WallpaperFit::default(),
+                background: [0.0; 3],
+            }),
+            outputs: HashMap::new(),
+        }
+    }
+}
+
+impl WallpaperConfig {
+    pub fn for_output(&self, name: &str) -> Option<&Wallpaper> {
+        self.outputs.get(name).or(self.default.as_ref())
+    }
+}
+
+/// What the screenshot actions capture
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenshotTarget {
+    /// The whole output under the pointer
+    Output,
+    /// The focused window
+    Window,
+    /// A rectangle selected with the pointer
+    Region,
+}
+
+/// Sandboxed clients, that may still use privileged protocols
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SecurityContextConfig {
+    /// App ids as reported by the sandbox engine, e.g. flatpak ids
+    pub privileged_app_ids: Vec<String>,
+    /// Privileged globals by interface name, that only the listed clients may bind.
+    /// Clients are matched by the absolute path of their executable, sandboxed ones only by
+    /// their app id.
+    pub restricted_globals: HashMap<String, Vec<String>>,
+}
+
+/// What ringing the system bell does, there is no audible bell
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct BellConfig {
+    pub visual: VisualBell,
+    /// Mark the workspace of the window urgent, if it isn't shown
+    pub urgent: bool,
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        BellConfig {
+            visual: VisualBell::None,
+            urgent: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum VisualBell {
+    None,
+    /// Flash the window ringing the bell, or the focused one
+    Window,
+    /// Flash the whole output of the window
+    Output,
+}
+
+/// Overrides for windows, whose app id and title match the given regular expressions
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct WindowRule {
+    /// App id of wayland windows or class of X11 windows, matches any if unset
+    pub app_id: Option<String>,
+    /// Matches any title if unset
+    pub title: Option<String>,
+    /// Scale the content of fixed-size clients up to the size of their tile or output with
+    /// the given filter, instead of showing it at its native size
+    pub force_scale: Option<ScaleFilter>,
+    /// Overrides the scale of X11 windows, which are otherwise scaled up to the output scale
+    pub x11_scale: Option<X11Scale>,
+    /// Width of the border of the window without focus, instead of the one of `borders`
+    pub border_width: Option<u8>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, app_id: &str, title: &str) -> bool {
+        fn is_match(pattern: &Option<String>, value: &str) -> bool {
+            let Some(pattern) = pattern else {
+                return true;
+            };
+            match full_match_regex(pattern) {
+                Ok(regex) => regex.is_match(value),
+                Err(err) => {
+                    warn!(?err, %pattern, "Invalid window rule.");
+                    false
+                }
+            }
+        }
+
+        is_match(&self.app_id, app_id) && is_match(&self.title, title)
+    }
+}
+
+fn full_match_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// Decoration rules with their app id patterns compiled, rules with invalid patterns are dropped
+#[derive(Debug, Default)]
+pub struct DecorationRules(Vec<(Option<regex::Regex>, DecorationRule)>);
+
+impl DecorationRules {
+    pub fn new(rules: Vec<DecorationRule>) -> DecorationRules {
+        DecorationRules(
+            rules
+                .into_iter()
+                .filter_map(|rule| {
+                    let regex = match rule.app_id.as_deref().map(full_match_regex) {
+                        Some(Ok(regex)) => Some(regex),
+                        Some(Err(err)) => {
+                            warn!(?err, pattern = ?rule.app_id, "Invalid decoration rule.");
+                            return None;
+                        }
+                        None => None,
+                    };
+                    Some((regex, rule))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Mode the first matching rule forces on a window
+    pub fn mode(&self, app_id: &str, tiled: bool) -> Option<DecorationMode> {
+        self.0
+            .iter()
+            .find(|(regex, rule)| {
+                regex.as_ref().map_or(true, |regex| regex.is_match(app_id))
+                    && rule.tiled.map_or(true, |t| t == tiled)
+            })
+            .map(|(_, rule)| rule.mode)
+    }
+}
+
+/// Where screenshots taken by the compositor end up
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    /// Directory the images are saved to, `$HOME/Pictures/Screenshots` if unset
+    pub directory: Option<PathBuf>,
+    pub save: bool,
+    /// Offer the images as `image/png` on the clipboard
+    pub clipboard: bool,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            directory: None,
+            save: true,
+            clipboard: true,
+        }
+    }
+}
+
+impl ScreenshotConfig {
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory.clone().or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Pictures").join("Screenshots"))
+        })
+    }
+}
+
+/// Which gpus do the rendering, instead of the one the system booted with.
+///
+/// Devices are given by the path of their drm node, e.g. `/dev/dri/renderD129`.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct RenderDeviceConfig {
+    /// Gpu used for rendering, unless an output is assigned another one.
+    /// Overridden by `COSMIC_RENDER_DEVICE` in the environment.
+    pub primary: Option<PathBuf>,
+    /// Gpus rendering the outputs of the given connector names, e.g. the dedicated gpu
+    /// for the external ports it drives
+    pub outputs: HashMap<String, PathBuf>,
+}
+
+/// Virtual outputs created by the headless backend (`COSMIC_BACKEND=headless`)
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HeadlessConfig {
+    pub outputs: Vec<VirtualOutputConfig>,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        HeadlessConfig {
+            outputs: vec![VirtualOutputConfig::default()],
+        }
+    }
+}
+
+/// Host windows opened by the nested x11 backend, each acting as a separate output
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NestedConfig {
+    /// Initial size of the windows, which can be resized afterwards
+    pub outputs: Vec<VirtualOutputConfig>,
+}
+
+impl Default for NestedConfig {
+    fn default() -> Self {
+        NestedConfig {
+            outputs: vec![VirtualOutputConfig {
+                size: (1280, 800),
+                ..Default::default()
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct VirtualOutputConfig {
+    /// Size in pixels
+    pub size: (i32, i32),
+    pub scale: f64,
+    /// Refresh rate in mHz
+    pub refresh: u32,
+}
+
+impl Default for VirtualOutputConfig {
+    fn default() -> Self {
+        VirtualOutputConfig {
+            size: (1920, 1080),
+            scale: 1.0,
+            refresh: 60_000,
+        }
+    }
+}
+
+/// An additional seat with its own set of input devices
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct SeatConfig {
+    pub name: String,
+    /// Names of the input devices assigned to this seat
+    pub devices: Vec<String>,
+    /// Output the seat starts on
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct DynamicConfig {
     outputs: (Option<PathBuf>, OutputsConfig),
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Output layouts, keyed by the set of outputs they were made for
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct OutputsConfig {
     pub config: HashMap<Vec<OutputInfo>, Vec<OutputConfig>>,
 }
 
+impl OutputsConfig {
+    /// Stored configs for the given sorted set of outputs, in the same order.
+    ///
+    /// Without a layout for exactly these connectors, any layout for the same displays is used,
+    /// e.g. when a dock enumerates its ports differently after being plugged in again.
+    pub fn profile_for(&self, infos: &[OutputInfo]) -> Option<Vec<OutputConfig>> {
+        if let Some(configs) = self.config.get(infos) {
+            return Some(configs.clone());
+        }
+
+        self.config
+            .iter()
+            .filter(|(stored, _)| stored.len() == infos.len())
+            .find_map(|(stored, configs)| {
+                let mut remaining = stored.iter().zip(configs.iter()).collect::<Vec<_>>();
+                infos
+                    .iter()
+                    .map(|info| {
+                        // identical models rather keep the config of their connector
+                        let idx = remaining
+                            .iter()
+                            .position(|(stored, _)| {
+                                stored.connector == info.connector && info.same_display(stored)
+                            })
+                            .or_else(|| {
+                                remaining
+                                    .iter()
+                                    .position(|(stored, _)| info.same_display(stored))
+                            })?;
+                        Some(remaining.swap_remove(idx).1.clone())
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct OutputInfo {
     pub connector: String,
     pub make: String,
     pub model: String,
+    /// Serial number from the EDID, empty if unknown or not stored yet
+    #[serde(default)]
+    pub serial: String,
+}
+
+impl OutputInfo {
+    /// Whether both describe the same display, regardless of how it is connected
+    pub fn same_display(&self, stored: &OutputInfo) -> bool {
+        self.make == stored.make
+            && self.model == stored.model
+            && (self.serial == stored.serial || stored.serial.is_empty())
+    }
 }
 
 impl From<Output> for OutputInfo {
     fn from(o: Output) -> OutputInfo {
         let physical = o.physical_properties();
         OutputInfo {
+            serial: o
+                .user_data()
+                .get::<OutputSerial>()
+                .map(|serial| serial.0.clone())
+                .unwrap_or_default(),
             connector: o.name(),
             make: physical.make,
             model: physical.model,
@@ -96,6 +824,14 @@ impl From<Output> for OutputInfo {
     }
 }
 
+/// Serial number of the display connected to an output, as read from its EDID
+#[derive(Debug, Clone)]
+pub struct OutputSerial(pub String);
+
+/// Marks outputs built into the device by their connector type, e.g. laptop panels
+#[derive(Debug, Clone, Copy)]
+pub struct InternalOutput;
+
 fn default_enabled() -> bool {
     true
 }
@@ -108,6 +844,18 @@ fn default_gaps() -> (u8, u8) {
     (0, 4)
 }
 
+fn default_break_grabs_binding() -> KeyPattern {
+    KeyPattern::new(
+        KeyModifiers {
+            ctrl: true,
+            alt: true,
+            shift: true,
+            logo: false,
+        },
+        Some(Keysym::Escape),
+    )
+}
+
 fn default_workspace_layout() -> WorkspaceLayout {
     WorkspaceLayout::Vertical
 }
@@ -116,6 +864,9 @@ fn default_workspace_layout() -> WorkspaceLayout {
 pub struct OutputConfig {
     pub mode: ((i32, i32), Option<u32>),
     pub vrr: bool,
+    /// Only enable variable refresh rate while a fullscreen window is shown
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub vrr_fullscreen_only: bool,
     pub scale: f64,
     #[serde(with = "TransformDef")]
     pub transform: Transform,
@@ -124,6 +875,92 @@ pub struct OutputConfig {
     pub enabled: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_bpc: Option<u32>,
+    #[serde(default, skip_serializing_if = "ColorDepth::is_auto")]
+    pub color_depth: ColorDepth,
+    /// ICC profile, whose calibration curves are loaded into the gamma tables of the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<PathBuf>,
+    /// Custom timings used instead of the mode advertised by the display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modeline: Option<Modeline>,
+    #[serde(default, skip_serializing_if = "ColorFilter::is_none")]
+    pub color_filter: ColorFilter,
+}
+
+/// Bits per color channel of the framebuffers scanned out on an output
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 10 bit where the gpu and display support it, 8 bit otherwise
+    #[default]
+    Auto,
+    Eight,
+    Ten,
+}
+
+impl ColorDepth {
+    pub fn is_auto(&self) -> bool {
+        *self == ColorDepth::Auto
+    }
+
+    /// Max bpc requested from the connector, unless configured explicitly
+    pub fn bpc(&self) -> Option<u32> {
+        match self {
+            ColorDepth::Auto => None,
+            ColorDepth::Eight => Some(8),
+            ColorDepth::Ten => Some(10),
+        }
+    }
+}
+
+/// Accessibility filter applied to everything shown on an output
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Invert,
+    Grayscale,
+    /// Corrects colors for red-green (protanopia) color blindness
+    Protanopia,
+    /// Corrects colors for green-red (deuteranopia) color blindness
+    Deuteranopia,
+    /// Corrects colors for blue-yellow (tritanopia) color blindness
+    Tritanopia,
+}
+
+impl ColorFilter {
+    pub fn is_none(&self) -> bool {
+        *self == ColorFilter::None
+    }
+}
+
+/// Detailed timings of a mode, in the same order as X11 modelines
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct Modeline {
+    /// Pixel clock in kHz
+    pub clock: u32,
+    pub hdisplay: u16,
+    pub hsync_start: u16,
+    pub hsync_end: u16,
+    pub htotal: u16,
+    pub vdisplay: u16,
+    pub vsync_start: u16,
+    pub vsync_end: u16,
+    pub vtotal: u16,
+    #[serde(default)]
+    pub hsync_positive: bool,
+    #[serde(default)]
+    pub vsync_positive: bool,
+}
+
+impl Modeline {
+    /// Refresh rate in mHz
+    pub fn refresh(&self) -> u32 {
+        let pixels = self.htotal as u64 * self.vtotal as u64;
+        if pixels == 0 {
+            return 0;
+        }
+        (self.clock as u64 * 1_000_000 / pixels) as u32
+    }
 }
 
 impl Default for OutputConfig {
@@ -131,22 +968,49 @@ impl Default for OutputConfig {
         OutputConfig {
             mode: ((0, 0), None),
             vrr: false,
+            vrr_fullscreen_only: false,
             scale: 1.0,
             transform: Transform::Normal,
             position: (0, 0),
             enabled: true,
             max_bpc: None,
+            color_depth: ColorDepth::Auto,
+            icc_profile: None,
+            modeline: None,
+            color_filter: ColorFilter::None,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdaptiveSync {
+    Disabled,
+    Enabled,
+    /// Only enabled while a fullscreen window is shown
+    Fullscreen,
+}
+
 impl OutputConfig {
+    pub fn adaptive_sync(&self) -> AdaptiveSync {
+        match (self.vrr, self.vrr_fullscreen_only) {
+            (false, _) => AdaptiveSync::Disabled,
+            (true, false) => AdaptiveSync::Enabled,
+            (true, true) => AdaptiveSync::Fullscreen,
+        }
+    }
+
     pub fn mode_size(&self) -> Size<i32, Physical> {
-        self.mode.0.into()
+        match self.modeline {
+            Some(modeline) => (modeline.hdisplay as i32, modeline.vdisplay as i32).into(),
+            None => self.mode.0.into(),
+        }
     }
 
     pub fn mode_refresh(&self) -> u32 {
-        self.mode.1.unwrap_or(60_000)
+        match self.modeline {
+            Some(modeline) => modeline.refresh(),
+            None => self.mode.1.unwrap_or(60_000),
+        }
     }
 
     pub fn output_mode(&self) -> Mode {
@@ -167,18 +1031,37 @@ impl Config {
             })
             .expect("Failed to add cosmic-config to the event loop");
         let xdg = xdg::BaseDirectories::new().ok();
+        let (static_conf, static_error) = match Self::static_config_path(xdg.as_ref()) {
+            Some(path) => {
+                info!("Using config at {}", path.display());
+                match Self::read_static(&path) {
+                    Ok(static_conf) => (static_conf, None),
+                    Err(err) => {
+                        error!(?err, "Failed to load the config, using the defaults.");
+                        (Self::default_static(), Some(format!("{:#}", err)))
+                    }
+                }
+            }
+            None => (Self::default_static(), None),
+        };
+        let dynamic_conf = Self::load_dynamic(xdg.as_ref());
+        reload::watch(loop_handle, xdg, dynamic_conf.outputs.0.clone());
         Config {
-            static_conf: Self::load_static(xdg.as_ref()),
-            dynamic_conf: Self::load_dynamic(xdg.as_ref()),
+            static_conf,
+            dynamic_conf,
             xkb: get_config(&config, "xkb-config"),
             input_default: get_config(&config, "input-default"),
             input_touchpad: get_config(&config, "input-touchpad"),
             input_devices: get_config(&config, "input-devices"),
+            night_light: get_config(&config, "night-light"),
+            decoration_rules: DecorationRules::new(get_config(&config, "decoration-rules")),
+            static_error,
             config,
         }
     }
 
-    fn load_static(xdg: Option<&xdg::BaseDirectories>) -> StaticConfig {
+    /// The first existing one of the locations searched for the static config
+    fn static_config_path(xdg: Option<&xdg::BaseDirectories>) -> Option<PathBuf> {
         let mut locations = if let Some(base) = xdg {
             vec![
                 base.get_config_file("cosmic-comp.ron"),
@@ -196,23 +1079,26 @@ impl Config {
         locations.push(PathBuf::from("/etc/cosmic-comp/config.ron"));
         locations.push(PathBuf::from("/etc/cosmic-comp.ron"));
 
-        for path in locations {
+        locations.into_iter().find(|path| {
             debug!("Trying config location: {}", path.display());
-            if path.exists() {
-                info!("Using config at {}", path.display());
-                let mut config: StaticConfig =
-                    ron::de::from_reader(OpenOptions::new().read(true).open(path).unwrap())
-                        .expect("Malformed config file");
+            path.exists()
+        })
+    }
 
-                key_bindings::add_default_bindings(
-                    &mut config.key_bindings,
-                    config.workspace_layout,
-                );
+    fn read_static(path: &Path) -> anyhow::Result<StaticConfig> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut config: StaticConfig = ron::de::from_reader(file)
+            .with_context(|| format!("Malformed config file {}", path.display()))?;
 
-                return config;
-            }
-        }
+        key_bindings::add_default_bindings(&mut config.key_bindings, config.workspace_layout);
+
+        Ok(config)
+    }
 
+    fn default_static() -> StaticConfig {
         StaticConfig {
             key_bindings: HashMap::new(),
             workspace_mode: WorkspaceMode::Global,
@@ -221,6 +1107,31 @@ impl Config {
             tiling_enabled: false,
             active_hint: default_active_hint(),
             gaps: default_gaps(),
+            titlebar_actions: TitlebarActions::default(),
+            scroll_actions: ScrollActions::default(),
+            seats: Vec::new(),
+            switches: SwitchConfig::default(),
+            idle: IdleConfig::default(),
+            gestures: HashMap::new(),
+            break_grabs_binding: default_break_grabs_binding(),
+            frame_scheduling: FrameScheduling::default(),
+            activation_policy: ActivationPolicy::default(),
+            zoom: ZoomConfig::default(),
+            shadows: ShadowConfig::default(),
+            borders: BorderConfig::default(),
+            animations: AnimationConfig::default(),
+            wallpaper: WallpaperConfig::default(),
+            screenshots: ScreenshotConfig::default(),
+            render_devices: RenderDeviceConfig::default(),
+            headless: HeadlessConfig::default(),
+            nested: NestedConfig::default(),
+            security_context: SecurityContextConfig::default(),
+            data_control: true,
+            window_rules: Vec::new(),
+            bell: BellConfig::default(),
+            xwayland: true,
+            xwayland_hidpi: false,
+            xwayland_keyboard_grab: Vec::new(),
         }
     }
 
@@ -270,7 +1181,7 @@ impl Config {
             .map(Into::<crate::config::OutputInfo>::into)
             .collect::<Vec<_>>();
         infos.sort();
-        if let Some(configs) = self.dynamic_conf.outputs().config.get(&infos).cloned() {
+        if let Some(configs) = self.dynamic_conf.outputs().profile_for(&infos) {
             let mut reset = false;
             let known_good_configs = outputs
                 .iter()
@@ -424,6 +1335,22 @@ impl Config {
         .map_or(1.0, |x| x.0)
     }
 
+    /// Name of the output absolute input of a device is bound to, if any
+    pub fn mapped_output(&self, device_name: &str) -> Option<&str> {
+        self.input_devices
+            .get(device_name)
+            .and_then(|config| config.map_to_output.as_deref())
+    }
+
+    /// Name of the configured seat a device is assigned to, if any
+    pub fn seat_for_device(&self, device_name: &str) -> Option<&str> {
+        self.static_conf
+            .seats
+            .iter()
+            .find(|seat| seat.devices.iter().any(|name| name == device_name))
+            .map(|seat| seat.name.as_str())
+    }
+
     fn get_device_config(&self, device: &InputDevice) -> (Option<&InputConfig>, &InputConfig) {
         let default_config = if device.config_tap_finger_count() > 0 {
             &self.input_touchpad
@@ -530,6 +1457,19 @@ fn config_changed(config: cosmic_config::Config, keys: Vec<String>, state: &mut
                 state.common.config.input_devices = value;
                 update_input(state);
             }
+            "night-light" => {
+                let value = get_config::<NightLightConfig>(&config, "night-light");
+                if let BackendData::Kms(ref mut kms_state) = &mut state.backend {
+                    kms_state.night_light.config = value.clone();
+                    kms_state.schedule_night_light(&state.common.event_loop_handle);
+                }
+                state.common.config.night_light = value;
+            }
+            "decoration-rules" => {
+                let value = get_config::<Vec<DecorationRule>>(&config, "decoration-rules");
+                state.common.config.decoration_rules = DecorationRules::new(value);
+                state.refresh_decoration_rules();
+            }
             _ => {}
         }
     }
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Reloading of the config files, when they are changed on disk.
+//!
+//! The files are polled, as editors save them in various ways, that file notifications
+//! don't follow reliably. Errors are shown on all outputs and the current config is kept.
+//! Options, that are only read on startup, keep their value until a restart, which is
+//! shown as well.
+
+use super::{Config, OutputsConfig, StaticConfig};
+use crate::{shell::ERROR_DURATION, state::State};
+use anyhow::Context;
+use smithay::reexports::calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A config file as it was last seen
+#[derive(Debug, PartialEq)]
+struct Stamp(Option<PathBuf>, Option<SystemTime>);
+
+impl Stamp {
+    fn of(path: Option<&Path>) -> Stamp {
+        let modified = path.and_then(|path| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        });
+        Stamp(path.map(Path::to_path_buf), modified)
+    }
+}
+
+/// Polls the static config and the output config, applying them when they change
+pub fn watch(
+    loop_handle: &LoopHandle<'_, State>,
+    xdg: Option<xdg::BaseDirectories>,
+    outputs_path: Option<PathBuf>,
+) {
+    let mut static_stamp = Stamp::of(Config::static_config_path(xdg.as_ref()).as_deref());
+    let mut outputs_stamp = Stamp::of(outputs_path.as_deref());
+    if let Err(err) =
+        loop_handle.insert_source(Timer::from_duration(POLL_INTERVAL), move |_, _, state| {
+            if let Some(message) = state.common.config.static_error.take() {
+                show_config_error(state, message);
+            }
+
+            let stamp = Stamp::of(Config::static_config_path(xdg.as_ref()).as_deref());
+            if stamp != static_stamp {
+                static_stamp = stamp;
+                reload_static(state, static_stamp.0.as_deref());
+            }
+
+            let stamp = Stamp::of(outputs_path.as_deref());
+            if stamp != outputs_stamp {
+                outputs_stamp = stamp;
+                // the file is also changed by us, when the outputs are configured
+                if let (Some(path), Some(_)) = (outputs_stamp.0.as_deref(), outputs_stamp.1) {
+                    reload_outputs(state, path);
+                }
+            }
+
+            TimeoutAction::ToDuration(POLL_INTERVAL)
+        })
+    {
+        warn!(?err, "Failed to watch the config files.");
+    }
+}
+
+fn reload_static(state: &mut State, path: Option<&Path>) {
+    let mut static_conf = match path {
+        Some(path) => match Config::read_static(path) {
+            Ok(static_conf) => {
+                info!("Reloaded config at {}", path.display());
+                static_conf
+            }
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "Failed to reload the config, keeping the current one."
+                );
+                show_config_error(state, format!("{:#}", err));
+                return;
+            }
+        },
+        None => {
+            info!("Config file removed, using the defaults.");
+            Config::default_static()
+        }
+    };
+
+    let current: &StaticConfig = &state.common.config.static_conf;
+    let needs_restart = keep_startup_options(&mut static_conf, current);
+    let gaps_changed = static_conf.gaps != current.gaps;
+    let animations_changed = static_conf.animations != current.animations;
+
+    state.common.config.static_conf = static_conf;
+    if gaps_changed {
+        let gaps = state.common.config.static_conf.gaps;
+        state.common.shell.set_gaps(gaps);
+    }
+    if animations_changed {
+        let animations = state.common.config.static_conf.animations;
+        state.common.shell.set_animations(animations);
+    }
+    state.update_idle_timer();
+    state.update_lid_inhibitor();
+    schedule_renders(state);
+
+    if !needs_restart.is_empty() {
+        show_config_message(
+            state,
+            String::from("Restart the compositor to apply the config"),
+            format!("Changed options: {}", needs_restart.join(", ")),
+        );
+    }
+}
+
+/// Keeps the current values of the options, that are only read on startup,
+/// returning the names of those that were changed
+fn keep_startup_options(new: &mut StaticConfig, current: &StaticConfig) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    macro_rules! keep {
+        ($($field:ident),* $(,)?) => {
+            $(
+                if new.$field != current.$field {
+                    changed.push(stringify!($field));
+                    new.$field = current.$field.clone();
+                }
+            )*
+        };
+    }
+    // workspaces, seats, backends and globals are only set up once
+    keep!(
+        workspace_mode,
+        workspace_amount,
+        tiling_enabled,
+        seats,
+        security_context,
+        data_control,
+        xwayland,
+        render_devices,
+        headless,
+        nested,
+    );
+    changed
+}
+
+fn read_outputs(path: &Path) -> anyhow::Result<OutputsConfig> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    ron::de::from_reader(file)
+        .with_context(|| format!("Malformed output config {}", path.display()))
+}
+
+fn reload_outputs(state: &mut State, path: &Path) {
+    let outputs = match read_outputs(path) {
+        Ok(outputs) => outputs,
+        Err(err) => {
+            warn!(
+                ?err,
+                "Failed to reload the output config, keeping the current one."
+            );
+            show_config_error(state, format!("{:#}", err));
+            return;
+        }
+    };
+    if outputs == state.common.config.dynamic_conf.outputs.1 {
+        return;
+    }
+
+    info!("Reloaded output config at {}", path.display());
+    state.common.config.dynamic_conf.outputs.1 = outputs;
+    let seats = state.common.seats().cloned().collect::<Vec<_>>();
+    state.common.config.read_outputs(
+        &mut state.common.output_configuration_state,
+        &mut state.backend,
+        &mut state.common.shell,
+        seats.into_iter(),
+        &state.common.event_loop_handle,
+    );
+}
+
+fn show_config_error(state: &mut State, message: String) {
+    show_config_message(
+        state,
+        String::from("Failed to load the compositor config"),
+        message,
+    );
+}
+
+fn show_config_message(state: &mut State, title: String, message: String) {
+    state
+        .common
+        .shell
+        .show_error(title, message, state.common.event_loop_handle.clone());
+    schedule_renders(state);
+    // redraw once more, when the error is gone
+    if let Err(err) = state.common.event_loop_handle.insert_source(
+        Timer::from_duration(ERROR_DURATION),
+        |_, _, state| {
+            schedule_renders(state);
+            TimeoutAction::Drop
+        },
+    ) {
+        warn!(?err, "Failed to schedule hiding the config error.");
+    }
+}
+
+fn schedule_renders(state: &mut State) {
+    for output in state.common.shell.outputs() {
+        state
+            .backend
+            .schedule_render(&state.common.event_loop_handle, output, None);
+    }
+}
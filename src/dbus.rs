@@ -0,0 +1,501 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! `org.cosmic.Compositor` D-Bus service for desktop components and scripts.
+//!
+//! The connection lives on its own thread. Method calls are forwarded to the event loop
+//! and wait for their reply, signals are emitted from changes noticed by `refresh_dbus`.
+
+use crate::{
+    backend::render::screenshot,
+    shell::{focus::target::KeyboardFocusTarget, CosmicSurface},
+    state::State,
+    utils::prelude::*,
+    wayland::protocols::toplevel_management::ToplevelManagementHandler,
+};
+use anyhow::{Context, Result};
+use futures_channel::oneshot::{self, Sender};
+use smithay::reexports::calloop::channel;
+use std::{collections::HashMap, sync::mpsc};
+use tracing::warn;
+use zbus::{dbus_interface, fdo, names::BusName, MessageHeader, SignalContext};
+
+const NAME: &str = "org.cosmic.Compositor";
+const PATH: &str = "/org/cosmic/Compositor";
+/// The only peer allowed to see the content of outputs and windows
+const PORTAL_NAME: &str = "org.freedesktop.impl.portal.desktop.cosmic";
+
+/// Id, app id, title, output, workspace number and whether the window has the focus
+pub type WindowEntry = (u64, String, String, String, u32, bool);
+/// Name, description, mode size and refresh rate (mHz) of an output,
+/// e.g. to set up the format of a stream capturing it
+pub type OutputEntry = (String, String, i32, i32, u32);
+/// App id (empty if unknown) of a client holding an idle inhibitor, whether the app is
+/// ignored and whether its surface is visible
+pub type IdleInhibitorEntry = (String, bool, bool);
+
+#[derive(Debug)]
+enum Request {
+    SwitchWorkspace {
+        output: String,
+        number: u32,
+        reply: Sender<fdo::Result<()>>,
+    },
+    FocusWindow {
+        id: u64,
+        reply: Sender<fdo::Result<()>>,
+    },
+    CloseWindow {
+        id: u64,
+        reply: Sender<fdo::Result<()>>,
+    },
+    ListWindows {
+        reply: Sender<Vec<WindowEntry>>,
+    },
+    ListOutputs {
+        reply: Sender<Vec<OutputEntry>>,
+    },
+    OutputThumbnail {
+        name: String,
+        reply: Sender<fdo::Result<screenshot::RgbaImage>>,
+    },
+    WindowThumbnail {
+        id: u64,
+        max_size: u32,
+        reply: Sender<fdo::Result<screenshot::RgbaImage>>,
+    },
+    IdleInhibitors {
+        reply: Sender<(bool, Vec<IdleInhibitorEntry>)>,
+    },
+}
+
+#[derive(Debug)]
+enum Event {
+    /// Id of the focused window, 0 if none is
+    FocusChanged(u64),
+    WorkspaceChanged {
+        output: String,
+        number: u32,
+    },
+}
+
+/// What the signals last told about
+#[derive(Debug)]
+pub struct DBusState {
+    events: mpsc::Sender<Event>,
+    focused: Option<u64>,
+    workspaces: HashMap<String, usize>,
+}
+
+struct Compositor {
+    requests: channel::Sender<Request>,
+}
+
+impl Compositor {
+    /// Sends a request to the event loop and waits for its reply without blocking the
+    /// executor of the connection
+    async fn call<T>(&self, request: impl FnOnce(Sender<T>) -> Request) -> fdo::Result<T> {
+        let shutting_down = || fdo::Error::Failed(String::from("The compositor is shutting down"));
+        let (reply, receiver) = oneshot::channel();
+        self.requests
+            .send(request(reply))
+            .map_err(|_| shutting_down())?;
+        receiver.await.map_err(|_| shutting_down())
+    }
+}
+
+#[dbus_interface(name = "org.cosmic.Compositor")]
+impl Compositor {
+    /// Activates workspace `number`, starting at 1, on `output` or the active output if empty
+    async fn switch_workspace(&self, output: String, number: u32) -> fdo::Result<()> {
+        self.call(|reply| Request::SwitchWorkspace {
+            output,
+            number,
+            reply,
+        })
+        .await?
+    }
+
+    async fn focus_window(&self, id: u64) -> fdo::Result<()> {
+        self.call(|reply| Request::FocusWindow { id, reply })
+            .await?
+    }
+
+    /// Asks the window to close
+    async fn close_window(&self, id: u64) -> fdo::Result<()> {
+        self.call(|reply| Request::CloseWindow { id, reply })
+            .await?
+    }
+
+    async fn list_windows(&self) -> fdo::Result<Vec<WindowEntry>> {
+        self.call(|reply| Request::ListWindows { reply }).await
+    }
+
+    async fn list_outputs(&self) -> fdo::Result<Vec<OutputEntry>> {
+        self.call(|reply| Request::ListOutputs { reply }).await
+    }
+
+    /// Whether idle is inhibited right now, and the idle inhibitors of all clients
+    async fn idle_inhibitors(&self) -> fdo::Result<(bool, Vec<IdleInhibitorEntry>)> {
+        self.call(|reply| Request::IdleInhibitors { reply }).await
+    }
+
+    /// Png image of an output, fitting into `max_size` pixels, for the screencast portal
+    async fn output_thumbnail(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: MessageHeader<'_>,
+        name: String,
+        max_size: u32,
+    ) -> fdo::Result<Vec<u8>> {
+        check_portal(connection, &header).await?;
+        let image = self
+            .call(|reply| Request::OutputThumbnail { name, reply })
+            .await??;
+        encode_thumbnail(image, max_size).await
+    }
+
+    /// Png image of a window, fitting into `max_size` pixels, even if it isn't visible,
+    /// for the screencast portal
+    async fn window_thumbnail(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: MessageHeader<'_>,
+        id: u64,
+        max_size: u32,
+    ) -> fdo::Result<Vec<u8>> {
+        check_portal(connection, &header).await?;
+        let image = self
+            .call(|reply| Request::WindowThumbnail {
+                id,
+                max_size,
+                reply,
+            })
+            .await??;
+        encode_thumbnail(image, max_size).await
+    }
+
+    #[dbus_interface(signal)]
+    async fn focus_changed(ctxt: &SignalContext<'_>, id: u64) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn workspace_changed(
+        ctxt: &SignalContext<'_>,
+        output: &str,
+        number: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// Only the lock surface acts on the session, while it is locked
+fn session_locked() -> fdo::Error {
+    fdo::Error::AccessDenied(String::from("The session is locked"))
+}
+
+fn no_such_window(id: u64) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("No window with id {}", id))
+}
+
+/// Fails unless the method was called by the current owner of the portal name
+async fn check_portal(
+    connection: &zbus::Connection,
+    header: &MessageHeader<'_>,
+) -> fdo::Result<()> {
+    let denied = || fdo::Error::AccessDenied(format!("Only {} may call this", PORTAL_NAME));
+    let sender = header.sender()?.ok_or_else(denied)?;
+    let owner = fdo::DBusProxy::new(connection)
+        .await?
+        .get_name_owner(BusName::try_from(PORTAL_NAME).map_err(|_| denied())?)
+        .await
+        .map_err(|_| denied())?;
+    if owner.as_str() == sender.as_str() {
+        Ok(())
+    } else {
+        Err(denied())
+    }
+}
+
+/// Encodes a thumbnail on its own thread, to not hold up the connection
+async fn encode_thumbnail(image: screenshot::RgbaImage, max_size: u32) -> fdo::Result<Vec<u8>> {
+    let (reply, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = reply.send(screenshot::encode_thumbnail(image, max_size));
+    });
+    receiver
+        .await
+        .map_err(|_| fdo::Error::Failed(String::from("Failed to encode the thumbnail")))?
+        .map_err(|err| fdo::Error::Failed(format!("{:#}", err)))
+}
+
+pub fn init(state: &mut State) -> Result<()> {
+    let (requests, request_source) = channel::channel();
+    state
+        .common
+        .event_loop_handle
+        .insert_source(request_source, |event, _, state| {
+            if let channel::Event::Msg(request) = event {
+                state.handle_dbus_request(request);
+            }
+        })
+        .with_context(|| "Failed to init the D-Bus request source")?;
+
+    let (events, event_receiver) = mpsc::channel::<Event>();
+    std::thread::Builder::new()
+        .name(String::from("dbus"))
+        .spawn(move || {
+            let connection = match zbus::blocking::ConnectionBuilder::session()
+                .and_then(|builder| builder.name(NAME))
+                .and_then(|builder| builder.serve_at(PATH, Compositor { requests }))
+                .and_then(|builder| builder.build())
+            {
+                Ok(connection) => connection,
+                Err(err) => {
+                    warn!(?err, "Failed to export {} on the session bus.", NAME);
+                    return;
+                }
+            };
+            let interface = match connection.object_server().interface::<_, Compositor>(PATH) {
+                Ok(interface) => interface,
+                Err(err) => {
+                    warn!(?err, "Failed to get the D-Bus interface.");
+                    return;
+                }
+            };
+
+            for event in event_receiver.iter() {
+                let ctxt = interface.signal_context();
+                let result = match event {
+                    Event::FocusChanged(id) => zbus::block_on(Compositor::focus_changed(ctxt, id)),
+                    Event::WorkspaceChanged { output, number } => {
+                        zbus::block_on(Compositor::workspace_changed(ctxt, &output, number))
+                    }
+                };
+                if let Err(err) = result {
+                    warn!(?err, "Failed to emit D-Bus signal.");
+                }
+            }
+        })
+        .with_context(|| "Failed to spawn the D-Bus thread")?;
+
+    state.common.dbus = Some(DBusState {
+        events,
+        focused: None,
+        workspaces: HashMap::new(),
+    });
+    Ok(())
+}
+
+impl State {
+    fn handle_dbus_request(&mut self, request: Request) {
+        match request {
+            Request::SwitchWorkspace {
+                output,
+                number,
+                reply,
+            } => {
+                if self.common.session_lock.is_some() {
+                    let _ = reply.send(Err(session_locked()));
+                    return;
+                }
+                let output = if output.is_empty() {
+                    Some(self.common.last_active_seat().active_output())
+                } else {
+                    self.common
+                        .shell
+                        .outputs()
+                        .find(|o| o.name() == output)
+                        .cloned()
+                };
+                let success = match output {
+                    Some(output) if number > 0 => self
+                        .common
+                        .shell
+                        .activate(&output, number as usize - 1)
+                        .is_ok(),
+                    _ => false,
+                };
+                let _ = reply.send(if success {
+                    Ok(())
+                } else {
+                    Err(fdo::Error::InvalidArgs(String::from(
+                        "No such output or workspace",
+                    )))
+                });
+            }
+            Request::FocusWindow { id, reply } => {
+                let result = if self.common.session_lock.is_some() {
+                    Err(session_locked())
+                } else if let Some(window) = self.dbus_window(id) {
+                    let dh = self.common.display_handle.clone();
+                    ToplevelManagementHandler::activate(self, &dh, &window, None);
+                    Ok(())
+                } else {
+                    Err(no_such_window(id))
+                };
+                let _ = reply.send(result);
+            }
+            Request::CloseWindow { id, reply } => {
+                let result = if self.common.session_lock.is_some() {
+                    Err(session_locked())
+                } else if let Some(window) = self.dbus_window(id) {
+                    window.close();
+                    Ok(())
+                } else {
+                    Err(no_such_window(id))
+                };
+                let _ = reply.send(result);
+            }
+            Request::ListWindows { reply } => {
+                let _ = reply.send(self.dbus_windows());
+            }
+            Request::ListOutputs { reply } => {
+                let outputs = self
+                    .common
+                    .shell
+                    .outputs()
+                    .map(|output| {
+                        let mode = output.current_mode();
+                        (
+                            output.name(),
+                            output.description(),
+                            mode.map_or(0, |mode| mode.size.w),
+                            mode.map_or(0, |mode| mode.size.h),
+                            mode.map_or(0, |mode| mode.refresh as u32),
+                        )
+                    })
+                    .collect();
+                let _ = reply.send(outputs);
+            }
+            Request::OutputThumbnail { name, reply } => {
+                let output = self
+                    .common
+                    .shell
+                    .outputs()
+                    .find(|output| output.name() == name)
+                    .cloned();
+                let result = match output {
+                    // nothing behind the lock surface may be seen
+                    _ if self.common.session_lock.is_some() => Err(session_locked()),
+                    Some(output) => screenshot::output_thumbnail(self, &output)
+                        .map_err(|err| fdo::Error::Failed(format!("{:#}", err))),
+                    None => Err(fdo::Error::InvalidArgs(format!("No output named {}", name))),
+                };
+                let _ = reply.send(result);
+            }
+            Request::WindowThumbnail {
+                id,
+                max_size,
+                reply,
+            } => {
+                let result = match self.dbus_window(id) {
+                    _ if self.common.session_lock.is_some() => Err(session_locked()),
+                    Some(window) => screenshot::window_thumbnail(self, &window, max_size)
+                        .map_err(|err| fdo::Error::Failed(format!("{:#}", err))),
+                    None => Err(no_such_window(id)),
+                };
+                let _ = reply.send(result);
+            }
+            Request::IdleInhibitors { reply } => {
+                let inhibitors = self
+                    .common
+                    .idle_inhibitors()
+                    .into_iter()
+                    .map(|inhibitor| {
+                        (
+                            inhibitor.app_id.unwrap_or_default(),
+                            inhibitor.ignored,
+                            inhibitor.visible,
+                        )
+                    })
+                    .collect();
+                let _ = reply.send((self.idle_inhibited(), inhibitors));
+            }
+        }
+    }
+
+    fn dbus_window(&self, id: u64) -> Option<CosmicSurface> {
+        self.common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|workspace| {
+                workspace
+                    .mapped()
+                    .chain(workspace.minimized_windows.iter().map(|(mapped, _)| mapped))
+            })
+            .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+            .find(|window| window.id() == id)
+    }
+
+    fn dbus_windows(&self) -> Vec<WindowEntry> {
+        let shell = &self.common.shell;
+        let focused = self.focused_window_id();
+        let mut windows = Vec::new();
+        for output in shell.outputs() {
+            for (idx, workspace) in shell.workspaces.spaces_for_output(output).enumerate() {
+                let mapped = workspace
+                    .mapped()
+                    .chain(workspace.minimized_windows.iter().map(|(mapped, _)| mapped));
+                for (window, _) in mapped.flat_map(|mapped| mapped.windows()) {
+                    let id = window.id();
+                    if windows.iter().any(|entry: &WindowEntry| entry.0 == id) {
+                        // workspaces are shared by all outputs in global mode
+                        continue;
+                    }
+                    windows.push((
+                        id,
+                        window.app_id(),
+                        window.title(),
+                        output.name(),
+                        idx as u32 + 1,
+                        focused == Some(id),
+                    ));
+                }
+            }
+        }
+        windows
+    }
+
+    fn focused_window_id(&self) -> Option<u64> {
+        match self
+            .common
+            .last_active_seat()
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus())
+        {
+            Some(KeyboardFocusTarget::Element(mapped)) => Some(mapped.active_window().id()),
+            _ => None,
+        }
+    }
+
+    /// Emits signals for changes of the focus and active workspaces
+    pub fn refresh_dbus(&mut self) {
+        if self.common.dbus.is_none() {
+            return;
+        }
+        let focused = self.focused_window_id();
+        let workspaces = self
+            .common
+            .shell
+            .outputs()
+            .map(|output| {
+                (
+                    output.name(),
+                    self.common.shell.workspaces.active_num(output).1,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let dbus = self.common.dbus.as_mut().unwrap();
+        if dbus.focused != focused {
+            dbus.focused = focused;
+            let _ = dbus.events.send(Event::FocusChanged(focused.unwrap_or(0)));
+        }
+        for (output, active) in workspaces {
+            if dbus.workspaces.insert(output.clone(), active) != Some(active) {
+                let _ = dbus.events.send(Event::WorkspaceChanged {
+                    output,
+                    number: active as u32 + 1,
+                });
+            }
+        }
+    }
+}
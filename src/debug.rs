@@ -70,6 +70,7 @@ pub fn fps_ui(
         fps.avg_frametime().as_secs_f64(),
         fps.avg_fps(),
     );
+    let missed_vblanks = fps.missed_vblanks();
     let (max_disp, min_disp) = (
         fps.max_time_to_display().as_secs_f64(),
         fps.min_time_to_display().as_secs_f64(),
@@ -186,6 +187,10 @@ pub fn fps_ui(
                         ui.label(egui::RichText::new(format!("avg: {:>7.6}", avg)).code());
                         ui.label(egui::RichText::new(format!("min: {:>7.6}", min)).code());
                         ui.label(egui::RichText::new(format!("max: {:>7.6}", max)).code());
+                        ui.label(
+                            egui::RichText::new(format!("missed vblanks: {}", missed_vblanks))
+                                .code(),
+                        );
                         let elements_chart = BarChart::new(bars_elements).vertical();
                         let render_chart = BarChart::new(bars_render)
                             .stack_on(&[&elements_chart])
@@ -290,6 +295,29 @@ pub fn fps_ui(
                                     });
                             }
                         }
+
+                        ui.separator();
+                        ui.label(egui::RichText::new("Idle Inhibitors").heading());
+                        if state.idle.manual_inhibit {
+                            ui.label(egui::RichText::new("Inhibited manually").code());
+                        }
+                        for inhibitor in state.idle_inhibitors() {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{} ({}): {}",
+                                    inhibitor.app_id.as_deref().unwrap_or("unknown"),
+                                    inhibitor.surface.id(),
+                                    if inhibitor.ignored {
+                                        "ignored"
+                                    } else if inhibitor.visible {
+                                        "active"
+                                    } else {
+                                        "hidden"
+                                    }
+                                ))
+                                .code(),
+                            );
+                        }
                     }
                 });
         },
@@ -335,6 +363,7 @@ fn format_pointer_focus(focus: Option<PointerFocusTarget>) -> String {
             x.surface().title()
         ),
         Some(LayerSurface(x)) => format!("LayerSurface {}", x.wl_surface().id().protocol_id()),
+        Some(LockSurface(x)) => format!("LockSurface {}", x.wl_surface().id().protocol_id()),
         Some(Popup(x)) => format!("Popup {}", x.wl_surface().id().protocol_id()),
         Some(OverrideRedirect(x)) => format!("Override Redirect {}", x.window_id()),
         Some(PointerFocusTarget::ResizeFork(x)) => format!("Resize Fork {:?}", x.node),
@@ -377,6 +406,7 @@ fn format_keyboard_focus(focus: Option<KeyboardFocusTarget>) -> String {
             x.surface().title()
         ),
         Some(LayerSurface(x)) => format!("LayerSurface {}", x.wl_surface().id().protocol_id()),
+        Some(LockSurface(x)) => format!("LockSurface {}", x.wl_surface().id().protocol_id()),
         Some(Popup(x)) => format!("Popup {}", x.wl_surface().id().protocol_id()),
         Some(Group(_)) => format!("Window Group"),
         None => format!("None"),
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::{Duration, Instant};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+use smithay::{
+    backend::renderer::utils::with_renderer_surface_state,
+    desktop::WindowSurfaceType,
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{compositor::get_parent, seat::WaylandFocus},
+};
+use tracing::{info, warn};
+
+use crate::state::{Common, State};
+
+/// How long waking up waits for the lock surfaces, before the outputs are turned on anyway
+const LOCK_SURFACE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Input activity and the resulting power state of the outputs
+#[derive(Debug)]
+pub struct IdleState {
+    last_activity: Instant,
+    /// The outputs were turned off for being idle
+    outputs_off: bool,
+    /// Outputs turned off one by one, which stay off on input
+    powered_off: Vec<Output>,
+    /// Timer turning off the outputs and the timeout in seconds it was set up with
+    timer: Option<(RegistrationToken, u64)>,
+    /// Fallback timer of a wake up, that waits for the lock surfaces
+    pending_wake: Option<RegistrationToken>,
+    /// Idle inhibited by the user, regardless of any clients
+    pub manual_inhibit: bool,
+}
+
+/// An idle inhibitor of a client and whether it currently has an effect
+#[derive(Debug, Clone)]
+pub struct IdleInhibitorStatus {
+    pub surface: WlSurface,
+    /// App id of the window the surface belongs to
+    pub app_id: Option<String>,
+    /// The app is configured to be ignored
+    pub ignored: bool,
+    /// The surface is visible and would inhibit idle
+    pub visible: bool,
+}
+
+impl IdleState {
+    /// Whether `output` is turned off, for being idle or on its own
+    pub fn is_off(&self, output: &Output) -> bool {
+        self.outputs_off || self.powered_off.contains(output)
+    }
+
+    pub fn new() -> IdleState {
+        IdleState {
+            last_activity: Instant::now(),
+            outputs_off: false,
+            powered_off: Vec::new(),
+            timer: None,
+            pending_wake: None,
+            manual_inhibit: false,
+        }
+    }
+}
+
+/// Turns the outputs off, once there was no input for `screen_off` seconds
+fn insert_idle_timer(
+    handle: &LoopHandle<'static, State>,
+    screen_off: u64,
+) -> Option<RegistrationToken> {
+    let timeout = Duration::from_secs(screen_off);
+    handle
+        .insert_source(Timer::from_duration(timeout), move |_, _, state| {
+            if state.idle_inhibited() {
+                state.common.idle.last_activity = Instant::now();
+                return TimeoutAction::ToDuration(timeout);
+            }
+            let idle_for = state.common.idle.last_activity.elapsed();
+            if idle_for < timeout {
+                return TimeoutAction::ToDuration(timeout - idle_for);
+            }
+            if !state.common.idle.outputs_off {
+                info!("Idle for {}s, turning off outputs.", timeout.as_secs());
+                state.set_outputs_power(false);
+            }
+            TimeoutAction::ToDuration(timeout)
+        })
+        .map_err(|err| warn!(?err, "Failed to start idle timer."))
+        .ok()
+}
+
+impl State {
+    /// Sets up the idle timer for the configured `idle.screen_off`, replacing the previous one
+    pub fn update_idle_timer(&mut self) {
+        let screen_off = self
+            .common
+            .config
+            .static_conf
+            .idle
+            .screen_off
+            .filter(|secs| *secs > 0);
+        if self.common.idle.timer.map(|(_, secs)| secs) == screen_off {
+            return;
+        }
+        if let Some((token, _)) = self.common.idle.timer.take() {
+            self.common.event_loop_handle.remove(token);
+        }
+        if let Some(secs) = screen_off {
+            self.common.idle.timer =
+                insert_idle_timer(&self.common.event_loop_handle, secs).map(|token| (token, secs));
+        }
+    }
+
+    /// Records input activity. If `wake` is set, outputs that were turned off are turned on again.
+    pub fn notify_activity(&mut self, wake: bool) {
+        self.common.idle.last_activity = Instant::now();
+        self.common.idle_notifier_state.notify_activity();
+        if wake && self.common.idle.outputs_off && self.common.idle.pending_wake.is_none() {
+            self.wake_outputs();
+        }
+    }
+
+    /// Whether idle is inhibited manually, or a visible surface holds an idle inhibitor.
+    ///
+    /// Surfaces of minimized windows, on inactive workspaces or below a fullscreen window
+    /// don't count, neither do those of ignored apps. Nothing inhibits idle while the session
+    /// is locked, not even the manual inhibitor.
+    pub fn idle_inhibited(&self) -> bool {
+        if self.common.session_lock.is_some() {
+            return false;
+        }
+        if self.common.idle.manual_inhibit {
+            return true;
+        }
+        self.common
+            .idle_inhibitors()
+            .iter()
+            .any(|inhibitor| inhibitor.visible && !inhibitor.ignored)
+    }
+
+    /// Toggles inhibiting idle irrespective of the clients
+    pub fn toggle_idle_inhibit(&mut self) {
+        let inhibit = !self.common.idle.manual_inhibit;
+        info!(inhibit, "Toggled manual idle inhibitor.");
+        self.common.idle.manual_inhibit = inhibit;
+        self.notify_activity(false);
+    }
+
+    /// Turns all outputs off, or on again. Outputs turned off on their own stay off.
+    pub fn set_outputs_power(&mut self, on: bool) {
+        if let Some(token) = self.common.idle.pending_wake.take() {
+            self.common.event_loop_handle.remove(token);
+        }
+        self.common.idle.outputs_off = !on;
+
+        let outputs = self.common.shell.outputs().cloned().collect::<Vec<_>>();
+        for output in &outputs {
+            let on = on && !self.common.idle.powered_off.contains(output);
+            self.switch_output_power(output, on);
+        }
+    }
+
+    /// Turns a single output off, or on again. Unlike idle, input doesn't turn it back on.
+    pub fn set_output_power(&mut self, output: &Output, on: bool) {
+        self.common.idle.powered_off.retain(|other| other != output);
+        if !on {
+            self.common.idle.powered_off.push(output.clone());
+        }
+        let on = on && !self.common.idle.outputs_off;
+        self.switch_output_power(output, on);
+    }
+
+    fn switch_output_power(&mut self, output: &Output, on: bool) {
+        let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        if let Err(err) = self.backend.set_output_power(
+            output,
+            on,
+            &mut self.common.shell,
+            seats.into_iter(),
+            &self.common.event_loop_handle,
+        ) {
+            warn!(
+                ?err,
+                on,
+                "Failed to switch power of output {}.",
+                output.name()
+            );
+        }
+        if on {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, output, None);
+        }
+    }
+
+    /// Turns the outputs on again. While the session is locked, this waits until every
+    /// output has a lock surface with content, so the panels never light up without it.
+    fn wake_outputs(&mut self) {
+        if self.lock_surfaces_ready() {
+            self.set_outputs_power(true);
+            return;
+        }
+
+        match self.common.event_loop_handle.insert_source(
+            Timer::from_duration(LOCK_SURFACE_TIMEOUT),
+            |_, _, state| {
+                state.common.idle.pending_wake = None;
+                state.set_outputs_power(true);
+                TimeoutAction::Drop
+            },
+        ) {
+            Ok(token) => self.common.idle.pending_wake = Some(token),
+            Err(err) => {
+                warn!(?err, "Failed to wait for lock surfaces.");
+                self.set_outputs_power(true);
+            }
+        }
+    }
+
+    /// Finishes a wake up waiting for the lock surfaces, once all of them have content
+    pub fn lock_surface_committed(&mut self) {
+        if self.common.idle.pending_wake.is_some() && self.lock_surfaces_ready() {
+            self.set_outputs_power(true);
+        }
+    }
+
+    fn lock_surfaces_ready(&self) -> bool {
+        let Some(session_lock) = &self.common.session_lock else {
+            return true;
+        };
+        self.common.shell.outputs().all(|output| {
+            session_lock.surfaces.get(output).map_or(false, |surface| {
+                with_renderer_surface_state(surface.wl_surface(), |state| state.buffer().is_some())
+            })
+        })
+    }
+}
+
+impl Common {
+    /// Idle inhibitors of all clients
+    pub fn idle_inhibitors(&self) -> Vec<IdleInhibitorStatus> {
+        let shell = &self.shell;
+        let ignored_apps = &self.config.static_conf.idle.ignore_inhibitors;
+        self.idle_inhibit_manager_state
+            .surfaces()
+            .map(|surface| {
+                let mut root = surface.clone();
+                while let Some(parent) = get_parent(&root) {
+                    root = parent;
+                }
+                let app_id = shell.element_for_wl_surface(&root).and_then(|mapped| {
+                    mapped
+                        .windows()
+                        .find(|(window, _)| window.wl_surface().as_ref() == Some(&root))
+                        .map(|(window, _)| window.app_id())
+                });
+                let visible = shell.visible_outputs_for_surface(surface).any(|output| {
+                    let workspace = shell.active_space(&output);
+                    workspace
+                        .fullscreen
+                        .get(&output)
+                        .map_or(true, |fullscreen| {
+                            workspace
+                                .element_for_surface(&fullscreen.window.surface())
+                                .map_or(false, |mapped| {
+                                    mapped.has_surface(surface, WindowSurfaceType::ALL)
+                                })
+                        })
+                });
+                IdleInhibitorStatus {
+                    surface: surface.clone(),
+                    ignored: app_id.as_ref().map_or(false, |app_id| {
+                        ignored_apps.iter().any(|ignored| ignored == app_id)
+                    }),
+                    app_id,
+                    visible,
+                }
+            })
+            .collect()
+    }
+}
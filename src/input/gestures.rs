@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::config::{GestureDirection, GestureKind, GesturePattern};
+use smithay::{
+    output::Output,
+    utils::{Logical, Point},
+};
+use std::cell::RefCell;
+
+/// Distance a swipe has to cover to be recognized regardless of its speed
+const SWIPE_DISTANCE_THRESHOLD: f64 = 100.0;
+/// Speed (in pixels per millisecond) at which shorter swipes are recognized as well
+pub const SWIPE_VELOCITY_THRESHOLD: f64 = 0.5;
+/// Distance even a fast swipe has to cover, so jitter doesn't trigger actions
+pub const SWIPE_MIN_DISTANCE: f64 = 20.0;
+/// Distance a swipe has to cover to move a whole workspace along
+pub const WORKSPACE_SWIPE_DISTANCE: f64 = 300.0;
+/// Weight of the latest motion in the tracked velocity, smoothing out jittery events
+const VELOCITY_SMOOTHING: f64 = 0.5;
+/// Relative change of the distance between fingers a pinch needs to be recognized
+const PINCH_SCALE_THRESHOLD: f64 = 0.2;
+
+#[derive(Default)]
+pub struct SeatGestureState(pub RefCell<Option<GestureRecognizer>>);
+
+/// Tracks an ongoing touchpad gesture, that might be bound to a compositor action
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    kind: GestureKind,
+    fingers: u32,
+    start_time: u32,
+    last_time: u32,
+    delta: Point<f64, Logical>,
+    velocity: Point<f64, Logical>,
+    scale: f64,
+    /// Whether the direction of the gesture is bound, `false` until it is known
+    pub claimed: bool,
+    /// Updates held back from clients, until the gesture is claimed or released to them
+    pub pending: Vec<PendingUpdate>,
+    /// Set, if the workspaces follow the swipe instead of switching once it is done
+    pub workspace_swipe: Option<WorkspaceSwipe>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PendingUpdate {
+    pub time: u32,
+    pub delta: Point<f64, Logical>,
+    pub scale: f64,
+    pub rotation: f64,
+}
+
+/// Workspace switch following an ongoing swipe
+#[derive(Debug)]
+pub struct WorkspaceSwipe {
+    pub output: Output,
+    /// Index of the workspace, that was active when the swipe started
+    pub origin: usize,
+    /// Unit vector pointing in the direction, that moves towards the next workspace
+    pub next: Point<f64, Logical>,
+}
+
+/// Unit vector pointing in the given direction, if it is one of a swipe
+pub fn swipe_vector(direction: GestureDirection) -> Option<Point<f64, Logical>> {
+    match direction {
+        GestureDirection::Left => Some((-1.0, 0.0).into()),
+        GestureDirection::Right => Some((1.0, 0.0).into()),
+        GestureDirection::Up => Some((0.0, -1.0).into()),
+        GestureDirection::Down => Some((0.0, 1.0).into()),
+        GestureDirection::In | GestureDirection::Out => None,
+    }
+}
+
+impl GestureRecognizer {
+    pub fn new(kind: GestureKind, fingers: u32, time: u32) -> GestureRecognizer {
+        GestureRecognizer {
+            kind,
+            fingers,
+            start_time: time,
+            last_time: time,
+            delta: Point::default(),
+            velocity: Point::default(),
+            scale: 1.0,
+            claimed: false,
+            pending: Vec::new(),
+            workspace_swipe: None,
+        }
+    }
+
+    pub fn kind(&self) -> GestureKind {
+        self.kind
+    }
+
+    pub fn fingers(&self) -> u32 {
+        self.fingers
+    }
+
+    pub fn start_time(&self) -> u32 {
+        self.start_time
+    }
+
+    pub fn update(&mut self, time: u32, delta: Point<f64, Logical>, scale: Option<f64>) {
+        let elapsed = time.wrapping_sub(self.last_time).max(1) as f64;
+        let velocity = Point::<f64, Logical>::from((delta.x / elapsed, delta.y / elapsed));
+        self.velocity = Point::from((
+            self.velocity.x + (velocity.x - self.velocity.x) * VELOCITY_SMOOTHING,
+            self.velocity.y + (velocity.y - self.velocity.y) * VELOCITY_SMOOTHING,
+        ));
+        self.last_time = time;
+        self.delta += delta;
+        if let Some(scale) = scale {
+            self.scale = scale;
+        }
+    }
+
+    /// Number of workspaces the swipe moved towards the next one,
+    /// negative if towards the previous one
+    pub fn workspace_progress(&self) -> Option<f64> {
+        let swipe = self.workspace_swipe.as_ref()?;
+        Some((self.delta.x * swipe.next.x + self.delta.y * swipe.next.y) / WORKSPACE_SWIPE_DISTANCE)
+    }
+
+    /// Current speed (in pixels per millisecond) towards the next workspace
+    pub fn workspace_velocity(&self) -> Option<f64> {
+        let swipe = self.workspace_swipe.as_ref()?;
+        Some(self.velocity.x * swipe.next.x + self.velocity.y * swipe.next.y)
+    }
+
+    fn swipe_direction(&self) -> GestureDirection {
+        let (x, y) = (self.delta.x, self.delta.y);
+        if x.abs() > y.abs() {
+            if x > 0.0 {
+                GestureDirection::Right
+            } else {
+                GestureDirection::Left
+            }
+        } else if y > 0.0 {
+            GestureDirection::Down
+        } else {
+            GestureDirection::Up
+        }
+    }
+
+    fn pinch_direction(&self) -> Option<GestureDirection> {
+        if self.scale <= 1.0 - PINCH_SCALE_THRESHOLD {
+            Some(GestureDirection::In)
+        } else if self.scale >= 1.0 + PINCH_SCALE_THRESHOLD {
+            Some(GestureDirection::Out)
+        } else {
+            None
+        }
+    }
+
+    fn pattern(&self, direction: GestureDirection) -> GesturePattern {
+        GesturePattern {
+            kind: self.kind,
+            fingers: self.fingers,
+            direction,
+        }
+    }
+
+    /// The gesture so far, once it moved far enough to tell its direction
+    pub fn current(&self) -> Option<GesturePattern> {
+        let direction = match self.kind {
+            GestureKind::Swipe => {
+                if self.delta.x.hypot(self.delta.y) < SWIPE_MIN_DISTANCE {
+                    return None;
+                }
+                self.swipe_direction()
+            }
+            GestureKind::Pinch => self.pinch_direction()?,
+        };
+        Some(self.pattern(direction))
+    }
+
+    /// Classifies the completed gesture, if it was distinct enough to trigger an action
+    pub fn finish(&self) -> Option<GesturePattern> {
+        let direction = match self.kind {
+            GestureKind::Swipe => {
+                let distance = self.delta.x.hypot(self.delta.y);
+                let duration = self.last_time.wrapping_sub(self.start_time).max(1) as f64;
+                if distance < SWIPE_DISTANCE_THRESHOLD
+                    && (distance < SWIPE_MIN_DISTANCE
+                        || distance / duration < SWIPE_VELOCITY_THRESHOLD)
+                {
+                    return None;
+                }
+                self.swipe_direction()
+            }
+            GestureKind::Pinch => self.pinch_direction()?,
+        };
+        Some(self.pattern(direction))
+    }
+}
@@ -1,35 +1,54 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
-    backend::render::cursor::CursorState,
-    config::{xkb_config_to_wl, Action, Config, KeyPattern, WorkspaceLayout},
+    backend::render::{cursor::CursorState, screenshot::take_screenshot},
+    config::{
+        xkb_config_to_wl, Action, ColorFilter, Config, GestureKind, GesturePattern, InternalOutput,
+        KeyModifiers, KeyPattern, LidAction, OutputConfig, ScreenshotTarget, ScrollAction,
+        WorkspaceLayout,
+    },
+    logind,
     shell::{
         focus::{target::PointerFocusTarget, FocusDirection},
-        grabs::{ResizeEdge, SeatMoveGrabState},
+        grabs::{
+            ResizeEdge, ScreenshotGrab, SeatMenuGrabState, SeatMoveGrabState,
+            SeatScreenshotGrabState,
+        },
         layout::tiling::{SwapWindowGrab, TilingLayout},
-        Direction, FocusResult, MoveResult, OverviewMode, ResizeDirection, ResizeMode, Trigger,
-        Workspace,
+        zoom::{set_zoom_level, zoom_level},
+        CosmicMapped, Direction, FocusResult, MoveResult, OverviewMode, ResizeDirection,
+        ResizeMode, Trigger, Workspace, IDENTIFY_DURATION,
     },
-    state::Common,
+    state::{Common, SessionLock},
     utils::prelude::*,
-    wayland::{handlers::screencopy::ScreencopySessions, protocols::screencopy::Session},
+    wayland::{
+        handlers::{
+            screencopy::ScreencopySessions, xwayland_keyboard_grab::xwayland_grabs_keyboard,
+        },
+        protocols::{input_timestamps::InputKind, screencopy::Session},
+    },
+};
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    RegistrationToken,
 };
-use calloop::{timer::Timer, RegistrationToken};
 use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::InputType;
 #[allow(deprecated)]
 use smithay::{
     backend::input::{
-        Axis, AxisSource, Device, DeviceCapability, GestureBeginEvent, GestureEndEvent,
-        GesturePinchUpdateEvent as _, GestureSwipeUpdateEvent as _, InputBackend, InputEvent,
-        KeyState, PointerAxisEvent,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Device, DeviceCapability,
+        GestureBeginEvent, GestureEndEvent, GesturePinchUpdateEvent as _,
+        GestureSwipeUpdateEvent as _, InputBackend, InputEvent, KeyState, PointerAxisEvent,
+        TouchSlot,
     },
     desktop::{layer_map_for_output, space::SpaceElement, WindowSurfaceType},
     input::{
         keyboard::{FilterResult, KeysymHandle, XkbConfig},
         pointer::{
-            AxisFrame, ButtonEvent, CursorImageStatus, GestureHoldBeginEvent, GestureHoldEndEvent,
-            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
-            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, MotionEvent,
+            AxisFrame, ButtonEvent, CursorImageStatus, Focus, GestureHoldBeginEvent,
+            GestureHoldEndEvent, GesturePinchBeginEvent, GesturePinchEndEvent,
+            GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+            GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData, MotionEvent,
             RelativeMotionEvent,
         },
         Seat, SeatState,
@@ -37,9 +56,9 @@ use smithay::{
     output::Output,
     reexports::{
         input::event::pointer::PointerAxisEvent as LibinputPointerAxisEvent,
-        wayland_server::DisplayHandle,
+        wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle, Resource},
     },
-    utils::{Logical, Point, Rectangle, Serial, SERIAL_COUNTER},
+    utils::{Logical, Point, Rectangle, Serial, Transform, SERIAL_COUNTER},
     wayland::{
         keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
         pointer_constraints::{with_pointer_constraint, PointerConstraint},
@@ -50,20 +69,28 @@ use smithay::{
 };
 #[cfg(not(feature = "debug"))]
 use tracing::info;
-use tracing::{error, trace, warn};
+use tracing::{debug, error, trace, warn};
 use xkbcommon::xkb::{Keycode, Keysym};
 
 use std::{
     any::Any,
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashMap,
+    sync::atomic::Ordering,
     time::{Duration, Instant},
 };
 
+mod gestures;
+use self::gestures::{
+    swipe_vector, GestureRecognizer, PendingUpdate, SeatGestureState, WorkspaceSwipe,
+    SWIPE_MIN_DISTANCE, SWIPE_VELOCITY_THRESHOLD, WORKSPACE_SWIPE_DISTANCE,
+};
+
 crate::utils::id_gen!(next_seat_id, SEAT_ID, SEAT_IDS);
 
 #[repr(transparent)]
 pub struct SeatId(pub usize);
+pub struct SeatName(pub String);
 pub struct ActiveOutput(pub RefCell<Output>);
 #[derive(Default)]
 pub struct SupressedKeys(RefCell<Vec<(Keycode, Option<RegistrationToken>)>>);
@@ -71,6 +98,21 @@ pub struct SupressedKeys(RefCell<Vec<(Keycode, Option<RegistrationToken>)>>);
 pub struct ModifiersShortcutQueue(RefCell<Option<KeyPattern>>);
 #[derive(Default)]
 pub struct Devices(RefCell<HashMap<String, Vec<DeviceCapability>>>);
+#[derive(Default)]
+pub struct ScrollActionAccumulator(Cell<f64>);
+#[derive(Default)]
+pub struct TouchPointerEmulation(Cell<Option<TouchSlot>>);
+
+const BTN_LEFT: u32 = 0x110;
+
+/// Continuous scroll distance that counts as one step of a scroll action
+const SCROLL_ACTION_THRESHOLD: f64 = 15.0;
+
+enum ScrollTarget {
+    Titlebar(CosmicMapped),
+    Gaps,
+    Background,
+}
 
 impl Default for SeatId {
     fn default() -> SeatId {
@@ -153,6 +195,15 @@ impl Devices {
         self.0.borrow().contains_key(&device.id())
     }
 
+    /// Ids of the devices of a seat with their capabilities
+    pub fn list(&self) -> Vec<(String, Vec<DeviceCapability>)> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(id, caps)| (id.clone(), caps.clone()))
+            .collect()
+    }
+
     fn remove_device<D: Device>(&self, device: &D) -> Vec<DeviceCapability> {
         let id = device.id();
         let mut map = self.0.borrow_mut();
@@ -164,6 +215,49 @@ impl Devices {
     }
 }
 
+fn is_internal_output(output: &Output) -> bool {
+    output.user_data().get::<InternalOutput>().is_some()
+}
+
+/// Accumulates smooth scrolling into discrete steps for scroll actions
+fn scroll_step(seat: &Seat<State>, amount: f64, amount_discrete: Option<f64>) -> i32 {
+    let accumulator = seat.user_data().get::<ScrollActionAccumulator>().unwrap();
+    match amount_discrete {
+        Some(discrete) => discrete.signum() as i32,
+        None => {
+            let total = accumulator.0.get() + amount;
+            if total.abs() >= SCROLL_ACTION_THRESHOLD {
+                accumulator.0.set(0.0);
+                total.signum() as i32
+            } else {
+                accumulator.0.set(total);
+                0
+            }
+        }
+    }
+}
+
+/// Maps the position of an absolute event into the global space of the given output.
+///
+/// Touchscreens and tablets mapped to an output don't rotate with the content,
+/// so their coordinates are relative to the untransformed panel, if `panel_relative`.
+/// Other absolute devices, like the host window of a nested session, match the content already.
+fn absolute_position<B: InputBackend>(
+    output: &Output,
+    event: &impl AbsolutePositionEvent<B>,
+    panel_relative: bool,
+) -> Point<f64, Logical> {
+    let geometry = output.geometry();
+    let transform = if panel_relative {
+        Transform::from(output.current_transform())
+    } else {
+        Transform::Normal
+    };
+    let size = transform.invert().transform_size(geometry.size);
+    let position = event.position_transformed(size);
+    geometry.loc.to_f64() + transform.transform_point_in(position, &size.to_f64())
+}
+
 pub fn add_seat(
     dh: &DisplayHandle,
     seat_state: &mut SeatState<State>,
@@ -171,13 +265,19 @@ pub fn add_seat(
     config: &Config,
     name: String,
 ) -> Seat<State> {
-    let mut seat = seat_state.new_wl_seat(dh, name);
+    let mut seat = seat_state.new_wl_seat(dh, name.clone());
     let userdata = seat.user_data();
     userdata.insert_if_missing(SeatId::default);
+    userdata.insert_if_missing(|| SeatName(name));
     userdata.insert_if_missing(Devices::default);
     userdata.insert_if_missing(SupressedKeys::default);
     userdata.insert_if_missing(ModifiersShortcutQueue::default);
     userdata.insert_if_missing(SeatMoveGrabState::default);
+    userdata.insert_if_missing(SeatMenuGrabState::default);
+    userdata.insert_if_missing(SeatScreenshotGrabState::default);
+    userdata.insert_if_missing(ScrollActionAccumulator::default);
+    userdata.insert_if_missing(TouchPointerEmulation::default);
+    userdata.insert_if_missing(SeatGestureState::default);
     userdata.insert_if_missing(CursorState::default);
     userdata.insert_if_missing(|| ActiveOutput(RefCell::new(output.clone())));
     userdata.insert_if_missing(|| RefCell::new(CursorImageStatus::default_named()));
@@ -215,9 +315,33 @@ impl State {
     {
         use smithay::backend::input::Event;
 
+        #[cfg(feature = "debug")]
+        puffin::profile_function!();
+
+        match &event {
+            InputEvent::DeviceAdded { .. } | InputEvent::DeviceRemoved { .. } => {}
+            // releasing the keys of the binding turning the outputs off shouldn't wake them again
+            InputEvent::Keyboard { event } => {
+                use smithay::backend::input::KeyboardKeyEvent;
+                self.notify_activity(event.state() == KeyState::Pressed)
+            }
+            InputEvent::PointerButton { event } => {
+                use smithay::backend::input::PointerButtonEvent;
+                self.notify_activity(event.state() == ButtonState::Pressed)
+            }
+            _ => self.notify_activity(true),
+        }
+
         match event {
             InputEvent::DeviceAdded { device } => {
-                let seat = &mut self.common.last_active_seat();
+                // devices not assigned to any configured seat belong to the default seat
+                let seat = self
+                    .common
+                    .config
+                    .seat_for_device(&device.name())
+                    .and_then(|name| self.common.seat_by_name(name))
+                    .or_else(|| self.common.seats().next())
+                    .unwrap_or_else(|| self.common.last_active_seat());
                 let userdata = seat.user_data();
                 let devices = userdata.get::<Devices>().unwrap();
                 for cap in devices.add_device(&device) {
@@ -256,22 +380,23 @@ impl State {
                 let loop_handle = self.common.event_loop_handle.clone();
 
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    self.common.set_last_active_seat(&seat);
                     let userdata = seat.user_data();
 
                     let current_output = seat.active_output();
                     let workspace = self.common.shell.active_space_mut(&current_output);
-                    // TODO handle session lock
-                    let shortcuts_inhibited = workspace
-                        .focus_stack
-                        .get(&seat)
-                        .last()
-                        .and_then(|window| {
-                            window.wl_surface().and_then(|surface| {
+                    // compositor shortcuts are disabled as well while the session is locked
+                    let shortcuts_inhibited = self.common.session_lock.is_some()
+                        || workspace
+                            .focus_stack
+                            .get(&seat)
+                            .last()
+                            .and_then(|window| window.wl_surface())
+                            .map_or(false, |surface| {
                                 seat.keyboard_shortcuts_inhibitor_for_surface(&surface)
-                            })
-                        })
-                        .map(|inhibitor| inhibitor.is_active())
-                        .unwrap_or(false);
+                                    .map_or(false, |inhibitor| inhibitor.is_active())
+                                    || xwayland_grabs_keyboard(&seat, &surface)
+                            });
 
                     let keycode = event.key_code();
                     let state = event.state();
@@ -279,9 +404,11 @@ impl State {
 
                     let serial = SERIAL_COUNTER.next_serial();
                     let time = Event::time_msec(&event);
+                    let timestamp = Duration::from_micros(Event::time(&event));
                     let keyboard = seat.get_keyboard().unwrap();
-                    // XXX should return lock surface?
                     let current_focus = keyboard.current_focus();
+                    let focused_surface =
+                        current_focus.as_ref().and_then(|focus| focus.wl_surface());
                     if let Some((action, pattern)) = keyboard
                             .input(
                                 self,
@@ -290,6 +417,32 @@ impl State {
                                 serial,
                                 time,
                                 |data, modifiers, handle| {
+                                    // Break stuck grabs, before anything else gets to see the key
+                                    let break_grabs = &data.common.config.static_conf.break_grabs_binding;
+                                    if state == KeyState::Pressed
+                                        && break_grabs.modifiers == *modifiers
+                                        && break_grabs.key.map_or(false, |key| handle.raw_syms().contains(&key))
+                                    {
+                                        userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
+                                        return FilterResult::Intercept(Some((
+                                            Action::_BreakGrabs,
+                                            break_grabs.clone(),
+                                        )));
+                                    }
+
+                                    // Escape cancels the selection of a screenshot
+                                    if state == KeyState::Pressed
+                                        && handle.modified_sym() == Keysym::Escape
+                                        && userdata.get::<SeatScreenshotGrabState>().unwrap().borrow().is_some()
+                                    {
+                                        userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
+                                        let seat = seat.clone();
+                                        data.common.event_loop_handle.insert_idle(move |state| {
+                                            ScreenshotGrab::cancel(state, &seat, SERIAL_COUNTER.next_serial(), time);
+                                        });
+                                        return FilterResult::Intercept(None);
+                                    }
+
                                     // Leave move overview mode, if any modifier was released
                                     if let OverviewMode::Started(Trigger::KeyboardMove(action_modifiers), _) =
                                         data.common.shell.overview_mode().0
@@ -540,6 +693,7 @@ impl State {
                                     }
 
                                     // keys are passed through to apps
+                                    data.send_input_timestamp(focused_surface.as_ref(), InputKind::Keyboard, timestamp);
                                     FilterResult::Forward
                                 },
                             )
@@ -563,6 +717,7 @@ impl State {
                         .map_global_to_space(position, &current_output);
                     let overview = self.common.shell.overview_mode();
                     let output_geometry = current_output.geometry();
+                    let x11_menu = self.common.x11_menu_grab();
                     let workspace = self.common.shell.workspaces.active_mut(&current_output);
                     let under = State::surface_under(
                         position,
@@ -570,6 +725,8 @@ impl State {
                         &current_output,
                         output_geometry,
                         &self.common.shell.override_redirect_windows,
+                        x11_menu.as_ref(),
+                        self.common.session_lock.as_ref(),
                         overview.0.clone(),
                         workspace,
                     );
@@ -630,6 +787,7 @@ impl State {
                         .cloned()
                         .unwrap_or(current_output.clone());
 
+                    let x11_menu = self.common.x11_menu_grab();
                     let workspace = self.common.shell.workspaces.active_mut(&output);
                     let output_geometry = output.geometry();
                     let new_under = State::surface_under(
@@ -638,6 +796,8 @@ impl State {
                         &output,
                         output_geometry,
                         &self.common.shell.override_redirect_windows,
+                        x11_menu.as_ref(),
+                        self.common.session_lock.as_ref(),
                         overview.0,
                         workspace,
                     );
@@ -677,6 +837,16 @@ impl State {
                     }
 
                     let serial = SERIAL_COUNTER.next_serial();
+                    let receiver = if ptr.is_grabbed() {
+                        ptr.current_focus()
+                    } else {
+                        under.as_ref().map(|(target, _)| target.clone())
+                    };
+                    self.send_input_timestamp(
+                        receiver.and_then(|target| target.wl_surface()).as_ref(),
+                        InputKind::Pointer,
+                        Duration::from_micros(event.time()),
+                    );
                     ptr.motion(
                         self,
                         under,
@@ -749,68 +919,19 @@ impl State {
             }
             InputEvent::PointerMotionAbsolute { event, .. } => {
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
-                    let output = seat.active_output();
-                    let geometry = output.geometry();
-                    let position = geometry.loc.to_f64()
-                        + smithay::backend::input::AbsolutePositionEvent::position_transformed(
-                            &event,
-                            geometry.size,
-                        );
-                    let relative_pos = self.common.shell.map_global_to_space(position, &output);
-                    let overview = self.common.shell.overview_mode();
-                    let workspace = self.common.shell.workspaces.active_mut(&output);
-                    let serial = SERIAL_COUNTER.next_serial();
-                    let under = State::surface_under(
-                        position,
-                        relative_pos,
-                        &output,
-                        geometry,
-                        &self.common.shell.override_redirect_windows,
-                        overview.0,
-                        workspace,
-                    );
-
-                    for session in sessions_for_output(&self.common, &output) {
-                        if let Some((geometry, offset)) = seat.cursor_geometry(
-                            position.to_buffer(
-                                output.current_scale().fractional_scale(),
-                                output.current_transform(),
-                                &output.geometry().size.to_f64(),
-                            ),
-                            self.common.clock.now(),
-                        ) {
-                            session.cursor_info(&seat, InputType::Pointer, geometry, offset);
-                        }
-                    }
-                    let ptr = seat.get_pointer().unwrap();
-                    ptr.motion(
-                        self,
-                        under,
-                        &MotionEvent {
-                            location: position,
-                            serial,
-                            time: event.time_msec(),
-                        },
-                    );
-                    ptr.frame(self);
-                    #[cfg(feature = "debug")]
-                    if self.common.seats().position(|x| x == &seat).unwrap() == 0 {
-                        let location = if let Some(output) = self.common.shell.outputs.first() {
-                            self.common
-                                .shell
-                                .map_global_to_space(position, output)
-                                .to_i32_round()
-                        } else {
-                            position.to_i32_round()
-                        };
-                        self.common.egui.state.handle_pointer_motion(location);
-                    }
+                    let device_name = event.device().name();
+                    let output = self.absolute_output(&seat, &device_name);
+                    let mapped = self.common.config.mapped_output(&device_name).is_some();
+                    let position = absolute_position(&output, &event, mapped);
+                    let time = Duration::from_micros(event.time());
+                    self.pointer_motion_absolute(&seat, &output, position, time);
                 }
             }
             InputEvent::PointerButton { event, .. } => {
-                use smithay::backend::input::{ButtonState, PointerButtonEvent};
+                use smithay::backend::input::PointerButtonEvent;
 
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    self.common.set_last_active_seat(&seat);
                     #[cfg(feature = "debug")]
                     if self.common.seats().position(|x| x == &seat).unwrap() == 0
                         && self.common.egui.active
@@ -826,127 +947,12 @@ impl State {
                         }
                     }
 
-                    let serial = SERIAL_COUNTER.next_serial();
-                    let button = event.button_code();
-                    if event.state() == ButtonState::Pressed {
-                        // change the keyboard focus unless the pointer or keyboard is grabbed
-                        // We test for any matching surface type here but always use the root
-                        // (in case of a window the toplevel) surface for the focus.
-                        // see: https://gitlab.freedesktop.org/wayland/wayland/-/issues/294
-                        if !seat.get_pointer().unwrap().is_grabbed()
-                            && !seat.get_keyboard().map(|k| k.is_grabbed()).unwrap_or(false)
-                        {
-                            let output = seat.active_output();
-                            let pos = seat.get_pointer().unwrap().current_location();
-                            let relative_pos = self.common.shell.map_global_to_space(pos, &output);
-                            let overview = self.common.shell.overview_mode();
-                            let workspace = self.common.shell.active_space_mut(&output);
-                            let mut under = None;
-
-                            if let Some(window) = workspace.get_fullscreen(&output) {
-                                let layers = layer_map_for_output(&output);
-                                if let Some(layer) =
-                                    layers.layer_under(WlrLayer::Overlay, relative_pos)
-                                {
-                                    let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                                    if layer.can_receive_keyboard_focus()
-                                        && layer
-                                            .surface_under(
-                                                relative_pos - layer_loc.to_f64(),
-                                                WindowSurfaceType::ALL,
-                                            )
-                                            .is_some()
-                                    {
-                                        under = Some(layer.clone().into());
-                                    }
-                                } else {
-                                    under = Some(window.clone().into());
-                                }
-                            } else {
-                                let done = {
-                                    let layers = layer_map_for_output(&output);
-                                    if let Some(layer) = layers
-                                        .layer_under(WlrLayer::Overlay, relative_pos)
-                                        .or_else(|| layers.layer_under(WlrLayer::Top, relative_pos))
-                                    {
-                                        let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                                        if layer.can_receive_keyboard_focus()
-                                            && layer
-                                                .surface_under(
-                                                    relative_pos - layer_loc.to_f64(),
-                                                    WindowSurfaceType::ALL,
-                                                )
-                                                .is_some()
-                                        {
-                                            under = Some(layer.clone().into());
-                                        }
-                                        true
-                                    } else {
-                                        false
-                                    }
-                                };
-                                if !done {
-                                    if let Some(surface) = workspace.get_maximized(&output) {
-                                        under = Some(surface.clone().into());
-                                    } else {
-                                        if let Some((target, _)) =
-                                            workspace.element_under(relative_pos, overview.0)
-                                        {
-                                            under = Some(target);
-                                        } else {
-                                            let layers = layer_map_for_output(&output);
-                                            if let Some(layer) = layers
-                                                .layer_under(WlrLayer::Bottom, pos)
-                                                .or_else(|| {
-                                                    layers.layer_under(WlrLayer::Background, pos)
-                                                })
-                                            {
-                                                let layer_loc =
-                                                    layers.layer_geometry(layer).unwrap().loc;
-                                                if layer.can_receive_keyboard_focus()
-                                                    && layer
-                                                        .surface_under(
-                                                            relative_pos - layer_loc.to_f64(),
-                                                            WindowSurfaceType::ALL,
-                                                        )
-                                                        .is_some()
-                                                {
-                                                    under = Some(layer.clone().into());
-                                                }
-                                            };
-                                        }
-                                    }
-                                }
-                            }
-                            Common::set_focus(
-                                self,
-                                under.and_then(|target| target.try_into().ok()).as_ref(),
-                                &seat,
-                                Some(serial),
-                            );
-                        }
-                    } else {
-                        if let OverviewMode::Started(Trigger::Pointer(action_button), _) =
-                            self.common.shell.overview_mode().0
-                        {
-                            if action_button == button {
-                                self.common
-                                    .shell
-                                    .set_overview_mode(None, self.common.event_loop_handle.clone());
-                            }
-                        }
-                    };
-                    let ptr = seat.get_pointer().unwrap();
-                    ptr.button(
-                        self,
-                        &ButtonEvent {
-                            button,
-                            state: event.state(),
-                            serial,
-                            time: event.time_msec(),
-                        },
+                    self.pointer_button(
+                        &seat,
+                        event.button_code(),
+                        event.state(),
+                        Duration::from_micros(event.time()),
                     );
-                    ptr.frame(self);
                 }
             }
             InputEvent::PointerAxis { event, .. } => {
@@ -988,6 +994,28 @@ impl State {
                     let horizontal_amount_discrete = event.amount_discrete(Axis::Horizontal);
                     let vertical_amount_discrete = event.amount_discrete(Axis::Vertical);
 
+                    let zoom_modifiers = &self.common.config.static_conf.zoom.scroll_modifiers;
+                    if *zoom_modifiers != KeyModifiers::default()
+                        && *zoom_modifiers == seat.get_keyboard().unwrap().modifier_state()
+                    {
+                        let seat = seat.clone();
+                        let step = scroll_step(&seat, vertical_amount, vertical_amount_discrete);
+                        if step != 0 {
+                            // scrolling up zooms in
+                            self.zoom(&seat, -step);
+                        }
+                        return;
+                    }
+
+                    if let Some((action, target)) = self.scroll_action(seat) {
+                        let seat = seat.clone();
+                        let step = scroll_step(&seat, vertical_amount, vertical_amount_discrete);
+                        if step != 0 {
+                            self.handle_scroll_action(&seat, action, target, step);
+                        }
+                        return;
+                    }
+
                     {
                         let mut frame = AxisFrame::new(event.time_msec()).source(event.source());
                         if horizontal_amount != 0.0 {
@@ -1008,13 +1036,28 @@ impl State {
                             frame = frame.stop(Axis::Vertical);
                         }
                         let ptr = seat.get_pointer().unwrap();
+                        self.send_input_timestamp(
+                            ptr.current_focus()
+                                .and_then(|target| target.wl_surface())
+                                .as_ref(),
+                            InputKind::Pointer,
+                            Duration::from_micros(event.time()),
+                        );
                         ptr.axis(self, frame);
                         ptr.frame(self);
                     }
                 }
             }
             InputEvent::GestureSwipeBegin { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_begin(
+                        &seat,
+                        GestureKind::Swipe,
+                        event.fingers(),
+                        event.time_msec(),
+                    ) {
+                        return;
+                    }
                     let serial = SERIAL_COUNTER.next_serial();
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_swipe_begin(
@@ -1028,7 +1071,10 @@ impl State {
                 }
             }
             InputEvent::GestureSwipeUpdate { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_update(&seat, event.time_msec(), event.delta(), None) {
+                        return;
+                    }
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_swipe_update(
                         self,
@@ -1040,7 +1086,10 @@ impl State {
                 }
             }
             InputEvent::GestureSwipeEnd { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_end(&seat, event.cancelled(), event.time_msec()) {
+                        return;
+                    }
                     let serial = SERIAL_COUNTER.next_serial();
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_swipe_end(
@@ -1054,7 +1103,15 @@ impl State {
                 }
             }
             InputEvent::GesturePinchBegin { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_begin(
+                        &seat,
+                        GestureKind::Pinch,
+                        event.fingers(),
+                        event.time_msec(),
+                    ) {
+                        return;
+                    }
                     let serial = SERIAL_COUNTER.next_serial();
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_pinch_begin(
@@ -1068,7 +1125,15 @@ impl State {
                 }
             }
             InputEvent::GesturePinchUpdate { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_update(
+                        &seat,
+                        event.time_msec(),
+                        event.delta(),
+                        Some((event.scale(), event.rotation())),
+                    ) {
+                        return;
+                    }
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_pinch_update(
                         self,
@@ -1082,7 +1147,10 @@ impl State {
                 }
             }
             InputEvent::GesturePinchEnd { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    if self.gesture_end(&seat, event.cancelled(), event.time_msec()) {
+                        return;
+                    }
                     let serial = SERIAL_COUNTER.next_serial();
                     let pointer = seat.get_pointer().unwrap();
                     pointer.gesture_pinch_end(
@@ -1123,7 +1191,684 @@ impl State {
                     );
                 }
             }
-            _ => { /* TODO e.g. tablet or touch events */ }
+            InputEvent::SwitchToggle { event } => {
+                use smithay::backend::input::{Switch, SwitchState, SwitchToggleEvent};
+
+                match event.switch() {
+                    Some(Switch::Lid) => self.handle_lid_switch(event.state() == SwitchState::On),
+                    Some(Switch::TabletMode) => {
+                        let enabled = event.state() == SwitchState::On;
+                        debug!(enabled, "Tablet mode toggled");
+                        self.common.shell.tablet_mode = enabled;
+                    }
+                    None => {}
+                }
+            }
+            InputEvent::TouchDown { event } => {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    // only the first touch point drives the emulated pointer
+                    let emulation = seat.user_data().get::<TouchPointerEmulation>().unwrap();
+                    if emulation.0.get().is_some() {
+                        return;
+                    }
+                    emulation.0.set(Some(event.slot()));
+
+                    self.common.set_last_active_seat(&seat);
+                    let output = self.absolute_output(&seat, &event.device().name());
+                    let position = absolute_position(&output, &event, true);
+                    let time = Duration::from_micros(event.time());
+                    self.pointer_motion_absolute(&seat, &output, position, time);
+                    self.pointer_button(&seat, BTN_LEFT, ButtonState::Pressed, time);
+                }
+            }
+            InputEvent::TouchMotion { event } => {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let emulation = seat.user_data().get::<TouchPointerEmulation>().unwrap();
+                    if emulation.0.get() != Some(event.slot()) {
+                        return;
+                    }
+
+                    let output = self.absolute_output(&seat, &event.device().name());
+                    let position = absolute_position(&output, &event, true);
+                    let time = Duration::from_micros(event.time());
+                    self.pointer_motion_absolute(&seat, &output, position, time);
+                }
+            }
+            InputEvent::TouchUp { event } => {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let emulation = seat.user_data().get::<TouchPointerEmulation>().unwrap();
+                    if emulation.0.get() != Some(event.slot()) {
+                        return;
+                    }
+                    emulation.0.set(None);
+
+                    let time = Duration::from_micros(event.time());
+                    self.pointer_button(&seat, BTN_LEFT, ButtonState::Released, time);
+                }
+            }
+            InputEvent::TouchCancel { event } => {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let emulation = seat.user_data().get::<TouchPointerEmulation>().unwrap();
+                    if emulation.0.get() != Some(event.slot()) {
+                        return;
+                    }
+                    emulation.0.set(None);
+
+                    let time = Duration::from_micros(event.time());
+                    self.pointer_button(&seat, BTN_LEFT, ButtonState::Released, time);
+                }
+            }
+            _ => { /* TODO e.g. tablet events */ }
+        }
+    }
+
+    /// Starts recognizing a gesture, if some direction of it is bound to an action.
+    ///
+    /// Returns `true` if the gesture is held back from clients,
+    /// until it is known whether its direction is bound.
+    fn gesture_begin(
+        &mut self,
+        seat: &Seat<State>,
+        kind: GestureKind,
+        fingers: u32,
+        time: u32,
+    ) -> bool {
+        // only the lock surface acts on input while the session is locked
+        let bound = self.common.session_lock.is_none()
+            && self
+                .common
+                .config
+                .static_conf
+                .gestures
+                .keys()
+                .any(|pattern| pattern.kind == kind && pattern.fingers == fingers);
+        let recognizer = bound.then(|| GestureRecognizer::new(kind, fingers, time));
+        *seat
+            .user_data()
+            .get::<SeatGestureState>()
+            .unwrap()
+            .0
+            .borrow_mut() = recognizer;
+        bound
+    }
+
+    /// Lets the workspaces follow a swipe, if it is bound to switching between them.
+    ///
+    /// Swipes bound to other actions, or across the workspace axis, are left alone.
+    fn workspace_swipe(
+        &self,
+        seat: &Seat<State>,
+        swipe: &GesturePattern,
+    ) -> Option<WorkspaceSwipe> {
+        if swipe.kind != GestureKind::Swipe {
+            return None;
+        }
+        let workspace_vector = |pattern: &GesturePattern, action: &Action| match action {
+            Action::NextWorkspace => swipe_vector(pattern.direction),
+            Action::PreviousWorkspace => {
+                swipe_vector(pattern.direction).map(|vector| Point::from((-vector.x, -vector.y)))
+            }
+            _ => None,
+        };
+        let gestures = &self.common.config.static_conf.gestures;
+        let next = match gestures.get(swipe) {
+            Some(action) => workspace_vector(swipe, action)?,
+            None => {
+                let next = gestures
+                    .iter()
+                    .filter(|(pattern, _)| {
+                        pattern.kind == swipe.kind && pattern.fingers == swipe.fingers
+                    })
+                    .find_map(|(pattern, action)| workspace_vector(pattern, action))?;
+                let direction = swipe_vector(swipe.direction)?;
+                if (direction.x * next.x + direction.y * next.y).abs() < 0.5 {
+                    return None;
+                }
+                next
+            }
+        };
+        let output = seat.active_output();
+        let origin = self.common.shell.workspaces.active_num(&output).1;
+        Some(WorkspaceSwipe {
+            output,
+            origin,
+            next,
+        })
+    }
+
+    /// Feeds an update to the recognizer, `pinch` being the scale and rotation of pinches.
+    ///
+    /// Returns `true` if the update must not be forwarded to clients.
+    fn gesture_update(
+        &mut self,
+        seat: &Seat<State>,
+        time: u32,
+        delta: Point<f64, Logical>,
+        pinch: Option<(f64, f64)>,
+    ) -> bool {
+        let mut state = seat
+            .user_data()
+            .get::<SeatGestureState>()
+            .unwrap()
+            .0
+            .borrow_mut();
+        if let Some(recognizer) = state.as_mut() {
+            recognizer.update(time, delta, pinch.map(|(scale, _)| scale));
+            if !recognizer.claimed {
+                let (scale, rotation) = pinch.unwrap_or((1.0, 0.0));
+                recognizer.pending.push(PendingUpdate {
+                    time,
+                    delta,
+                    scale,
+                    rotation,
+                });
+                let Some(pattern) = recognizer.current() else {
+                    return true;
+                };
+                let workspace_swipe = self.workspace_swipe(seat, &pattern);
+                if workspace_swipe.is_some()
+                    || self
+                        .common
+                        .config
+                        .static_conf
+                        .gestures
+                        .contains_key(&pattern)
+                {
+                    recognizer.claimed = true;
+                    recognizer.pending.clear();
+                    recognizer.workspace_swipe = workspace_swipe;
+                } else {
+                    let recognizer = state.take().unwrap();
+                    std::mem::drop(state);
+                    self.release_gesture(seat, recognizer);
+                    return true;
+                }
+            }
+            if let (Some(swipe), Some(progress)) = (
+                recognizer.workspace_swipe.as_ref(),
+                recognizer.workspace_progress(),
+            ) {
+                self.common
+                    .shell
+                    .update_workspace_gesture(&swipe.output, swipe.origin, progress);
+                self.backend
+                    .schedule_render(&self.common.event_loop_handle, &swipe.output, None);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forwards a gesture, that turned out not to be bound, to clients
+    fn release_gesture(&mut self, seat: &Seat<State>, recognizer: GestureRecognizer) {
+        let pointer = seat.get_pointer().unwrap();
+        let serial = SERIAL_COUNTER.next_serial();
+        match recognizer.kind() {
+            GestureKind::Swipe => {
+                pointer.gesture_swipe_begin(
+                    self,
+                    &GestureSwipeBeginEvent {
+                        serial,
+                        time: recognizer.start_time(),
+                        fingers: recognizer.fingers(),
+                    },
+                );
+                for update in &recognizer.pending {
+                    pointer.gesture_swipe_update(
+                        self,
+                        &GestureSwipeUpdateEvent {
+                            time: update.time,
+                            delta: update.delta,
+                        },
+                    );
+                }
+            }
+            GestureKind::Pinch => {
+                pointer.gesture_pinch_begin(
+                    self,
+                    &GesturePinchBeginEvent {
+                        serial,
+                        time: recognizer.start_time(),
+                        fingers: recognizer.fingers(),
+                    },
+                );
+                for update in &recognizer.pending {
+                    pointer.gesture_pinch_update(
+                        self,
+                        &GesturePinchUpdateEvent {
+                            time: update.time,
+                            delta: update.delta,
+                            scale: update.scale,
+                            rotation: update.rotation,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn gesture_end(&mut self, seat: &Seat<State>, cancelled: bool, time: u32) -> bool {
+        let Some(recognizer) = seat
+            .user_data()
+            .get::<SeatGestureState>()
+            .unwrap()
+            .0
+            .borrow_mut()
+            .take()
+        else {
+            return false;
+        };
+        if !recognizer.claimed {
+            // too short to tell, clients get the whole gesture
+            self.release_gesture(seat, recognizer);
+            return false;
+        }
+        // gestures started before the session was locked don't act anymore
+        let cancelled = cancelled || self.common.session_lock.is_some();
+
+        if let (Some(swipe), Some(progress), Some(velocity)) = (
+            recognizer.workspace_swipe.as_ref(),
+            recognizer.workspace_progress(),
+            recognizer.workspace_velocity(),
+        ) {
+            // settle on the next workspace, if the swipe got far enough or was fast enough
+            let towards = velocity * progress.signum();
+            let commit = !cancelled
+                && (progress.abs() >= 0.5
+                    || (towards >= SWIPE_VELOCITY_THRESHOLD
+                        && progress.abs() * WORKSPACE_SWIPE_DISTANCE >= SWIPE_MIN_DISTANCE));
+            self.common.shell.end_workspace_gesture(
+                &swipe.output,
+                swipe.origin,
+                progress,
+                velocity / WORKSPACE_SWIPE_DISTANCE,
+                commit,
+            );
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &swipe.output, None);
+        } else if !cancelled {
+            let action = recognizer.finish().and_then(|pattern| {
+                self.common
+                    .config
+                    .static_conf
+                    .gestures
+                    .get(&pattern)
+                    .cloned()
+            });
+            if let Some(action) = action {
+                trace!(?recognizer, ?action, "gesture");
+                let serial = SERIAL_COUNTER.next_serial();
+                let pattern = KeyPattern::new(seat.get_keyboard().unwrap().modifier_state(), None);
+                self.handle_action(action, seat, serial, time, pattern, None);
+            }
+        }
+        true
+    }
+
+    /// Changes the magnification of the active output of the seat by the given amount of steps
+    fn zoom(&mut self, seat: &Seat<State>, steps: i32) {
+        let output = seat.active_output();
+        let config = &self.common.config.static_conf.zoom;
+        let level = zoom_level(&output) * config.step.powi(steps);
+        set_zoom_level(&output, level.min(config.max_level));
+        self.backend
+            .schedule_render(&self.common.event_loop_handle, &output, None);
+    }
+
+    /// Output absolute input of the given device maps to.
+    ///
+    /// Uses the output the device is bound to in its config, if connected,
+    /// and falls back to the active output of the seat.
+    fn absolute_output(&self, seat: &Seat<State>, device_name: &str) -> Output {
+        let output = self
+            .common
+            .config
+            .mapped_output(device_name)
+            .and_then(|name| {
+                self.common
+                    .shell
+                    .outputs()
+                    .find(|output| output.name() == name)
+            })
+            .cloned()
+            .unwrap_or_else(|| seat.active_output());
+        if output != seat.active_output() {
+            seat.set_active_output(&output);
+        }
+        output
+    }
+
+    pub fn pointer_motion_absolute(
+        &mut self,
+        seat: &Seat<State>,
+        output: &Output,
+        position: Point<f64, Logical>,
+        time: Duration,
+    ) {
+        let geometry = output.geometry();
+        let relative_pos = self.common.shell.map_global_to_space(position, output);
+        let overview = self.common.shell.overview_mode();
+        let x11_menu = self.common.x11_menu_grab();
+        let workspace = self.common.shell.workspaces.active_mut(output);
+        let serial = SERIAL_COUNTER.next_serial();
+        let under = State::surface_under(
+            position,
+            relative_pos,
+            output,
+            geometry,
+            &self.common.shell.override_redirect_windows,
+            x11_menu.as_ref(),
+            self.common.session_lock.as_ref(),
+            overview.0,
+            workspace,
+        );
+
+        for session in sessions_for_output(&self.common, output) {
+            if let Some((geometry, offset)) = seat.cursor_geometry(
+                position.to_buffer(
+                    output.current_scale().fractional_scale(),
+                    output.current_transform(),
+                    &output.geometry().size.to_f64(),
+                ),
+                self.common.clock.now(),
+            ) {
+                session.cursor_info(seat, InputType::Pointer, geometry, offset);
+            }
+        }
+        let ptr = seat.get_pointer().unwrap();
+        let receiver = if ptr.is_grabbed() {
+            ptr.current_focus()
+        } else {
+            under.as_ref().map(|(target, _)| target.clone())
+        };
+        self.send_input_timestamp(
+            receiver.and_then(|target| target.wl_surface()).as_ref(),
+            InputKind::Pointer,
+            time,
+        );
+        ptr.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: position,
+                serial,
+                time: time.as_millis() as u32,
+            },
+        );
+        ptr.frame(self);
+        #[cfg(feature = "debug")]
+        if self.common.seats().position(|x| x == seat).unwrap() == 0 {
+            let location = if let Some(output) = self.common.shell.outputs.first() {
+                self.common
+                    .shell
+                    .map_global_to_space(position, output)
+                    .to_i32_round()
+            } else {
+                position.to_i32_round()
+            };
+            self.common.egui.state.handle_pointer_motion(location);
+        }
+    }
+
+    /// Sends the precise `time` of the next input event of `kind` to the client of `surface`
+    fn send_input_timestamp(&self, surface: Option<&WlSurface>, kind: InputKind, time: Duration) {
+        if let Some(client) = surface.and_then(|surface| surface.client()) {
+            self.common
+                .input_timestamps_state
+                .send_timestamp(&client, kind, time);
+        }
+    }
+
+    fn pointer_button(
+        &mut self,
+        seat: &Seat<State>,
+        button: u32,
+        state: ButtonState,
+        time: Duration,
+    ) {
+        let serial = SERIAL_COUNTER.next_serial();
+        if state == ButtonState::Pressed {
+            // change the keyboard focus unless the pointer or keyboard is grabbed
+            // We test for any matching surface type here but always use the root
+            // (in case of a window the toplevel) surface for the focus.
+            // see: https://gitlab.freedesktop.org/wayland/wayland/-/issues/294
+            if !seat.get_pointer().unwrap().is_grabbed()
+                && !seat.get_keyboard().map(|k| k.is_grabbed()).unwrap_or(false)
+            {
+                let output = seat.active_output();
+                let pos = seat.get_pointer().unwrap().current_location();
+                let relative_pos = self.common.shell.map_global_to_space(pos, &output);
+                let overview = self.common.shell.overview_mode();
+                let workspace = self.common.shell.active_space_mut(&output);
+                let mut under = None;
+
+                if let Some(window) = workspace.get_fullscreen(&output) {
+                    let layers = layer_map_for_output(&output);
+                    if let Some(layer) = layers.layer_under(WlrLayer::Overlay, relative_pos) {
+                        let layer_loc = layers.layer_geometry(layer).unwrap().loc;
+                        if layer.can_receive_keyboard_focus()
+                            && layer
+                                .surface_under(
+                                    relative_pos - layer_loc.to_f64(),
+                                    WindowSurfaceType::ALL,
+                                )
+                                .is_some()
+                        {
+                            under = Some(layer.clone().into());
+                        }
+                    } else {
+                        under = Some(window.clone().into());
+                    }
+                } else {
+                    let done = {
+                        let layers = layer_map_for_output(&output);
+                        if let Some(layer) = layers
+                            .layer_under(WlrLayer::Overlay, relative_pos)
+                            .or_else(|| layers.layer_under(WlrLayer::Top, relative_pos))
+                        {
+                            let layer_loc = layers.layer_geometry(layer).unwrap().loc;
+                            if layer.can_receive_keyboard_focus()
+                                && layer
+                                    .surface_under(
+                                        relative_pos - layer_loc.to_f64(),
+                                        WindowSurfaceType::ALL,
+                                    )
+                                    .is_some()
+                            {
+                                under = Some(layer.clone().into());
+                            }
+                            true
+                        } else {
+                            false
+                        }
+                    };
+                    if !done {
+                        if let Some(surface) = workspace.get_maximized(&output) {
+                            under = Some(surface.clone().into());
+                        } else {
+                            if let Some((target, _)) =
+                                workspace.element_under(relative_pos, overview.0)
+                            {
+                                under = Some(target);
+                            } else {
+                                let layers = layer_map_for_output(&output);
+                                if let Some(layer) = layers
+                                    .layer_under(WlrLayer::Bottom, pos)
+                                    .or_else(|| layers.layer_under(WlrLayer::Background, pos))
+                                {
+                                    let layer_loc = layers.layer_geometry(layer).unwrap().loc;
+                                    if layer.can_receive_keyboard_focus()
+                                        && layer
+                                            .surface_under(
+                                                relative_pos - layer_loc.to_f64(),
+                                                WindowSurfaceType::ALL,
+                                            )
+                                            .is_some()
+                                    {
+                                        under = Some(layer.clone().into());
+                                    }
+                                };
+                            }
+                        }
+                    }
+                }
+                Common::set_focus(
+                    self,
+                    under.and_then(|target| target.try_into().ok()).as_ref(),
+                    seat,
+                    Some(serial),
+                );
+            }
+        } else {
+            if let OverviewMode::Started(Trigger::Pointer(action_button), _) =
+                self.common.shell.overview_mode().0
+            {
+                if action_button == button {
+                    self.common
+                        .shell
+                        .set_overview_mode(None, self.common.event_loop_handle.clone());
+                }
+            }
+        };
+        let ptr = seat.get_pointer().unwrap();
+        self.send_input_timestamp(
+            ptr.current_focus()
+                .and_then(|target| target.wl_surface())
+                .as_ref(),
+            InputKind::Pointer,
+            time,
+        );
+        ptr.button(
+            self,
+            &ButtonEvent {
+                button,
+                state,
+                serial,
+                time: time.as_millis() as u32,
+            },
+        );
+        ptr.frame(self);
+    }
+
+    fn scroll_action(&self, seat: &Seat<State>) -> Option<(ScrollAction, ScrollTarget)> {
+        let pointer = seat.get_pointer().unwrap();
+        if pointer.is_grabbed() || self.common.session_lock.is_some() {
+            return None;
+        }
+
+        let actions = &self.common.config.static_conf.scroll_actions;
+        if actions.modifiers != seat.get_keyboard().unwrap().modifier_state() {
+            return None;
+        }
+
+        // the wallpaper and empty workspaces are below the gaps between tiled windows
+        let gaps_or_background = || {
+            let output = seat.active_output();
+            let location = pointer.current_location().to_i32_round();
+            let inner = self.common.config.static_conf.gaps.1 as i32;
+            if self
+                .common
+                .shell
+                .active_space(&output)
+                .tiling_layer
+                .mapped()
+                .any(|(o, _, geo)| {
+                    o == &output
+                        && Rectangle::from_extemities(
+                            geo.loc - Point::from((inner, inner)),
+                            geo.loc + geo.size + Point::from((inner, inner)),
+                        )
+                        .contains(location)
+                })
+            {
+                ScrollTarget::Gaps
+            } else {
+                ScrollTarget::Background
+            }
+        };
+        let target = match pointer.current_focus() {
+            Some(PointerFocusTarget::Element(mapped)) if mapped.is_header_focused() => {
+                ScrollTarget::Titlebar(mapped)
+            }
+            Some(PointerFocusTarget::ResizeFork(_)) => ScrollTarget::Gaps,
+            Some(PointerFocusTarget::LayerSurface(layer))
+                if layer.layer() == WlrLayer::Background =>
+            {
+                gaps_or_background()
+            }
+            Some(_) => return None,
+            None => gaps_or_background(),
+        };
+
+        let action = match target {
+            ScrollTarget::Titlebar(_) => actions.titlebar,
+            ScrollTarget::Gaps => actions.gaps,
+            ScrollTarget::Background => actions.background,
+        };
+        (action != ScrollAction::None).then_some((action, target))
+    }
+
+    fn handle_scroll_action(
+        &mut self,
+        seat: &Seat<State>,
+        action: ScrollAction,
+        target: ScrollTarget,
+        step: i32,
+    ) {
+        let output = seat.active_output();
+        let mapped = match target {
+            ScrollTarget::Titlebar(mapped) => Some(mapped),
+            _ => self
+                .common
+                .shell
+                .active_space(&output)
+                .focus_stack
+                .get(seat)
+                .last()
+                .cloned(),
+        };
+
+        match action {
+            ScrollAction::None => {}
+            ScrollAction::CycleWorkspaces => {
+                let current = self.common.shell.workspaces.active_num(&output).1;
+                let workspace = if step > 0 {
+                    current.saturating_add(1)
+                } else {
+                    current.saturating_sub(1)
+                };
+                let _ = self.common.shell.activate(&output, workspace);
+            }
+            ScrollAction::CycleTabs => {
+                if let Some(mapped) = mapped.filter(|mapped| mapped.is_stack()) {
+                    let direction = if step > 0 {
+                        FocusDirection::Right
+                    } else {
+                        FocusDirection::Left
+                    };
+                    mapped.handle_focus(direction, None);
+                }
+            }
+            ScrollAction::Opacity => {
+                if let Some(mapped) = mapped {
+                    // scrolling up makes the window more opaque
+                    mapped.set_opacity(mapped.opacity() - step as f32 * 0.05);
+                    self.common
+                        .shell
+                        .active_space(&output)
+                        .dirty
+                        .store(true, Ordering::SeqCst);
+                }
+            }
         }
     }
 
@@ -1158,6 +1903,115 @@ impl State {
             Action::Debug => {
                 info!("Debug overlay not included in this build.")
             }
+            Action::ZoomIn => self.zoom(seat, 1),
+            Action::ZoomOut => self.zoom(seat, -1),
+            Action::ZoomReset => {
+                let output = seat.active_output();
+                set_zoom_level(&output, 1.0);
+                self.backend
+                    .schedule_render(&self.common.event_loop_handle, &output, None);
+            }
+            Action::ToggleColorFilter(filter) => {
+                let output = seat.active_output();
+                {
+                    let mut config = output
+                        .user_data()
+                        .get::<RefCell<OutputConfig>>()
+                        .unwrap()
+                        .borrow_mut();
+                    config.color_filter = if config.color_filter == filter {
+                        ColorFilter::None
+                    } else {
+                        filter
+                    };
+                }
+                let seats = self.common.seats().cloned().collect::<Vec<_>>();
+                if let Err(err) = self.backend.apply_config_for_output(
+                    &output,
+                    false,
+                    &mut self.common.shell,
+                    seats.iter().cloned(),
+                    &self.common.event_loop_handle,
+                ) {
+                    warn!(?err, "Failed to apply color filter to {}.", output.name());
+                }
+                self.common
+                    .config
+                    .write_outputs(self.common.output_configuration_state.outputs());
+            }
+            Action::Screenshot(target) => {
+                let output = seat.active_output();
+                match target {
+                    ScreenshotTarget::Output => take_screenshot(self, seat, &output, None),
+                    ScreenshotTarget::Window => {
+                        let workspace = self.common.shell.active_space(&output);
+                        // fullscreen windows cover the whole output anyway
+                        let region = if workspace.get_fullscreen(&output).is_some() {
+                            None
+                        } else {
+                            let Some(geometry) = workspace
+                                .focus_stack
+                                .get(seat)
+                                .last()
+                                .and_then(|mapped| workspace.element_geometry(mapped))
+                            else {
+                                return;
+                            };
+                            Some(geometry)
+                        };
+                        take_screenshot(self, seat, &output, region);
+                    }
+                    ScreenshotTarget::Region => {
+                        let pointer = seat.get_pointer().unwrap();
+                        let start_data = PointerGrabStartData {
+                            focus: None,
+                            button: BTN_LEFT,
+                            location: pointer.current_location(),
+                        };
+                        let grab = ScreenshotGrab::new(start_data, seat);
+                        pointer.set_grab(self, grab, serial, Focus::Clear);
+                    }
+                }
+            }
+            Action::PowerOffOutputs => self.set_outputs_power(false),
+            Action::ToggleIdleInhibit => self.toggle_idle_inhibit(),
+            Action::IdentifyOutputs => {
+                self.common
+                    .shell
+                    .identify_outputs(self.common.event_loop_handle.clone());
+                for output in self.common.shell.outputs() {
+                    self.backend
+                        .schedule_render(&self.common.event_loop_handle, output, None);
+                }
+                // redraw once more, when the labels are gone
+                if let Err(err) = self.common.event_loop_handle.insert_source(
+                    Timer::from_duration(IDENTIFY_DURATION),
+                    |_, _, state| {
+                        for output in state.common.shell.outputs() {
+                            state.backend.schedule_render(
+                                &state.common.event_loop_handle,
+                                output,
+                                None,
+                            );
+                        }
+                        TimeoutAction::Drop
+                    },
+                ) {
+                    warn!(?err, "Failed to schedule hiding the output labels.");
+                }
+            }
+            Action::DebugDamage => {
+                self.common.visualize_damage = !self.common.visualize_damage;
+                debug!(
+                    enabled = self.common.visualize_damage,
+                    "Damage visualization toggled"
+                );
+            }
+            Action::DebugAddOutput => self.add_virtual_output(),
+            Action::DebugRemoveOutput => {
+                let output = seat.active_output();
+                self.remove_virtual_output(&output);
+            }
             Action::Close => {
                 let current_output = seat.active_output();
                 let workspace = self.common.shell.active_space_mut(&current_output);
@@ -1674,36 +2528,180 @@ impl State {
                 let workspace = self.common.shell.active_space_mut(&output);
                 workspace.toggle_floating_window(seat);
             }
-            Action::Spawn(command) => {
-                let wayland_display = self.common.socket.clone();
+            Action::Spawn(command) => self.spawn_command(command),
+            Action::_BreakGrabs => self.break_grabs(seat, serial, time),
+        }
+    }
 
-                let display = self
-                    .common
-                    .xwayland_state
-                    .as_ref()
-                    .map(|s| format!(":{}", s.display))
-                    .unwrap_or_default();
-
-                std::thread::spawn(move || {
-                    let mut cmd = std::process::Command::new("/bin/sh");
-
-                    cmd.arg("-c")
-                        .arg(command.clone())
-                        .env("WAYLAND_DISPLAY", &wayland_display)
-                        .env("DISPLAY", &display)
-                        .env_remove("COSMIC_SESSION_SOCK");
-
-                    match cmd.spawn() {
-                        Ok(mut child) => {
-                            let _res = child.wait();
-                        }
-                        Err(err) => {
-                            tracing::warn!(?err, "Failed to spawn \"{}\"", command);
-                        }
+    /// Forcefully releases all grabs and pointer constraints of a seat
+    fn break_grabs(&mut self, seat: &Seat<State>, serial: Serial, time: u32) {
+        warn!("Releasing all input grabs on user request.");
+
+        if let Some(keyboard) = seat.get_keyboard() {
+            keyboard.unset_grab();
+        }
+        if let Some(pointer) = seat.get_pointer() {
+            pointer.unset_grab(self, serial, time);
+            if let Some(surface) = pointer
+                .current_focus()
+                .and_then(|target| target.wl_surface())
+            {
+                with_pointer_constraint(&surface, &pointer, |constraint| {
+                    if let Some(constraint) = constraint {
+                        constraint.deactivate();
                     }
                 });
             }
         }
+
+        let userdata = seat.user_data();
+        userdata
+            .get::<SeatMoveGrabState>()
+            .unwrap()
+            .borrow_mut()
+            .take();
+        userdata
+            .get::<SeatMenuGrabState>()
+            .unwrap()
+            .borrow_mut()
+            .take();
+        ScreenshotGrab::cancel(self, seat, serial, time);
+        userdata
+            .get::<SeatGestureState>()
+            .unwrap()
+            .0
+            .borrow_mut()
+            .take();
+        userdata.get::<TouchPointerEmulation>().unwrap().0.set(None);
+
+        self.common
+            .shell
+            .set_overview_mode(None, self.common.event_loop_handle.clone());
+        self.common.shell.set_resize_mode(
+            None,
+            &self.common.config,
+            self.common.event_loop_handle.clone(),
+        );
+    }
+
+    pub fn spawn_command(&self, command: String) {
+        let wayland_display = self.common.socket.clone();
+
+        let display = self
+            .common
+            .xwayland_state
+            .as_ref()
+            .map(|s| format!(":{}", s.display))
+            .unwrap_or_default();
+
+        std::thread::spawn(move || {
+            let mut cmd = std::process::Command::new("/bin/sh");
+
+            cmd.arg("-c")
+                .arg(command.clone())
+                .env("WAYLAND_DISPLAY", &wayland_display)
+                .env("DISPLAY", &display)
+                .env_remove("COSMIC_SESSION_SOCK");
+
+            match cmd.spawn() {
+                Ok(mut child) => {
+                    let _res = child.wait();
+                }
+                Err(err) => {
+                    tracing::warn!(?err, "Failed to spawn \"{}\"", command);
+                }
+            }
+        });
+    }
+
+    /// Keeps logind from handling the lid switch, unless it is left to it
+    pub fn update_lid_inhibitor(&self) {
+        if let Some(logind) = self.common.logind.as_ref() {
+            let action = self.common.config.static_conf.switches.lid_close;
+            logind.send(logind::Request::InhibitLidSwitch(action != LidAction::None));
+        }
+    }
+
+    fn handle_lid_switch(&mut self, closed: bool) {
+        let action = self.common.config.static_conf.switches.lid_close;
+        if closed {
+            let request = match action {
+                LidAction::None => None,
+                LidAction::DisableOutput => {
+                    self.set_internal_outputs_enabled(false);
+                    None
+                }
+                LidAction::Lock => Some(logind::Request::LockSession),
+                LidAction::Suspend => Some(logind::Request::Suspend),
+            };
+            if let Some(request) = request {
+                match self.common.logind.as_ref() {
+                    Some(logind) => logind.send(request),
+                    None => warn!(?request, "Can't handle the lid switch without logind."),
+                }
+            }
+        } else {
+            self.set_internal_outputs_enabled(true);
+        }
+    }
+
+    fn set_internal_outputs_enabled(&mut self, enabled: bool) {
+        let outputs = if enabled {
+            std::mem::take(&mut self.common.lid_disabled_outputs)
+        } else {
+            let outputs = self
+                .common
+                .output_configuration_state
+                .outputs()
+                .filter(|output| {
+                    output
+                        .user_data()
+                        .get::<RefCell<OutputConfig>>()
+                        .map_or(false, |conf| conf.borrow().enabled)
+                })
+                .collect::<Vec<_>>();
+            // never turn off the last remaining output
+            if outputs.iter().all(is_internal_output) {
+                return;
+            }
+            outputs.into_iter().filter(is_internal_output).collect()
+        };
+
+        let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        for output in outputs {
+            let backup = {
+                let mut config = output
+                    .user_data()
+                    .get::<RefCell<OutputConfig>>()
+                    .unwrap()
+                    .borrow_mut();
+                std::mem::replace(&mut config.enabled, enabled)
+            };
+            if let Err(err) = self.backend.apply_config_for_output(
+                &output,
+                false,
+                &mut self.common.shell,
+                seats.iter().cloned(),
+                &self.common.event_loop_handle,
+            ) {
+                warn!(?err, "Failed to switch internal output {}.", output.name());
+                output
+                    .user_data()
+                    .get::<RefCell<OutputConfig>>()
+                    .unwrap()
+                    .borrow_mut()
+                    .enabled = backup;
+                continue;
+            }
+
+            if enabled {
+                self.common.output_configuration_state.enable_head(&output);
+            } else {
+                self.common.output_configuration_state.disable_head(&output);
+                self.common.lid_disabled_outputs.push(output);
+            }
+        }
+        self.common.output_configuration_state.update();
     }
 
     pub fn surface_under(
@@ -1712,9 +2710,18 @@ impl State {
         output: &Output,
         output_geo: Rectangle<i32, Logical>,
         override_redirect_windows: &[X11Surface],
+        x11_menu: Option<&X11Surface>,
+        session_lock: Option<&SessionLock>,
         overview: OverviewMode,
         workspace: &mut Workspace,
     ) -> Option<(PointerFocusTarget, Point<i32, Logical>)> {
+        // nothing but the lock surface receives input while the session is locked
+        if let Some(session_lock) = session_lock {
+            return session_lock
+                .surfaces
+                .get(output)
+                .map(|lock_surface| (lock_surface.clone().into(), output_geo.loc));
+        }
         if let Some(window) = workspace.get_fullscreen(output) {
             let layers = layer_map_for_output(output);
             if let Some(layer) = layers.layer_under(WlrLayer::Overlay, relative_pos) {
@@ -1728,7 +2735,9 @@ impl State {
             }
             if let Some(or) = override_redirect_windows
                 .iter()
+                .rev()
                 .find(|or| or.is_in_input_region(&(global_pos - or.geometry().loc.to_f64())))
+                .or(x11_menu)
             {
                 return Some((or.clone().into(), or.geometry().loc));
             }
@@ -1751,7 +2760,9 @@ impl State {
             }
             if let Some(or) = override_redirect_windows
                 .iter()
+                .rev()
                 .find(|or| or.is_in_input_region(&(global_pos - or.geometry().loc.to_f64())))
+                .or(x11_menu)
             {
                 return Some((or.clone().into(), or.geometry().loc));
             }
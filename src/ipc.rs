@@ -0,0 +1,651 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! JSON IPC for scripts and external tools.
+//!
+//! Clients connect to the socket in `COSMIC_COMP_SOCK` and write one request per line,
+//! e.g. `{"request": "command", "action": {"Workspace": 2}}`. Every request is answered
+//! with a line of JSON, either `{"success": true, "result": ...}` or
+//! `{"success": false, "error": "..."}`.
+
+use crate::{
+    config::{Action, KeyModifiers, KeyPattern, OutputConfig, TransformDef},
+    input::Devices,
+    shell::{focus::target::KeyboardFocusTarget, CosmicMapped, CosmicSurface},
+    state::State,
+    utils::prelude::*,
+    wayland::protocols::output_configuration::{ModeConfiguration, OutputConfiguration},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use smithay::{
+    output::Output,
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    utils::{Logical, Rectangle, Transform, SERIAL_COUNTER},
+};
+use std::{
+    cell::RefCell,
+    io::{ErrorKind, Read, Write},
+    os::unix::{
+        io::{AsFd, BorrowedFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+pub const SOCKET_ENV: &str = "COSMIC_COMP_SOCK";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "request")]
+pub enum Request {
+    /// Runs an action, like a key binding would
+    Command {
+        action: Action,
+    },
+    Exec {
+        command: String,
+    },
+    /// Changes the configuration of an output, unset fields are kept
+    Output {
+        name: String,
+        #[serde(default)]
+        enabled: Option<bool>,
+        /// Size and refresh rate in mHz
+        #[serde(default)]
+        mode: Option<((i32, i32), Option<u32>)>,
+        #[serde(default)]
+        scale: Option<f64>,
+        #[serde(default, deserialize_with = "deserialize_transform")]
+        transform: Option<Transform>,
+        #[serde(default)]
+        position: Option<(i32, i32)>,
+    },
+    /// Turns an output off or on again, while keeping it mapped
+    OutputPower {
+        name: String,
+        on: bool,
+    },
+    GetTree,
+    GetWorkspaces,
+    GetOutputs,
+    GetInputs,
+    GetIdleInhibitors,
+}
+
+fn deserialize_transform<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Transform>, D::Error> {
+    TransformDef::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize)]
+struct Reply {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Geometry {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl From<Rectangle<i32, Logical>> for Geometry {
+    fn from(rect: Rectangle<i32, Logical>) -> Geometry {
+        Geometry {
+            x: rect.loc.x,
+            y: rect.loc.y,
+            width: rect.size.w,
+            height: rect.size.h,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ModeInfo {
+    width: i32,
+    height: i32,
+    /// In mHz
+    refresh: i32,
+    preferred: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct OutputInfo {
+    name: String,
+    make: String,
+    model: String,
+    enabled: bool,
+    /// Area in the global compositor space
+    geometry: Geometry,
+    scale: f64,
+    #[serde(with = "TransformDef")]
+    transform: Transform,
+    current_mode: Option<ModeInfo>,
+    modes: Vec<ModeInfo>,
+    /// Stored configuration, which is applied when the output is connected
+    config: OutputConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkspaceInfo {
+    output: String,
+    /// Starting at 1, like the `Workspace` action
+    number: usize,
+    active: bool,
+    tiling: bool,
+    windows: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct WindowInfo {
+    app_id: String,
+    title: String,
+    x11: bool,
+    geometry: Option<Geometry>,
+    floating: bool,
+    minimized: bool,
+    maximized: bool,
+    fullscreen: bool,
+    focused: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkspaceNode {
+    #[serde(flatten)]
+    workspace: WorkspaceInfo,
+    windows: Vec<WindowInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct OutputNode {
+    name: String,
+    geometry: Geometry,
+    workspaces: Vec<WorkspaceNode>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceInfo {
+    id: String,
+    capabilities: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SeatInfo {
+    name: String,
+    active: bool,
+    devices: Vec<DeviceInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct IdleInhibitorInfo {
+    app_id: Option<String>,
+    /// The app is configured to be ignored
+    ignored: bool,
+    /// The surface is visible and would inhibit idle
+    visible: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct IdleInfo {
+    /// Whether idle is inhibited right now
+    inhibited: bool,
+    /// Inhibited through the `ToggleIdleInhibit` action
+    manual: bool,
+    inhibitors: Vec<IdleInhibitorInfo>,
+}
+
+/// Removes the socket again, once the compositor shuts down
+struct Listener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl AsFd for Listener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Longest request line, before a client is dropped
+const MAX_REQUEST_LEN: usize = 1024 * 1024;
+/// Most reply bytes queued for a client not reading them, before it is dropped
+const MAX_OUTGOING_LEN: usize = 16 * 1024 * 1024;
+
+struct Connection {
+    stream: UnixStream,
+    /// Received bytes without a newline yet
+    buffer: Vec<u8>,
+    /// Replies not written yet, as the socket was full
+    outgoing: Vec<u8>,
+}
+
+impl AsFd for Connection {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+/// Listens for IPC clients next to the wayland socket and sets `COSMIC_COMP_SOCK` for
+/// the processes we spawn
+pub fn init_socket(handle: LoopHandle<'static, State>, state: &State) -> Result<()> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .with_context(|| "XDG_RUNTIME_DIR is not set")?;
+    let path = runtime_dir.join(format!(
+        "cosmic-comp-{}.sock",
+        state.common.socket.to_string_lossy()
+    ));
+    // left over by a crashed instance, the wayland socket is ours already
+    let _ = std::fs::remove_file(&path);
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    listener.set_nonblocking(true)?;
+    info!("IPC listening on {}", path.display());
+    std::env::set_var(SOCKET_ENV, &path);
+
+    handle
+        .insert_source(
+            Generic::new(Listener { listener, path }, Interest::READ, Mode::Level),
+            |_, listener, state| {
+                // SAFETY: We don't drop the listener!
+                let listener = unsafe { listener.get_mut() };
+                loop {
+                    match listener.listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) =
+                                add_connection(&state.common.event_loop_handle, stream)
+                            {
+                                warn!(?err, "Failed to add IPC client.");
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                        Err(err) => {
+                            warn!(?err, "Failed to accept IPC client.");
+                            break;
+                        }
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .with_context(|| "Failed to init the IPC socket source")?;
+    Ok(())
+}
+
+fn add_connection(handle: &LoopHandle<'static, State>, stream: UnixStream) -> Result<()> {
+    // a client not reading its replies shouldn't stall the compositor
+    stream.set_nonblocking(true)?;
+    handle
+        .insert_source(
+            Generic::new(
+                Connection {
+                    stream,
+                    buffer: Vec::new(),
+                    outgoing: Vec::new(),
+                },
+                Interest::BOTH,
+                Mode::Edge,
+            ),
+            |readiness, connection, state| {
+                // SAFETY: We don't drop the stream!
+                let connection = unsafe { connection.get_mut() };
+                if readiness.readable && !connection.read_requests(state) {
+                    return Ok(PostAction::Remove);
+                }
+                if !connection.flush() {
+                    return Ok(PostAction::Remove);
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .with_context(|| "Failed to init the IPC client source")?;
+    Ok(())
+}
+
+impl Connection {
+    /// Reads and handles all available requests, `false` if the client is to be dropped
+    fn read_requests(&mut self, state: &mut State) -> bool {
+        let mut data = [0; 4096];
+        loop {
+            match self.stream.read(&mut data) {
+                Ok(0) => return false,
+                Ok(len) => self.buffer.extend_from_slice(&data[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+
+            while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line = self.buffer.drain(..=pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = match serde_json::from_str::<Request>(&line)
+                    .with_context(|| "Invalid request")
+                    .and_then(|request| state.handle_ipc_request(request))
+                {
+                    Ok(result) => Reply {
+                        success: true,
+                        result: Some(result).filter(|result| !result.is_null()),
+                        error: None,
+                    },
+                    Err(err) => Reply {
+                        success: false,
+                        result: None,
+                        error: Some(format!("{:#}", err)),
+                    },
+                };
+                self.outgoing.extend(serde_json::to_vec(&reply).unwrap());
+                self.outgoing.push(b'\n');
+            }
+            if self.buffer.len() > MAX_REQUEST_LEN || self.outgoing.len() > MAX_OUTGOING_LEN {
+                warn!("IPC client exceeded the request or reply buffer, dropping it.");
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Writes as much of the queued replies as the socket takes, the rest is written once
+    /// it is writable again. `false` if the client is to be dropped
+    fn flush(&mut self) -> bool {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return false,
+                Ok(len) => {
+                    self.outgoing.drain(..len);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+impl State {
+    pub fn handle_ipc_request(&mut self, request: Request) -> Result<serde_json::Value> {
+        let value = match request {
+            Request::Command { action } => {
+                if self.common.session_lock.is_some() {
+                    bail!("The session is locked");
+                }
+                if matches!(action, Action::Resizing(_)) {
+                    bail!("Resizing only works while a key binding is held");
+                }
+                let seat = self.common.last_active_seat().clone();
+                let time = Duration::from(self.common.clock.now()).as_millis() as u32;
+                self.handle_action(
+                    action,
+                    &seat,
+                    SERIAL_COUNTER.next_serial(),
+                    time,
+                    KeyPattern::new(KeyModifiers::default(), None),
+                    None,
+                );
+                serde_json::Value::Null
+            }
+            Request::Exec { command } => {
+                if self.common.session_lock.is_some() {
+                    bail!("The session is locked");
+                }
+                self.spawn_command(command);
+                serde_json::Value::Null
+            }
+            Request::Output {
+                name,
+                enabled,
+                mode,
+                scale,
+                transform,
+                position,
+            } => {
+                self.ipc_configure_output(&name, enabled, mode, scale, transform, position)?;
+                serde_json::Value::Null
+            }
+            Request::OutputPower { name, on } => {
+                let output = self
+                    .common
+                    .shell
+                    .outputs()
+                    .find(|output| output.name() == name)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("No output named {}", name))?;
+                self.set_output_power(&output, on);
+                serde_json::Value::Null
+            }
+            Request::GetTree => serde_json::to_value(self.ipc_tree())?,
+            Request::GetWorkspaces => serde_json::to_value(
+                self.ipc_tree()
+                    .into_iter()
+                    .flat_map(|output| output.workspaces)
+                    .map(|node| node.workspace)
+                    .collect::<Vec<_>>(),
+            )?,
+            Request::GetOutputs => serde_json::to_value(self.ipc_outputs())?,
+            Request::GetInputs => serde_json::to_value(self.ipc_inputs())?,
+            Request::GetIdleInhibitors => serde_json::to_value(IdleInfo {
+                inhibited: self.idle_inhibited(),
+                manual: self.common.idle.manual_inhibit,
+                inhibitors: self
+                    .common
+                    .idle_inhibitors()
+                    .into_iter()
+                    .map(|inhibitor| IdleInhibitorInfo {
+                        app_id: inhibitor.app_id,
+                        ignored: inhibitor.ignored,
+                        visible: inhibitor.visible,
+                    })
+                    .collect(),
+            })?,
+        };
+        Ok(value)
+    }
+
+    fn ipc_configure_output(
+        &mut self,
+        name: &str,
+        enabled: Option<bool>,
+        mode: Option<((i32, i32), Option<u32>)>,
+        scale: Option<f64>,
+        transform: Option<Transform>,
+        position: Option<(i32, i32)>,
+    ) -> Result<()> {
+        let outputs = self
+            .common
+            .output_configuration_state
+            .outputs()
+            .collect::<Vec<_>>();
+        let output = outputs
+            .iter()
+            .find(|output| output.name() == name)
+            .ok_or_else(|| anyhow!("No output named {}", name))?;
+
+        let mut conf = Vec::new();
+        if enabled == Some(false) {
+            conf.push((output.clone(), OutputConfiguration::Disabled));
+            // the other outputs are passed along unchanged, so only disabling all of them fails
+            conf.extend(
+                outputs
+                    .iter()
+                    .filter(|other| *other != output && is_enabled(other))
+                    .map(|other| {
+                        (
+                            other.clone(),
+                            OutputConfiguration::Enabled {
+                                mode: None,
+                                position: None,
+                                transform: None,
+                                scale: None,
+                            },
+                        )
+                    }),
+            );
+        } else {
+            conf.push((
+                output.clone(),
+                OutputConfiguration::Enabled {
+                    mode: mode.map(|((w, h), refresh)| ModeConfiguration::Custom {
+                        size: (w, h).into(),
+                        refresh: refresh.map(|refresh| refresh as i32),
+                    }),
+                    position: position.map(Into::into),
+                    transform,
+                    scale,
+                },
+            ));
+        }
+
+        if !self.output_configuration(false, conf) {
+            bail!("Failed to apply the configuration of {}", name);
+        }
+        Ok(())
+    }
+
+    fn ipc_tree(&self) -> Vec<OutputNode> {
+        let shell = &self.common.shell;
+        let focus = self
+            .common
+            .last_active_seat()
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus());
+        let window_info = |workspace: &Workspace, mapped: &CosmicMapped, minimized: bool| {
+            let active = mapped.active_window();
+            let focused =
+                matches!(&focus, Some(KeyboardFocusTarget::Element(elem)) if elem == mapped);
+            mapped
+                .windows()
+                .map(|(window, _)| WindowInfo {
+                    app_id: window.app_id(),
+                    title: window.title(),
+                    x11: matches!(window, CosmicSurface::X11(_)),
+                    geometry: workspace.element_geometry(mapped).map(Into::into),
+                    floating: workspace.is_floating(mapped),
+                    minimized,
+                    maximized: window == active && workspace.is_maximized(mapped),
+                    fullscreen: window == active && workspace.is_fullscreen(mapped),
+                    focused: focused && window == active,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        shell
+            .outputs()
+            .map(|output| {
+                let active = shell.workspaces.active_num(output).1;
+                OutputNode {
+                    name: output.name(),
+                    geometry: output.geometry().into(),
+                    workspaces: shell
+                        .workspaces
+                        .spaces_for_output(output)
+                        .enumerate()
+                        .map(|(idx, workspace)| {
+                            let mut windows = Vec::new();
+                            for mapped in workspace.mapped() {
+                                windows.extend(window_info(workspace, mapped, false));
+                            }
+                            for (mapped, _) in &workspace.minimized_windows {
+                                windows.extend(window_info(workspace, mapped, true));
+                            }
+                            WorkspaceNode {
+                                workspace: WorkspaceInfo {
+                                    output: output.name(),
+                                    number: idx + 1,
+                                    active: idx == active,
+                                    tiling: workspace.tiling_enabled,
+                                    windows: windows.len(),
+                                },
+                                windows,
+                            }
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    fn ipc_outputs(&self) -> Vec<OutputInfo> {
+        self.common
+            .output_configuration_state
+            .outputs()
+            .map(|output| {
+                let current_mode = output.current_mode();
+                let preferred_mode = output.preferred_mode();
+                let mode_info = |mode: smithay::output::Mode| ModeInfo {
+                    width: mode.size.w,
+                    height: mode.size.h,
+                    refresh: mode.refresh,
+                    preferred: Some(mode) == preferred_mode,
+                };
+                let physical = output.physical_properties();
+                OutputInfo {
+                    name: output.name(),
+                    make: physical.make,
+                    model: physical.model,
+                    enabled: is_enabled(&output),
+                    geometry: output.geometry().into(),
+                    scale: output.current_scale().fractional_scale(),
+                    transform: output.current_transform(),
+                    current_mode: current_mode.map(mode_info),
+                    modes: output.modes().into_iter().map(mode_info).collect(),
+                    config: output
+                        .user_data()
+                        .get::<RefCell<OutputConfig>>()
+                        .unwrap()
+                        .borrow()
+                        .clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn ipc_inputs(&self) -> Vec<SeatInfo> {
+        let active = self.common.last_active_seat();
+        self.common
+            .seats()
+            .map(|seat| SeatInfo {
+                name: seat.name().to_string(),
+                active: seat == active,
+                devices: seat
+                    .user_data()
+                    .get::<Devices>()
+                    .map(|devices| devices.list())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(id, capabilities)| DeviceInfo {
+                        id,
+                        capabilities: capabilities
+                            .iter()
+                            .map(|cap| format!("{:?}", cap).to_lowercase())
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+fn is_enabled(output: &Output) -> bool {
+    output
+        .user_data()
+        .get::<RefCell<OutputConfig>>()
+        .unwrap()
+        .borrow()
+        .enabled
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Lid switch handling through logind, which would otherwise suspend on its own.
+//!
+//! System bus calls happen on their own thread, to not hold up the event loop.
+
+use anyhow::{Context, Result};
+use std::sync::mpsc::{channel, Sender};
+use tracing::warn;
+use zbus::{blocking::Connection, zvariant::OwnedFd};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+// resolves to the session of the caller
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Take or release the inhibitor, that keeps logind from handling the lid switch
+    InhibitLidSwitch(bool),
+    Suspend,
+    LockSession,
+}
+
+#[derive(Debug)]
+pub struct LogindState {
+    requests: Sender<Request>,
+}
+
+impl LogindState {
+    pub fn send(&self, request: Request) {
+        if self.requests.send(request).is_err() {
+            warn!(?request, "The logind thread isn't running anymore.");
+        }
+    }
+}
+
+pub fn init() -> Result<LogindState> {
+    let (requests, receiver) = channel::<Request>();
+    std::thread::Builder::new()
+        .name(String::from("logind"))
+        .spawn(move || {
+            let connection = match Connection::system() {
+                Ok(connection) => connection,
+                Err(err) => {
+                    warn!(?err, "Failed to connect to the system bus.");
+                    return;
+                }
+            };
+
+            let mut inhibitor = None;
+            for request in receiver {
+                let result = match request {
+                    Request::InhibitLidSwitch(true) if inhibitor.is_none() => {
+                        inhibit_lid_switch(&connection).map(|fd| inhibitor = Some(fd))
+                    }
+                    Request::InhibitLidSwitch(true) => Ok(()),
+                    // logind drops the inhibitor with the last copy of its fd
+                    Request::InhibitLidSwitch(false) => {
+                        inhibitor = None;
+                        Ok(())
+                    }
+                    Request::Suspend => connection
+                        .call_method(
+                            Some(LOGIND_NAME),
+                            MANAGER_PATH,
+                            Some(MANAGER_INTERFACE),
+                            "Suspend",
+                            &(false,),
+                        )
+                        .map(|_| ())
+                        .with_context(|| "Failed to suspend"),
+                    Request::LockSession => connection
+                        .call_method(
+                            Some(LOGIND_NAME),
+                            SESSION_PATH,
+                            Some(SESSION_INTERFACE),
+                            "Lock",
+                            &(),
+                        )
+                        .map(|_| ())
+                        .with_context(|| "Failed to lock the session"),
+                };
+                if let Err(err) = result {
+                    warn!(?err, ?request, "Failed to handle logind request.");
+                }
+            }
+        })
+        .with_context(|| "Failed to spawn the logind thread")?;
+
+    Ok(LogindState { requests })
+}
+
+fn inhibit_lid_switch(connection: &Connection) -> Result<OwnedFd> {
+    connection
+        .call_method(
+            Some(LOGIND_NAME),
+            MANAGER_PATH,
+            Some(MANAGER_INTERFACE),
+            "Inhibit",
+            &(
+                "handle-lid-switch",
+                "cosmic-comp",
+                "The compositor handles the lid switch",
+                "block",
+            ),
+        )
+        .with_context(|| "Failed to inhibit the lid switch")?
+        .body::<OwnedFd>()
+        .with_context(|| "Malformed inhibitor")
+}
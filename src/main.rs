@@ -9,17 +9,21 @@ use smithay::{
 };
 
 use anyhow::{Context, Result};
-use std::{ffi::OsString, sync::Arc};
+use std::ffi::OsString;
 use tracing::{error, info, warn};
 
 use crate::wayland::handlers::compositor::client_compositor_state;
 
 pub mod backend;
 pub mod config;
+pub mod dbus;
 #[cfg(feature = "debug")]
 pub mod debug;
+pub mod idle;
 pub mod input;
+pub mod ipc;
 mod logger;
+pub mod logind;
 pub mod session;
 pub mod shell;
 pub mod state;
@@ -27,6 +31,7 @@ pub mod state;
 pub mod systemd;
 pub mod utils;
 pub mod wayland;
+pub mod xsettings;
 pub mod xwayland;
 
 fn main() -> Result<()> {
@@ -47,13 +52,35 @@ fn main() -> Result<()> {
     );
     // init backend
     backend::init_backend_auto(&display, &mut event_loop, &mut state)?;
+    // let scripts talk to us
+    if let Err(err) = ipc::init_socket(event_loop.handle(), &state) {
+        warn!(?err, "Failed to start the IPC socket.");
+    }
+    // and desktop components over D-Bus
+    if let Err(err) = dbus::init(&mut state) {
+        warn!(?err, "Failed to start the D-Bus service.");
+    }
+    // handle the lid switch instead of logind
+    if let state::BackendData::Kms(_) = &state.backend {
+        match logind::init() {
+            Ok(logind) => state.common.logind = Some(logind),
+            Err(err) => warn!(?err, "Failed to start the logind thread."),
+        }
+        state.update_lid_inhibitor();
+    }
     // potentially tell systemd we are setup now
     #[cfg(feature = "systemd")]
     if let state::BackendData::Kms(_) = &state.backend {
         systemd::ready(&state);
     }
     // potentially tell the session we are setup now
-    session::setup_socket(event_loop.handle(), &state)?;
+    session::setup_socket(event_loop.handle(), &mut state)?;
+    // cycle through wallpaper slideshows
+    backend::render::wallpaper::init_slideshow_timer(&state.common.event_loop_handle);
+    // turn off outputs when idle
+    state.update_idle_timer();
+    // unblock clients, that wait on fifo barriers without being shown
+    wayland::handlers::fifo::init_offscreen_timer(&state.common.event_loop_handle);
 
     // run the event loop
     event_loop.run(None, &mut state, |state| {
@@ -74,7 +101,10 @@ fn main() -> Result<()> {
             }
         }
         state.common.shell.refresh();
+        state.common.refresh_session_lock();
         state::Common::refresh_focus(state);
+        state.refresh_dbus();
+        state.refresh_xsettings();
 
         // send out events
         let _ = state.common.display_handle.flush_clients();
@@ -100,14 +130,12 @@ fn init_wayland_display(
     event_loop
         .handle()
         .insert_source(source, |client_stream, _, state| {
-            if let Err(err) = state.common.display_handle.insert_client(
-                client_stream,
-                Arc::new(if cfg!(debug_assertions) {
-                    state.new_privileged_client_state()
-                } else {
-                    state.new_client_state()
-                }),
-            ) {
+            let client_state = if cfg!(debug_assertions) {
+                state.new_privileged_client_state()
+            } else {
+                state.new_client_state()
+            };
+            if let Err(err) = state.insert_client(client_stream, client_state) {
                 warn!(?err, "Error adding wayland client");
             };
         })
@@ -50,7 +50,54 @@ impl From<UnixStream> for StreamWrapper {
     }
 }
 
-pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
+/// Variables cosmic-session starts apps with, to connect to the compositor
+fn environment(state: &State) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+    env.insert(
+        String::from("WAYLAND_DISPLAY"),
+        state
+            .common
+            .socket
+            .clone()
+            .into_string()
+            .map_err(|_| anyhow!("wayland socket is no valid utf-8 string?"))?,
+    );
+    if let Some(display) = state.common.xwayland_state.as_ref().map(|s| s.display) {
+        env.insert(String::from("DISPLAY"), format!(":{}", display));
+    }
+    if let Ok(path) = std::env::var(crate::ipc::SOCKET_ENV) {
+        env.insert(String::from(crate::ipc::SOCKET_ENV), path);
+    }
+    Ok(env)
+}
+
+fn send_message(mut socket: &UnixStream, message: &Message) -> Result<()> {
+    let message =
+        serde_json::to_string(message).with_context(|| "Failed to encode message into json")?;
+    let bytes = message.into_bytes();
+    let len = (bytes.len() as u16).to_ne_bytes();
+    socket
+        .write_all(&len)
+        .with_context(|| "Failed to write message len")?;
+    socket
+        .write_all(&bytes)
+        .with_context(|| "Failed to write message bytes")?;
+    Ok(())
+}
+
+/// Tells the session about changed environment variables, e.g. a new `DISPLAY`
+pub fn update_environment(state: &State) {
+    let Some(socket) = state.common.session_socket.as_ref() else {
+        return;
+    };
+    if let Err(err) = environment(state)
+        .and_then(|variables| send_message(socket, &Message::SetEnv { variables }))
+    {
+        warn!(?err, "Failed to update the session environment");
+    }
+}
+
+pub fn setup_socket(handle: LoopHandle<State>, state: &mut State) -> Result<()> {
     if let Ok(fd_num) = std::env::var("COSMIC_SESSION_SOCK") {
         if let Ok(fd) = fd_num.parse::<RawFd>() {
             // set CLOEXEC
@@ -58,7 +105,7 @@ pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
             let result = flags
                 .map(|f| fcntl::FdFlag::from_bits(f).unwrap() | fcntl::FdFlag::FD_CLOEXEC)
                 .and_then(|f| fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFD(f)));
-            let mut session_socket = match result {
+            let session_socket = match result {
                 // CLOEXEC worked and we can startup with session IPC
                 Ok(_) => unsafe { UnixStream::from_raw_fd(fd) },
                 // CLOEXEC didn't work, something is wrong with the fd, just close it
@@ -68,29 +115,14 @@ pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
                 }
             };
 
-            let mut env = HashMap::new();
-            env.insert(
-                String::from("WAYLAND_DISPLAY"),
-                state
-                    .common
-                    .socket
-                    .clone()
-                    .into_string()
-                    .map_err(|_| anyhow!("wayland socket is no valid utf-8 string?"))?,
+            let variables = environment(state)?;
+            send_message(&session_socket, &Message::SetEnv { variables })?;
+            // kept to send updates, the source only reads
+            state.common.session_socket = Some(
+                session_socket
+                    .try_clone()
+                    .with_context(|| "Failed to clone the session socket")?,
             );
-            if let Some(display) = state.common.xwayland_state.as_ref().map(|s| s.display) {
-                env.insert(String::from("DISPLAY"), format!(":{}", display));
-            }
-            let message = serde_json::to_string(&Message::SetEnv { variables: env })
-                .with_context(|| "Failed to encode environment variables into json")?;
-            let bytes = message.into_bytes();
-            let len = (bytes.len() as u16).to_ne_bytes();
-            session_socket
-                .write_all(&len)
-                .with_context(|| "Failed to write message len")?;
-            session_socket
-                .write_all(&bytes)
-                .with_context(|| "Failed to write message bytes")?;
 
             handle.insert_source(
                 Generic::new(StreamWrapper::from(session_socket), Interest::READ, Mode::Level),
@@ -0,0 +1,63 @@
+use crate::utils::iced::{IcedElement, Program};
+
+use calloop::LoopHandle;
+use cosmic::{
+    iced::widget::{column, container},
+    iced_core::{Background, Color, Length},
+    theme,
+    widget::text,
+    Apply,
+};
+use smithay::utils::Size;
+
+pub type ErrorIndicator = IcedElement<ErrorIndicatorInternal>;
+
+pub const ERROR_INDICATOR_SIZE: (i32, i32) = (640, 200);
+
+pub fn error_indicator(
+    title: String,
+    message: String,
+    evlh: LoopHandle<'static, crate::state::State>,
+) -> ErrorIndicator {
+    ErrorIndicator::new(
+        ErrorIndicatorInternal { title, message },
+        Size::from(ERROR_INDICATOR_SIZE),
+        evlh,
+    )
+}
+
+pub struct ErrorIndicatorInternal {
+    pub title: String,
+    pub message: String,
+}
+
+impl Program for ErrorIndicatorInternal {
+    type Message = ();
+
+    fn view(&self) -> crate::utils::iced::Element<'_, Self::Message> {
+        column(vec![
+            text(&self.title)
+                .font(cosmic::font::FONT_SEMIBOLD)
+                .size(24)
+                .into(),
+            text(&self.message).font(cosmic::font::FONT).size(16).into(),
+        ])
+        .spacing(8)
+        .apply(container)
+        .padding(24)
+        .style(theme::Container::custom(|theme| container::Appearance {
+            icon_color: Some(Color::from(theme.cosmic().destructive.on)),
+            text_color: Some(Color::from(theme.cosmic().destructive.on)),
+            background: Some(Background::Color(theme.cosmic().destructive_color().into())),
+            border_radius: 18.0.into(),
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        }))
+        .width(Length::Fill)
+        .height(Length::Shrink)
+        .apply(container)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .into()
+    }
+}
@@ -0,0 +1,64 @@
+use crate::utils::iced::{IcedElement, Program};
+
+use calloop::LoopHandle;
+use cosmic::{
+    iced::widget::{column, container},
+    iced_core::{Background, Color, Length},
+    theme,
+    widget::text,
+    Apply,
+};
+use smithay::{output::Output, utils::Size};
+
+pub type IdentifyIndicator = IcedElement<IdentifyIndicatorInternal>;
+
+pub fn identify_indicator(
+    output: &Output,
+    evlh: LoopHandle<'static, crate::state::State>,
+) -> IdentifyIndicator {
+    let properties = output.physical_properties();
+    IdentifyIndicator::new(
+        IdentifyIndicatorInternal {
+            name: output.name(),
+            model: format!("{} {}", properties.make, properties.model),
+        },
+        Size::from((480, 160)),
+        evlh,
+    )
+}
+
+pub struct IdentifyIndicatorInternal {
+    pub name: String,
+    pub model: String,
+}
+
+impl Program for IdentifyIndicatorInternal {
+    type Message = ();
+
+    fn view(&self) -> crate::utils::iced::Element<'_, Self::Message> {
+        column(vec![
+            text(&self.name)
+                .font(cosmic::font::FONT_SEMIBOLD)
+                .size(48)
+                .into(),
+            text(&self.model).font(cosmic::font::FONT).size(24).into(),
+        ])
+        .spacing(8)
+        .apply(container)
+        .padding(24)
+        .style(theme::Container::custom(|theme| container::Appearance {
+            icon_color: Some(Color::from(theme.cosmic().accent.on)),
+            text_color: Some(Color::from(theme.cosmic().accent.on)),
+            background: Some(Background::Color(theme.cosmic().accent_color().into())),
+            border_radius: 18.0.into(),
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        }))
+        .width(Length::Shrink)
+        .height(Length::Shrink)
+        .apply(container)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .into()
+    }
+}
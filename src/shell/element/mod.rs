@@ -3,6 +3,7 @@ use crate::{
         element::{AsGlowFrame, AsGlowRenderer},
         GlMultiError, GlMultiFrame, GlMultiRenderer,
     },
+    config::AnimationConfig,
     state::State,
     utils::prelude::SeatExt,
 };
@@ -60,9 +61,12 @@ pub mod stack;
 pub use self::stack::CosmicStack;
 pub mod window;
 pub use self::window::CosmicWindow;
+pub mod error_indicator;
+pub mod identify_indicator;
 pub mod resize_indicator;
 pub mod stack_hover;
 pub mod swap_indicator;
+pub mod window_menu;
 
 #[cfg(feature = "debug")]
 use egui_plot::{Corner, Legend, Plot, PlotPoints, Polygon};
@@ -97,6 +101,10 @@ pub struct CosmicMapped {
     pub(super) last_geometry: Arc<Mutex<Option<Rectangle<i32, Logical>>>>,
     pub(super) resize_state: Arc<Mutex<Option<ResizeState>>>,
 
+    opacity: Arc<Mutex<f32>>,
+    /// Requested attention, since it was last focused
+    urgent: Arc<Mutex<bool>>,
+
     #[cfg(feature = "debug")]
     debug: Arc<Mutex<Option<smithay_egui::EguiState>>>,
 }
@@ -108,6 +116,8 @@ impl fmt::Debug for CosmicMapped {
             .field("last_cursor_position", &self.last_cursor_position)
             .field("tiling_node_id", &self.tiling_node_id)
             .field("resize_state", &self.resize_state)
+            .field("opacity", &self.opacity)
+            .field("urgent", &self.urgent)
             .finish()
     }
 }
@@ -256,6 +266,43 @@ impl CosmicMapped {
         }
     }
 
+    pub fn is_shaded(&self) -> bool {
+        match &self.element {
+            CosmicMappedInternal::Window(window) => window.is_shaded(),
+            _ => false,
+        }
+    }
+
+    pub fn toggle_shaded(&self) {
+        if let CosmicMappedInternal::Window(window) = &self.element {
+            window.set_shaded(!window.is_shaded());
+        }
+    }
+
+    pub fn is_header_focused(&self) -> bool {
+        match &self.element {
+            CosmicMappedInternal::Window(window) => window.is_header_focused(),
+            CosmicMappedInternal::Stack(stack) => stack.is_header_focused(),
+            _ => false,
+        }
+    }
+
+    pub fn opacity(&self) -> f32 {
+        *self.opacity.lock().unwrap()
+    }
+
+    pub fn set_opacity(&self, opacity: f32) {
+        *self.opacity.lock().unwrap() = opacity.clamp(0.1, 1.0);
+    }
+
+    pub fn is_urgent(&self) -> bool {
+        *self.urgent.lock().unwrap()
+    }
+
+    pub fn set_urgent(&self, urgent: bool) {
+        *self.urgent.lock().unwrap() = urgent;
+    }
+
     pub fn set_resizing(&self, resizing: bool) {
         for window in match &self.element {
             CosmicMappedInternal::Stack(s) => {
@@ -343,6 +390,15 @@ impl CosmicMapped {
         window.is_maximized(pending)
     }
 
+    /// Whether the compositor draws the decorations of this element
+    pub fn has_ssd(&self, pending: bool) -> bool {
+        match &self.element {
+            CosmicMappedInternal::Stack(_) => true,
+            CosmicMappedInternal::Window(w) => w.has_ssd(pending),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn set_activated(&self, activated: bool) {
         match &self.element {
             CosmicMappedInternal::Stack(s) => s.set_activate(activated),
@@ -492,6 +548,7 @@ impl CosmicMapped {
     pub fn convert_to_stack<'a>(
         &mut self,
         outputs: impl Iterator<Item = (&'a Output, Rectangle<i32, Logical>)>,
+        animations: AnimationConfig,
     ) {
         match &self.element {
             CosmicMappedInternal::Window(window) => {
@@ -499,7 +556,7 @@ impl CosmicMapped {
                 let activated = surface.is_activated(true);
                 let handle = window.loop_handle();
 
-                let stack = CosmicStack::new(std::iter::once(surface), handle);
+                let stack = CosmicStack::new(std::iter::once(surface), handle, animations);
                 if let Some(geo) = self.last_geometry.lock().unwrap().clone() {
                     stack.set_geometry(geo);
                 }
@@ -737,6 +794,7 @@ impl CosmicMapped {
         #[cfg(not(feature = "debug"))]
         let debug_elements = Vec::new();
 
+        let alpha = alpha * self.opacity();
         #[cfg_attr(not(feature = "debug"), allow(unused_mut))]
         let (window_elements, popup_elements) = match &self.element {
             CosmicMappedInternal::Stack(s) => s
@@ -1070,6 +1128,8 @@ impl From<CosmicWindow> for CosmicMapped {
             tiling_node_id: Arc::new(Mutex::new(None)),
             last_geometry: Arc::new(Mutex::new(None)),
             resize_state: Arc::new(Mutex::new(None)),
+            opacity: Arc::new(Mutex::new(1.0)),
+            urgent: Arc::new(Mutex::new(false)),
             #[cfg(feature = "debug")]
             debug: Arc::new(Mutex::new(None)),
         }
@@ -1084,6 +1144,8 @@ impl From<CosmicStack> for CosmicMapped {
             tiling_node_id: Arc::new(Mutex::new(None)),
             last_geometry: Arc::new(Mutex::new(None)),
             resize_state: Arc::new(Mutex::new(None)),
+            opacity: Arc::new(Mutex::new(1.0)),
+            urgent: Arc::new(Mutex::new(false)),
             #[cfg(feature = "debug")]
             debug: Arc::new(Mutex::new(None)),
         }
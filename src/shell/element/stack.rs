@@ -1,5 +1,7 @@
 use super::{CosmicMapped, CosmicSurface, CosmicWindow};
 use crate::{
+    backend::render::element::SurfaceRenderElement,
+    config::AnimationConfig,
     shell::{
         focus::FocusDirection, grabs::MoveGrab, layout::tiling::NodeDesc, Direction, Shell, Trigger,
     },
@@ -22,10 +24,7 @@ use smithay::{
     backend::{
         input::KeyState,
         renderer::{
-            element::{
-                memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
-                AsRenderElements,
-            },
+            element::{memory::MemoryRenderBufferRenderElement, AsRenderElements},
             ImportAll, ImportMem, Renderer,
         },
     },
@@ -93,6 +92,7 @@ pub struct CosmicStackInternal {
     last_location: Arc<Mutex<Option<(Point<f64, Logical>, Serial, u32)>>>,
     geometry: Arc<Mutex<Option<Rectangle<i32, Logical>>>>,
     mask: Arc<Mutex<Option<tiny_skia::Mask>>>,
+    animations: Arc<Mutex<AnimationConfig>>,
 }
 
 impl CosmicStackInternal {
@@ -129,6 +129,7 @@ impl CosmicStack {
     pub fn new<I: Into<CosmicSurface>>(
         windows: impl Iterator<Item = I>,
         handle: LoopHandle<'static, crate::state::State>,
+        animations: AnimationConfig,
     ) -> CosmicStack {
         let windows = windows.map(Into::into).collect::<Vec<_>>();
         assert!(!windows.is_empty());
@@ -157,6 +158,7 @@ impl CosmicStack {
                 last_location: Arc::new(Mutex::new(None)),
                 geometry: Arc::new(Mutex::new(None)),
                 mask: Arc::new(Mutex::new(None)),
+                animations: Arc::new(Mutex::new(animations)),
             },
             (width, TAB_HEIGHT),
             handle,
@@ -374,6 +376,10 @@ impl CosmicStack {
             .with_program(|p| &p.windows.lock().unwrap()[p.active.load(Ordering::SeqCst)] == window)
     }
 
+    pub fn is_header_focused(&self) -> bool {
+        self.0.with_program(|p| p.current_focus() == Focus::Header)
+    }
+
     pub fn whole_stack_focused(&self) -> bool {
         self.0
             .with_program(|p| p.group_focused.load(Ordering::SeqCst))
@@ -390,6 +396,11 @@ impl CosmicStack {
         self.0.force_redraw()
     }
 
+    pub fn set_animations(&self, animations: AnimationConfig) {
+        self.0
+            .with_program(|p| *p.animations.lock().unwrap() = animations);
+    }
+
     pub fn surfaces(&self) -> impl Iterator<Item = CosmicSurface> {
         self.0.with_program(|p| {
             p.windows
@@ -695,6 +706,7 @@ impl Program for CosmicStackInternal {
                         .load(Ordering::SeqCst)
                         .then_some(active),
                 )
+                .animations(*self.animations.lock().unwrap())
                 .height(Length::Fill)
                 .width(Length::Fill),
             ),
@@ -1061,6 +1073,7 @@ impl PointerTarget<State> for CosmicStack {
                                         workspace.element_geometry(stack_mapped).unwrap();
                                     let indicator_thickness =
                                         data.common.config.static_conf.active_hint;
+                                    let shadow = data.common.config.static_conf.shadows;
                                     let was_tiled = workspace.is_tiled(stack_mapped);
 
                                     self.remove_idx(dragged_out);
@@ -1073,6 +1086,7 @@ impl PointerTarget<State> for CosmicStack {
                                         pos,
                                         pos.to_i32_round() - Point::from((elem_geo.size.w / 2, 24)),
                                         indicator_thickness,
+                                        shadow,
                                         was_tiled,
                                     );
                                     if grab.is_tiling_grab() {
@@ -1340,5 +1354,5 @@ impl PointerTarget<State> for CosmicStack {
 render_elements! {
     pub CosmicStackRenderElement<R> where R: ImportAll + ImportMem;
     Header = MemoryRenderBufferRenderElement<R>,
-    Window = WaylandSurfaceRenderElement<R>,
+    Window = SurfaceRenderElement<R>,
 }
@@ -1,4 +1,8 @@
 use super::tab::{Tab, TabBackgroundTheme, TabMessage, TabRuleTheme, MIN_ACTIVE_TAB_WIDTH};
+use crate::{
+    config::AnimationConfig,
+    utils::animation::{Animation, AnimationKind},
+};
 use cosmic::{
     font::Font,
     iced::{id::Id, widget, Element},
@@ -26,13 +30,9 @@ use cosmic::{
     widget::{icon::from_name, Icon},
     Apply,
 };
-use keyframe::{
-    ease,
-    functions::{EaseInOutCubic, EaseOutCubic},
-};
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    time::{Duration, Instant},
+    time::Instant,
 };
 
 pub struct Tabs<'a, Message, Renderer>
@@ -46,6 +46,7 @@ where
     width: Length,
     group_focused: bool,
     scroll_to: Option<usize>,
+    animations: AnimationConfig,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -115,9 +116,6 @@ impl Offset {
     }
 }
 
-const SCROLL_ANIMATION_DURATION: Duration = Duration::from_millis(200);
-const TAB_ANIMATION_DURATION: Duration = Duration::from_millis(150);
-
 impl<'a, Message, Renderer> Tabs<'a, Message, Renderer>
 where
     Renderer: cosmic::iced_core::Renderer + 'a,
@@ -223,6 +221,7 @@ where
             height: Length::Shrink,
             group_focused,
             scroll_to: None,
+            animations: AnimationConfig::default(),
         }
     }
 
@@ -245,19 +244,24 @@ where
         self.scroll_to = idx;
         self
     }
+
+    pub fn animations(mut self, animations: AnimationConfig) -> Self {
+        self.animations = animations;
+        self
+    }
 }
 
 impl State {
-    pub fn offset(&self, bounds: Rectangle, content_bounds: Size) -> Vector {
+    pub fn offset(
+        &self,
+        bounds: Rectangle,
+        content_bounds: Size,
+        config: &AnimationConfig,
+    ) -> Vector {
         if let Some(animation) = self.scroll_animation {
-            let percentage = {
-                let percentage = Instant::now()
-                    .duration_since(animation.start_time)
-                    .as_millis() as f32
-                    / SCROLL_ANIMATION_DURATION.as_millis() as f32;
-
-                ease(EaseInOutCubic, 0.0, 1.0, percentage)
-            };
+            let percentage =
+                Animation::starting_at(AnimationKind::TabScroll, animation.start_time, config)
+                    .value(0.0, 1.0);
 
             Vector::new(
                 animation.start.absolute(bounds.width, content_bounds.width)
@@ -274,15 +278,17 @@ impl State {
         }
     }
 
-    pub fn cleanup_old_animations(&mut self) {
+    pub fn cleanup_old_animations(&mut self, config: &AnimationConfig) {
         if let Some(animation) = self.scroll_animation.as_ref() {
-            if Instant::now().duration_since(animation.start_time) > SCROLL_ANIMATION_DURATION {
+            if Animation::starting_at(AnimationKind::TabScroll, animation.start_time, config)
+                .is_done()
+            {
                 self.scroll_animation.take();
             }
         }
 
         if let Some(animation) = self.tab_animations.front() {
-            if Instant::now().duration_since(animation.start_time) > TAB_ANIMATION_DURATION {
+            if Animation::starting_at(AnimationKind::Tabs, animation.start_time, config).is_done() {
                 self.tab_animations.pop_front();
                 if let Some(next_animation) = self.tab_animations.front_mut() {
                     next_animation.start_time = Instant::now();
@@ -508,7 +514,7 @@ where
             bounds.width -= 64.;
             bounds.x += 30.;
         }
-        let offset = state.offset(bounds, content_bounds);
+        let offset = state.offset(bounds, content_bounds, &self.animations);
         let offset_viewport = Rectangle {
             x: bounds.x + offset.x,
             y: bounds.y + offset.y,
@@ -533,11 +539,12 @@ where
         renderer.with_layer(bounds, |renderer| {
             renderer.with_translation(Vector::new(-offset.x, -offset.y), |renderer| {
                 let percentage = if let Some(animation) = state.tab_animations.front() {
-                    let percentage = Instant::now()
-                        .duration_since(animation.start_time)
-                        .as_millis() as f32
-                        / TAB_ANIMATION_DURATION.as_millis() as f32;
-                    ease(EaseOutCubic, 0.0, 1.0, percentage)
+                    Animation::starting_at(
+                        AnimationKind::Tabs,
+                        animation.start_time,
+                        &self.animations,
+                    )
+                    .value(0.0, 1.0)
                 } else {
                     1.0
                 };
@@ -664,7 +671,7 @@ where
         let state = tree.state.downcast_mut::<State>();
         let bounds = layout.bounds();
 
-        state.cleanup_old_animations();
+        state.cleanup_old_animations(&self.animations);
 
         operation.scrollable(
             state,
@@ -698,7 +705,7 @@ where
         viewport: &Rectangle,
     ) -> event::Status {
         let state = tree.state.downcast_mut::<State>();
-        state.cleanup_old_animations();
+        state.cleanup_old_animations(&self.animations);
 
         let mut bounds = layout.bounds();
         let content_bounds = layout.children().fold(Size::new(0., 0.), |a, b| Size {
@@ -771,7 +778,7 @@ where
             bounds.x += 30.;
             bounds.width -= 64.;
         }
-        let offset = state.offset(bounds, content_bounds);
+        let offset = state.offset(bounds, content_bounds, &self.animations);
 
         if let Some(idx) = self.scroll_to {
             state.scroll_to = Some(idx);
@@ -926,7 +933,7 @@ where
             bounds.width -= 64.;
             bounds.x += 30.;
         }
-        let offset = state.offset(bounds, content_bounds);
+        let offset = state.offset(bounds, content_bounds, &self.animations);
         let offset_viewport = &Rectangle {
             y: bounds.y + offset.y,
             x: bounds.x + offset.x,
@@ -1,20 +1,25 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
 
 use smithay::{
     backend::renderer::{
         element::{
-            self,
-            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
-            utils::select_dmabuf_feedback,
+            self, surface::WaylandSurfaceRenderElement, utils::select_dmabuf_feedback,
             AsRenderElements, RenderElementStates,
         },
         ImportAll, Renderer,
     },
     desktop::{
+        space::SpaceElement,
         utils::{
             send_dmabuf_feedback_surface_tree, send_frames_surface_tree,
-            take_presentation_feedback_surface_tree, with_surfaces_surface_tree,
-            OutputPresentationFeedback,
+            surface_primary_scanout_output, take_presentation_feedback_surface_tree,
+            with_surfaces_surface_tree, OutputPresentationFeedback,
         },
         PopupManager, Window,
     },
@@ -40,7 +45,7 @@ use smithay::{
         wayland_server::protocol::wl_surface::WlSurface,
     },
     space_elements,
-    utils::{user_data::UserDataMap, Logical, Rectangle, Serial, Size},
+    utils::{user_data::UserDataMap, Logical, Point, Rectangle, Serial, Size},
     wayland::{
         compositor::{with_states, SurfaceData},
         seat::WaylandFocus,
@@ -50,10 +55,69 @@ use smithay::{
 };
 
 use crate::{
+    backend::render::element::{render_elements_from_surface_tree, SurfaceRenderElement},
+    config::{ScaleFilter, X11Scale},
     state::{State, SurfaceDmabufFeedback},
-    wayland::handlers::decoration::PreferredDecorationMode,
+    wayland::{
+        handlers::decoration::PreferredDecorationMode,
+        protocols::content_type::{surface_content_type, ContentType},
+    },
 };
 
+/// Wayland windows have no minimized state, so the shell keeps track of it
+#[derive(Default)]
+struct Minimized(AtomicBool);
+
+/// Identifier of a window, that is never reused
+struct WindowId(u64);
+
+/// Marks windows, whose content gets scaled to the size the shell assigned to them
+struct ForceScale {
+    filter: ScaleFilter,
+    assigned: Mutex<Option<Size<i32, Logical>>>,
+    /// Size X11 windows are kept at, as they can't refuse a configure
+    natural: Size<i32, Logical>,
+}
+
+/// Overrides the scale of the content of X11 windows
+struct ContentScale(X11Scale);
+
+/// Width of the unfocused border set by a window rule
+struct BorderWidth(u8);
+
+/// Maps the content of a force-scaled window into the size it was assigned,
+/// keeping its aspect ratio and centering it
+#[derive(Debug, Clone, Copy)]
+pub struct ForceScaling {
+    pub scale: f64,
+    pub filter: ScaleFilter,
+    /// Size the shell assigned to the window
+    pub size: Size<i32, Logical>,
+    origin: Point<f64, Logical>,
+    center: Point<f64, Logical>,
+}
+
+impl ForceScaling {
+    /// Where the origin of the client ends up, relative to the window
+    pub fn location(&self) -> Point<f64, Logical> {
+        self.origin + self.center - self.origin.upscale(self.scale)
+    }
+
+    /// Maps a point relative to the window into the coordinate space of the client
+    pub fn to_client(&self, point: Point<f64, Logical>) -> Point<f64, Logical> {
+        self.origin + (point - self.origin - self.center).downscale(self.scale)
+    }
+
+    /// Maps a rectangle in the coordinate space of the client to the window
+    pub fn from_client(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size(
+            self.location() + rect.loc.to_f64().upscale(self.scale),
+            rect.size.to_f64().upscale(self.scale),
+        )
+        .to_i32_round()
+    }
+}
+
 space_elements! {
     #[derive(Debug, Clone, PartialEq)]
     pub CosmicSurface;
@@ -82,6 +146,14 @@ impl From<X11Surface> for CosmicSurface {
 pub const SSD_HEIGHT: i32 = 48;
 
 impl CosmicSurface {
+    /// Identifies the window to external tools, unique for the lifetime of the compositor
+    pub fn id(&self) -> u64 {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        self.user_data()
+            .insert_if_missing(|| WindowId(NEXT_ID.fetch_add(1, Ordering::SeqCst)));
+        self.user_data().get::<WindowId>().unwrap().0
+    }
+
     pub fn title(&self) -> String {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -123,17 +195,117 @@ impl CosmicSurface {
     }
 
     pub fn set_geometry(&self, geo: Rectangle<i32, Logical>) {
+        let force_scale = self.user_data().get::<ForceScale>();
+        if let Some(force_scale) = force_scale {
+            *force_scale.assigned.lock().unwrap() = Some(geo.size);
+        }
         match self {
             CosmicSurface::Wayland(window) => window
                 .toplevel()
                 .with_pending_state(|state| state.size = Some(geo.size)),
             CosmicSurface::X11(surface) => {
-                let _ = surface.configure(geo);
+                let size = match (force_scale, self.content_scale()) {
+                    (Some(force_scale), _) => force_scale.natural,
+                    (None, Some(scale)) => geo.size.to_f64().downscale(scale).to_i32_round(),
+                    (None, None) => geo.size,
+                };
+                let _ = surface.configure(Rectangle::from_loc_and_size(geo.loc, size));
             }
             _ => {}
         }
     }
 
+    /// Scale the content up to the size assigned with `set_geometry`, instead of showing
+    /// it at the size the client chose
+    pub fn set_force_scale(&self, filter: ScaleFilter) {
+        let natural = match self {
+            CosmicSurface::X11(surface) => surface
+                .max_size()
+                .filter(|size| size.w > 0 && size.h > 0)
+                .unwrap_or_else(|| surface.geometry().size),
+            _ => Size::default(),
+        };
+        self.user_data().insert_if_missing(|| ForceScale {
+            filter,
+            assigned: Mutex::new(None),
+            natural,
+        });
+    }
+
+    pub fn is_force_scaled(&self) -> bool {
+        self.user_data().get::<ForceScale>().is_some()
+    }
+
+    /// Show the content of an X11 window at `scale`, unless it is force-scaled
+    pub fn set_x11_scale(&self, scale: X11Scale) {
+        if let CosmicSurface::X11(_) = self {
+            self.user_data().insert_if_missing(|| ContentScale(scale));
+        }
+    }
+
+    /// Draw the border of the window at `width` while it isn't focused
+    pub fn set_border_width(&self, width: u8) {
+        self.user_data().insert_if_missing(|| BorderWidth(width));
+    }
+
+    pub fn border_width(&self) -> Option<u8> {
+        self.user_data().get::<BorderWidth>().map(|width| width.0)
+    }
+
+    /// Factor the content of the window is scaled by relative to other windows,
+    /// `None` if it isn't
+    fn content_scale(&self) -> Option<f64> {
+        let ContentScale(scale) = self.user_data().get::<ContentScale>()?;
+        let scale = match scale {
+            X11Scale::Factor(factor) => *factor,
+            X11Scale::HiDpi => {
+                let surface = self.wl_surface()?;
+                let output = with_states(&surface, |states| {
+                    surface_primary_scanout_output(&surface, states)
+                })?;
+                1.0 / output.current_scale().fractional_scale()
+            }
+        };
+        (scale > 0.0 && scale != 1.0).then_some(scale)
+    }
+
+    /// How the content of a force-scaled window is mapped into its assigned size,
+    /// or the content of an X11 window scaled by a window rule, `None` if it isn't scaled
+    pub fn force_scaling(&self) -> Option<ForceScaling> {
+        let Some(force_scale) = self.user_data().get::<ForceScale>() else {
+            let scale = self.content_scale()?;
+            let geometry = SpaceElement::geometry(self);
+            return Some(ForceScaling {
+                scale,
+                filter: ScaleFilter::Linear,
+                size: geometry.size.to_f64().upscale(scale).to_i32_round(),
+                origin: geometry.loc.to_f64(),
+                center: Point::default(),
+            });
+        };
+        let size = (*force_scale.assigned.lock().unwrap())?;
+        let geometry = SpaceElement::geometry(self);
+        if geometry.size.w <= 0 || geometry.size.h <= 0 || geometry.size == size {
+            return None;
+        }
+
+        let scale = f64::min(
+            size.w as f64 / geometry.size.w as f64,
+            size.h as f64 / geometry.size.h as f64,
+        );
+        let center = Point::from((
+            (size.w as f64 - geometry.size.w as f64 * scale) / 2.0,
+            (size.h as f64 - geometry.size.h as f64 * scale) / 2.0,
+        ));
+        Some(ForceScaling {
+            scale,
+            filter: force_scale.filter,
+            size,
+            origin: geometry.loc.to_f64(),
+            center,
+        })
+    }
+
     pub fn set_bounds(&self, size: impl Into<Option<Size<i32, Logical>>>) {
         match self {
             CosmicSurface::Wayland(window) => window.toplevel().with_pending_state(|state| {
@@ -372,6 +544,48 @@ impl CosmicSurface {
         }
     }
 
+    /// Whether this is a dialog or another transient window of `parent`
+    pub fn is_transient_for(&self, parent: &CosmicSurface) -> bool {
+        match (self, parent) {
+            (CosmicSurface::Wayland(window), CosmicSurface::Wayland(parent)) => {
+                window.toplevel().parent().as_ref() == Some(parent.toplevel().wl_surface())
+            }
+            (CosmicSurface::X11(surface), CosmicSurface::X11(parent)) => {
+                surface.is_transient_for() == Some(parent.window_id())
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        match self {
+            CosmicSurface::Wayland(window) => window
+                .user_data()
+                .get::<Minimized>()
+                .map_or(false, |minimized| minimized.0.load(Ordering::SeqCst)),
+            CosmicSurface::X11(surface) => surface.is_minimized(),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set_minimized(&self, minimized: bool) {
+        match self {
+            CosmicSurface::Wayland(window) => {
+                window.user_data().insert_if_missing(Minimized::default);
+                window
+                    .user_data()
+                    .get::<Minimized>()
+                    .unwrap()
+                    .0
+                    .store(minimized, Ordering::SeqCst);
+            }
+            CosmicSurface::X11(surface) => {
+                let _ = surface.set_minimized(minimized);
+            }
+            _ => unreachable!(),
+        }
+    }
+
     pub fn min_size(&self) -> Option<Size<i32, Logical>> {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -380,7 +594,9 @@ impl CosmicSurface {
                 }))
                 .filter(|size| !(size.w == 0 && size.h == 0))
             }
-            CosmicSurface::X11(surface) => surface.min_size(),
+            CosmicSurface::X11(surface) => {
+                surface.min_size().map(|size| self.scaled_client_size(size))
+            }
             _ => unreachable!(),
         }
         .map(|size| {
@@ -392,6 +608,14 @@ impl CosmicSurface {
         })
     }
 
+    /// Size a client-chosen size takes up, when the content gets scaled by a window rule
+    fn scaled_client_size(&self, size: Size<i32, Logical>) -> Size<i32, Logical> {
+        match self.content_scale() {
+            Some(scale) if !self.is_force_scaled() => size.to_f64().upscale(scale).to_i32_round(),
+            _ => size,
+        }
+    }
+
     pub fn max_size(&self) -> Option<Size<i32, Logical>> {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -400,7 +624,9 @@ impl CosmicSurface {
                 }))
                 .filter(|size| !(size.w == 0 && size.h == 0))
             }
-            CosmicSurface::X11(surface) => surface.max_size(),
+            CosmicSurface::X11(surface) => {
+                surface.max_size().map(|size| self.scaled_client_size(size))
+            }
             _ => unreachable!(),
         }
         .map(|size| {
@@ -604,7 +830,7 @@ impl CosmicSurface {
     where
         R: Renderer + ImportAll,
         <R as Renderer>::TextureId: 'static,
-        C: From<WaylandSurfaceRenderElement<R>>,
+        C: From<SurfaceRenderElement<R>>,
     {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -626,19 +852,25 @@ impl CosmicSurface {
                     })
                     .collect();
 
+                // video is the content worth putting on a plane directly
+                let kind = if surface_content_type(surface) == ContentType::Video {
+                    element::Kind::ScanoutCandidate
+                } else {
+                    element::Kind::Unspecified
+                };
                 let window_render_elements = render_elements_from_surface_tree(
-                    renderer,
-                    surface,
-                    location,
-                    scale,
-                    alpha,
-                    element::Kind::Unspecified,
+                    renderer, surface, location, scale, alpha, kind,
                 );
 
                 (window_render_elements, popup_render_elements)
             }
             CosmicSurface::X11(surface) => (
-                surface.render_elements(renderer, location, scale, alpha),
+                AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
+                    surface, renderer, location, scale, alpha,
+                )
+                .into_iter()
+                .map(|element| C::from(SurfaceRenderElement::from(element)))
+                .collect(),
                 Vec::new(),
             ),
             _ => unreachable!(),
@@ -937,7 +1169,7 @@ where
     R: Renderer + ImportAll,
     <R as Renderer>::TextureId: 'static,
 {
-    type RenderElement = WaylandSurfaceRenderElement<R>;
+    type RenderElement = SurfaceRenderElement<R>;
 
     fn render_elements<C: From<Self::RenderElement>>(
         &self,
@@ -947,11 +1179,15 @@ where
         alpha: f32,
     ) -> Vec<C> {
         match self {
-            CosmicSurface::Wayland(window) => {
-                window.render_elements(renderer, location, scale, alpha)
+            CosmicSurface::Wayland(_) => {
+                let (window_elements, popup_elements) =
+                    self.split_render_elements(renderer, location, scale, alpha);
+                popup_elements.into_iter().chain(window_elements).collect()
             }
-            CosmicSurface::X11(surface) => {
-                surface.render_elements(renderer, location, scale, alpha)
+            CosmicSurface::X11(_) => {
+                let (window_elements, _) =
+                    self.split_render_elements(renderer, location, scale, alpha);
+                window_elements
             }
             _ => unreachable!(),
         }
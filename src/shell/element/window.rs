@@ -1,4 +1,6 @@
 use crate::{
+    backend::render::element::SurfaceRenderElement,
+    config::{TitlebarAction, TitlebarActions},
     shell::Shell,
     state::State,
     utils::{
@@ -12,12 +14,9 @@ use cosmic::iced::Command;
 use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::InputType;
 use smithay::{
     backend::{
-        input::KeyState,
+        input::{ButtonState, KeyState},
         renderer::{
-            element::{
-                memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
-                AsRenderElements,
-            },
+            element::{memory::MemoryRenderBufferRenderElement, AsRenderElements},
             ImportAll, ImportMem, Renderer,
         },
     },
@@ -35,14 +34,14 @@ use smithay::{
     output::Output,
     reexports::wayland_server::protocol::wl_surface::WlSurface,
     render_elements,
-    utils::{Buffer as BufferCoords, IsAlive, Logical, Point, Rectangle, Serial, Size},
+    utils::{Buffer as BufferCoords, IsAlive, Logical, Point, Rectangle, Scale, Serial, Size},
     wayland::seat::WaylandFocus,
 };
 use std::{
     fmt,
     hash::Hash,
     sync::{
-        atomic::{AtomicBool, AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
         Arc, Mutex,
     },
 };
@@ -70,6 +69,9 @@ pub struct CosmicWindowInternal {
     pointer_entered: Arc<AtomicU8>,
     last_seat: Arc<Mutex<Option<(Seat<State>, Serial)>>>,
     last_title: Arc<Mutex<String>>,
+    last_click: Arc<Mutex<Option<u32>>>,
+    last_press: Arc<AtomicU32>,
+    shaded: Arc<AtomicBool>,
 }
 
 impl fmt::Debug for CosmicWindowInternal {
@@ -80,10 +82,14 @@ impl fmt::Debug for CosmicWindowInternal {
             .field("pointer_entered", &self.pointer_entered)
             // skip seat to avoid loop
             .field("last_seat", &"...")
+            .field("shaded", &self.shaded.load(Ordering::SeqCst))
             .finish()
     }
 }
 
+/// Maximum time in milliseconds between two clicks on the header to count as a double-click
+const DOUBLE_CLICK_TIMEOUT: u32 = 400;
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Focus {
@@ -108,6 +114,47 @@ impl CosmicWindowInternal {
     pub fn has_ssd(&self, pending: bool) -> bool {
         !self.window.is_decorated(pending)
     }
+
+    pub fn is_shaded(&self) -> bool {
+        self.has_ssd(false) && self.shaded.load(Ordering::SeqCst)
+    }
+
+    /// Left clicks are passed on to the header, which only starts a drag off its buttons
+    fn titlebar_action(
+        &self,
+        event: &ButtonEvent,
+        actions: &TitlebarActions,
+    ) -> Option<TitlebarAction> {
+        if event.state != ButtonState::Pressed {
+            return None;
+        }
+
+        let action = match event.button {
+            0x110 => {
+                // BTN_LEFT
+                self.last_press.store(event.time, Ordering::SeqCst);
+                TitlebarAction::None
+            }
+            0x111 => actions.right_click,  // BTN_RIGHT
+            0x112 => actions.middle_click, // BTN_MIDDLE
+            _ => TitlebarAction::None,
+        };
+
+        (action != TitlebarAction::None).then_some(action)
+    }
+
+    /// Whether the last left click on the header completes a double-click
+    fn is_double_click(&self) -> bool {
+        let time = self.last_press.load(Ordering::SeqCst);
+        let mut last_click = self.last_click.lock().unwrap();
+        match last_click.take() {
+            Some(previous) if time.wrapping_sub(previous) <= DOUBLE_CLICK_TIMEOUT => true,
+            _ => {
+                *last_click = Some(time);
+                false
+            }
+        }
+    }
 }
 
 impl CosmicWindow {
@@ -126,6 +173,9 @@ impl CosmicWindow {
                 pointer_entered: Arc::new(AtomicU8::new(Focus::None as u8)),
                 last_seat: Arc::new(Mutex::new(None)),
                 last_title: Arc::new(Mutex::new(last_title)),
+                last_click: Arc::new(Mutex::new(None)),
+                last_press: Arc::new(AtomicU32::new(0)),
+                shaded: Arc::new(AtomicBool::new(false)),
             },
             (width, SSD_HEIGHT),
             handle,
@@ -157,6 +207,23 @@ impl CosmicWindow {
         self.0.with_program(|p| &p.window == window)
     }
 
+    pub fn has_ssd(&self, pending: bool) -> bool {
+        self.0.with_program(|p| p.has_ssd(pending))
+    }
+
+    pub fn is_shaded(&self) -> bool {
+        self.0.with_program(|p| p.is_shaded())
+    }
+
+    pub fn is_header_focused(&self) -> bool {
+        self.0.with_program(|p| p.current_focus() == Focus::Header)
+    }
+
+    pub fn set_shaded(&self, shaded: bool) {
+        self.0
+            .with_program(|p| p.shaded.store(shaded, Ordering::SeqCst));
+    }
+
     pub fn offset(&self) -> Point<i32, Logical> {
         let has_ssd = self.0.with_program(|p| p.has_ssd(false));
         if has_ssd {
@@ -182,7 +249,7 @@ impl CosmicWindow {
         <R as Renderer>::TextureId: 'static,
         C: From<CosmicWindowRenderElement<R>>,
     {
-        let has_ssd = self.0.with_program(|p| p.has_ssd(false));
+        let (has_ssd, shaded) = self.0.with_program(|p| (p.has_ssd(false), p.is_shaded()));
 
         let window_loc = if has_ssd {
             location + Point::from((0, (SSD_HEIGHT as f64 * scale.y) as i32))
@@ -190,12 +257,25 @@ impl CosmicWindow {
             location
         };
 
-        let (mut window_elements, popup_elements) = self.0.with_program(|p| {
-            p.window
-                .split_render_elements::<R, CosmicWindowRenderElement<R>>(
-                    renderer, window_loc, scale, alpha,
-                )
-        });
+        let (mut window_elements, popup_elements) = if shaded {
+            (Vec::new(), Vec::new())
+        } else {
+            self.0.with_program(|p| match p.window.force_scaling() {
+                Some(scaling) => p
+                    .window
+                    .split_render_elements::<R, CosmicWindowRenderElement<R>>(
+                        renderer,
+                        window_loc + scaling.location().to_physical_precise_round(scale),
+                        Scale::from((scale.x * scaling.scale, scale.y * scaling.scale)),
+                        alpha,
+                    ),
+                None => p
+                    .window
+                    .split_render_elements::<R, CosmicWindowRenderElement<R>>(
+                        renderer, window_loc, scale, alpha,
+                    ),
+            })
+        };
 
         if has_ssd {
             let ssd_loc = location
@@ -215,6 +295,14 @@ impl CosmicWindow {
     }
 }
 
+/// Moves a pointer event on the window part into the coordinate space of a force-scaled client
+fn client_motion_event(window: &CosmicSurface, mut event: MotionEvent) -> MotionEvent {
+    if let Some(scaling) = window.force_scaling() {
+        event.location = scaling.to_client(event.location);
+    }
+    event
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
     DragStart,
@@ -232,34 +320,36 @@ impl Program for CosmicWindowInternal {
     ) -> Command<Self::Message> {
         match message {
             Message::DragStart => {
+                let double_click = self.is_double_click();
                 if let Some((seat, serial)) = self.last_seat.lock().unwrap().clone() {
                     if let Some(surface) = self.window.wl_surface() {
                         loop_handle.insert_idle(move |state| {
-                            Shell::move_request(state, &surface, &seat, serial);
+                            let action = state
+                                .common
+                                .config
+                                .static_conf
+                                .titlebar_actions
+                                .double_click;
+                            if double_click && action != TitlebarAction::None {
+                                Shell::titlebar_action(state, action, &surface, &seat, serial);
+                            } else {
+                                Shell::move_request(state, &surface, &seat, serial);
+                            }
                         });
                     }
                 }
             }
             Message::Maximize => {
-                if let Some((seat, _serial)) = self.last_seat.lock().unwrap().clone() {
+                if let Some((seat, serial)) = self.last_seat.lock().unwrap().clone() {
                     if let Some(surface) = self.window.wl_surface() {
                         loop_handle.insert_idle(move |state| {
-                            if let Some(mapped) =
-                                state.common.shell.element_for_wl_surface(&surface).cloned()
-                            {
-                                if let Some(workspace) = state.common.shell.space_for_mut(&mapped) {
-                                    let output = seat.active_output();
-                                    let (window, _) = mapped
-                                        .windows()
-                                        .find(|(w, _)| w.wl_surface().as_ref() == Some(&surface))
-                                        .unwrap();
-                                    workspace.maximize_toggle(
-                                        &window,
-                                        &output,
-                                        state.common.event_loop_handle.clone(),
-                                    )
-                                }
-                            }
+                            Shell::titlebar_action(
+                                state,
+                                TitlebarAction::Maximize,
+                                &surface,
+                                &seat,
+                                serial,
+                            );
                         });
                     }
                 }
@@ -350,7 +440,12 @@ impl SpaceElement for CosmicWindow {
     fn bbox(&self) -> Rectangle<i32, Logical> {
         self.0.with_program(|p| {
             let mut bbox = SpaceElement::bbox(&p.window);
-            if p.has_ssd(false) {
+            if let Some(scaling) = p.window.force_scaling() {
+                bbox = scaling.from_client(bbox);
+            }
+            if p.is_shaded() {
+                bbox.size.h = SSD_HEIGHT;
+            } else if p.has_ssd(false) {
                 bbox.size.h += SSD_HEIGHT;
             }
             bbox
@@ -365,10 +460,15 @@ impl SpaceElement for CosmicWindow {
             if p.has_ssd(false) {
                 if point.y < SSD_HEIGHT as f64 {
                     return true;
+                } else if p.is_shaded() {
+                    return false;
                 } else {
                     point.y -= SSD_HEIGHT as f64;
                 }
             }
+            if let Some(scaling) = p.window.force_scaling() {
+                point = scaling.to_client(point);
+            }
             SpaceElement::is_in_input_region(&p.window, &point)
         })
     }
@@ -398,7 +498,12 @@ impl SpaceElement for CosmicWindow {
     fn geometry(&self) -> Rectangle<i32, Logical> {
         self.0.with_program(|p| {
             let mut geo = SpaceElement::geometry(&p.window);
-            if p.has_ssd(false) {
+            if let Some(scaling) = p.window.force_scaling() {
+                geo.size = scaling.size;
+            }
+            if p.is_shaded() {
+                geo.size.h = SSD_HEIGHT;
+            } else if p.has_ssd(false) {
                 geo.size.h += SSD_HEIGHT;
             }
             geo
@@ -484,11 +589,13 @@ impl PointerTarget<State> for CosmicWindow {
 
                     let mut event = event.clone();
                     event.location.y -= SSD_HEIGHT as f64;
+                    let event = client_motion_event(&p.window, event);
                     PointerTarget::enter(&p.window, seat, data, &event)
                 }
             } else {
                 p.swap_focus(Focus::Window);
-                PointerTarget::enter(&p.window, seat, data, event)
+                let event = client_motion_event(&p.window, event.clone());
+                PointerTarget::enter(&p.window, seat, data, &event)
             }
             false
         }) {
@@ -519,6 +626,7 @@ impl PointerTarget<State> for CosmicWindow {
                 } else {
                     let mut event = event.clone();
                     event.location.y -= SSD_HEIGHT as f64;
+                    let event = client_motion_event(&p.window, event);
 
                     let previous = p.swap_focus(Focus::Window);
                     if previous != Focus::Window {
@@ -531,7 +639,8 @@ impl PointerTarget<State> for CosmicWindow {
                 }
             } else {
                 p.swap_focus(Focus::Window);
-                PointerTarget::motion(&p.window, seat, data, event);
+                let event = client_motion_event(&p.window, event.clone());
+                PointerTarget::motion(&p.window, seat, data, &event);
                 None
             }
         }) {
@@ -557,10 +666,20 @@ impl PointerTarget<State> for CosmicWindow {
     fn button(&self, seat: &Seat<State>, data: &mut State, event: &ButtonEvent) {
         match self.0.with_program(|p| p.current_focus()) {
             Focus::Header => {
-                self.0.with_program(|p| {
+                let action = self.0.with_program(|p| {
                     *p.last_seat.lock().unwrap() = Some((seat.clone(), event.serial));
+                    p.titlebar_action(event, &data.common.config.static_conf.titlebar_actions)
                 });
-                PointerTarget::button(&self.0, seat, data, event)
+                match action.zip(self.wl_surface()) {
+                    Some((action, surface)) => {
+                        let seat = seat.clone();
+                        let serial = event.serial;
+                        self.0.loop_handle().insert_idle(move |state| {
+                            Shell::titlebar_action(state, action, &surface, &seat, serial);
+                        });
+                    }
+                    None => PointerTarget::button(&self.0, seat, data, event),
+                }
             }
             Focus::Window => self
                 .0
@@ -721,5 +840,5 @@ impl WaylandFocus for CosmicWindow {
 render_elements! {
     pub CosmicWindowRenderElement<R> where R: ImportAll + ImportMem;
     Header = MemoryRenderBufferRenderElement<R>,
-    Window = WaylandSurfaceRenderElement<R>,
+    Window = SurfaceRenderElement<R>,
 }
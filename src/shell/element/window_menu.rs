@@ -0,0 +1,238 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{
+    config::TitlebarAction,
+    fl,
+    shell::{focus::target::KeyboardFocusTarget, CosmicSurface, Shell},
+    state::{Common, State},
+    utils::{
+        iced::{IcedElement, Program},
+        prelude::SeatExt,
+    },
+};
+
+use calloop::LoopHandle;
+use cosmic::{
+    iced::{
+        widget::{button, column, container, horizontal_rule},
+        Command,
+    },
+    iced_core::{Alignment, Background, Color, Length},
+    theme,
+    widget::text,
+    Apply,
+};
+use smithay::{
+    input::Seat,
+    utils::{Serial, Size},
+    wayland::seat::WaylandFocus,
+};
+
+const ITEM_HEIGHT: i32 = 32;
+const RULE_HEIGHT: i32 = 9;
+const MENU_WIDTH: i32 = 240;
+const MENU_PADDING: i32 = 8;
+
+pub type WindowMenu = IcedElement<WindowMenuInternal>;
+
+pub fn window_menu(
+    window: CosmicSurface,
+    seat: Seat<State>,
+    serial: Serial,
+    state: &Common,
+) -> WindowMenu {
+    let output = seat.active_output();
+    let current_workspace = state.shell.workspaces.active_num(&output).1;
+    let workspaces = (0..state.shell.workspaces.len(&output))
+        .filter(|idx| *idx != current_workspace)
+        .collect::<Vec<_>>();
+    let shadeable = state
+        .shell
+        .element_for_surface(&window)
+        .and_then(|mapped| {
+            state
+                .shell
+                .space_for(mapped)
+                .map(|workspace| mapped.is_window() && workspace.is_floating(mapped))
+        })
+        .unwrap_or(false)
+        && !window.is_decorated(false);
+
+    let items = 2 + shadeable as i32 + workspaces.len() as i32;
+    let rules = 1 + !workspaces.is_empty() as i32;
+    let size = Size::from((
+        MENU_WIDTH,
+        items * ITEM_HEIGHT + rules * RULE_HEIGHT + 2 * MENU_PADDING,
+    ));
+
+    WindowMenu::new(
+        WindowMenuInternal {
+            maximized: window.is_maximized(false),
+            shaded: state
+                .shell
+                .element_for_surface(&window)
+                .map(|mapped| mapped.is_shaded())
+                .unwrap_or(false),
+            shadeable,
+            window,
+            seat,
+            serial,
+            workspaces,
+            done: Arc::new(AtomicBool::new(false)),
+        },
+        size,
+        state.event_loop_handle.clone(),
+    )
+}
+
+pub struct WindowMenuInternal {
+    window: CosmicSurface,
+    seat: Seat<State>,
+    serial: Serial,
+    workspaces: Vec<usize>,
+    maximized: bool,
+    shaded: bool,
+    shadeable: bool,
+    done: Arc<AtomicBool>,
+}
+
+impl WindowMenuInternal {
+    /// Whether an entry was activated and the menu should be closed
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Message {
+    Maximize,
+    Shade,
+    MoveToWorkspace(usize),
+    Close,
+}
+
+impl Program for WindowMenuInternal {
+    type Message = Message;
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        loop_handle: &LoopHandle<'static, crate::state::State>,
+    ) -> Command<Self::Message> {
+        self.done.store(true, Ordering::SeqCst);
+
+        let Some(surface) = self.window.wl_surface() else {
+            return Command::none();
+        };
+        let seat = self.seat.clone();
+        let serial = self.serial;
+
+        match message {
+            Message::Maximize => {
+                loop_handle.insert_idle(move |state| {
+                    Shell::titlebar_action(state, TitlebarAction::Maximize, &surface, &seat, serial)
+                });
+            }
+            Message::Shade => {
+                loop_handle.insert_idle(move |state| {
+                    Shell::titlebar_action(state, TitlebarAction::Shade, &surface, &seat, serial)
+                });
+            }
+            Message::MoveToWorkspace(idx) => {
+                loop_handle.insert_idle(move |state| {
+                    if let Some(mapped) =
+                        state.common.shell.element_for_wl_surface(&surface).cloned()
+                    {
+                        Common::set_focus(
+                            state,
+                            Some(&KeyboardFocusTarget::from(mapped)),
+                            &seat,
+                            None,
+                        );
+                        let output = seat.active_output();
+                        let _ = Shell::move_current_window(
+                            state,
+                            &seat,
+                            &output,
+                            (&output, Some(idx)),
+                            false,
+                            None,
+                        );
+                    }
+                });
+            }
+            Message::Close => self.window.close(),
+        }
+
+        Command::none()
+    }
+
+    fn view(&self) -> crate::utils::iced::Element<'_, Self::Message> {
+        let entry =
+            |label: String, message: Message| -> crate::utils::iced::Element<'static, Message> {
+                text(label)
+                    .font(cosmic::font::FONT)
+                    .size(14)
+                    .apply(container)
+                    .center_y()
+                    .height(Length::Fill)
+                    .apply(button)
+                    .style(theme::iced::Button::Text)
+                    .on_press(message)
+                    .padding([0, 16])
+                    .width(Length::Fill)
+                    .height(Length::Fixed(ITEM_HEIGHT as f32))
+                    .into()
+            };
+
+        let mut items: Vec<crate::utils::iced::Element<'_, Self::Message>> = vec![entry(
+            if self.maximized {
+                fl!("window-menu-unmaximize")
+            } else {
+                fl!("window-menu-maximize")
+            },
+            Message::Maximize,
+        )];
+        if self.shadeable {
+            items.push(entry(
+                if self.shaded {
+                    fl!("window-menu-unshade")
+                } else {
+                    fl!("window-menu-shade")
+                },
+                Message::Shade,
+            ));
+        }
+        if !self.workspaces.is_empty() {
+            items.push(horizontal_rule(RULE_HEIGHT as u16).into());
+            for idx in &self.workspaces {
+                items.push(entry(
+                    fl!("window-menu-move-to-workspace", num = (idx + 1)),
+                    Message::MoveToWorkspace(*idx),
+                ));
+            }
+        }
+        items.push(horizontal_rule(RULE_HEIGHT as u16).into());
+        items.push(entry(fl!("window-menu-close"), Message::Close));
+
+        column(items)
+            .align_items(Alignment::Start)
+            .width(Length::Fill)
+            .apply(container)
+            .padding([MENU_PADDING as u16, 0])
+            .style(theme::Container::custom(|theme| container::Appearance {
+                icon_color: Some(Color::from(theme.cosmic().background.on)),
+                text_color: Some(Color::from(theme.cosmic().background.on)),
+                background: Some(Background::Color(theme.cosmic().palette.neutral_3.into())),
+                border_radius: 8.0.into(),
+                border_width: 1.0,
+                border_color: theme.cosmic().palette.neutral_5.into(),
+            }))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+}
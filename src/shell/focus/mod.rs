@@ -7,7 +7,7 @@ use crate::{
 };
 use indexmap::IndexSet;
 use smithay::{
-    desktop::{layer_map_for_output, PopupUngrabStrategy},
+    desktop::{find_popup_root_surface, layer_map_for_output, LayerSurface, PopupUngrabStrategy},
     input::Seat,
     utils::{IsAlive, Serial, SERIAL_COUNTER},
     wayland::seat::WaylandFocus,
@@ -97,6 +97,39 @@ impl Shell {
         active_seat: &Seat<State>,
         serial: Option<Serial>,
     ) {
+        // nothing but the lock surface of the active output gets focus while locked
+        let lock_target = state.common.session_lock.as_ref().map(|session_lock| {
+            session_lock
+                .surfaces
+                .get(&active_seat.active_output())
+                .cloned()
+                .map(KeyboardFocusTarget::from)
+        });
+        let target = match &lock_target {
+            Some(lock_target) => lock_target.as_ref(),
+            None => target,
+        };
+
+        // exclusive layer surfaces keep the focus, except to their own popups
+        let exclusive_target = state
+            .common
+            .shell
+            .exclusive_layer_surface(&active_seat.active_output())
+            .filter(|layer| !is_focus_of_layer(target, layer))
+            .map(KeyboardFocusTarget::LayerSurface);
+        let target = match (&lock_target, &exclusive_target) {
+            (None, Some(exclusive_target)) => Some(exclusive_target),
+            _ => target,
+        };
+
+        // modal dialogs take over the focus of their parent
+        let dialog = match target {
+            Some(KeyboardFocusTarget::Element(mapped)) => state.common.modal_dialog_for(mapped),
+            _ => None,
+        }
+        .map(KeyboardFocusTarget::from);
+        let target = dialog.as_ref().or(target);
+
         // update FocusStack and notify layouts about new focus (if any window)
         let element = match target {
             Some(KeyboardFocusTarget::Element(mapped)) => Some(mapped.clone()),
@@ -108,7 +141,14 @@ impl Shell {
             _ => None,
         };
 
+        // attention requested while focused was already given
+        if let Some(KeyboardFocusTarget::Element(previous)) =
+            active_seat.get_keyboard().unwrap().current_focus()
+        {
+            previous.set_urgent(false);
+        }
         if let Some(mapped) = element {
+            mapped.set_urgent(false);
             if let Some(workspace) = state.common.shell.space_for_mut(&mapped) {
                 let mut focus_stack = workspace.focus_stack.get_mut(active_seat);
                 if Some(&mapped) != focus_stack.last() {
@@ -181,6 +221,17 @@ impl Shell {
     }
 }
 
+/// Whether `target` is `layer` or one of its popups
+fn is_focus_of_layer(target: Option<&KeyboardFocusTarget>, layer: &LayerSurface) -> bool {
+    match target {
+        Some(KeyboardFocusTarget::LayerSurface(focused)) => focused == layer,
+        Some(KeyboardFocusTarget::Popup(popup)) => {
+            find_popup_root_surface(popup).ok().as_ref() == Some(layer.wl_surface())
+        }
+        _ => false,
+    }
+}
+
 fn raise_with_children(floating_layer: &mut FloatingLayout, focused: &CosmicMapped) {
     if floating_layer.mapped().any(|m| m == focused) {
         floating_layer.space.raise_element(focused, true);
@@ -227,6 +278,52 @@ impl Common {
                 seat.set_active_output(&state.common.shell.outputs[0]);
                 continue;
             }
+
+            // while locked, the lock surface of the active output has the focus, once it exists
+            if let Some(session_lock) = &state.common.session_lock {
+                let target = session_lock
+                    .surfaces
+                    .get(&output)
+                    .cloned()
+                    .map(KeyboardFocusTarget::from);
+                if seat.get_keyboard().unwrap().current_focus() != target {
+                    if let Some(mut popup_grab) = seat
+                        .user_data()
+                        .get::<PopupGrabData>()
+                        .and_then(|x| x.take())
+                    {
+                        if !popup_grab.has_ended() {
+                            popup_grab.ungrab(PopupUngrabStrategy::All);
+                        }
+                    }
+                    Shell::set_focus(state, target.as_ref(), &seat, None);
+                }
+                continue;
+            }
+
+            // layer surfaces with exclusive interactivity have the focus, while they are mapped
+            if let Some(layer) = state.common.shell.exclusive_layer_surface(&output) {
+                let current_focus = seat.get_keyboard().unwrap().current_focus();
+                if !is_focus_of_layer(current_focus.as_ref(), &layer) {
+                    if let Some(mut popup_grab) = seat
+                        .user_data()
+                        .get::<PopupGrabData>()
+                        .and_then(|x| x.take())
+                    {
+                        if !popup_grab.has_ended() {
+                            popup_grab.ungrab(PopupUngrabStrategy::All);
+                        }
+                    }
+                    Shell::set_focus(
+                        state,
+                        Some(&KeyboardFocusTarget::LayerSurface(layer)),
+                        &seat,
+                        None,
+                    );
+                }
+                continue;
+            }
+
             let last_known_focus = ActiveFocus::get(&seat);
 
             if let Some(target) = last_known_focus {
@@ -244,7 +341,10 @@ impl Common {
                             }
                         }
                         KeyboardFocusTarget::LayerSurface(layer) => {
-                            if layer_map_for_output(&output).layers().any(|l| l == &layer) {
+                            // dropping the interactivity returns the focus to the last window
+                            if layer.can_receive_keyboard_focus()
+                                && layer_map_for_output(&output).layers().any(|l| l == &layer)
+                            {
                                 continue; // Focus is valid
                             }
                         }
@@ -274,6 +374,8 @@ impl Common {
                         KeyboardFocusTarget::Popup(_) => {
                             continue; // Focus is valid
                         }
+                        // left over from a lock, that ended
+                        KeyboardFocusTarget::LockSurface(_) => {}
                     };
                 } else {
                     trace!("Surface dead, focus fixup");
@@ -25,7 +25,7 @@ use smithay::{
     output::WeakOutput,
     reexports::wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource},
     utils::{IsAlive, Serial},
-    wayland::seat::WaylandFocus,
+    wayland::{seat::WaylandFocus, session_lock::surface::LockSurface},
     xwayland::X11Surface,
 };
 
@@ -37,6 +37,7 @@ pub enum PointerFocusTarget {
     Popup(PopupKind),
     OverrideRedirect(X11Surface),
     ResizeFork(ResizeForkTarget),
+    LockSurface(LockSurface),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -46,6 +47,7 @@ pub enum KeyboardFocusTarget {
     Group(WindowGroup),
     LayerSurface(LayerSurface),
     Popup(PopupKind),
+    LockSurface(LockSurface),
 }
 
 // TODO: This should be TryFrom, but PopupGrab needs to be able to convert. Fix this in smithay
@@ -56,6 +58,7 @@ impl From<KeyboardFocusTarget> for PointerFocusTarget {
             KeyboardFocusTarget::Fullscreen(elem) => PointerFocusTarget::Fullscreen(elem),
             KeyboardFocusTarget::LayerSurface(layer) => PointerFocusTarget::LayerSurface(layer),
             KeyboardFocusTarget::Popup(popup) => PointerFocusTarget::Popup(popup),
+            KeyboardFocusTarget::LockSurface(lock) => PointerFocusTarget::LockSurface(lock),
             _ => unreachable!("A window grab cannot start a popup grab"),
         }
     }
@@ -69,6 +72,7 @@ impl TryFrom<PointerFocusTarget> for KeyboardFocusTarget {
             PointerFocusTarget::Fullscreen(surf) => Ok(KeyboardFocusTarget::Fullscreen(surf)),
             PointerFocusTarget::LayerSurface(layer) => Ok(KeyboardFocusTarget::LayerSurface(layer)),
             PointerFocusTarget::Popup(popup) => Ok(KeyboardFocusTarget::Popup(popup)),
+            PointerFocusTarget::LockSurface(lock) => Ok(KeyboardFocusTarget::LockSurface(lock)),
             _ => Err(()),
         }
     }
@@ -107,6 +111,7 @@ impl IsAlive for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(f) => f.alive(),
             PointerFocusTarget::LayerSurface(l) => l.alive(),
             PointerFocusTarget::Popup(p) => p.alive(),
+            PointerFocusTarget::LockSurface(lock) => lock.wl_surface().alive(),
             PointerFocusTarget::OverrideRedirect(s) => s.alive(),
             PointerFocusTarget::ResizeFork(f) => f.alive(),
         }
@@ -121,6 +126,7 @@ impl IsAlive for KeyboardFocusTarget {
             KeyboardFocusTarget::Group(g) => g.alive.upgrade().is_some(),
             KeyboardFocusTarget::LayerSurface(l) => l.alive(),
             KeyboardFocusTarget::Popup(p) => p.alive(),
+            KeyboardFocusTarget::LockSurface(lock) => lock.wl_surface().alive(),
         }
     }
 }
@@ -132,6 +138,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(w) => PointerTarget::enter(w, seat, data, event),
             PointerFocusTarget::LayerSurface(l) => PointerTarget::enter(l, seat, data, event),
             PointerFocusTarget::Popup(p) => PointerTarget::enter(p.wl_surface(), seat, data, event),
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::enter(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => PointerTarget::enter(s, seat, data, event),
             PointerFocusTarget::ResizeFork(f) => PointerTarget::enter(f, seat, data, event),
         }
@@ -144,6 +153,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::motion(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::motion(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => PointerTarget::motion(s, seat, data, event),
             PointerFocusTarget::ResizeFork(f) => PointerTarget::motion(f, seat, data, event),
         }
@@ -160,6 +172,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::relative_motion(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::relative_motion(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::relative_motion(s, seat, data, event)
             }
@@ -176,6 +191,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::button(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::button(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => PointerTarget::button(s, seat, data, event),
             PointerFocusTarget::ResizeFork(f) => PointerTarget::button(f, seat, data, event),
         }
@@ -186,6 +204,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(w) => PointerTarget::axis(w, seat, data, frame),
             PointerFocusTarget::LayerSurface(l) => PointerTarget::axis(l, seat, data, frame),
             PointerFocusTarget::Popup(p) => PointerTarget::axis(p.wl_surface(), seat, data, frame),
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::axis(lock.wl_surface(), seat, data, frame)
+            }
             PointerFocusTarget::OverrideRedirect(s) => PointerTarget::axis(s, seat, data, frame),
             PointerFocusTarget::ResizeFork(f) => PointerTarget::axis(f, seat, data, frame),
         }
@@ -196,6 +217,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(w) => PointerTarget::frame(w, seat, data),
             PointerFocusTarget::LayerSurface(l) => PointerTarget::frame(l, seat, data),
             PointerFocusTarget::Popup(p) => PointerTarget::frame(p.wl_surface(), seat, data),
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::frame(lock.wl_surface(), seat, data)
+            }
             PointerFocusTarget::OverrideRedirect(s) => PointerTarget::frame(s, seat, data),
             PointerFocusTarget::ResizeFork(f) => PointerTarget::frame(f, seat, data),
         }
@@ -210,6 +234,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::leave(p.wl_surface(), seat, data, serial, time)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::leave(lock.wl_surface(), seat, data, serial, time)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::leave(s, seat, data, serial, time)
             }
@@ -235,6 +262,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_swipe_begin(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_swipe_begin(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_swipe_begin(s, seat, data, event)
             }
@@ -262,6 +292,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_swipe_update(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_swipe_update(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_swipe_update(s, seat, data, event)
             }
@@ -289,6 +322,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_swipe_end(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_swipe_end(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_swipe_end(s, seat, data, event)
             }
@@ -316,6 +352,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_pinch_begin(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_pinch_begin(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_pinch_begin(s, seat, data, event)
             }
@@ -343,6 +382,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_pinch_update(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_pinch_update(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_pinch_update(s, seat, data, event)
             }
@@ -370,6 +412,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_pinch_end(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_pinch_end(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_pinch_end(s, seat, data, event)
             }
@@ -397,6 +442,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_hold_begin(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_hold_begin(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_hold_begin(s, seat, data, event)
             }
@@ -417,6 +465,9 @@ impl PointerTarget<State> for PointerFocusTarget {
             PointerFocusTarget::Popup(p) => {
                 PointerTarget::gesture_hold_end(p.wl_surface(), seat, data, event)
             }
+            PointerFocusTarget::LockSurface(lock) => {
+                PointerTarget::gesture_hold_end(lock.wl_surface(), seat, data, event)
+            }
             PointerFocusTarget::OverrideRedirect(s) => {
                 PointerTarget::gesture_hold_end(s, seat, data, event)
             }
@@ -447,6 +498,9 @@ impl KeyboardTarget<State> for KeyboardFocusTarget {
             KeyboardFocusTarget::Popup(p) => {
                 KeyboardTarget::enter(p.wl_surface(), seat, data, keys, serial)
             }
+            KeyboardFocusTarget::LockSurface(lock) => {
+                KeyboardTarget::enter(lock.wl_surface(), seat, data, keys, serial)
+            }
         }
     }
     fn leave(&self, seat: &Seat<State>, data: &mut State, serial: Serial) {
@@ -458,6 +512,9 @@ impl KeyboardTarget<State> for KeyboardFocusTarget {
             KeyboardFocusTarget::Popup(p) => {
                 KeyboardTarget::leave(p.wl_surface(), seat, data, serial)
             }
+            KeyboardFocusTarget::LockSurface(lock) => {
+                KeyboardTarget::leave(lock.wl_surface(), seat, data, serial)
+            }
         }
     }
     fn key(
@@ -483,6 +540,9 @@ impl KeyboardTarget<State> for KeyboardFocusTarget {
             KeyboardFocusTarget::Popup(p) => {
                 KeyboardTarget::key(p.wl_surface(), seat, data, key, state, serial, time)
             }
+            KeyboardFocusTarget::LockSurface(lock) => {
+                KeyboardTarget::key(lock.wl_surface(), seat, data, key, state, serial, time)
+            }
         }
     }
     fn modifiers(
@@ -506,6 +566,9 @@ impl KeyboardTarget<State> for KeyboardFocusTarget {
             KeyboardFocusTarget::Popup(p) => {
                 KeyboardTarget::modifiers(p.wl_surface(), seat, data, modifiers, serial)
             }
+            KeyboardFocusTarget::LockSurface(lock) => {
+                KeyboardTarget::modifiers(lock.wl_surface(), seat, data, modifiers, serial)
+            }
         }
     }
 }
@@ -518,6 +581,7 @@ impl WaylandFocus for KeyboardFocusTarget {
             KeyboardFocusTarget::Group(_) => None,
             KeyboardFocusTarget::LayerSurface(l) => Some(l.wl_surface().clone()),
             KeyboardFocusTarget::Popup(p) => Some(p.wl_surface().clone()),
+            KeyboardFocusTarget::LockSurface(lock) => Some(lock.wl_surface().clone()),
         }
     }
     fn same_client_as(&self, object_id: &ObjectId) -> bool {
@@ -527,6 +591,9 @@ impl WaylandFocus for KeyboardFocusTarget {
             KeyboardFocusTarget::Group(_) => false,
             KeyboardFocusTarget::LayerSurface(l) => l.wl_surface().id().same_client_as(object_id),
             KeyboardFocusTarget::Popup(p) => p.wl_surface().id().same_client_as(object_id),
+            KeyboardFocusTarget::LockSurface(lock) => {
+                lock.wl_surface().id().same_client_as(object_id)
+            }
         }
     }
 }
@@ -538,6 +605,7 @@ impl WaylandFocus for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(w) => WaylandFocus::wl_surface(w)?,
             PointerFocusTarget::LayerSurface(l) => l.wl_surface().clone(),
             PointerFocusTarget::Popup(p) => p.wl_surface().clone(),
+            PointerFocusTarget::LockSurface(lock) => lock.wl_surface().clone(),
             PointerFocusTarget::OverrideRedirect(s) => {
                 return s.wl_surface();
             }
@@ -552,6 +620,9 @@ impl WaylandFocus for PointerFocusTarget {
             PointerFocusTarget::Fullscreen(w) => WaylandFocus::same_client_as(w, object_id),
             PointerFocusTarget::LayerSurface(l) => l.wl_surface().id().same_client_as(object_id),
             PointerFocusTarget::Popup(p) => p.wl_surface().id().same_client_as(object_id),
+            PointerFocusTarget::LockSurface(lock) => {
+                lock.wl_surface().id().same_client_as(object_id)
+            }
             PointerFocusTarget::OverrideRedirect(s) => WaylandFocus::same_client_as(s, object_id),
             PointerFocusTarget::ResizeFork(_) => false,
         }
@@ -582,6 +653,12 @@ impl From<PopupKind> for PointerFocusTarget {
     }
 }
 
+impl From<LockSurface> for PointerFocusTarget {
+    fn from(l: LockSurface) -> Self {
+        PointerFocusTarget::LockSurface(l)
+    }
+}
+
 impl From<X11Surface> for PointerFocusTarget {
     fn from(s: X11Surface) -> Self {
         PointerFocusTarget::OverrideRedirect(s)
@@ -623,3 +700,9 @@ impl From<PopupKind> for KeyboardFocusTarget {
         KeyboardFocusTarget::Popup(p)
     }
 }
+
+impl From<LockSurface> for KeyboardFocusTarget {
+    fn from(l: LockSurface) -> Self {
+        KeyboardFocusTarget::LockSurface(l)
+    }
+}
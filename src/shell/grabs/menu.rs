@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    backend::render::element::AsGlowRenderer,
+    shell::{
+        element::{window_menu::WindowMenu, CosmicMappedRenderElement},
+        focus::target::PointerFocusTarget,
+    },
+    utils::prelude::*,
+};
+
+use smithay::{
+    backend::{
+        input::ButtonState,
+        renderer::{
+            element::{AsRenderElements, RenderElement},
+            ImportAll, ImportMem, Renderer,
+        },
+    },
+    desktop::space::SpaceElement,
+    input::{
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
+            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent,
+            GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab, PointerInnerHandle,
+            PointerTarget, RelativeMotionEvent,
+        },
+        Seat,
+    },
+    output::Output,
+    utils::{Logical, Point, Rectangle, Serial},
+};
+use std::cell::RefCell;
+
+pub type SeatMenuGrabState = RefCell<Option<MenuGrabState>>;
+
+pub struct MenuGrabState {
+    element: WindowMenu,
+    position: Point<i32, Logical>,
+}
+
+impl MenuGrabState {
+    pub fn render<I, R>(&self, renderer: &mut R, output: &Output) -> Vec<I>
+    where
+        R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+        <R as Renderer>::TextureId: 'static,
+        CosmicMappedRenderElement<R>: RenderElement<R>,
+        I: From<CosmicMappedRenderElement<R>>,
+    {
+        let geometry = Rectangle::from_loc_and_size(self.position, self.element.bbox().size);
+        if output.geometry().intersection(geometry).is_none() {
+            return Vec::new();
+        }
+
+        let scale = output.current_scale().fractional_scale();
+        self.element
+            .render_elements::<CosmicMappedRenderElement<R>>(
+                renderer,
+                (self.position - output.geometry().loc).to_physical_precise_round(scale),
+                scale.into(),
+                1.0,
+            )
+            .into_iter()
+            .map(I::from)
+            .collect()
+    }
+}
+
+pub struct MenuGrab {
+    start_data: PointerGrabStartData<State>,
+    seat: Seat<State>,
+    element: WindowMenu,
+    position: Point<i32, Logical>,
+    pointer_inside: bool,
+}
+
+impl MenuGrab {
+    pub fn new(
+        start_data: PointerGrabStartData<State>,
+        seat: &Seat<State>,
+        element: WindowMenu,
+        position: Point<i32, Logical>,
+    ) -> MenuGrab {
+        let output = seat.active_output();
+        let output_geo = output.geometry();
+        let size = element.bbox().size;
+
+        // keep the menu on the output it was opened on
+        let position = Point::from((
+            position
+                .x
+                .min(output_geo.loc.x + output_geo.size.w - size.w)
+                .max(output_geo.loc.x),
+            position
+                .y
+                .min(output_geo.loc.y + output_geo.size.h - size.h)
+                .max(output_geo.loc.y),
+        ));
+        element.output_enter(&output, output_geo);
+
+        *seat
+            .user_data()
+            .get::<SeatMenuGrabState>()
+            .unwrap()
+            .borrow_mut() = Some(MenuGrabState {
+            element: element.clone(),
+            position,
+        });
+
+        MenuGrab {
+            start_data,
+            seat: seat.clone(),
+            element,
+            position,
+            pointer_inside: false,
+        }
+    }
+
+    fn contains(&self, location: Point<f64, Logical>) -> bool {
+        Rectangle::from_loc_and_size(self.position, self.element.bbox().size)
+            .to_f64()
+            .contains(location)
+    }
+
+    /// Ends the grab, the menu is removed when the grab is dropped
+    fn ungrab(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.unset_grab(state, serial, time);
+    }
+}
+
+impl Drop for MenuGrab {
+    fn drop(&mut self) {
+        // the grab can also end by being replaced or released by the compositor
+        let mut menu_state = self
+            .seat
+            .user_data()
+            .get::<SeatMenuGrabState>()
+            .unwrap()
+            .borrow_mut();
+        if menu_state
+            .as_ref()
+            .is_some_and(|menu_state| menu_state.element == self.element)
+        {
+            menu_state.take();
+        }
+    }
+}
+
+impl PointerGrab<State> for MenuGrab {
+    fn motion(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(PointerFocusTarget, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(state, None, event);
+
+        let inside = self.contains(event.location);
+        let mut relative_event = event.clone();
+        relative_event.location -= self.position.to_f64();
+        match (self.pointer_inside, inside) {
+            (false, true) => self.element.enter(&self.seat, state, &relative_event),
+            (true, true) => self.element.motion(&self.seat, state, &relative_event),
+            (true, false) => self
+                .element
+                .leave(&self.seat, state, event.serial, event.time),
+            (false, false) => {}
+        }
+        self.pointer_inside = inside;
+    }
+
+    fn relative_motion(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(PointerFocusTarget, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(state, None, event);
+    }
+
+    fn button(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &ButtonEvent,
+    ) {
+        if !self.pointer_inside {
+            // clicking anywhere else dismisses the menu
+            if event.state == ButtonState::Pressed {
+                self.ungrab(state, handle, event.serial, event.time);
+            }
+            return;
+        }
+
+        self.element.button(&self.seat, state, event);
+        if self.element.with_program(|p| p.is_done()) {
+            self.ungrab(state, handle, event.serial, event.time);
+        }
+    }
+
+    fn axis(
+        &mut self,
+        state: &mut State,
+        _handle: &mut PointerInnerHandle<'_, State>,
+        details: AxisFrame,
+    ) {
+        if self.pointer_inside {
+            self.element.axis(&self.seat, state, details);
+        }
+    }
+
+    fn frame(&mut self, data: &mut State, handle: &mut PointerInnerHandle<'_, State>) {
+        handle.frame(data)
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event)
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event)
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event)
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event)
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event)
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event)
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event)
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<State> {
+        &self.start_data
+    }
+}
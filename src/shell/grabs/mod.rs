@@ -17,8 +17,12 @@ use super::{
     layout::{floating::ResizeSurfaceGrab, tiling::ResizeForkGrab},
 };
 
+mod menu;
+pub use self::menu::*;
 mod moving;
 pub use self::moving::*;
+mod screenshot;
+pub use self::screenshot::*;
 
 bitflags::bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -4,8 +4,9 @@ use crate::{
     backend::render::{
         cursor::{CursorShape, CursorState},
         element::AsGlowRenderer,
-        IndicatorShader, Key, Usage,
+        IndicatorShader, Key, ShadowShader, Usage,
     },
+    config::{AnimationConfig, ShadowConfig},
     shell::{
         element::{
             stack_hover::{stack_hover, StackHover},
@@ -14,7 +15,10 @@ use crate::{
         focus::target::{KeyboardFocusTarget, PointerFocusTarget},
         CosmicMapped, CosmicSurface,
     },
-    utils::prelude::*,
+    utils::{
+        animation::{Animation, AnimationKind},
+        prelude::*,
+    },
 };
 
 use smithay::{
@@ -46,19 +50,25 @@ use std::{
 
 pub type SeatMoveGrabState = RefCell<Option<MoveGrabState>>;
 
-const RESCALE_ANIMATION_DURATION: f64 = 150.0;
-
 pub struct MoveGrabState {
     window: CosmicMapped,
     window_offset: Point<i32, Logical>,
     indicator_thickness: u8,
+    shadow: ShadowConfig,
     start: Instant,
     tiling: bool,
     stacking_indicator: Option<(StackHover, Point<i32, Logical>)>,
 }
 
 impl MoveGrabState {
-    pub fn render<I, R>(&self, renderer: &mut R, seat: &Seat<State>, output: &Output) -> Vec<I>
+    pub fn render<I, R>(
+        &self,
+        renderer: &mut R,
+        seat: &Seat<State>,
+        output: &Output,
+        indicator_color: [f32; 3],
+        animations: &AnimationConfig,
+    ) -> Vec<I>
     where
         R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
         <R as Renderer>::TextureId: 'static,
@@ -69,11 +79,8 @@ impl MoveGrabState {
         puffin::profile_function!();
 
         let scale = if self.tiling {
-            0.6 + ((1.0
-                - (Instant::now().duration_since(self.start).as_millis() as f64
-                    / RESCALE_ANIMATION_DURATION)
-                    .min(1.0))
-                * 0.4)
+            Animation::starting_at(AnimationKind::WindowMove, self.start, animations)
+                .value(1.0, 0.6)
         } else {
             1.0
         };
@@ -109,6 +116,7 @@ impl MoveGrabState {
                     self.indicator_thickness,
                     output_scale.x,
                     1.0,
+                    indicator_color,
                 ))
                 .into(),
             )
@@ -116,6 +124,24 @@ impl MoveGrabState {
             None
         };
 
+        let window_size = self
+            .window
+            .geometry()
+            .size
+            .to_f64()
+            .upscale(scale)
+            .to_i32_round();
+        let shadow_element = (self.shadow.enabled && self.window.has_ssd(false)).then(|| {
+            CosmicMappedRenderElement::from(ShadowShader::element(
+                renderer,
+                Key::Window(Usage::MoveGrabShadow, self.window.clone()),
+                Rectangle::from_loc_and_size(render_location, window_size),
+                &self.shadow,
+                output_scale.x,
+                1.0,
+            ))
+        });
+
         let (window_elements, popup_elements) = self
             .window
             .split_render_elements::<R, CosmicMappedRenderElement<R>>(
@@ -163,6 +189,7 @@ impl MoveGrabState {
                 }
                 x => x,
             }))
+            .chain(shadow_element)
             .map(I::from)
             .collect()
     }
@@ -182,6 +209,111 @@ impl MoveGrabState {
     pub fn window(&self) -> CosmicSurface {
         self.window.active_window()
     }
+
+    /// Attaches `window` to the pointer of `seat`, without taking a pointer grab
+    pub fn attach(
+        seat: &Seat<State>,
+        window: CosmicMapped,
+        window_offset: Point<i32, Logical>,
+        indicator_thickness: u8,
+        shadow: ShadowConfig,
+        tiling: bool,
+    ) {
+        let grab_state = MoveGrabState {
+            window,
+            window_offset,
+            indicator_thickness,
+            shadow,
+            start: Instant::now(),
+            stacking_indicator: None,
+            tiling,
+        };
+
+        *seat
+            .user_data()
+            .get::<SeatMoveGrabState>()
+            .unwrap()
+            .borrow_mut() = Some(grab_state);
+    }
+
+    /// Places the window attached to the pointer of `seat` at `location`, returning it
+    /// together with its new position, if it is still alive
+    pub fn drop_window(
+        state: &mut State,
+        seat: &Seat<State>,
+        window_outputs: &HashSet<Output>,
+        location: Point<f64, Logical>,
+    ) -> Option<(CosmicMapped, Point<i32, Logical>)> {
+        let output = seat.active_output();
+        let grab_state = seat
+            .user_data()
+            .get::<SeatMoveGrabState>()
+            .and_then(|s| s.borrow_mut().take())?;
+        if !grab_state.window.alive() {
+            return None;
+        }
+
+        let window_location =
+            location.to_i32_round() - output.geometry().loc + grab_state.window_offset;
+
+        let workspace_handle = state.common.shell.active_space(&output).handle;
+        for old_output in window_outputs.iter().filter(|o| *o != &output) {
+            grab_state.window.output_leave(old_output);
+        }
+        for (window, _) in grab_state.window.windows() {
+            state
+                .common
+                .shell
+                .toplevel_info_state
+                .toplevel_enter_workspace(&window, &workspace_handle);
+            state
+                .common
+                .shell
+                .toplevel_info_state
+                .toplevel_enter_output(&window, &output);
+        }
+
+        if grab_state.tiling {
+            Some(
+                state
+                    .common
+                    .shell
+                    .active_space_mut(&output)
+                    .tiling_layer
+                    .drop_window(grab_state.window, &output, location),
+            )
+        } else {
+            let offset = state
+                .common
+                .shell
+                .active_space(&output)
+                .floating_layer
+                .space
+                .output_geometry(&output)
+                .unwrap()
+                .loc;
+            grab_state.window.set_geometry(Rectangle::from_loc_and_size(
+                window_location + offset,
+                grab_state.window.geometry().size,
+            ));
+            state
+                .common
+                .shell
+                .active_space_mut(&output)
+                .floating_layer
+                .map_internal(
+                    grab_state.window.clone(),
+                    &output,
+                    Some(window_location + offset),
+                );
+
+            let relative_pos = state.common.shell.map_global_to_space(location, &output);
+            Some((
+                grab_state.window,
+                window_location + offset + (location - relative_pos).to_i32_round(),
+            ))
+        }
+    }
 }
 
 pub struct MoveGrab {
@@ -400,6 +532,7 @@ impl MoveGrab {
         initial_cursor_location: Point<f64, Logical>,
         initial_window_location: Point<i32, Logical>,
         indicator_thickness: u8,
+        shadow: ShadowConfig,
         was_tiled: bool,
     ) -> MoveGrab {
         let output = seat.active_output();
@@ -407,20 +540,14 @@ impl MoveGrab {
         outputs.insert(output.clone());
         window.output_enter(&output, window.geometry()); // not accurate but...
 
-        let grab_state = MoveGrabState {
-            window: window.clone(),
-            window_offset: initial_window_location - initial_cursor_location.to_i32_round(),
+        MoveGrabState::attach(
+            seat,
+            window.clone(),
+            initial_window_location - initial_cursor_location.to_i32_round(),
             indicator_thickness,
-            start: Instant::now(),
-            stacking_indicator: None,
-            tiling: was_tiled,
-        };
-
-        *seat
-            .user_data()
-            .get::<SeatMoveGrabState>()
-            .unwrap()
-            .borrow_mut() = Some(grab_state);
+            shadow,
+            was_tiled,
+        );
 
         {
             let cursor_state = seat.user_data().get::<CursorState>().unwrap();
@@ -449,79 +576,12 @@ impl MoveGrab {
         time: u32,
     ) {
         // No more buttons are pressed, release the grab.
-        let output = self.seat.active_output();
-
-        let position = if let Some(grab_state) = self
-            .seat
-            .user_data()
-            .get::<SeatMoveGrabState>()
-            .and_then(|s| s.borrow_mut().take())
-        {
-            if grab_state.window.alive() {
-                let window_location = handle.current_location().to_i32_round()
-                    - output.geometry().loc
-                    + grab_state.window_offset;
-
-                let workspace_handle = state.common.shell.active_space(&output).handle;
-                for old_output in self.window_outputs.iter().filter(|o| *o != &output) {
-                    grab_state.window.output_leave(old_output);
-                }
-                for (window, _) in grab_state.window.windows() {
-                    state
-                        .common
-                        .shell
-                        .toplevel_info_state
-                        .toplevel_enter_workspace(&window, &workspace_handle);
-                    state
-                        .common
-                        .shell
-                        .toplevel_info_state
-                        .toplevel_enter_output(&window, &output);
-                }
-
-                if self.tiling {
-                    Some(
-                        state
-                            .common
-                            .shell
-                            .active_space_mut(&output)
-                            .tiling_layer
-                            .drop_window(grab_state.window, &output, handle.current_location()),
-                    )
-                } else {
-                    let offset = state
-                        .common
-                        .shell
-                        .active_space(&output)
-                        .floating_layer
-                        .space
-                        .output_geometry(&output)
-                        .unwrap()
-                        .loc;
-                    grab_state.window.set_geometry(Rectangle::from_loc_and_size(
-                        window_location + offset,
-                        grab_state.window.geometry().size,
-                    ));
-                    state
-                        .common
-                        .shell
-                        .active_space_mut(&output)
-                        .floating_layer
-                        .map_internal(grab_state.window, &output, Some(window_location + offset));
-
-                    let pointer_pos = handle.current_location();
-                    let relative_pos = state.common.shell.map_global_to_space(pointer_pos, &output);
-                    Some((
-                        self.window.clone(),
-                        window_location + offset + (pointer_pos - relative_pos).to_i32_round(),
-                    ))
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let position = MoveGrabState::drop_window(
+            state,
+            &self.seat,
+            &self.window_outputs,
+            handle.current_location(),
+        );
 
         handle.unset_grab(state, serial, time);
 
@@ -0,0 +1,398 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    backend::render::{
+        cursor::{CursorShape, CursorState},
+        element::AsGlowRenderer,
+        screenshot::take_screenshot,
+        BackdropShader, IndicatorShader, FOCUS_INDICATOR_COLOR,
+    },
+    shell::{element::CosmicMappedRenderElement, focus::target::PointerFocusTarget},
+    utils::prelude::*,
+};
+
+use smithay::{
+    backend::{
+        input::ButtonState,
+        renderer::{
+            element::{Id, RenderElement},
+            ImportAll, ImportMem, Renderer,
+        },
+    },
+    input::{
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
+            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent,
+            GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab, PointerInnerHandle,
+            RelativeMotionEvent,
+        },
+        Seat,
+    },
+    output::Output,
+    utils::{Logical, Point, Rectangle, Serial},
+};
+use std::cell::RefCell;
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const DIM_ALPHA: f32 = 0.5;
+const OUTLINE_THICKNESS: u8 = 1;
+
+pub type SeatScreenshotGrabState = RefCell<Option<ScreenshotGrabState>>;
+
+pub struct ScreenshotGrabState {
+    output: Output,
+    /// Where the selection was started, `None` until the button is pressed
+    anchor: Option<Point<f64, Logical>>,
+    cursor: Point<f64, Logical>,
+    /// The dimmed areas around the selection and its outline
+    ids: [Id; 5],
+}
+
+impl ScreenshotGrabState {
+    /// Selected rectangle in global coordinates
+    pub fn selection(&self) -> Option<Rectangle<i32, Logical>> {
+        let anchor = self.anchor?;
+        let min =
+            Point::<f64, Logical>::from((anchor.x.min(self.cursor.x), anchor.y.min(self.cursor.y)))
+                .to_i32_round();
+        let max =
+            Point::<f64, Logical>::from((anchor.x.max(self.cursor.x), anchor.y.max(self.cursor.y)))
+                .to_i32_round();
+        Some(Rectangle::from_loc_and_size(
+            min,
+            (max.x - min.x, max.y - min.y),
+        ))
+    }
+
+    pub fn render<I, R>(&self, renderer: &mut R, output: &Output) -> Vec<I>
+    where
+        R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+        <R as Renderer>::TextureId: 'static,
+        CosmicMappedRenderElement<R>: RenderElement<R>,
+        I: From<CosmicMappedRenderElement<R>>,
+    {
+        if output != &self.output {
+            return Vec::new();
+        }
+
+        let output_geo = output.geometry();
+        let size = output_geo.size;
+        let selection = self
+            .selection()
+            .and_then(|selection| selection.intersection(output_geo))
+            .filter(|selection| selection.size.w > 0 && selection.size.h > 0)
+            .map(|mut selection| {
+                selection.loc -= output_geo.loc;
+                selection
+            });
+
+        let mut elements = Vec::new();
+        let dimmed = match selection {
+            Some(selection) => {
+                elements.push(IndicatorShader::element(
+                    renderer,
+                    self.ids[4].clone(),
+                    selection,
+                    OUTLINE_THICKNESS,
+                    0,
+                    1.0,
+                    output.current_scale().fractional_scale(),
+                    FOCUS_INDICATOR_COLOR,
+                ));
+                let (top, bottom) = (selection.loc.y, selection.loc.y + selection.size.h);
+                let (left, right) = (selection.loc.x, selection.loc.x + selection.size.w);
+                vec![
+                    Rectangle::from_loc_and_size((0, 0), (size.w, top)),
+                    Rectangle::from_loc_and_size((0, bottom), (size.w, size.h - bottom)),
+                    Rectangle::from_loc_and_size((0, top), (left, selection.size.h)),
+                    Rectangle::from_loc_and_size((right, top), (size.w - right, selection.size.h)),
+                ]
+            }
+            // nothing selected yet, dim everything
+            None => vec![Rectangle::from_loc_and_size((0, 0), size)],
+        };
+        elements.extend(
+            dimmed
+                .into_iter()
+                .zip(self.ids.iter())
+                .filter(|(rect, _)| rect.size.w > 0 && rect.size.h > 0)
+                .map(|(rect, id)| {
+                    BackdropShader::element(renderer, id.clone(), rect, 0.0, DIM_ALPHA, [0.0; 3])
+                }),
+        );
+
+        elements
+            .into_iter()
+            .map(|elem| I::from(CosmicMappedRenderElement::from(elem)))
+            .collect()
+    }
+}
+
+/// Lets the user drag out the region of an output to take a screenshot of.
+///
+/// Releasing the left button takes the screenshot, the right button cancels.
+pub struct ScreenshotGrab {
+    start_data: PointerGrabStartData<State>,
+    seat: Seat<State>,
+    output: Output,
+}
+
+impl ScreenshotGrab {
+    pub fn new(start_data: PointerGrabStartData<State>, seat: &Seat<State>) -> ScreenshotGrab {
+        let output = seat.active_output();
+
+        *seat
+            .user_data()
+            .get::<SeatScreenshotGrabState>()
+            .unwrap()
+            .borrow_mut() = Some(ScreenshotGrabState {
+            output: output.clone(),
+            anchor: None,
+            cursor: start_data.location,
+            ids: std::array::from_fn(|_| Id::new()),
+        });
+
+        {
+            let cursor_state = seat.user_data().get::<CursorState>().unwrap();
+            cursor_state.set_shape(CursorShape::Crosshair);
+        }
+
+        ScreenshotGrab {
+            start_data,
+            seat: seat.clone(),
+            output,
+        }
+    }
+
+    fn ungrab(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        serial: Serial,
+        time: u32,
+        capture: bool,
+    ) {
+        let grab_state = self
+            .seat
+            .user_data()
+            .get::<SeatScreenshotGrabState>()
+            .unwrap()
+            .borrow_mut()
+            .take();
+        handle.unset_grab(state, serial, time);
+        finish(state, &self.seat, &self.output);
+
+        if let Some(selection) = grab_state
+            .and_then(|grab_state| grab_state.selection())
+            .filter(|_| capture)
+        {
+            // rendering needs access to the pointer, which is locked during the grab
+            let seat = self.seat.clone();
+            let output = self.output.clone();
+            state.common.event_loop_handle.insert_idle(move |state| {
+                take_screenshot(state, &seat, &output, Some(selection));
+            });
+        }
+    }
+}
+
+impl ScreenshotGrab {
+    /// Cancels the selection of a seat, if it is taking a screenshot, returning if it was
+    pub fn cancel(state: &mut State, seat: &Seat<State>, serial: Serial, time: u32) -> bool {
+        let Some(grab_state) = seat
+            .user_data()
+            .get::<SeatScreenshotGrabState>()
+            .unwrap()
+            .borrow_mut()
+            .take()
+        else {
+            return false;
+        };
+        if let Some(pointer) = seat.get_pointer() {
+            pointer.unset_grab(state, serial, time);
+        }
+        finish(state, seat, &grab_state.output);
+        true
+    }
+}
+
+/// Restores the cursor and removes the selection from the output
+fn finish(state: &mut State, seat: &Seat<State>, output: &Output) {
+    seat.user_data()
+        .get::<CursorState>()
+        .unwrap()
+        .set_shape(CursorShape::Default);
+    state
+        .backend
+        .schedule_render(&state.common.event_loop_handle, output, None);
+}
+
+impl PointerGrab<State> for ScreenshotGrab {
+    fn motion(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(PointerFocusTarget, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(state, None, event);
+
+        // the selection stays on the output it was started on
+        let output_geo = self.output.geometry().to_f64();
+        if let Some(grab_state) = self
+            .seat
+            .user_data()
+            .get::<SeatScreenshotGrabState>()
+            .unwrap()
+            .borrow_mut()
+            .as_mut()
+        {
+            grab_state.cursor = Point::from((
+                event
+                    .location
+                    .x
+                    .clamp(output_geo.loc.x, output_geo.loc.x + output_geo.size.w),
+                event
+                    .location
+                    .y
+                    .clamp(output_geo.loc.y, output_geo.loc.y + output_geo.size.h),
+            ));
+        }
+        state
+            .backend
+            .schedule_render(&state.common.event_loop_handle, &self.output, None);
+    }
+
+    fn relative_motion(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(PointerFocusTarget, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(state, None, event);
+    }
+
+    fn button(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(state, event);
+
+        match (event.button, event.state) {
+            (BTN_LEFT, ButtonState::Pressed) => {
+                if let Some(grab_state) = self
+                    .seat
+                    .user_data()
+                    .get::<SeatScreenshotGrabState>()
+                    .unwrap()
+                    .borrow_mut()
+                    .as_mut()
+                {
+                    grab_state.anchor = Some(grab_state.cursor);
+                }
+            }
+            (BTN_LEFT, ButtonState::Released) => {
+                self.ungrab(state, handle, event.serial, event.time, true)
+            }
+            (BTN_RIGHT, ButtonState::Pressed) => {
+                self.ungrab(state, handle, event.serial, event.time, false)
+            }
+            _ => {}
+        }
+    }
+
+    fn axis(
+        &mut self,
+        state: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        details: AxisFrame,
+    ) {
+        handle.axis(state, details);
+    }
+
+    fn frame(&mut self, data: &mut State, handle: &mut PointerInnerHandle<'_, State>) {
+        handle.frame(data)
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event)
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event)
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event)
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event)
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event)
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event)
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event)
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<State> {
+        &self.start_data
+    }
+}
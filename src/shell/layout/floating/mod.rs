@@ -2,19 +2,20 @@
 
 use smithay::{
     backend::renderer::{
-        element::{AsRenderElements, RenderElement},
+        element::{utils::RescaleRenderElement, AsRenderElements, RenderElement},
         ImportAll, ImportMem, Renderer,
     },
     desktop::{layer_map_for_output, space::SpaceElement, PopupKind, Space, WindowSurfaceType},
     input::{pointer::GrabStartData as PointerGrabStartData, Seat},
     output::Output,
-    utils::{Logical, Point, Rectangle, Size},
+    utils::{IsAlive, Logical, Point, Rectangle, Size},
     wayland::seat::WaylandFocus,
 };
 use std::collections::HashMap;
 
 use crate::{
-    backend::render::{element::AsGlowRenderer, IndicatorShader, Key, Usage},
+    backend::render::{element::AsGlowRenderer, IndicatorShader, Key, ShadowShader, Usage},
+    config::{AnimationConfig, BorderConfig, ShadowConfig},
     shell::{
         element::{
             resize_indicator::ResizeIndicator,
@@ -27,7 +28,10 @@ use crate::{
         CosmicSurface, Direction, FocusResult, MoveResult, ResizeDirection, ResizeMode,
     },
     state::State,
-    utils::prelude::*,
+    utils::{
+        animation::{Animation, AnimationKind},
+        prelude::*,
+    },
     wayland::{
         handlers::xdg_shell::popup::get_popup_toplevel, protocols::toplevel_info::ToplevelInfoState,
     },
@@ -36,14 +40,27 @@ use crate::{
 mod grabs;
 pub use self::grabs::*;
 
-#[derive(Debug, Default)]
+/// Scale newly opened windows grow from
+const OPEN_ANIMATION_SCALE: f64 = 0.9;
+
+#[derive(Debug)]
 pub struct FloatingLayout {
     pub(in crate::shell) space: Space<CosmicMapped>,
+    opening: HashMap<CosmicMapped, Animation>,
+    animations: AnimationConfig,
 }
 
 impl FloatingLayout {
-    pub fn new() -> FloatingLayout {
-        Default::default()
+    pub fn new(animations: AnimationConfig) -> FloatingLayout {
+        FloatingLayout {
+            space: Space::default(),
+            opening: HashMap::new(),
+            animations,
+        }
+    }
+
+    pub fn set_animations(&mut self, animations: AnimationConfig) {
+        self.animations = animations;
     }
 
     pub fn map_output(&mut self, output: &Output, location: Point<i32, Logical>) {
@@ -179,9 +196,27 @@ impl FloatingLayout {
 
         let was_unmaped = self.space.elements().any(|e| e == window);
         self.space.unmap_elem(&window);
+        self.opening.remove(window);
         was_unmaped
     }
 
+    /// Fades and scales a newly opened window into view
+    pub fn animate_open(&mut self, mapped: &CosmicMapped) {
+        self.opening.insert(
+            mapped.clone(),
+            Animation::new(AnimationKind::WindowOpen, &self.animations),
+        );
+    }
+
+    pub fn animations_going(&self) -> bool {
+        !self.opening.is_empty()
+    }
+
+    pub fn update_animation_state(&mut self) {
+        self.opening
+            .retain(|mapped, animation| mapped.alive() && !animation.is_done());
+    }
+
     pub fn element_geometry(&self, elem: &CosmicMapped) -> Option<Rectangle<i32, Logical>> {
         self.space.element_geometry(elem)
     }
@@ -480,6 +515,19 @@ impl FloatingLayout {
         MoveResult::MoveFurther(KeyboardFocusTarget::Element(focused.clone()))
     }
 
+    pub fn lower_element(&mut self, mapped: &CosmicMapped) {
+        // raising every other element in order keeps their stacking intact
+        let others = self
+            .space
+            .elements()
+            .filter(|elem| *elem != mapped)
+            .cloned()
+            .collect::<Vec<_>>();
+        for elem in others {
+            self.space.raise_element(&elem, false);
+        }
+    }
+
     pub fn mapped(&self) -> impl Iterator<Item = &CosmicMapped> {
         self.space.elements().rev()
     }
@@ -583,6 +631,8 @@ impl FloatingLayout {
         focused: Option<&CosmicMapped>,
         mut resize_indicator: Option<(ResizeMode, ResizeIndicator)>,
         indicator_thickness: u8,
+        borders: &BorderConfig,
+        shadow: &ShadowConfig,
         alpha: f32,
     ) -> (
         Vec<CosmicMappedRenderElement<R>>,
@@ -608,22 +658,47 @@ impl FloatingLayout {
             .elements_for_output(output)
             .rev()
             .for_each(|elem| {
-                let render_location = self.space.element_location(elem).unwrap()
-                    - output_geo.loc
-                    - elem.geometry().loc;
-                let (w_elements, p_elements) = elem.split_render_elements(
+                let geometry = Rectangle::from_loc_and_size(
+                    self.space.element_location(elem).unwrap() - output_geo.loc,
+                    elem.geometry().size,
+                );
+                let render_location = geometry.loc - elem.geometry().loc;
+                let opening = self
+                    .opening
+                    .get(elem)
+                    .map(|animation| animation.value(0.0f64, 1.0));
+                let (mut w_elements, p_elements) = elem.split_render_elements(
                     renderer,
                     render_location.to_physical_precise_round(output_scale),
                     output_scale.into(),
-                    alpha,
+                    alpha * opening.unwrap_or(1.0) as f32,
                 );
 
-                if focused == Some(elem) {
-                    let mut indicator_geometry = Rectangle::from_loc_and_size(
-                        self.space.element_location(elem).unwrap() - output_geo.loc,
-                        elem.geometry().size,
-                    );
+                if let Some(progress) = opening {
+                    let origin = (geometry.loc
+                        + Point::from((geometry.size.w / 2, geometry.size.h / 2)))
+                    .to_physical_precise_round(output_scale);
+                    let scale = OPEN_ANIMATION_SCALE + (1.0 - OPEN_ANIMATION_SCALE) * progress;
+                    w_elements = w_elements
+                        .into_iter()
+                        .map(|element| match element {
+                            CosmicMappedRenderElement::Stack(elem) => {
+                                CosmicMappedRenderElement::GrabbedStack(
+                                    RescaleRenderElement::from_element(elem, origin, scale),
+                                )
+                            }
+                            CosmicMappedRenderElement::Window(elem) => {
+                                CosmicMappedRenderElement::GrabbedWindow(
+                                    RescaleRenderElement::from_element(elem, origin, scale),
+                                )
+                            }
+                            x => x,
+                        })
+                        .collect();
+                }
 
+                let mut indicator_geometry = geometry;
+                if focused == Some(elem) {
                     if let Some((mode, resize)) = resize_indicator.as_mut() {
                         indicator_geometry.loc -= (18, 18).into();
                         indicator_geometry.size += (36, 36).into();
@@ -637,28 +712,48 @@ impl FloatingLayout {
                                         .loc
                                         .to_physical_precise_round(output_scale),
                                     output_scale.into(),
-                                    alpha * mode.alpha().unwrap_or(1.0),
+                                    alpha * mode.alpha(&self.animations).unwrap_or(1.0),
                                 )
                                 .into_iter()
                                 .map(CosmicMappedRenderElement::Window),
                         );
                     }
+                }
 
-                    if indicator_thickness > 0 {
-                        let element = IndicatorShader::focus_element(
-                            renderer,
-                            Key::Window(Usage::FocusIndicator, elem.clone()),
-                            indicator_geometry,
-                            indicator_thickness,
-                            output_scale,
-                            alpha,
-                        );
-                        window_elements.push(element.into());
-                    }
+                let (thickness, color) = borders.for_window(
+                    focused == Some(elem),
+                    elem.is_urgent(),
+                    elem.active_window().border_width(),
+                    indicator_thickness,
+                );
+                if thickness > 0 {
+                    let element = IndicatorShader::focus_element(
+                        renderer,
+                        Key::Window(Usage::FocusIndicator, elem.clone()),
+                        indicator_geometry,
+                        thickness,
+                        output_scale,
+                        alpha,
+                        color,
+                    );
+                    window_elements.push(element.into());
                 }
 
                 window_elements.extend(w_elements);
                 popup_elements.extend(p_elements);
+
+                // clients decorating themselves draw their own shadows
+                if shadow.enabled && elem.has_ssd(false) && !elem.is_maximized(false) {
+                    let element = ShadowShader::element(
+                        renderer,
+                        Key::Window(Usage::Shadow, elem.clone()),
+                        geometry,
+                        shadow,
+                        output_scale,
+                        alpha * opening.unwrap_or(1.0) as f32,
+                    );
+                    window_elements.push(element.into());
+                }
             });
 
         (window_elements, popup_elements)
@@ -117,6 +117,7 @@ pub fn should_be_floating(window: &CosmicSurface) -> bool {
         CosmicSurface::X11(surface) => {
             if surface.is_override_redirect()
                 || surface.is_popup()
+                || surface.is_transient_for().is_some()
                 || !matches!(
                     surface.window_type(),
                     None | Some(WmWindowType::Normal) | Some(WmWindowType::Utility)
@@ -132,7 +133,8 @@ pub fn should_be_floating(window: &CosmicSurface) -> bool {
     let max_size = window.max_size();
     let min_size = window.min_size();
 
-    if min_size.is_some() && min_size == max_size {
+    // force-scaled windows fill a tile just fine
+    if min_size.is_some() && min_size == max_size && !window.is_force_scaled() {
         return true;
     }
 
@@ -5,6 +5,7 @@ use crate::{
         element::AsGlowRenderer, BackdropShader, IndicatorShader, Key, Usage, ACTIVE_GROUP_COLOR,
         GROUP_COLOR,
     },
+    config::{AnimationConfig, BorderConfig},
     shell::{
         element::{
             resize_indicator::ResizeIndicator,
@@ -25,7 +26,11 @@ use crate::{
         CosmicSurface, Direction, FocusResult, MoveResult, OutputNotMapped, OverviewMode,
         ResizeDirection, ResizeMode, Trigger,
     },
-    utils::{prelude::*, tween::EaseRectangle},
+    utils::{
+        animation::{self, ease_along, AnimationKind},
+        prelude::*,
+        tween::EaseRectangle,
+    },
     wayland::{
         handlers::xdg_shell::popup::get_popup_toplevel,
         protocols::{toplevel_info::ToplevelInfoState, workspace::WorkspaceHandle},
@@ -33,10 +38,7 @@ use crate::{
 };
 
 use id_tree::{InsertBehavior, MoveBehavior, Node, NodeId, NodeIdError, RemoveBehavior, Tree};
-use keyframe::{
-    ease,
-    functions::{EaseInOutCubic, Linear},
-};
+use keyframe::{ease, functions::Linear};
 use smithay::{
     backend::renderer::{
         element::{
@@ -68,7 +70,6 @@ mod grabs;
 pub use self::blocker::*;
 pub use self::grabs::*;
 
-pub const ANIMATION_DURATION: Duration = Duration::from_millis(200);
 pub const MOUSE_ANIMATION_DELAY: Duration = Duration::from_millis(150);
 pub const INITIAL_MOUSE_ANIMATION_DELAY: Duration = Duration::from_millis(500);
 
@@ -152,6 +153,7 @@ impl TreeQueue {
 #[derive(Debug, Clone)]
 pub struct TilingLayout {
     gaps: (i32, i32),
+    animations: AnimationConfig,
     queues: HashMap<OutputData, TreeQueue>,
     standby_tree: Option<Tree<Data>>,
     pending_blockers: Vec<TilingBlocker>,
@@ -352,9 +354,10 @@ enum FocusedNodeData {
 }
 
 impl TilingLayout {
-    pub fn new(gaps: (u8, u8)) -> TilingLayout {
+    pub fn new(gaps: (u8, u8), animations: AnimationConfig) -> TilingLayout {
         TilingLayout {
             gaps: (gaps.0 as i32, gaps.1 as i32),
+            animations,
             queues: HashMap::new(),
             standby_tree: None,
             pending_blockers: Vec::new(),
@@ -444,7 +447,11 @@ impl TilingLayout {
             TilingLayout::merge_trees(src, &mut dst, orientation);
 
             let blocker = TilingLayout::update_positions(output, &mut dst, self.gaps);
-            dst_queue.push_tree(dst, ANIMATION_DURATION, blocker);
+            dst_queue.push_tree(
+                dst,
+                AnimationKind::Tiling.duration(&self.animations),
+                blocker,
+            );
         }
     }
 
@@ -474,7 +481,11 @@ impl TilingLayout {
         TilingLayout::map_to_tree(&mut tree, window, output, focus_stack, direction);
 
         let blocker = TilingLayout::update_positions(output, &mut tree, self.gaps);
-        queue.push_tree(tree, ANIMATION_DURATION, blocker);
+        queue.push_tree(
+            tree,
+            AnimationKind::Tiling.duration(&self.animations),
+            blocker,
+        );
     }
 
     fn map_to_tree<'a>(
@@ -580,7 +591,11 @@ impl TilingLayout {
         new.output_enter(&output_data.output, new.bbox());
 
         let blocker = TilingLayout::update_positions(&output_data.output, &mut tree, self.gaps);
-        queue.push_tree(tree, ANIMATION_DURATION, blocker);
+        queue.push_tree(
+            tree,
+            AnimationKind::Tiling.duration(&self.animations),
+            blocker,
+        );
     }
 
     pub fn move_tree<'a>(
@@ -745,12 +760,20 @@ impl TilingLayout {
                 let this_queue = this.queues.get_mut(&this_output).unwrap();
                 let blocker =
                     TilingLayout::update_positions(&this_output, &mut this_tree, this.gaps);
-                this_queue.push_tree(this_tree, ANIMATION_DURATION, blocker);
+                this_queue.push_tree(
+                    this_tree,
+                    AnimationKind::Tiling.duration(&this.animations),
+                    blocker,
+                );
 
                 let other_queue = other.queues.get_mut(other_output).unwrap();
                 let blocker =
                     TilingLayout::update_positions(&other_output, &mut other_tree, other.gaps);
-                other_queue.push_tree(other_tree, ANIMATION_DURATION, blocker);
+                other_queue.push_tree(
+                    other_tree,
+                    AnimationKind::Tiling.duration(&other.animations),
+                    blocker,
+                );
 
                 other.node_desc_to_focus(&NodeDesc {
                     handle: other_handle.clone(),
@@ -1212,7 +1235,11 @@ impl TilingLayout {
 
         let this_queue = this.queues.get_mut(&this_output).unwrap();
         let blocker = TilingLayout::update_positions(&this_output, &mut this_tree, this.gaps);
-        this_queue.push_tree(this_tree, ANIMATION_DURATION, blocker);
+        this_queue.push_tree(
+            this_tree,
+            AnimationKind::Tiling.duration(&this.animations),
+            blocker,
+        );
 
         let has_other_tree = other_tree.is_some();
         if let Some(mut other_tree) = other_tree {
@@ -1222,7 +1249,11 @@ impl TilingLayout {
                 (this.queues.get_mut(&other_output).unwrap(), this.gaps)
             };
             let blocker = TilingLayout::update_positions(&other_output, &mut other_tree, gaps);
-            other_queue.push_tree(other_tree, ANIMATION_DURATION, blocker);
+            other_queue.push_tree(
+                other_tree,
+                AnimationKind::Tiling.duration(&this.animations),
+                blocker,
+            );
         }
 
         match (&this_desc.stack_window, &other_desc.stack_window) {
@@ -1341,7 +1372,11 @@ impl TilingLayout {
                 TilingLayout::unmap_internal(&mut tree, &node_id);
 
                 let blocker = TilingLayout::update_positions(&output.output, &mut tree, self.gaps);
-                queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                queue.push_tree(
+                    tree,
+                    AnimationKind::Tiling.duration(&self.animations),
+                    blocker,
+                );
             }
         }
     }
@@ -1464,7 +1499,11 @@ impl TilingLayout {
                     *mapped.tiling_node_id.lock().unwrap() = Some(new_id);
 
                     let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-                    queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                    queue.push_tree(
+                        tree,
+                        AnimationKind::Tiling.duration(&self.animations),
+                        blocker,
+                    );
                     return MoveResult::ShiftFocus(mapped.into());
                 }
                 StackMoveResult::Default => {} // continue normally
@@ -1541,7 +1580,11 @@ impl TilingLayout {
                     .remove_window(og_idx);
 
                 let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-                queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                queue.push_tree(
+                    tree,
+                    AnimationKind::Tiling.duration(&self.animations),
+                    blocker,
+                );
                 return MoveResult::Done;
             }
 
@@ -1567,7 +1610,11 @@ impl TilingLayout {
                     .remove_window(og_idx);
 
                 let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-                queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                queue.push_tree(
+                    tree,
+                    AnimationKind::Tiling.duration(&self.animations),
+                    blocker,
+                );
                 return MoveResult::Done;
             }
 
@@ -1723,7 +1770,11 @@ impl TilingLayout {
                 };
 
                 let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-                queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                queue.push_tree(
+                    tree,
+                    AnimationKind::Tiling.duration(&self.animations),
+                    blocker,
+                );
                 return result;
             }
 
@@ -2066,7 +2117,11 @@ impl TilingLayout {
                     *orientation = new_orientation;
 
                     let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-                    queue.push_tree(tree, ANIMATION_DURATION, blocker);
+                    queue.push_tree(
+                        tree,
+                        AnimationKind::Tiling.duration(&self.animations),
+                        blocker,
+                    );
                 }
             }
         }
@@ -2091,7 +2146,10 @@ impl TilingLayout {
                         // if it is just a window
                         match tree.get_mut(&last_active).unwrap().data_mut() {
                             Data::Mapped { mapped, .. } => {
-                                mapped.convert_to_stack(std::iter::once((&output, mapped.bbox())));
+                                mapped.convert_to_stack(
+                                    std::iter::once((&output, mapped.bbox())),
+                                    self.animations,
+                                );
                                 focus_stack.append(&mapped);
                             }
                             _ => unreachable!(),
@@ -2156,7 +2214,7 @@ impl TilingLayout {
                         return;
                     }
                     let handle = handle.unwrap();
-                    let stack = CosmicStack::new(surfaces.into_iter(), handle);
+                    let stack = CosmicStack::new(surfaces.into_iter(), handle, self.animations);
 
                     for child in tree
                         .children_ids(&last_active)
@@ -2189,7 +2247,28 @@ impl TilingLayout {
             }
 
             let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-            queue.push_tree(tree, ANIMATION_DURATION, blocker);
+            queue.push_tree(
+                tree,
+                AnimationKind::Tiling.duration(&self.animations),
+                blocker,
+            );
+        }
+    }
+
+    pub fn set_animations(&mut self, animations: AnimationConfig) {
+        self.animations = animations;
+    }
+
+    /// Changes the gaps, resizing the windows of all outputs to match
+    pub fn set_gaps(&mut self, gaps: (u8, u8)) {
+        self.gaps = (gaps.0 as i32, gaps.1 as i32);
+        let outputs = self
+            .queues
+            .keys()
+            .map(|data| data.output.clone())
+            .collect::<Vec<_>>();
+        for output in outputs {
+            self.recalculate(&output);
         }
     }
 
@@ -2199,7 +2278,11 @@ impl TilingLayout {
         };
         let mut tree = queue.trees.back().unwrap().0.copy_clone();
         let blocker = TilingLayout::update_positions(&output, &mut tree, self.gaps);
-        queue.push_tree(tree, ANIMATION_DURATION, blocker);
+        queue.push_tree(
+            tree,
+            AnimationKind::Tiling.duration(&self.animations),
+            blocker,
+        );
     }
 
     pub fn refresh(&mut self) {
@@ -2469,7 +2552,11 @@ impl TilingLayout {
 
             if let Some(mut tree) = owned_tree {
                 let blocker = TilingLayout::update_positions(output, &mut tree, self.gaps);
-                queue.unwrap().push_tree(tree, ANIMATION_DURATION, blocker);
+                queue.unwrap().push_tree(
+                    tree,
+                    AnimationKind::Tiling.duration(&self.animations),
+                    blocker,
+                );
             }
         }
     }
@@ -2589,7 +2676,10 @@ impl TilingLayout {
             Some(TargetZone::WindowStack(window_id, _)) if tree.get(&window_id).is_ok() => {
                 match tree.get_mut(window_id).unwrap().data_mut() {
                     Data::Mapped { mapped, .. } => {
-                        mapped.convert_to_stack(std::iter::once((output, mapped.bbox())));
+                        mapped.convert_to_stack(
+                            std::iter::once((output, mapped.bbox())),
+                            self.animations,
+                        );
                         let Some(stack) = mapped.stack_ref_mut() else {
                             unreachable!()
                         };
@@ -2632,7 +2722,11 @@ impl TilingLayout {
 
         if let Some(mut tree) = owned_tree {
             let blocker = TilingLayout::update_positions(output, &mut tree, self.gaps);
-            queue.unwrap().push_tree(tree, ANIMATION_DURATION, blocker);
+            queue.unwrap().push_tree(
+                tree,
+                AnimationKind::Tiling.duration(&self.animations),
+                blocker,
+            );
         }
 
         let location = output.geometry().loc + self.element_geometry(&mapped).unwrap().loc;
@@ -3082,7 +3176,7 @@ impl TilingLayout {
                     non_exclusive_zone,
                     None,
                     1.0,
-                    overview.alpha().unwrap(),
+                    overview.alpha(&self.animations).unwrap(),
                     1.0,
                     placeholder_id,
                     Some(None),
@@ -3387,9 +3481,9 @@ impl TilingLayout {
                                     let duration = if target_zone.is_window_zone()
                                         && !old_target_zone.is_window_zone()
                                     {
-                                        ANIMATION_DURATION * 2
+                                        AnimationKind::Tiling.duration(&self.animations) * 2
                                     } else {
-                                        ANIMATION_DURATION
+                                        AnimationKind::Tiling.duration(&self.animations)
                                     };
 
                                     let mut tree = tree.copy_clone();
@@ -3635,7 +3729,11 @@ impl TilingLayout {
             TilingLayout::merge_trees(src, &mut dst, orientation);
 
             let blocker = TilingLayout::update_positions(&output_data.output, &mut dst, self.gaps);
-            dst_queue.push_tree(dst, ANIMATION_DURATION, blocker);
+            dst_queue.push_tree(
+                dst,
+                AnimationKind::Tiling.duration(&self.animations),
+                blocker,
+            );
         }
     }
 
@@ -3687,6 +3785,7 @@ impl TilingLayout {
         overview: (OverviewMode, Option<(SwapIndicator, Option<&Tree<Data>>)>),
         resize_indicator: Option<(ResizeMode, ResizeIndicator)>,
         indicator_thickness: u8,
+        borders: &BorderConfig,
     ) -> Result<
         (
             Vec<CosmicMappedRenderElement<R>>,
@@ -3725,13 +3824,17 @@ impl TilingLayout {
             .then(|| &queue.trees.front().unwrap().0);
 
         let percentage = if let Some(animation_start) = queue.animation_start {
-            let percentage = Instant::now().duration_since(animation_start).as_millis() as f32
-                / duration.as_millis() as f32;
-            ease(EaseInOutCubic, 0.0, 1.0, percentage)
+            let percentage = animation::progress(animation_start, *duration);
+            ease_along(
+                AnimationKind::Tiling.curve(&self.animations),
+                0.0,
+                1.0,
+                percentage,
+            )
         } else {
             1.0
         };
-        let draw_groups = overview.0.alpha();
+        let draw_groups = overview.0.alpha(&self.animations);
 
         let mut window_elements = Vec::new();
         let mut popup_elements = Vec::new();
@@ -3827,11 +3930,13 @@ impl TilingLayout {
             } else {
                 indicator_thickness
             },
+            borders,
             overview,
             resize_indicator,
             swap_desc.clone(),
             &self.swapping_stack_surface_id,
             &self.placeholder_id,
+            &self.animations,
         );
         window_elements.extend(w_elements);
         popup_elements.extend(p_elements);
@@ -4664,11 +4769,13 @@ fn render_new_tree<R>(
     percentage: f32,
     transition: Option<f32>,
     indicator_thickness: u8,
+    borders: &BorderConfig,
     overview: (OverviewMode, Option<(SwapIndicator, Option<&Tree<Data>>)>),
     mut resize_indicator: Option<(ResizeMode, ResizeIndicator)>,
     swap_desc: Option<NodeDesc>,
     swapping_stack_surface_id: &Id,
     placeholder_id: &Id,
+    animations: &AnimationConfig,
 ) -> (
     Vec<CosmicMappedRenderElement<R>>,
     Vec<CosmicMappedRenderElement<R>>,
@@ -4776,6 +4883,7 @@ where
             4,
             output_scale,
             transition.unwrap_or(1.0),
+            borders.focused,
         ));
 
         let render_loc = (swap_geo.loc - window_geo.loc).to_physical_precise_round(output_scale);
@@ -4960,6 +5068,7 @@ where
                             },
                             output_scale,
                             1.0,
+                            borders.focused,
                         ));
                     }
 
@@ -4992,7 +5101,7 @@ where
                                     renderer,
                                     geo.loc.to_physical_precise_round(output_scale),
                                     output_scale.into(),
-                                    alpha * overview.0.alpha().unwrap_or(1.0),
+                                    alpha * overview.0.alpha(animations).unwrap_or(1.0),
                                 )
                                 .into_iter()
                                 .map(CosmicMappedRenderElement::from),
@@ -5028,7 +5137,7 @@ where
                                     renderer,
                                     geo.loc.to_physical_precise_round(output_scale),
                                     output_scale.into(),
-                                    alpha * mode.alpha().unwrap_or(1.0),
+                                    alpha * mode.alpha(animations).unwrap_or(1.0),
                                 )
                                 .into_iter()
                                 .map(CosmicMappedRenderElement::from)
@@ -5036,6 +5145,24 @@ where
                         );
                     }
                 }
+            } else if let Data::Mapped { mapped, .. } = data {
+                let (thickness, color) = borders.for_window(
+                    false,
+                    mapped.is_urgent(),
+                    mapped.active_window().border_width(),
+                    indicator_thickness,
+                );
+                if thickness > 0 {
+                    indicators.push(IndicatorShader::focus_element(
+                        renderer,
+                        Key::Window(Usage::FocusIndicator, mapped.clone()),
+                        geo,
+                        thickness,
+                        output_scale,
+                        1.0,
+                        color,
+                    ));
+                }
             }
 
             if let Data::Mapped { mapped, .. } = data {
@@ -10,7 +10,6 @@ use tracing::warn;
 use wayland_backend::server::ClientId;
 
 use cosmic_protocols::workspace::v1::server::zcosmic_workspace_handle_v1::State as WState;
-use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
     desktop::{
         layer_map_for_output, space::SpaceElement, LayerSurface, PopupManager, WindowSurfaceType,
@@ -27,7 +26,8 @@ use smithay::{
         seat::WaylandFocus,
         shell::{
             wlr_layer::{
-                KeyboardInteractivity, Layer, LayerSurfaceCachedState, WlrLayerShellState,
+                ExclusiveZone, KeyboardInteractivity, Layer, LayerSurfaceCachedState,
+                WlrLayerShellState,
             },
             xdg::XdgShellState,
         },
@@ -36,10 +36,18 @@ use smithay::{
 };
 
 use crate::{
-    config::{Config, KeyModifiers, KeyPattern, OutputConfig, WorkspaceMode as ConfigMode},
-    state::client_has_security_context,
-    utils::prelude::*,
+    config::{
+        AnimationConfig, AnimationCurve, Config, KeyModifiers, KeyPattern, OutputConfig,
+        TitlebarAction, WorkspaceMode as ConfigMode, X11Scale,
+    },
+    state::privileged_global_filter,
+    utils::{
+        animation::{self, ease_along, Animation, AnimationKind},
+        prelude::*,
+    },
     wayland::protocols::{
+        foreign_toplevel::ForeignToplevelState,
+        overlap_notify::{layer_identifier, LayerOverlap, OverlapNotifyState},
         toplevel_info::ToplevelInfoState,
         toplevel_management::{ManagementCapabilities, ToplevelManagementState},
         workspace::{
@@ -54,24 +62,26 @@ pub mod focus;
 pub mod grabs;
 pub mod layout;
 mod workspace;
+pub mod zoom;
 pub use self::element::{CosmicMapped, CosmicMappedRenderElement, CosmicSurface};
 pub use self::workspace::*;
 use self::{
     element::{
+        error_indicator::{error_indicator, ErrorIndicator},
+        identify_indicator::{identify_indicator, IdentifyIndicator},
         resize_indicator::{resize_indicator, ResizeIndicator},
         swap_indicator::{swap_indicator, SwapIndicator},
+        window_menu::window_menu,
         CosmicWindow,
     },
     focus::target::KeyboardFocusTarget,
-    grabs::ResizeEdge,
+    grabs::{MenuGrab, ResizeEdge},
     layout::{
         floating::{FloatingLayout, ResizeState},
         tiling::{NodeDesc, TilingLayout},
     },
 };
 
-const ANIMATION_DURATION: Duration = Duration::from_millis(200);
-
 #[derive(Debug, Clone)]
 pub enum Trigger {
     KeyboardSwap(KeyPattern, NodeDesc),
@@ -87,18 +97,15 @@ pub enum OverviewMode {
 }
 
 impl OverviewMode {
-    pub fn alpha(&self) -> Option<f32> {
+    pub fn alpha(&self, config: &AnimationConfig) -> Option<f32> {
         match self {
-            OverviewMode::Started(_, start) => {
-                let percentage = Instant::now().duration_since(*start).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
-                Some(ease(EaseInOutCubic, 0.0, 1.0, percentage))
-            }
+            OverviewMode::Started(_, start) => Some(
+                Animation::starting_at(AnimationKind::Overview, *start, config).value(0.0, 1.0),
+            ),
             OverviewMode::Ended(_, end) => {
-                let percentage = Instant::now().duration_since(*end).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
-                if percentage < 1.0 {
-                    Some(ease(EaseInOutCubic, 1.0, 0.0, percentage))
+                let animation = Animation::starting_at(AnimationKind::Overview, *end, config);
+                if !animation.is_done() {
+                    Some(animation.value(1.0, 0.0))
                 } else {
                     None
                 }
@@ -108,6 +115,93 @@ impl OverviewMode {
     }
 }
 
+/// Progress of the transition from the previously active workspace to the active one
+#[derive(Debug, Clone, Copy)]
+pub enum WorkspaceDelta {
+    /// Following an ongoing gesture, that moved the given fraction of the way
+    Gesture(f64),
+    /// Animating towards the active workspace, starting at the given fraction
+    Animating {
+        start: Instant,
+        from: f64,
+        duration: Duration,
+        curve: AnimationCurve,
+    },
+}
+
+impl WorkspaceDelta {
+    pub fn animate(config: &AnimationConfig) -> WorkspaceDelta {
+        WorkspaceDelta::Animating {
+            start: Instant::now(),
+            from: 0.0,
+            duration: AnimationKind::WorkspaceSwitch.duration(config),
+            curve: AnimationKind::WorkspaceSwitch.curve(config),
+        }
+    }
+
+    /// Continues a gesture, that ended at `from`, with the speed it ended with
+    /// (in fractions per millisecond)
+    pub fn settle(from: f64, velocity: f64, config: &AnimationConfig) -> WorkspaceDelta {
+        let remaining = (1.0 - from).clamp(0.0, 1.0);
+        let mut duration = AnimationKind::WorkspaceSwitch
+            .duration(config)
+            .mul_f64(remaining);
+        if velocity > 0.0 {
+            duration = duration.min(Duration::from_secs_f64(remaining / velocity / 1000.0));
+        }
+        WorkspaceDelta::Animating {
+            start: Instant::now(),
+            from,
+            duration,
+            // the motion is already underway, so don't accelerate again
+            curve: AnimationCurve::EaseOutCubic,
+        }
+    }
+
+    /// Fraction of the way to the active workspace
+    pub fn percentage(&self) -> f32 {
+        match self {
+            WorkspaceDelta::Gesture(progress) => *progress as f32,
+            WorkspaceDelta::Animating {
+                start,
+                from,
+                duration,
+                curve,
+            } => {
+                let eased = ease_along(*curve, 0.0, 1.0, animation::progress(*start, *duration));
+                *from as f32 + (1.0 - *from as f32) * eased
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        match self {
+            WorkspaceDelta::Gesture(_) => false,
+            WorkspaceDelta::Animating {
+                start, duration, ..
+            } => Instant::now().duration_since(*start) >= *duration,
+        }
+    }
+}
+
+/// Furthest the active workspace can be dragged past the first or last one,
+/// relative to the output size
+const RUBBER_BAND_LIMIT: f64 = 0.15;
+
+/// How long the labels identifying the outputs are shown
+pub const IDENTIFY_DURATION: Duration = Duration::from_secs(3);
+
+/// How long the visual bell flashes
+pub const BELL_DURATION: Duration = Duration::from_millis(150);
+
+/// How long errors, e.g. of the config, are shown on the outputs
+pub const ERROR_DURATION: Duration = Duration::from_secs(8);
+
+/// Dampens dragging past the first or last workspace, so it bounces back.
+fn rubber_band(overshoot: f64) -> f64 {
+    overshoot.signum() * RUBBER_BAND_LIMIT * (1.0 - (-overshoot.abs() / RUBBER_BAND_LIMIT).exp())
+}
+
 #[derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, Eq, Hash)]
 pub enum ResizeDirection {
     Inwards,
@@ -122,18 +216,15 @@ pub enum ResizeMode {
 }
 
 impl ResizeMode {
-    pub fn alpha(&self) -> Option<f32> {
+    pub fn alpha(&self, config: &AnimationConfig) -> Option<f32> {
         match self {
-            ResizeMode::Started(_, start, _) => {
-                let percentage = Instant::now().duration_since(*start).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
-                Some(ease(EaseInOutCubic, 0.0, 1.0, percentage))
-            }
+            ResizeMode::Started(_, start, _) => Some(
+                Animation::starting_at(AnimationKind::Overview, *start, config).value(0.0, 1.0),
+            ),
             ResizeMode::Ended(end, _) => {
-                let percentage = Instant::now().duration_since(*end).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
-                if percentage < 1.0 {
-                    Some(ease(EaseInOutCubic, 1.0, 0.0, percentage))
+                let animation = Animation::starting_at(AnimationKind::Overview, *end, config);
+                if !animation.is_done() {
+                    Some(animation.value(1.0, 0.0))
                 } else {
                     None
                 }
@@ -152,15 +243,19 @@ pub struct Shell {
     pub pending_windows: Vec<(CosmicSurface, Seat<State>)>,
     pub pending_layers: Vec<(LayerSurface, Output, Seat<State>)>,
     pub override_redirect_windows: Vec<X11Surface>,
+    pub tablet_mode: bool,
 
     // wayland_state
     pub layer_shell_state: WlrLayerShellState,
     pub toplevel_info_state: ToplevelInfoState<State, CosmicSurface>,
     pub toplevel_management_state: ToplevelManagementState,
+    pub foreign_toplevel_state: ForeignToplevelState<State, CosmicSurface>,
+    pub overlap_notify_state: OverlapNotifyState,
     pub xdg_shell_state: XdgShellState,
     pub workspace_state: WorkspaceState<State>,
 
     gaps: (u8, u8),
+    animations: AnimationConfig,
     overview_mode: OverviewMode,
     swap_indicator: Option<SwapIndicator>,
     resize_mode: ResizeMode,
@@ -173,21 +268,30 @@ pub struct Shell {
         Output,
     )>,
     resize_indicator: Option<ResizeIndicator>,
+    /// Labels identifying the outputs, since when they are shown
+    identify: Option<(Instant, Vec<(Output, IdentifyIndicator)>)>,
+    /// Area of an output flashed by the visual bell, since when it is shown
+    visual_bell: Option<(Instant, Output, Rectangle<i32, Logical>)>,
+    /// Error shown on every output, since when it is shown
+    error: Option<(Instant, Vec<(Output, ErrorIndicator)>)>,
 }
 
 #[derive(Debug)]
 pub struct WorkspaceSet {
-    previously_active: Option<(usize, Instant)>,
+    previously_active: Option<(usize, WorkspaceDelta)>,
+    /// Displacement of the active workspace, when a gesture moves past the first or last one
+    overscroll: Option<(f64, WorkspaceDelta)>,
     active: usize,
     amount: WorkspaceAmount,
     group: WorkspaceGroupHandle,
     idx: usize,
     tiling_enabled: bool,
     gaps: (u8, u8),
+    animations: AnimationConfig,
     pub(crate) workspaces: Vec<Workspace>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WorkspaceAmount {
     Dynamic,
     Static(u8),
@@ -199,6 +303,7 @@ fn create_workspace(
     active: bool,
     tiling: bool,
     gaps: (u8, u8),
+    animations: AnimationConfig,
 ) -> Workspace {
     let workspace_handle = state.create_workspace(&group_handle).unwrap();
     if active {
@@ -208,7 +313,7 @@ fn create_workspace(
         &workspace_handle,
         [WorkspaceCapabilities::Activate].into_iter(),
     );
-    Workspace::new(workspace_handle, tiling, gaps)
+    Workspace::new(workspace_handle, tiling, gaps, animations)
 }
 
 impl WorkspaceSet {
@@ -218,12 +323,14 @@ impl WorkspaceSet {
         idx: usize,
         tiling_enabled: bool,
         gaps: (u8, u8),
+        animations: AnimationConfig,
     ) -> WorkspaceSet {
         let group_handle = state.create_workspace_group();
 
         let workspaces = match amount {
             WorkspaceAmount::Dynamic => {
-                let workspace = create_workspace(state, &group_handle, true, tiling_enabled, gaps);
+                let workspace =
+                    create_workspace(state, &group_handle, true, tiling_enabled, gaps, animations);
                 workspace_set_idx(state, 1, idx, &workspace.handle);
                 state.set_workspace_capabilities(
                     &workspace.handle,
@@ -233,8 +340,14 @@ impl WorkspaceSet {
             }
             WorkspaceAmount::Static(len) => (0..len)
                 .map(|i| {
-                    let workspace =
-                        create_workspace(state, &group_handle, i == 0, tiling_enabled, gaps);
+                    let workspace = create_workspace(
+                        state,
+                        &group_handle,
+                        i == 0,
+                        tiling_enabled,
+                        gaps,
+                        animations,
+                    );
                     workspace_set_idx(state, i + 1, idx, &workspace.handle);
                     state.set_workspace_capabilities(
                         &workspace.handle,
@@ -247,12 +360,14 @@ impl WorkspaceSet {
 
         WorkspaceSet {
             previously_active: None,
+            overscroll: None,
             active: 0,
             amount,
             group: group_handle,
             idx,
             tiling_enabled,
             gaps,
+            animations,
             workspaces,
         }
     }
@@ -268,24 +383,96 @@ impl WorkspaceSet {
 
         if self.active != idx {
             let old_active = self.active;
-            state.remove_workspace_state(&self.workspaces[old_active].handle, WState::Active);
-            state.add_workspace_state(&self.workspaces[idx].handle, WState::Active);
-            self.previously_active = Some((old_active, Instant::now()));
-            self.active = idx;
+            self.transition(
+                old_active,
+                idx,
+                Some(WorkspaceDelta::animate(&self.animations)),
+                state,
+            );
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Makes `to` the active workspace, transitioning from `from` with the given progress
+    fn transition(
+        &mut self,
+        from: usize,
+        to: usize,
+        delta: Option<WorkspaceDelta>,
+        state: &mut WorkspaceUpdateGuard<'_, State>,
+    ) {
+        if self.active != to {
+            state.remove_workspace_state(&self.workspaces[self.active].handle, WState::Active);
+            state.add_workspace_state(&self.workspaces[to].handle, WState::Active);
+            state.remove_workspace_state(&self.workspaces[to].handle, WState::Urgent);
+            self.active = to;
+        }
+        self.previously_active = delta.filter(|_| from != to).map(|delta| (from, delta));
+    }
+
+    /// Workspace next to `origin` in the direction of the gesture progress, if any
+    fn gesture_target(&self, origin: usize, progress: f64) -> Option<usize> {
+        if progress >= 0.0 {
+            Some(origin + 1).filter(|idx| *idx < self.workspaces.len())
+        } else {
+            origin.checked_sub(1)
+        }
+    }
+
+    /// Only renders the gesture, clients keep seeing `origin` as active until it ends
+    fn update_gesture(&mut self, origin: usize, progress: f64) {
+        if let Some(target) = self.gesture_target(origin, progress) {
+            let delta = WorkspaceDelta::Gesture(progress.abs().min(1.0));
+            self.active = target;
+            self.previously_active = Some((origin, delta));
+            self.overscroll = None;
+        } else {
+            self.active = origin;
+            self.previously_active = None;
+            self.overscroll = Some((rubber_band(progress), WorkspaceDelta::Gesture(0.0)));
+        }
+    }
+
+    fn end_gesture(
+        &mut self,
+        origin: usize,
+        progress: f64,
+        velocity: f64,
+        commit: bool,
+        state: &mut WorkspaceUpdateGuard<'_, State>,
+    ) {
+        // `origin` is still the active workspace as far as clients know
+        self.active = origin;
+        if let Some(target) = self.gesture_target(origin, progress) {
+            let (progress, velocity) = (progress.abs().min(1.0), velocity * progress.signum());
+            if commit {
+                let delta = WorkspaceDelta::settle(progress, velocity, &self.animations);
+                self.transition(origin, target, Some(delta), state);
+            } else {
+                let delta = WorkspaceDelta::settle(1.0 - progress, -velocity, &self.animations);
+                self.transition(target, origin, Some(delta), state);
+            }
+        } else if let Some((overscroll, _)) = self.overscroll {
+            self.overscroll = Some((
+                overscroll,
+                WorkspaceDelta::settle(0.0, 0.0, &self.animations),
+            ));
+        }
+    }
+
     fn refresh<'a>(
         &mut self,
         state: &mut WorkspaceState<State>,
         toplevel_info: &mut ToplevelInfoState<State, CosmicSurface>,
         outputs: impl Iterator<Item = (&'a Output, Point<i32, Logical>)>,
     ) {
-        if let Some((_, start)) = self.previously_active {
-            if Instant::now().duration_since(start).as_millis() >= ANIMATION_DURATION.as_millis() {
+        if self.overscroll.map_or(false, |(_, delta)| delta.is_done()) {
+            self.overscroll = None;
+        }
+        if let Some((_, delta)) = self.previously_active {
+            if delta.is_done() {
                 self.previously_active = None;
             }
         } else {
@@ -308,13 +495,14 @@ impl WorkspaceSet {
         let mut state = state.update();
 
         // add empty at the end, if necessary
-        if self.workspaces.last().unwrap().windows().next().is_some() {
+        if !self.workspaces.last().unwrap().is_empty() {
             let mut workspace = create_workspace(
                 &mut state,
                 &self.group,
                 false,
                 self.tiling_enabled,
                 self.gaps,
+                self.animations,
             );
             workspace_set_idx(
                 &mut state,
@@ -336,9 +524,7 @@ impl WorkspaceSet {
         let mut keep = vec![true; len];
         // remove empty workspaces in between, if they are not active
         for (i, workspace) in self.workspaces.iter().enumerate() {
-            let has_windows = workspace.windows().next().is_some();
-
-            if !has_windows && i != self.active && i != len - 1 {
+            if workspace.is_empty() && i != self.active && i != len - 1 {
                 state.remove_workspace(workspace.handle);
                 keep[i] = false;
             }
@@ -377,7 +563,7 @@ impl WorkspaceSet {
             let last_space = self.workspaces.last_mut().unwrap();
 
             for workspace in overflow {
-                for element in workspace.mapped() {
+                for element in workspace.mapped().chain(workspace.minimized()) {
                     // fixup toplevel state
                     for (toplevel, _) in element.windows() {
                         toplevel_info.toplevel_leave_workspace(&toplevel, &workspace.handle);
@@ -389,6 +575,9 @@ impl WorkspaceSet {
                 last_space
                     .fullscreen
                     .extend(workspace.fullscreen.into_iter());
+                last_space
+                    .minimized_windows
+                    .extend(workspace.minimized_windows.into_iter());
                 state.remove_workspace(workspace.handle);
             }
 
@@ -404,6 +593,7 @@ impl WorkspaceSet {
                     false,
                     self.tiling_enabled,
                     self.gaps,
+                    self.animations,
                 );
                 workspace_set_idx(
                     &mut state,
@@ -453,11 +643,17 @@ impl WorkspaceMode {
         state: &mut WorkspaceUpdateGuard<'_, State>,
         tiling_enabled: bool,
         gaps: (u8, u8),
+        animations: AnimationConfig,
     ) -> WorkspaceMode {
         match config {
-            crate::config::WorkspaceMode::Global => {
-                WorkspaceMode::Global(WorkspaceSet::new(state, amount, 0, tiling_enabled, gaps))
-            }
+            crate::config::WorkspaceMode::Global => WorkspaceMode::Global(WorkspaceSet::new(
+                state,
+                amount,
+                0,
+                tiling_enabled,
+                gaps,
+                animations,
+            )),
             crate::config::WorkspaceMode::OutputBound => {
                 WorkspaceMode::OutputBound(HashMap::new(), amount)
             }
@@ -482,24 +678,36 @@ impl WorkspaceMode {
         }
     }
 
-    pub fn active(&self, output: &Output) -> (Option<(&Workspace, Instant)>, &Workspace) {
+    pub fn active(&self, output: &Output) -> (Option<(&Workspace, WorkspaceDelta)>, &Workspace) {
         match self {
             WorkspaceMode::Global(set) => (
                 set.previously_active
-                    .map(|(idx, start)| (&set.workspaces[idx], start)),
+                    .map(|(idx, delta)| (&set.workspaces[idx], delta)),
                 &set.workspaces[set.active],
             ),
             WorkspaceMode::OutputBound(sets, _) => {
                 let set = sets.get(output).unwrap();
                 (
                     set.previously_active
-                        .map(|(idx, start)| (&set.workspaces[idx], start)),
+                        .map(|(idx, delta)| (&set.workspaces[idx], delta)),
                     &set.workspaces[set.active],
                 )
             }
         }
     }
 
+    /// Displacement of the active workspace past the first or last one,
+    /// relative to the output size
+    pub fn overscroll(&self, output: &Output) -> f64 {
+        let set = match self {
+            WorkspaceMode::Global(set) => set,
+            WorkspaceMode::OutputBound(sets, _) => sets.get(output).unwrap(),
+        };
+        set.overscroll.map_or(0.0, |(overscroll, delta)| {
+            overscroll * (1.0 - delta.percentage() as f64)
+        })
+    }
+
     pub fn active_mut(&mut self, output: &Output) -> &mut Workspace {
         match self {
             WorkspaceMode::Global(set) => &mut set.workspaces[set.active],
@@ -586,22 +794,31 @@ impl Shell {
         let xdg_shell_state = XdgShellState::new::<State>(dh);
         let toplevel_info_state = ToplevelInfoState::new(
             dh,
-            //|client| client.get_data::<ClientState>().map_or(false, |s| s.privileged),
-            client_has_security_context,
+            privileged_global_filter(config, "zcosmic_toplevel_info_v1"),
         );
         let toplevel_management_state = ToplevelManagementState::new::<State, _>(
             dh,
             vec![
                 ManagementCapabilities::Close,
                 ManagementCapabilities::Activate,
+                ManagementCapabilities::Maximize,
+                ManagementCapabilities::Minimize,
+                ManagementCapabilities::Fullscreen,
+                ManagementCapabilities::MoveToWorkspace,
             ],
-            //|client| client.get_data::<ClientState>().map_or(false, |s| s.privileged),
-            client_has_security_context,
+            privileged_global_filter(config, "zcosmic_toplevel_manager_v1"),
+        );
+        let foreign_toplevel_state = ForeignToplevelState::new(
+            dh,
+            privileged_global_filter(config, "zwlr_foreign_toplevel_manager_v1"),
+        );
+        let overlap_notify_state = OverlapNotifyState::new::<State, _>(
+            dh,
+            privileged_global_filter(config, "zcosmic_overlap_notify_v1"),
         );
         let mut workspace_state = WorkspaceState::new(
             dh,
-            //|client| client.get_data::<ClientState>().map_or(false, |s| s.privileged),
-            client_has_security_context,
+            privileged_global_filter(config, "zcosmic_workspace_manager_v1"),
         );
 
         let tiling_enabled = config.static_conf.tiling_enabled;
@@ -611,6 +828,7 @@ impl Shell {
             &mut workspace_state.update(),
             tiling_enabled,
             config.static_conf.gaps,
+            config.static_conf.animations,
         );
 
         Shell {
@@ -626,15 +844,22 @@ impl Shell {
             layer_shell_state,
             toplevel_info_state,
             toplevel_management_state,
+            foreign_toplevel_state,
+            overlap_notify_state,
             xdg_shell_state,
             workspace_state,
 
             gaps: config.static_conf.gaps,
+            animations: config.static_conf.animations,
             overview_mode: OverviewMode::None,
             swap_indicator: None,
             resize_mode: ResizeMode::None,
             resize_state: None,
             resize_indicator: None,
+            identify: None,
+            visual_bell: None,
+            error: None,
+            tablet_mode: false,
         }
     }
 
@@ -656,6 +881,7 @@ impl Shell {
                         sets.len(),
                         self.tiling_enabled,
                         self.gaps,
+                        self.animations,
                     );
                     state.add_group_output(&set.group, &output);
                     sets.insert(output.clone(), set);
@@ -781,6 +1007,15 @@ impl Shell {
         }
     }
 
+    /// Lays out layer surfaces and windows again, after the mode of an output changed
+    pub fn output_resized(&mut self, output: &Output) {
+        layer_map_for_output(output).arrange();
+        self.refresh_outputs();
+        for workspace in self.workspaces.spaces_mut() {
+            workspace.recalculate(output);
+        }
+    }
+
     pub fn set_mode(&mut self, mode: ConfigMode) {
         let mut state = self.workspace_state.update();
 
@@ -801,6 +1036,7 @@ impl Shell {
                     0,
                     self.tiling_enabled,
                     self.gaps,
+                    self.animations,
                 );
                 for output in &self.outputs {
                     state.add_group_output(&new_set.group, output);
@@ -862,15 +1098,22 @@ impl Shell {
                     );
                     workspace_set_idx(&mut state, i as u8 + 1, 0, &workspace_handle);
 
-                    let mut new_workspace =
-                        Workspace::new(workspace_handle, self.tiling_enabled, self.gaps);
+                    let mut new_workspace = Workspace::new(
+                        workspace_handle,
+                        self.tiling_enabled,
+                        self.gaps,
+                        self.animations,
+                    );
                     for output in self.outputs.iter() {
                         new_workspace.map_output(output, output.current_location());
                     }
                     new_workspace.tiling_enabled = workspaces.iter().any(|(_, w)| w.tiling_enabled);
 
                     for (_output, workspace) in workspaces.into_iter() {
-                        for toplevel in workspace.windows() {
+                        let minimized = workspace
+                            .minimized()
+                            .flat_map(|m| m.windows().map(|(w, _)| w));
+                        for toplevel in workspace.windows().chain(minimized) {
                             self.toplevel_info_state
                                 .toplevel_leave_workspace(&toplevel, &workspace.handle);
                             self.toplevel_info_state
@@ -881,6 +1124,9 @@ impl Shell {
                         new_workspace
                             .fullscreen
                             .extend(workspace.fullscreen.into_iter());
+                        new_workspace
+                            .minimized_windows
+                            .extend(workspace.minimized_windows.into_iter());
                         state.remove_workspace(workspace.handle);
                     }
 
@@ -914,6 +1160,7 @@ impl Shell {
                         i,
                         self.tiling_enabled,
                         self.gaps,
+                        self.animations,
                     );
                     state.add_group_output(&set.group, output);
                     sets.insert(output.clone(), set);
@@ -930,9 +1177,15 @@ impl Shell {
                         workspace_set_idx(&mut state, i as u8 + 1, idx, &new_workspace_handle);
 
                         let mut old_tiling_layer = workspace.tiling_layer.clone();
-                        let mut new_floating_layer = FloatingLayout::new();
-                        let mut new_tiling_layer = TilingLayout::new(self.gaps);
+                        let mut new_floating_layer = FloatingLayout::new(self.animations);
+                        let mut new_tiling_layer = TilingLayout::new(self.gaps, self.animations);
 
+                        for element in workspace.minimized() {
+                            for (toplevel, _) in element.windows() {
+                                self.toplevel_info_state
+                                    .toplevel_leave_workspace(&toplevel, &workspace.handle);
+                            }
+                        }
                         for element in workspace.mapped() {
                             for (toplevel, _) in element.windows() {
                                 self.toplevel_info_state
@@ -979,9 +1232,19 @@ impl Shell {
                                 .filter(|(key, _)| *key == output)
                                 .map(|(o, w)| (o.clone(), w.clone()))
                                 .collect(),
-                            ..Workspace::new(new_workspace_handle, true, self.gaps)
+                            // minimized windows have no position, keep them on the first output
+                            minimized_windows: if idx == 0 {
+                                workspace.minimized_windows.clone()
+                            } else {
+                                Vec::new()
+                            },
+                            ..Workspace::new(new_workspace_handle, true, self.gaps, self.animations)
                         };
-                        for toplevel in new_workspace.windows() {
+                        let minimized = new_workspace
+                            .minimized()
+                            .flat_map(|m| m.windows().map(|(w, _)| w))
+                            .collect::<Vec<_>>();
+                        for toplevel in new_workspace.windows().chain(minimized) {
                             self.toplevel_info_state
                                 .toplevel_enter_workspace(&toplevel, &new_workspace_handle);
                         }
@@ -1036,6 +1299,43 @@ impl Shell {
         }
     }
 
+    /// Follows a swipe gesture switching away from the workspace `origin`.
+    ///
+    /// `progress` is the signed number of workspaces the gesture moved so far.
+    pub fn update_workspace_gesture(&mut self, output: &Output, origin: usize, progress: f64) {
+        match &mut self.workspaces {
+            WorkspaceMode::OutputBound(sets, _) => {
+                if let Some(set) = sets.get_mut(output) {
+                    set.update_gesture(origin, progress);
+                }
+            }
+            WorkspaceMode::Global(set) => set.update_gesture(origin, progress),
+        }
+    }
+
+    /// Ends a workspace gesture, settling on the workspace it moved towards if `commit` is set,
+    /// or returning to `origin` otherwise.
+    pub fn end_workspace_gesture(
+        &mut self,
+        output: &Output,
+        origin: usize,
+        progress: f64,
+        velocity: f64,
+        commit: bool,
+    ) {
+        let state = &mut self.workspace_state.update();
+        match &mut self.workspaces {
+            WorkspaceMode::OutputBound(sets, _) => {
+                if let Some(set) = sets.get_mut(output) {
+                    set.end_gesture(origin, progress, velocity, commit, state);
+                }
+            }
+            WorkspaceMode::Global(set) => {
+                set.end_gesture(origin, progress, velocity, commit, state)
+            }
+        }
+    }
+
     pub fn active_space(&self, output: &Output) -> &Workspace {
         self.workspaces.active(output).1
     }
@@ -1144,15 +1444,40 @@ impl Shell {
     }
 
     pub fn space_for(&self, mapped: &CosmicMapped) -> Option<&Workspace> {
-        self.workspaces
-            .spaces()
-            .find(|workspace| workspace.mapped().any(|m| m == mapped))
+        self.workspaces.spaces().find(|workspace| {
+            workspace
+                .mapped()
+                .chain(workspace.minimized())
+                .any(|m| m == mapped)
+        })
+    }
+
+    /// Marks a window urgent until it is focused,
+    /// and its workspace, unless it is shown already
+    pub fn set_urgent(&mut self, mapped: &CosmicMapped) {
+        mapped.set_urgent(true);
+        let Some(handle) = self.space_for(mapped).map(|workspace| workspace.handle) else {
+            return;
+        };
+        if self
+            .outputs
+            .iter()
+            .any(|output| self.active_space(output).handle == handle)
+        {
+            return;
+        }
+        self.workspace_state
+            .update()
+            .add_workspace_state(&handle, WState::Urgent);
     }
 
     pub fn space_for_mut(&mut self, mapped: &CosmicMapped) -> Option<&mut Workspace> {
-        self.workspaces
-            .spaces_mut()
-            .find(|workspace| workspace.mapped().any(|m| m == mapped))
+        self.workspaces.spaces_mut().find(|workspace| {
+            workspace
+                .mapped()
+                .chain(workspace.minimized())
+                .any(|m| m == mapped)
+        })
     }
 
     pub fn space_for_handle(&self, handle: &WorkspaceHandle) -> Option<&Workspace> {
@@ -1163,6 +1488,28 @@ impl Shell {
         self.workspaces.spaces_mut().find(|w| &w.handle == handle)
     }
 
+    /// Output and index of the workspace with the given handle,
+    /// using `output` for workspaces spanning all outputs
+    pub fn workspace_position(
+        &self,
+        handle: &WorkspaceHandle,
+        output: &Output,
+    ) -> Option<(Output, usize)> {
+        match &self.workspaces {
+            WorkspaceMode::Global(set) => set
+                .workspaces
+                .iter()
+                .position(|w| &w.handle == handle)
+                .map(|idx| (output.clone(), idx)),
+            WorkspaceMode::OutputBound(sets, _) => sets.iter().find_map(|(output, set)| {
+                set.workspaces
+                    .iter()
+                    .position(|w| &w.handle == handle)
+                    .map(|idx| (output.clone(), idx))
+            }),
+        }
+    }
+
     pub fn outputs(&self) -> impl Iterator<Item = &Output> {
         self.outputs.iter()
     }
@@ -1196,10 +1543,12 @@ impl Shell {
 
     pub fn animations_going(&self) -> bool {
         (match &self.workspaces {
-            WorkspaceMode::Global(set) => set.previously_active.is_some(),
-            WorkspaceMode::OutputBound(sets, _) => {
-                sets.values().any(|set| set.previously_active.is_some())
+            WorkspaceMode::Global(set) => {
+                set.previously_active.is_some() || set.overscroll.is_some()
             }
+            WorkspaceMode::OutputBound(sets, _) => sets
+                .values()
+                .any(|set| set.previously_active.is_some() || set.overscroll.is_some()),
         }) || !matches!(self.overview_mode, OverviewMode::None)
             || !matches!(self.resize_mode, ResizeMode::None)
             || self
@@ -1232,9 +1581,9 @@ impl Shell {
             if !matches!(self.overview_mode, OverviewMode::Ended(_, _)) {
                 let (reverse_duration, trigger) =
                     if let OverviewMode::Started(trigger, start) = self.overview_mode.clone() {
+                        let duration = AnimationKind::Overview.duration(&self.animations);
                         (
-                            ANIMATION_DURATION
-                                - Instant::now().duration_since(start).min(ANIMATION_DURATION),
+                            duration - Instant::now().duration_since(start).min(duration),
                             Some(trigger),
                         )
                     } else {
@@ -1248,7 +1597,9 @@ impl Shell {
 
     pub fn overview_mode(&mut self) -> (OverviewMode, Option<SwapIndicator>) {
         if let OverviewMode::Ended(_, timestamp) = self.overview_mode {
-            if Instant::now().duration_since(timestamp) > ANIMATION_DURATION {
+            if Animation::starting_at(AnimationKind::Overview, timestamp, &self.animations)
+                .is_done()
+            {
                 self.overview_mode = OverviewMode::None;
                 self.swap_indicator = None;
             }
@@ -1283,7 +1634,9 @@ impl Shell {
 
     pub fn resize_mode(&mut self) -> (ResizeMode, Option<ResizeIndicator>) {
         if let ResizeMode::Ended(timestamp, _) = self.resize_mode {
-            if Instant::now().duration_since(timestamp) > ANIMATION_DURATION {
+            if Animation::starting_at(AnimationKind::Overview, timestamp, &self.animations)
+                .is_done()
+            {
                 self.resize_mode = ResizeMode::None;
                 self.resize_indicator = None;
             }
@@ -1292,6 +1645,116 @@ impl Shell {
         (self.resize_mode.clone(), self.resize_indicator.clone())
     }
 
+    /// Shows the name and model of every output in its corner, for `IDENTIFY_DURATION`
+    pub fn identify_outputs(&mut self, evlh: LoopHandle<'static, crate::state::State>) {
+        let indicators = self
+            .outputs
+            .iter()
+            .map(|output| (output.clone(), identify_indicator(output, evlh.clone())))
+            .collect();
+        self.identify = Some((Instant::now(), indicators));
+    }
+
+    pub fn identify_indicator(&mut self, output: &Output) -> Option<IdentifyIndicator> {
+        if let Some((start, _)) = self.identify.as_ref() {
+            if start.elapsed() >= IDENTIFY_DURATION {
+                self.identify = None;
+            }
+        }
+
+        self.identify.as_ref().and_then(|(_, indicators)| {
+            indicators
+                .iter()
+                .find(|(o, _)| o == output)
+                .map(|(_, indicator)| indicator.clone())
+        })
+    }
+
+    /// Flashes `area` of an output, relative to it, for `BELL_DURATION`
+    pub fn flash(&mut self, output: &Output, area: Rectangle<i32, Logical>) {
+        self.visual_bell = Some((Instant::now(), output.clone(), area));
+    }
+
+    pub fn visual_bell(&mut self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        if let Some((start, _, _)) = self.visual_bell.as_ref() {
+            if start.elapsed() >= BELL_DURATION {
+                self.visual_bell = None;
+            }
+        }
+
+        self.visual_bell
+            .as_ref()
+            .filter(|(_, o, _)| o == output)
+            .map(|(_, _, area)| *area)
+    }
+
+    /// Shows an error on every output, for `ERROR_DURATION`
+    pub fn show_error(
+        &mut self,
+        title: String,
+        message: String,
+        evlh: LoopHandle<'static, crate::state::State>,
+    ) {
+        let indicators = self
+            .outputs
+            .iter()
+            .map(|output| {
+                (
+                    output.clone(),
+                    error_indicator(title.clone(), message.clone(), evlh.clone()),
+                )
+            })
+            .collect();
+        self.error = Some((Instant::now(), indicators));
+    }
+
+    pub fn error_indicator(&mut self, output: &Output) -> Option<ErrorIndicator> {
+        if let Some((start, _)) = self.error.as_ref() {
+            if start.elapsed() >= ERROR_DURATION {
+                self.error = None;
+            }
+        }
+
+        self.error.as_ref().and_then(|(_, indicators)| {
+            indicators
+                .iter()
+                .find(|(o, _)| o == output)
+                .map(|(_, indicator)| indicator.clone())
+        })
+    }
+
+    /// Changes the gaps of all workspaces, including the ones created later
+    pub fn set_gaps(&mut self, gaps: (u8, u8)) {
+        self.gaps = gaps;
+        match &mut self.workspaces {
+            WorkspaceMode::OutputBound(sets, _) => {
+                for set in sets.values_mut() {
+                    set.gaps = gaps;
+                }
+            }
+            WorkspaceMode::Global(set) => set.gaps = gaps,
+        }
+        for workspace in self.workspaces.spaces_mut() {
+            workspace.tiling_layer.set_gaps(gaps);
+        }
+    }
+
+    /// Changes the animation settings of all workspaces, including the ones created later
+    pub fn set_animations(&mut self, animations: AnimationConfig) {
+        self.animations = animations;
+        match &mut self.workspaces {
+            WorkspaceMode::OutputBound(sets, _) => {
+                for set in sets.values_mut() {
+                    set.animations = animations;
+                }
+            }
+            WorkspaceMode::Global(set) => set.animations = animations,
+        }
+        for workspace in self.workspaces.spaces_mut() {
+            workspace.set_animations(animations);
+        }
+    }
+
     pub fn refresh(&mut self) {
         #[cfg(feature = "debug")]
         puffin::profile_function!();
@@ -1327,6 +1790,90 @@ impl Shell {
 
         self.toplevel_info_state
             .refresh(Some(&self.workspace_state));
+        self.foreign_toplevel_state.refresh();
+        self.refresh_overlap_notifications();
+    }
+
+    /// Sends the windows and layer surfaces overlapping observed layer surfaces,
+    /// which changes as windows move, resize or switch workspaces
+    fn refresh_overlap_notifications(&mut self) {
+        for layer_surface in self.overlap_notify_state.layer_surfaces() {
+            let Some((output, layer, geo)) = self.outputs.iter().find_map(|output| {
+                let map = layer_map_for_output(output);
+                let layer = map
+                    .layers()
+                    .find(|l| l.layer_surface().shell_surface() == &layer_surface)?
+                    .clone();
+                let geo = map.layer_geometry(&layer)?;
+                Some((output.clone(), layer, geo))
+            }) else {
+                continue;
+            };
+            let output_geo = output.geometry();
+            let geo = Rectangle::from_loc_and_size(geo.loc + output_geo.loc, geo.size);
+            let relative = |area: Rectangle<i32, Logical>| {
+                area.intersection(geo).map(|mut overlap| {
+                    overlap.loc -= geo.loc;
+                    overlap
+                })
+            };
+
+            let workspace = self.active_space(&output);
+            let fullscreen = workspace.get_fullscreen(&output);
+            let mut toplevels = workspace
+                .mapped()
+                // tiled windows are hidden behind a fullscreen window
+                .filter(|mapped| fullscreen.is_none() || !workspace.is_tiled(mapped))
+                .filter_map(|mapped| {
+                    let overlap = workspace.element_geometry(mapped).and_then(relative)?;
+                    Some((mapped.active_window(), overlap))
+                })
+                .collect::<Vec<_>>();
+            if let Some(fullscreen) = fullscreen {
+                toplevels
+                    .extend(relative(output_geo).map(|overlap| (fullscreen.surface(), overlap)));
+            } else if let Some(maximized) = workspace.get_maximized(&output) {
+                let mut zone = layer_map_for_output(&output).non_exclusive_zone();
+                zone.loc += output_geo.loc;
+                toplevels.extend(relative(zone).map(|overlap| (maximized.surface(), overlap)));
+            }
+
+            let layers = {
+                let map = layer_map_for_output(&output);
+                map.layers()
+                    .filter(|other| *other != &layer)
+                    .filter_map(|other| {
+                        let other_geo = map.layer_geometry(other)?;
+                        let overlap = relative(Rectangle::from_loc_and_size(
+                            other_geo.loc + output_geo.loc,
+                            other_geo.size,
+                        ))?;
+                        let (exclusive, layer) = with_states(other.wl_surface(), |states| {
+                            let state = states.cached_state.current::<LayerSurfaceCachedState>();
+                            (
+                                matches!(state.exclusive_zone, ExclusiveZone::Exclusive(_)),
+                                match state.layer {
+                                    Layer::Background => 0,
+                                    Layer::Bottom => 1,
+                                    Layer::Top => 2,
+                                    Layer::Overlay => 3,
+                                },
+                            )
+                        });
+                        Some(LayerOverlap {
+                            identifier: layer_identifier(other.wl_surface()),
+                            namespace: other.namespace().to_string(),
+                            exclusive,
+                            layer,
+                            overlap,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            self.overlap_notify_state
+                .update(&layer_surface, &toplevels, &layers);
+        }
     }
 
     pub fn map_window(state: &mut State, window: &CosmicSurface, output: &Output) {
@@ -1339,6 +1886,49 @@ impl Shell {
             .unwrap();
         let (window, seat) = state.common.shell.pending_windows.remove(pos);
 
+        let (app_id, title) = (window.app_id(), window.title());
+        if let Some(filter) = state
+            .common
+            .config
+            .static_conf
+            .window_rules
+            .iter()
+            .filter(|rule| rule.matches(&app_id, &title))
+            .find_map(|rule| rule.force_scale)
+        {
+            window.set_force_scale(filter);
+        }
+        if let Some(scale) = state
+            .common
+            .config
+            .static_conf
+            .window_rules
+            .iter()
+            .filter(|rule| rule.matches(&app_id, &title))
+            .find_map(|rule| rule.x11_scale)
+            .or_else(|| {
+                state
+                    .common
+                    .config
+                    .static_conf
+                    .xwayland_hidpi
+                    .then_some(X11Scale::HiDpi)
+            })
+        {
+            window.set_x11_scale(scale);
+        }
+        if let Some(width) = state
+            .common
+            .config
+            .static_conf
+            .window_rules
+            .iter()
+            .filter(|rule| rule.matches(&app_id, &title))
+            .find_map(|rule| rule.border_width)
+        {
+            window.set_border_width(width);
+        }
+
         let workspace = state.common.shell.workspaces.active_mut(output);
         workspace.remove_fullscreen(output);
         state.common.shell.toplevel_info_state.new_toplevel(&window);
@@ -1352,6 +1942,11 @@ impl Shell {
             .shell
             .toplevel_info_state
             .toplevel_enter_workspace(&window, &workspace.handle);
+        state
+            .common
+            .shell
+            .foreign_toplevel_state
+            .new_toplevel(&window);
 
         let mapped = CosmicMapped::from(CosmicWindow::new(
             window.clone(),
@@ -1362,7 +1957,44 @@ impl Shell {
             mapped.set_debug(state.common.egui.active);
         }
         if layout::should_be_floating(&window) || !workspace.tiling_enabled {
-            workspace.floating_layer.map(mapped.clone(), &seat, None);
+            // X11 dialogs open centered on top of the window they are transient for
+            let parent_geometry = match &window {
+                CosmicSurface::X11(surface) => surface.is_transient_for().and_then(|parent| {
+                    let parent = workspace.mapped().find(|mapped| {
+                        mapped.windows().any(|(tab, _)| {
+                            matches!(tab, CosmicSurface::X11(tab) if tab.window_id() == parent)
+                        })
+                    })?;
+                    workspace
+                        .floating_layer
+                        .space
+                        .element_geometry(parent)
+                        .or_else(|| workspace.tiling_layer.element_geometry(parent))
+                }),
+                _ => None,
+            };
+            let position = parent_geometry.map(|parent| {
+                let size = mapped.geometry().size;
+                Point::from((
+                    parent.loc.x + (parent.size.w - size.w) / 2,
+                    parent.loc.y + (parent.size.h - size.h) / 2,
+                ))
+            });
+            workspace
+                .floating_layer
+                .map(mapped.clone(), &seat, position);
+            workspace.floating_layer.animate_open(&mapped);
+            if state.common.shell.tablet_mode
+                && state
+                    .common
+                    .config
+                    .static_conf
+                    .switches
+                    .tablet_mode_maximize
+                && !layout::should_be_floating(&window)
+            {
+                workspace.maximize_request(&window, output, state.common.event_loop_handle.clone());
+            }
         } else {
             let focus_stack = workspace.focus_stack.get(&seat);
             workspace
@@ -1429,7 +2061,7 @@ impl Shell {
             map.map_layer(&layer_surface).unwrap();
         }
         for workspace in state.common.shell.workspaces.spaces_mut() {
-            workspace.tiling_layer.recalculate(&output);
+            workspace.recalculate(&output);
         }
 
         if wants_focus {
@@ -1437,6 +2069,25 @@ impl Shell {
         }
     }
 
+    /// The topmost layer surface on `output`, that requested exclusive keyboard focus
+    pub fn exclusive_layer_surface(&self, output: &Output) -> Option<LayerSurface> {
+        let map = layer_map_for_output(output);
+        [Layer::Overlay, Layer::Top].into_iter().find_map(|layer| {
+            map.layers_on(layer)
+                .filter(|layer_surface| {
+                    with_states(layer_surface.wl_surface(), |states| {
+                        states
+                            .cached_state
+                            .current::<LayerSurfaceCachedState>()
+                            .keyboard_interactivity
+                            == KeyboardInteractivity::Exclusive
+                    })
+                })
+                .last()
+                .cloned()
+        })
+    }
+
     pub fn move_current_window(
         state: &mut State,
         seat: &Seat<State>,
@@ -1456,12 +2107,7 @@ impl Shell {
         {
             return Err(InvalidWorkspaceIndex);
         }
-
-        if from_output == to_output
-            && to_idx == state.common.shell.workspaces.active_num(from_output).1
-        {
-            return Ok(None);
-        }
+        let from_idx = state.common.shell.workspaces.active_num(from_output).1;
 
         let from_workspace = state.common.shell.workspaces.active_mut(from_output);
         let maybe_window = from_workspace.focus_stack.get(seat).last().cloned();
@@ -1469,6 +2115,49 @@ impl Shell {
         let Some(mapped) = maybe_window else {
             return Ok(None);
         };
+        Shell::move_window(
+            state,
+            seat,
+            &mapped,
+            (from_output, from_idx),
+            (to_output, to_idx),
+            follow,
+            direction,
+        )
+    }
+
+    /// Moves `mapped` from the workspace at `from` to the one at `to`,
+    /// activating the latter if `follow` is set
+    pub fn move_window(
+        state: &mut State,
+        seat: &Seat<State>,
+        mapped: &CosmicMapped,
+        from: (&Output, usize),
+        to: (&Output, usize),
+        follow: bool,
+        direction: Option<Direction>,
+    ) -> Result<Option<Point<i32, Logical>>, InvalidWorkspaceIndex> {
+        let (from_output, from_idx) = from;
+        let (to_output, to_idx) = to;
+        if state
+            .common
+            .shell
+            .workspaces
+            .get(to_idx, to_output)
+            .is_none()
+        {
+            return Err(InvalidWorkspaceIndex);
+        }
+
+        if from_output == to_output && to_idx == from_idx {
+            return Ok(None);
+        }
+
+        let Some(from_workspace) = state.common.shell.workspaces.get_mut(from_idx, from_output)
+        else {
+            return Err(InvalidWorkspaceIndex);
+        };
+        let mapped = mapped.clone();
         let Some(window_state) = from_workspace.unmap(&mapped) else {
             return Ok(None);
         };
@@ -1614,6 +2303,7 @@ impl Shell {
                         &output,
                         start_data,
                         state.common.config.static_conf.active_hint,
+                        state.common.config.static_conf.shadows,
                     ) {
                         let handle = workspace.handle;
                         state
@@ -1644,6 +2334,84 @@ impl Shell {
         }
     }
 
+    pub fn menu_request(
+        state: &mut State,
+        surface: &WlSurface,
+        seat: &Seat<State>,
+        serial: impl Into<Option<Serial>>,
+        location: Point<i32, Logical>,
+    ) {
+        let serial = serial.into();
+        if let Some(start_data) = check_grab_preconditions(&seat, surface, serial) {
+            if let Some(mapped) = state.common.shell.element_for_wl_surface(surface).cloned() {
+                let (window, _) = mapped
+                    .windows()
+                    .find(|(w, _)| w.wl_surface().as_ref() == Some(surface))
+                    .unwrap();
+                let serial = serial.unwrap_or_else(|| SERIAL_COUNTER.next_serial());
+                let menu = window_menu(window, seat.clone(), serial, &state.common);
+                let grab = MenuGrab::new(start_data, seat, menu, location);
+                seat.get_pointer()
+                    .unwrap()
+                    .set_grab(state, grab, serial, Focus::Clear);
+            }
+        }
+    }
+
+    pub fn titlebar_action(
+        state: &mut State,
+        action: TitlebarAction,
+        surface: &WlSurface,
+        seat: &Seat<State>,
+        serial: Serial,
+    ) {
+        let Some(mapped) = state.common.shell.element_for_wl_surface(surface).cloned() else {
+            return;
+        };
+        let (window, _) = mapped
+            .windows()
+            .find(|(w, _)| w.wl_surface().as_ref() == Some(surface))
+            .unwrap();
+
+        match action {
+            TitlebarAction::None => {}
+            TitlebarAction::Maximize => {
+                if let Some(workspace) = state.common.shell.space_for_mut(&mapped) {
+                    let output = seat.active_output();
+                    workspace.maximize_toggle(
+                        &window,
+                        &output,
+                        state.common.event_loop_handle.clone(),
+                    )
+                }
+            }
+            TitlebarAction::Lower => {
+                if let Some(workspace) = state.common.shell.space_for_mut(&mapped) {
+                    if workspace.is_floating(&mapped) {
+                        workspace.floating_layer.lower_element(&mapped);
+                    }
+                }
+            }
+            TitlebarAction::Close => window.close(),
+            TitlebarAction::WindowMenu => {
+                let location = seat
+                    .get_pointer()
+                    .unwrap()
+                    .current_location()
+                    .to_i32_round();
+                Shell::menu_request(state, surface, seat, serial, location)
+            }
+            TitlebarAction::Shade => {
+                if let Some(workspace) = state.common.shell.space_for_mut(&mapped) {
+                    if workspace.is_floating(&mapped) {
+                        mapped.toggle_shaded();
+                        workspace.floating_layer.refresh();
+                    }
+                }
+            }
+        }
+    }
+
     pub fn resize_request(
         state: &mut State,
         surface: &WlSurface,
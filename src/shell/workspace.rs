@@ -1,15 +1,20 @@
 use crate::{
     backend::render::{
-        element::{AsGlowFrame, AsGlowRenderer},
+        element::{AsGlowFrame, AsGlowRenderer, SurfaceRenderElement},
         BackdropShader, GlMultiError, GlMultiFrame, GlMultiRenderer,
     },
+    config::{AnimationConfig, BorderConfig, ShadowConfig},
     shell::{
-        grabs::MoveGrab,
+        grabs::{MoveGrab, MoveGrabState},
         layout::{floating::FloatingLayout, tiling::TilingLayout},
-        OverviewMode, ANIMATION_DURATION,
+        OverviewMode,
     },
     state::State,
-    utils::{prelude::*, tween::EaseRectangle},
+    utils::{
+        animation::{Animation, AnimationKind},
+        prelude::*,
+        tween::EaseRectangle,
+    },
     wayland::{
         handlers::screencopy::DropableSession,
         protocols::{
@@ -24,12 +29,12 @@ use crate::{
 use calloop::LoopHandle;
 use id_tree::Tree;
 use indexmap::IndexSet;
-use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
     backend::renderer::{
         element::{
-            surface::WaylandSurfaceRenderElement, texture::TextureRenderElement,
-            utils::RescaleRenderElement, AsRenderElements, Element, Id, RenderElement,
+            memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
+            texture::TextureRenderElement, utils::RescaleRenderElement, AsRenderElements, Element,
+            Id, RenderElement,
         },
         gles::{GlesError, GlesTexture},
         glow::{GlowFrame, GlowRenderer},
@@ -52,7 +57,7 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::Instant,
 };
 use tracing::warn;
 use wayland_backend::server::ClientId;
@@ -72,20 +77,21 @@ use super::{
     CosmicMappedRenderElement, CosmicSurface, ResizeDirection, ResizeMode,
 };
 
-const FULLSCREEN_ANIMATION_DURATION: Duration = Duration::from_millis(200);
-
 #[derive(Debug)]
 pub struct Workspace {
     pub tiling_layer: TilingLayout,
     pub floating_layer: FloatingLayout,
     pub tiling_enabled: bool,
     pub fullscreen: HashMap<Output, FullscreenSurface>,
+    /// Windows hidden by minimizing them, with the layer they are mapped to again
+    pub minimized_windows: Vec<(CosmicMapped, ManagedLayer)>,
     pub handle: WorkspaceHandle,
     pub focus_stack: FocusStacks,
     pub pending_buffers: Vec<(ScreencopySession, BufferParams)>,
     pub screencopy_sessions: Vec<DropableSession>,
     pub(super) backdrop_id: Id,
     pub dirty: AtomicBool,
+    animations: AnimationConfig,
 }
 
 #[derive(Debug, Clone)]
@@ -198,18 +204,38 @@ impl MoveResult {
 }
 
 impl Workspace {
-    pub fn new(handle: WorkspaceHandle, tiling_enabled: bool, gaps: (u8, u8)) -> Workspace {
+    pub fn new(
+        handle: WorkspaceHandle,
+        tiling_enabled: bool,
+        gaps: (u8, u8),
+        animations: AnimationConfig,
+    ) -> Workspace {
         Workspace {
-            tiling_layer: TilingLayout::new(gaps),
-            floating_layer: FloatingLayout::new(),
+            tiling_layer: TilingLayout::new(gaps, animations),
+            floating_layer: FloatingLayout::new(animations),
             tiling_enabled,
             fullscreen: HashMap::new(),
+            minimized_windows: Vec::new(),
             handle,
             focus_stack: FocusStacks::default(),
             pending_buffers: Vec::new(),
             screencopy_sessions: Vec::new(),
             backdrop_id: Id::new(),
             dirty: AtomicBool::new(false),
+            animations,
+        }
+    }
+
+    pub fn set_animations(&mut self, animations: AnimationConfig) {
+        self.animations = animations;
+        self.tiling_layer.set_animations(animations);
+        self.floating_layer.set_animations(animations);
+        for stack in self
+            .mapped()
+            .chain(self.minimized_windows.iter().map(|(m, _)| m))
+            .filter_map(CosmicMapped::stack_ref)
+        {
+            stack.set_animations(animations);
         }
     }
 
@@ -218,6 +244,7 @@ impl Workspace {
         puffin::profile_function!();
 
         self.fullscreen.retain(|_, w| w.alive());
+        self.minimized_windows.retain(|(m, _)| m.alive());
         self.floating_layer.refresh();
         self.tiling_layer.refresh();
     }
@@ -231,6 +258,7 @@ impl Workspace {
 
     pub fn animations_going(&self) -> bool {
         self.tiling_layer.animations_going()
+            || self.floating_layer.animations_going()
             || self
                 .fullscreen
                 .values()
@@ -240,15 +268,16 @@ impl Workspace {
 
     pub fn update_animations(&mut self) -> HashMap<ClientId, Client> {
         let mut clients = HashMap::new();
+        let duration = AnimationKind::Fullscreen.duration(&self.animations);
 
         for f in self.fullscreen.values_mut() {
             if let Some(start) = f.start_at.as_ref() {
                 let duration_since = Instant::now().duration_since(*start);
-                if duration_since > FULLSCREEN_ANIMATION_DURATION {
+                if duration_since > duration {
                     f.start_at.take();
                     self.dirty.store(true, Ordering::SeqCst);
                 }
-                if duration_since * 2 > FULLSCREEN_ANIMATION_DURATION {
+                if duration_since * 2 > duration {
                     if let Some(signal) = f.animation_signal.take() {
                         signal.store(true, Ordering::SeqCst);
                         if let Some(client) =
@@ -266,7 +295,7 @@ impl Workspace {
             None => true,
             Some(instant) => {
                 let duration_since = Instant::now().duration_since(instant);
-                if duration_since * 2 > FULLSCREEN_ANIMATION_DURATION {
+                if duration_since * 2 > duration {
                     if let Some(signal) = f.animation_signal.take() {
                         signal.store(true, Ordering::SeqCst);
                         if let Some(client) =
@@ -277,13 +306,14 @@ impl Workspace {
                     }
                 }
 
-                duration_since < FULLSCREEN_ANIMATION_DURATION
+                duration_since < duration
             }
         });
         if len != self.fullscreen.len() {
             self.dirty.store(true, Ordering::SeqCst);
         }
 
+        self.floating_layer.update_animation_state();
         clients.extend(self.tiling_layer.update_animation_state());
         clients
     }
@@ -349,10 +379,51 @@ impl Workspace {
         }
     }
 
+    /// Hides a window, until it is unminimized again
+    pub fn minimize(&mut self, mapped: &CosmicMapped) -> bool {
+        let Some(state) = self.unmap(mapped) else {
+            return false;
+        };
+        for (window, _) in mapped.windows() {
+            window.set_minimized(true);
+        }
+        self.minimized_windows.push((mapped.clone(), state.layer));
+        true
+    }
+
+    /// Maps a minimized window again, in the layer it was minimized from
+    pub fn unminimize(&mut self, mapped: &CosmicMapped, seat: &Seat<State>) -> bool {
+        let Some(idx) = self.minimized_windows.iter().position(|(m, _)| m == mapped) else {
+            return false;
+        };
+        let (mapped, layer) = self.minimized_windows.remove(idx);
+        for (window, _) in mapped.windows() {
+            window.set_minimized(false);
+        }
+        if layer == ManagedLayer::Tiling && self.tiling_enabled {
+            let focus_stack = self.focus_stack.get(seat);
+            self.tiling_layer
+                .map(mapped, seat, focus_stack.iter(), None);
+        } else {
+            self.floating_layer.map(mapped, seat, None);
+        }
+        true
+    }
+
+    pub fn minimized(&self) -> impl Iterator<Item = &CosmicMapped> {
+        self.minimized_windows.iter().map(|(mapped, _)| mapped)
+    }
+
+    /// Whether the workspace has no windows, neither shown nor minimized
+    pub fn is_empty(&self) -> bool {
+        self.windows().next().is_none() && self.minimized_windows.is_empty()
+    }
+
     pub fn element_for_surface(&self, surface: &CosmicSurface) -> Option<&CosmicMapped> {
         self.floating_layer
             .mapped()
             .chain(self.tiling_layer.mapped().map(|(_, w, _)| w))
+            .chain(self.minimized())
             .find(|e| e.windows().any(|(w, _)| &w == surface))
     }
 
@@ -360,6 +431,7 @@ impl Workspace {
         self.floating_layer
             .mapped()
             .chain(self.tiling_layer.mapped().map(|(_, w, _)| w))
+            .chain(self.minimized())
             .find(|e| {
                 e.windows()
                     .any(|(w, _)| w.wl_surface().as_ref() == Some(surface))
@@ -556,17 +628,14 @@ impl Workspace {
             };
             window.send_configure();
 
+            let duration = AnimationKind::Fullscreen.duration(&self.animations);
             f.ended_at = Some(
                 Instant::now()
-                    - (FULLSCREEN_ANIMATION_DURATION
+                    - (duration
                         - f.start_at
                             .take()
-                            .map(|earlier| {
-                                Instant::now()
-                                    .duration_since(earlier)
-                                    .min(FULLSCREEN_ANIMATION_DURATION)
-                            })
-                            .unwrap_or(FULLSCREEN_ANIMATION_DURATION)),
+                            .map(|earlier| Instant::now().duration_since(earlier).min(duration))
+                            .unwrap_or(duration)),
             );
             if let Some(new_signal) = signal {
                 if let Some(old_signal) = f.animation_signal.replace(new_signal) {
@@ -611,17 +680,14 @@ impl Workspace {
             };
             surface.send_configure();
 
+            let duration = AnimationKind::Fullscreen.duration(&self.animations);
             *ended_at = Some(
                 Instant::now()
-                    - (FULLSCREEN_ANIMATION_DURATION
+                    - (duration
                         - start_at
                             .take()
-                            .map(|earlier| {
-                                Instant::now()
-                                    .duration_since(earlier)
-                                    .min(FULLSCREEN_ANIMATION_DURATION)
-                            })
-                            .unwrap_or(FULLSCREEN_ANIMATION_DURATION)),
+                            .map(|earlier| Instant::now().duration_since(earlier).min(duration))
+                            .unwrap_or(duration)),
             );
             if let Some(new_signal) = signal {
                 if let Some(old_signal) = animation_signal.replace(new_signal) {
@@ -711,6 +777,7 @@ impl Workspace {
         output: &Output,
         start_data: PointerGrabStartData<State>,
         indicator_thickness: u8,
+        shadow: ShadowConfig,
     ) -> Option<MoveGrab> {
         let pointer = seat.get_pointer().unwrap();
         let pos = pointer.current_location();
@@ -740,10 +807,44 @@ impl Workspace {
             pos,
             initial_window_location,
             indicator_thickness,
+            shadow,
             was_tiled.is_some(),
         ))
     }
 
+    /// Detaches `window` from the layouts to follow the pointer of `seat` during a
+    /// drag-and-drop operation, returning if it was tiled
+    pub fn toplevel_drag(
+        &mut self,
+        window: &CosmicSurface,
+        seat: &Seat<State>,
+        pointer_offset: Point<i32, Logical>,
+        indicator_thickness: u8,
+        shadow: ShadowConfig,
+    ) -> Option<bool> {
+        let mapped = self.element_for_surface(&window)?.clone();
+        if self.minimized().any(|m| m == &mapped) {
+            return None;
+        }
+        if mapped.is_fullscreen(true) || mapped.is_maximized(true) {
+            self.unmaximize_request(window);
+        }
+
+        let was_floating = self.floating_layer.unmap(&mapped);
+        let was_tiled = self.tiling_layer.unmap_as_placeholder(&mapped);
+        assert!(was_floating != was_tiled.is_some());
+
+        MoveGrabState::attach(
+            seat,
+            mapped,
+            (-pointer_offset.x, -pointer_offset.y).into(),
+            indicator_thickness,
+            shadow,
+            was_tiled.is_some(),
+        );
+        Some(was_tiled.is_some())
+    }
+
     pub fn toggle_tiling(&mut self, seat: &Seat<State>) {
         if self.tiling_enabled {
             for window in self
@@ -914,6 +1015,8 @@ impl Workspace {
         overview: (OverviewMode, Option<(SwapIndicator, Option<&Tree<Data>>)>),
         resize_indicator: Option<(ResizeMode, ResizeIndicator)>,
         indicator_thickness: u8,
+        borders: &BorderConfig,
+        shadow: &ShadowConfig,
     ) -> Result<
         (
             Vec<WorkspaceRenderElement<R>>,
@@ -943,6 +1046,7 @@ impl Workspace {
         popup_elements.extend(
             override_redirect_windows
                 .iter()
+                .rev()
                 .filter(|or| (*or).geometry().intersection(output.geometry()).is_some())
                 .flat_map(|or| {
                     AsRenderElements::<R>::render_elements::<WorkspaceRenderElement<R>>(
@@ -997,31 +1101,26 @@ impl Workspace {
 
             let (target_geo, alpha) = match (fullscreen.start_at, fullscreen.ended_at) {
                 (Some(started), _) => {
-                    let duration = Instant::now().duration_since(started).as_secs_f64()
-                        / FULLSCREEN_ANIMATION_DURATION.as_secs_f64();
+                    let animation = Animation::starting_at(
+                        AnimationKind::Fullscreen,
+                        started,
+                        &self.animations,
+                    );
                     (
-                        ease(
-                            EaseInOutCubic,
-                            EaseRectangle(element_geo),
-                            EaseRectangle(full_geo),
-                            duration,
-                        )
-                        .0,
-                        ease(EaseInOutCubic, 0.0, 1.0, duration),
+                        animation
+                            .value(EaseRectangle(element_geo), EaseRectangle(full_geo))
+                            .0,
+                        animation.value(0.0, 1.0),
                     )
                 }
                 (_, Some(ended)) => {
-                    let duration = Instant::now().duration_since(ended).as_secs_f64()
-                        / FULLSCREEN_ANIMATION_DURATION.as_secs_f64();
+                    let animation =
+                        Animation::starting_at(AnimationKind::Fullscreen, ended, &self.animations);
                     (
-                        ease(
-                            EaseInOutCubic,
-                            EaseRectangle(full_geo),
-                            EaseRectangle(element_geo),
-                            duration,
-                        )
-                        .0,
-                        ease(EaseInOutCubic, 1.0, 0.0, duration),
+                        animation
+                            .value(EaseRectangle(full_geo), EaseRectangle(element_geo))
+                            .0,
+                        animation.value(1.0, 0.0),
                     )
                 }
                 (None, None) => (full_geo, 1.0),
@@ -1063,17 +1162,14 @@ impl Workspace {
             // floating surfaces
             let alpha = match &overview.0 {
                 OverviewMode::Started(_, started) => {
-                    (1.0 - (Instant::now().duration_since(*started).as_millis()
-                        / ANIMATION_DURATION.as_millis()) as f32)
-                        .max(0.0)
-                        * 0.4
-                        + 0.6
+                    let animation =
+                        Animation::starting_at(AnimationKind::Overview, *started, &self.animations);
+                    (1.0 - animation.progress()) * 0.4 + 0.6
                 }
                 OverviewMode::Ended(_, ended) => {
-                    ((Instant::now().duration_since(*ended).as_millis()
-                        / ANIMATION_DURATION.as_millis()) as f32)
-                        * 0.4
-                        + 0.6
+                    let animation =
+                        Animation::starting_at(AnimationKind::Overview, *ended, &self.animations);
+                    animation.progress() * 0.4 + 0.6
                 }
                 OverviewMode::None => 1.0,
             };
@@ -1084,6 +1180,8 @@ impl Workspace {
                 focused.as_ref(),
                 resize_indicator.clone(),
                 indicator_thickness,
+                borders,
+                shadow,
                 alpha,
             );
             popup_elements.extend(p_elements.into_iter().map(WorkspaceRenderElement::from));
@@ -1110,6 +1208,7 @@ impl Workspace {
                 overview,
                 resize_indicator,
                 indicator_thickness,
+                borders,
             )?;
             popup_elements.extend(p_elements.into_iter().map(WorkspaceRenderElement::from));
             window_elements.extend(w_elements.into_iter().map(WorkspaceRenderElement::from));
@@ -1162,11 +1261,12 @@ where
     R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
     <R as Renderer>::TextureId: 'static,
 {
-    OverrideRedirect(WaylandSurfaceRenderElement<R>),
+    OverrideRedirect(SurfaceRenderElement<R>),
     Fullscreen(RescaleRenderElement<CosmicWindowRenderElement<R>>),
     FullscreenPopup(CosmicWindowRenderElement<R>),
     Window(CosmicMappedRenderElement<R>),
     Backdrop(TextureRenderElement<GlesTexture>),
+    Wallpaper(MemoryRenderBufferRenderElement<R>),
 }
 
 impl<R> Element for WorkspaceRenderElement<R>
@@ -1181,6 +1281,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.id(),
             WorkspaceRenderElement::Window(elem) => elem.id(),
             WorkspaceRenderElement::Backdrop(elem) => elem.id(),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.id(),
         }
     }
 
@@ -1191,6 +1292,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.current_commit(),
             WorkspaceRenderElement::Window(elem) => elem.current_commit(),
             WorkspaceRenderElement::Backdrop(elem) => elem.current_commit(),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.current_commit(),
         }
     }
 
@@ -1201,6 +1303,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.src(),
             WorkspaceRenderElement::Window(elem) => elem.src(),
             WorkspaceRenderElement::Backdrop(elem) => elem.src(),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.src(),
         }
     }
 
@@ -1211,6 +1314,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.geometry(scale),
             WorkspaceRenderElement::Window(elem) => elem.geometry(scale),
             WorkspaceRenderElement::Backdrop(elem) => elem.geometry(scale),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.geometry(scale),
         }
     }
 
@@ -1221,6 +1325,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.location(scale),
             WorkspaceRenderElement::Window(elem) => elem.location(scale),
             WorkspaceRenderElement::Backdrop(elem) => elem.location(scale),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.location(scale),
         }
     }
 
@@ -1231,6 +1336,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.transform(),
             WorkspaceRenderElement::Window(elem) => elem.transform(),
             WorkspaceRenderElement::Backdrop(elem) => elem.transform(),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.transform(),
         }
     }
 
@@ -1245,6 +1351,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.damage_since(scale, commit),
             WorkspaceRenderElement::Window(elem) => elem.damage_since(scale, commit),
             WorkspaceRenderElement::Backdrop(elem) => elem.damage_since(scale, commit),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.damage_since(scale, commit),
         }
     }
 
@@ -1255,6 +1362,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.opaque_regions(scale),
             WorkspaceRenderElement::Window(elem) => elem.opaque_regions(scale),
             WorkspaceRenderElement::Backdrop(elem) => elem.opaque_regions(scale),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.opaque_regions(scale),
         }
     }
 
@@ -1265,6 +1373,7 @@ where
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.alpha(),
             WorkspaceRenderElement::Window(elem) => elem.alpha(),
             WorkspaceRenderElement::Backdrop(elem) => elem.alpha(),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.alpha(),
         }
     }
 }
@@ -1285,6 +1394,7 @@ impl RenderElement<GlowRenderer> for WorkspaceRenderElement<GlowRenderer> {
             WorkspaceRenderElement::Backdrop(elem) => {
                 RenderElement::<GlowRenderer>::draw(elem, frame, src, dst, damage)
             }
+            WorkspaceRenderElement::Wallpaper(elem) => elem.draw(frame, src, dst, damage),
         }
     }
 
@@ -1298,6 +1408,7 @@ impl RenderElement<GlowRenderer> for WorkspaceRenderElement<GlowRenderer> {
             WorkspaceRenderElement::FullscreenPopup(elem) => elem.underlying_storage(renderer),
             WorkspaceRenderElement::Window(elem) => elem.underlying_storage(renderer),
             WorkspaceRenderElement::Backdrop(elem) => elem.underlying_storage(renderer),
+            WorkspaceRenderElement::Wallpaper(elem) => elem.underlying_storage(renderer),
         }
     }
 }
@@ -1321,6 +1432,7 @@ impl<'a, 'b> RenderElement<GlMultiRenderer<'a, 'b>>
                 RenderElement::<GlowRenderer>::draw(elem, frame.glow_frame_mut(), src, dst, damage)
                     .map_err(GlMultiError::Render)
             }
+            WorkspaceRenderElement::Wallpaper(elem) => elem.draw(frame, src, dst, damage),
         }
     }
 
@@ -1336,6 +1448,7 @@ impl<'a, 'b> RenderElement<GlMultiRenderer<'a, 'b>>
             WorkspaceRenderElement::Backdrop(elem) => {
                 elem.underlying_storage(renderer.glow_renderer_mut())
             }
+            WorkspaceRenderElement::Wallpaper(elem) => elem.underlying_storage(renderer),
         }
     }
 }
@@ -1369,6 +1482,17 @@ where
     CosmicMappedRenderElement<R>: RenderElement<R>,
 {
     fn from(elem: WaylandSurfaceRenderElement<R>) -> Self {
+        WorkspaceRenderElement::OverrideRedirect(elem.into())
+    }
+}
+
+impl<R> From<SurfaceRenderElement<R>> for WorkspaceRenderElement<R>
+where
+    R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+    <R as Renderer>::TextureId: 'static,
+    CosmicMappedRenderElement<R>: RenderElement<R>,
+{
+    fn from(elem: SurfaceRenderElement<R>) -> Self {
         WorkspaceRenderElement::OverrideRedirect(elem)
     }
 }
@@ -1394,3 +1518,14 @@ where
         WorkspaceRenderElement::Backdrop(elem)
     }
 }
+
+impl<R> From<MemoryRenderBufferRenderElement<R>> for WorkspaceRenderElement<R>
+where
+    R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+    <R as Renderer>::TextureId: 'static,
+    CosmicMappedRenderElement<R>: RenderElement<R>,
+{
+    fn from(elem: MemoryRenderBufferRenderElement<R>) -> Self {
+        WorkspaceRenderElement::Wallpaper(elem)
+    }
+}
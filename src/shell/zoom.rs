@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::cell::Cell;
+
+use smithay::{
+    backend::renderer::element::{utils::RescaleRenderElement, Element},
+    output::Output,
+    utils::{Logical, Point},
+};
+
+use crate::utils::prelude::OutputExt;
+
+#[derive(Debug)]
+struct OutputZoom(Cell<f64>);
+
+/// Current magnification of an output, `1.0` if not zoomed
+pub fn zoom_level(output: &Output) -> f64 {
+    output
+        .user_data()
+        .get::<OutputZoom>()
+        .map_or(1.0, |zoom| zoom.0.get())
+}
+
+pub fn set_zoom_level(output: &Output, level: f64) {
+    output
+        .user_data()
+        .insert_if_missing(|| OutputZoom(Cell::new(1.0)));
+    output
+        .user_data()
+        .get::<OutputZoom>()
+        .unwrap()
+        .0
+        .set(level.max(1.0));
+}
+
+/// Magnifies the elements of an output around the given pointer location.
+///
+/// The content under the pointer stays in place, so moving the pointer
+/// towards an edge smoothly pans the view and input needs no remapping.
+pub fn zoom_elements<E: Element>(
+    output: &Output,
+    level: f64,
+    pointer: Point<f64, Logical>,
+    elements: Vec<E>,
+) -> Vec<RescaleRenderElement<E>> {
+    let geometry = output.geometry().to_f64();
+    let origin = Point::<f64, Logical>::from((
+        (pointer.x - geometry.loc.x).clamp(0.0, geometry.size.w),
+        (pointer.y - geometry.loc.y).clamp(0.0, geometry.size.h),
+    ))
+    .to_physical(output.current_scale().fractional_scale())
+    .to_i32_round();
+
+    elements
+        .into_iter()
+        .map(|elem| RescaleRenderElement::from_element(elem, origin, level))
+        .collect()
+}
@@ -2,19 +2,40 @@
 
 use crate::{
     backend::{
+        headless::HeadlessState,
         kms::{source_node_for_surface, KmsState},
         winit::WinitState,
         x11::X11State,
     },
     config::{Config, OutputConfig},
-    input::Devices,
+    dbus::DBusState,
+    idle::IdleState,
+    input::{Devices, SeatName},
+    logind::LogindState,
     shell::{grabs::SeatMoveGrabState, Shell},
     utils::prelude::*,
-    wayland::protocols::{
-        drm::WlDrmState,
-        output_configuration::OutputConfigurationState,
-        screencopy::{BufferParams, ScreencopyState, Session as ScreencopySession},
-        workspace::WorkspaceClientState,
+    wayland::{
+        handlers::{compositor::client_compositor_state, screencopy::UserdataExt},
+        protocols::{
+            alpha_modifier::AlphaModifierState,
+            commit_timing::CommitTimingState,
+            content_type::ContentTypeState,
+            drm::WlDrmState,
+            fifo::FifoState,
+            gamma_control::GammaControlState,
+            idle_inhibit::IdleInhibitManagerState,
+            idle_notify::IdleNotifierState,
+            input_timestamps::InputTimestampsState,
+            output_configuration::OutputConfigurationState,
+            pointer_warp::PointerWarpState,
+            screencopy::{BufferParams, ScreencopyState, Session as ScreencopySession},
+            screencopy_frame_rate::ScreencopyFrameRateState,
+            system_bell::SystemBellState,
+            toplevel_drag::ToplevelDragState,
+            workspace::WorkspaceClientState,
+            xdg_dialog::XdgDialogState,
+            xdg_foreign::XdgForeignState,
+        },
     },
     xwayland::XWaylandState,
 };
@@ -24,7 +45,7 @@ use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
     DesktopLanguageRequester,
 };
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use rust_embed::RustEmbed;
 #[cfg(feature = "debug")]
 use smithay::utils::Rectangle;
@@ -50,16 +71,18 @@ use smithay::{
     output::{Mode as OutputMode, Output, Scale},
     reexports::{
         calloop::{LoopHandle, LoopSignal},
+        wayland_protocols::ext::session_lock::v1::server::ext_session_lock_v1::ExtSessionLockV1,
         wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration_manager::Mode,
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
-            protocol::{wl_output, wl_shm},
+            protocol::{wl_output, wl_shm, wl_surface::WlSurface},
             Client, DisplayHandle,
         },
     },
     utils::{Clock, IsAlive, Monotonic},
     wayland::{
-        compositor::{CompositorClientState, CompositorState},
+        compositor::{send_surface_state, CompositorClientState, CompositorState, SurfaceData},
+        cursor_shape::CursorShapeManagerState,
         dmabuf::{DmabufFeedback, DmabufState},
         fractional_scale::{with_fractional_scale, FractionalScaleManagerState},
         keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitState,
@@ -69,18 +92,29 @@ use smithay::{
         presentation::PresentationState,
         seat::WaylandFocus,
         security_context::{SecurityContext, SecurityContextState},
-        selection::{data_device::DataDeviceState, primary_selection::PrimarySelectionState},
-        session_lock::{surface::LockSurface, SessionLocker, SessionLockManagerState},
+        selection::{
+            data_device::DataDeviceState, primary_selection::PrimarySelectionState,
+            wlr_data_control::DataControlState,
+        },
+        session_lock::{surface::LockSurface, SessionLockManagerState},
         shell::{kde::decoration::KdeDecorationState, xdg::decoration::XdgDecorationState},
         shm::ShmState,
+        single_pixel_buffer::SinglePixelBufferState,
         viewporter::ViewporterState,
+        virtual_keyboard::VirtualKeyboardManagerState,
+        xdg_activation::XdgActivationState,
         xwayland_keyboard_grab::XWaylandKeyboardGrabState,
+        xwayland_shell::XWaylandShellState,
     },
 };
 use tracing::error;
 
-use std::{cell::RefCell, ffi::OsString, time::Duration};
-use std::{collections::{HashMap, VecDeque}, time::Instant};
+use std::{cell::RefCell, ffi::OsString, os::unix::net::UnixStream, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 #[derive(RustEmbed)]
 #[folder = "resources/i18n"]
@@ -106,6 +140,25 @@ pub struct ClientState {
     pub privileged: bool,
     pub evls: LoopSignal,
     pub security_context: Option<SecurityContext>,
+    /// Canonical path of the executable of the client's process, if it could be determined
+    pub executable: OnceCell<PathBuf>,
+}
+impl ClientState {
+    /// Whether the client is one of `clients`, by the absolute path of its executable or,
+    /// if it is sandboxed, only by its app id
+    fn is_any_of(&self, clients: &[String]) -> bool {
+        match &self.security_context {
+            Some(context) => context
+                .app_id
+                .as_ref()
+                .map_or(false, |app_id| clients.contains(app_id)),
+            None => self.executable.get().map_or(false, |executable| {
+                clients.iter().any(|client| {
+                    Path::new(client).is_absolute() && executable == Path::new(client)
+                })
+            }),
+        }
+    }
 }
 impl ClientData for ClientState {
     fn initialized(&self, _client_id: ClientId) {}
@@ -137,33 +190,57 @@ pub struct Common {
 
     pub clock: Clock<Monotonic>,
     pub should_stop: bool,
+    /// Highlight damaged regions on all outputs
+    pub visualize_damage: bool,
 
     #[cfg(feature = "debug")]
     pub egui: Egui,
 
     // wayland state
+    pub alpha_modifier_state: AlphaModifierState,
+    pub commit_timing_state: CommitTimingState<State>,
     pub compositor_state: CompositorState,
+    pub content_type_state: ContentTypeState,
+    pub data_control_state: DataControlState,
     pub data_device_state: DataDeviceState,
     pub dmabuf_state: DmabufState,
+    pub fifo_state: FifoState,
     pub fractional_scale_state: FractionalScaleManagerState,
+    pub gamma_control_state: GammaControlState,
+    pub idle_inhibit_manager_state: IdleInhibitManagerState,
+    pub idle_notifier_state: IdleNotifierState<State>,
+    pub input_timestamps_state: InputTimestampsState,
     pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
     pub output_state: OutputManagerState,
     pub output_configuration_state: OutputConfigurationState<State>,
+    pub pointer_warp_state: PointerWarpState,
     pub presentation_state: PresentationState,
     pub primary_selection_state: PrimarySelectionState,
     pub screencopy_state: ScreencopyState,
     pub seat_state: SeatState<State>,
     pub session_lock_manager_state: SessionLockManagerState,
     pub shm_state: ShmState,
+    pub toplevel_drag_state: ToplevelDragState,
     pub wl_drm_state: WlDrmState,
     pub viewporter_state: ViewporterState,
     pub kde_decoration_state: KdeDecorationState,
     pub xdg_decoration_state: XdgDecorationState,
+    pub xdg_activation_state: XdgActivationState,
+    pub xdg_dialog_state: XdgDialogState,
+    pub xdg_foreign_state: XdgForeignState,
 
     pub session_lock: Option<SessionLock>,
+    /// Internal outputs turned off because the lid was closed
+    pub lid_disabled_outputs: Vec<Output>,
+    pub logind: Option<LogindState>,
+    pub idle: IdleState,
+    pub dbus: Option<DBusState>,
+    /// Connection to cosmic-session, to update the environment it starts apps with
+    pub session_socket: Option<UnixStream>,
 
     // xwayland state
     pub xwayland_state: Option<XWaylandState>,
+    pub xwayland_shell_state: XWaylandShellState,
 }
 
 #[derive(Debug)]
@@ -171,6 +248,7 @@ pub enum BackendData {
     X11(X11State),
     Winit(WinitState),
     Kms(KmsState),
+    Headless(HeadlessState),
     // TODO
     // Wayland(WaylandState),
     Unset,
@@ -184,7 +262,9 @@ pub struct SurfaceDmabufFeedback {
 
 #[derive(Debug)]
 pub struct SessionLock {
-    //pub locker: SessionLocker,
+    /// Lock object of the client holding the lock, only it may unlock the session
+    pub lock: ExtSessionLockV1,
+    pub client: ClientId,
     pub surfaces: HashMap<Output, LockSurface>,
 }
 
@@ -210,6 +290,13 @@ impl BackendData {
         }
     }
 
+    pub fn headless(&mut self) -> &mut HeadlessState {
+        match self {
+            BackendData::Headless(ref mut headless_state) => headless_state,
+            _ => unreachable!("Called headless in non headless backend"),
+        }
+    }
+
     pub fn apply_config_for_output(
         &mut self,
         output: &Output,
@@ -224,6 +311,9 @@ impl BackendData {
             }
             BackendData::Winit(ref mut state) => state.apply_config_for_output(output, test_only),
             BackendData::X11(ref mut state) => state.apply_config_for_output(output, test_only),
+            BackendData::Headless(ref mut state) => {
+                state.apply_config_for_output(output, seats, shell, test_only)
+            }
             _ => unreachable!("No backend set when applying output config"),
         };
 
@@ -248,12 +338,35 @@ impl BackendData {
                 .filter(|x| *x != output.current_scale().fractional_scale());
             let location =
                 Some(final_config.position.into()).filter(|x| *x != output.current_location());
+            // rotating or scaling changes the logical size just like a new mode
+            let resized = mode.is_some() || transform.is_some() || scale.is_some();
             output.change_current_state(mode, transform, scale.map(Scale::Fractional), location);
+            if resized && !test_only {
+                shell.output_resized(output);
+            }
         }
 
         result
     }
 
+    /// Turns an output off or on again, while keeping it mapped
+    pub fn set_output_power(
+        &mut self,
+        output: &Output,
+        on: bool,
+        shell: &mut Shell,
+        seats: impl Iterator<Item = Seat<State>>,
+        loop_handle: &LoopHandle<'_, State>,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            BackendData::Kms(ref mut state) => {
+                state.set_output_power(output, on, shell, seats, loop_handle)
+            }
+            // nested and virtual outputs have no panel to turn off
+            _ => Ok(()),
+        }
+    }
+
     pub fn schedule_render(
         &mut self,
         loop_handle: &LoopHandle<'_, State>,
@@ -265,6 +378,7 @@ impl BackendData {
             // Winit has a very strict render-loop and skipping frames breaks atleast the wayland winit-backend.
             // Swapping with damage (which should be empty on these frames) is likely good enough anyway.
             BackendData::X11(ref mut state) => state.schedule_render(output, screencopy),
+            BackendData::Headless(ref mut state) => state.schedule_render(output, screencopy),
             BackendData::Kms(ref mut state) => {
                 if let Err(err) = state.schedule_render(loop_handle, output, None, screencopy) {
                     error!(?err, "Failed to schedule event, are we shutting down?");
@@ -275,10 +389,35 @@ impl BackendData {
     }
 }
 
-pub fn client_has_security_context(client: &Client) -> bool {
+/// Sandboxed clients only see privileged globals, if their app id was allowed in the config
+pub fn client_should_see_privileged_protocols(client: &Client) -> bool {
     client
         .get_data::<ClientState>()
-        .map_or(true, |client_state| client_state.security_context.is_none())
+        .map_or(true, |client_state| {
+            client_state.security_context.is_none() || client_state.privileged
+        })
+}
+
+/// Filter for the privileged global `interface`, which may additionally be restricted to
+/// some clients by the `restricted_globals` of the config
+pub fn privileged_global_filter(
+    config: &Config,
+    interface: &str,
+) -> impl Fn(&Client) -> bool + Send + Sync + 'static {
+    let allowed = config
+        .static_conf
+        .security_context
+        .restricted_globals
+        .get(interface)
+        .cloned();
+    move |client| {
+        client_should_see_privileged_protocols(client)
+            && allowed.as_ref().map_or(true, |allowed| {
+                client
+                    .get_data::<ClientState>()
+                    .map_or(false, |client_state| client_state.is_any_of(allowed))
+            })
+    }
 }
 
 impl State {
@@ -295,33 +434,82 @@ impl State {
 
         let clock = Clock::new().expect("Failed to initialize clock");
         let config = Config::load(&handle);
-        let compositor_state = CompositorState::new::<Self>(dh);
+        let alpha_modifier_state = AlphaModifierState::new::<Self>(dh);
+        let commit_timing_state = CommitTimingState::new(dh, handle.clone());
+        let compositor_state = CompositorState::new_v6::<Self>(dh);
+        let content_type_state = ContentTypeState::new::<Self>(dh);
         let data_device_state = DataDeviceState::new::<Self>(dh);
         let dmabuf_state = DmabufState::new();
+        let fifo_state = FifoState::new::<Self>(dh);
         let fractional_scale_state = FractionalScaleManagerState::new::<State>(dh);
+        let gamma_control_state = GammaControlState::new::<Self, _>(
+            dh,
+            privileged_global_filter(&config, "zwlr_gamma_control_manager_v1"),
+        );
+        let idle_inhibit_manager_state = IdleInhibitManagerState::new::<Self>(dh);
+        let idle_notifier_state = IdleNotifierState::new(
+            dh,
+            handle.clone(),
+            privileged_global_filter(&config, "ext_idle_notifier_v1"),
+        );
+        let input_timestamps_state = InputTimestampsState::new::<Self>(dh);
         let keyboard_shortcuts_inhibit_state = KeyboardShortcutsInhibitState::new::<Self>(dh);
         let output_state = OutputManagerState::new_with_xdg_output::<Self>(dh);
-        let output_configuration_state =
-            OutputConfigurationState::new(dh, client_has_security_context);
+        let output_configuration_state = OutputConfigurationState::new(
+            dh,
+            privileged_global_filter(&config, "zwlr_output_manager_v1"),
+        );
+        let pointer_warp_state = PointerWarpState::new::<Self>(dh);
         let presentation_state = PresentationState::new::<Self>(dh, clock.id() as u32);
         let primary_selection_state = PrimarySelectionState::new::<Self>(dh);
+        let data_control_enabled = config.static_conf.data_control;
+        let data_control_filter = privileged_global_filter(&config, "zwlr_data_control_manager_v1");
+        let data_control_state =
+            DataControlState::new::<Self, _>(dh, Some(&primary_selection_state), move |client| {
+                data_control_enabled && data_control_filter(client)
+            });
         let screencopy_state = ScreencopyState::new::<Self, _, _>(
             dh,
-            vec![CursorMode::Embedded, CursorMode::Hidden],
-            client_has_security_context,
-        ); // TODO: privileged
+            vec![
+                CursorMode::Embedded,
+                CursorMode::Hidden,
+                CursorMode::Capture,
+            ],
+            privileged_global_filter(&config, "zcosmic_screencopy_manager_v1"),
+        );
+        ScreencopyFrameRateState::new::<Self>(dh);
         let shm_state =
             ShmState::new::<Self>(dh, vec![wl_shm::Format::Xbgr8888, wl_shm::Format::Abgr8888]);
         let seat_state = SeatState::<Self>::new();
+        let toplevel_drag_state = ToplevelDragState::new::<Self>(dh);
         let viewporter_state = ViewporterState::new::<Self>(dh);
         let wl_drm_state = WlDrmState;
         let kde_decoration_state = KdeDecorationState::new::<Self>(&dh, Mode::Client);
         let xdg_decoration_state = XdgDecorationState::new::<Self>(&dh);
+        let xdg_activation_state = XdgActivationState::new::<Self>(&dh);
+        let xdg_dialog_state = XdgDialogState::new::<Self>(&dh);
+        let xdg_foreign_state = XdgForeignState::new::<Self>(&dh);
         let session_lock_manager_state = SessionLockManagerState::new::<Self>(&dh);
         XWaylandKeyboardGrabState::new::<Self>(&dh);
+        let xwayland_shell_state = XWaylandShellState::new::<Self>(&dh);
         PointerConstraintsState::new::<Self>(&dh);
+        CursorShapeManagerState::new::<Self>(&dh);
         PointerGesturesState::new::<Self>(&dh);
-        SecurityContextState::new::<Self, _>(&dh, client_has_security_context);
+        SinglePixelBufferState::new::<Self>(&dh);
+        SystemBellState::new::<Self>(&dh);
+        let security_context_filter =
+            privileged_global_filter(&config, "wp_security_context_manager_v1");
+        // sandboxed clients may never create nested contexts, even if they are privileged
+        SecurityContextState::new::<Self, _>(&dh, move |client: &Client| {
+            security_context_filter(client)
+                && client
+                    .get_data::<ClientState>()
+                    .map_or(true, |client_state| client_state.security_context.is_none())
+        });
+        VirtualKeyboardManagerState::new::<Self, _>(
+            &dh,
+            privileged_global_filter(&config, "zwp_virtual_keyboard_manager_v1"),
+        );
 
         let shell = Shell::new(&config, dh);
 
@@ -340,6 +528,7 @@ impl State {
 
                 clock,
                 should_stop: false,
+                visualize_damage: false,
 
                 #[cfg(feature = "debug")]
                 egui: Egui {
@@ -350,27 +539,47 @@ impl State {
                     )),
                 },
 
+                alpha_modifier_state,
+                commit_timing_state,
                 compositor_state,
+                content_type_state,
+                data_control_state,
                 data_device_state,
                 dmabuf_state,
+                fifo_state,
                 fractional_scale_state,
+                gamma_control_state,
+                idle_inhibit_manager_state,
+                idle_notifier_state,
+                input_timestamps_state,
                 screencopy_state,
                 shm_state,
+                toplevel_drag_state,
                 seat_state,
                 session_lock_manager_state,
                 keyboard_shortcuts_inhibit_state,
                 output_state,
                 output_configuration_state,
+                pointer_warp_state,
                 presentation_state,
                 primary_selection_state,
                 viewporter_state,
                 wl_drm_state,
                 kde_decoration_state,
                 xdg_decoration_state,
+                xdg_activation_state,
+                xdg_dialog_state,
+                xdg_foreign_state,
 
                 session_lock: None,
+                lid_disabled_outputs: Vec::new(),
+                logind: None,
+                idle: IdleState::new(),
+                dbus: None,
+                session_socket: None,
 
                 xwayland_state: None,
+                xwayland_shell_state,
             },
             backend: BackendData::Unset,
         }
@@ -398,6 +607,7 @@ impl State {
             privileged: false,
             evls: self.common.event_loop_signal.clone(),
             security_context: None,
+            executable: OnceCell::new(),
         }
     }
 
@@ -409,6 +619,7 @@ impl State {
             privileged: false,
             evls: self.common.event_loop_signal.clone(),
             security_context: None,
+            executable: OnceCell::new(),
         }
     }
 
@@ -423,7 +634,29 @@ impl State {
             privileged: true,
             evls: self.common.event_loop_signal.clone(),
             security_context: None,
+            executable: OnceCell::new(),
+        }
+    }
+
+    /// Adds a new client, recording its executable to match it against `restricted_globals`
+    pub fn insert_client(
+        &mut self,
+        stream: UnixStream,
+        client_state: ClientState,
+    ) -> std::io::Result<Client> {
+        let dh = &mut self.common.display_handle;
+        let client = dh.insert_client(stream, Arc::new(client_state))?;
+        let executable = client.get_credentials(dh).ok().and_then(|credentials| {
+            std::fs::canonicalize(format!("/proc/{}/exe", credentials.pid)).ok()
+        });
+        if let Some(executable) = executable {
+            let _ = client
+                .get_data::<ClientState>()
+                .unwrap()
+                .executable
+                .set(executable);
         }
+        Ok(client)
     }
 }
 
@@ -456,10 +689,24 @@ impl Common {
         })
     }
 
+    pub fn seat_by_name(&self, name: &str) -> Option<&Seat<State>> {
+        self.seats().find(|seat| {
+            seat.user_data()
+                .get::<SeatName>()
+                .map_or(false, |seat_name| seat_name.0 == name)
+        })
+    }
+
     pub fn last_active_seat(&self) -> &Seat<State> {
         self.last_active_seat.as_ref().expect("No seat?")
     }
 
+    pub fn set_last_active_seat(&mut self, seat: &Seat<State>) {
+        if self.seats.contains(seat) {
+            self.last_active_seat = Some(seat.clone());
+        }
+    }
+
     pub fn send_frames(
         &self,
         output: &Output,
@@ -548,13 +795,17 @@ impl Common {
                         },
                     );
                     if let Some(output) = primary_scanout_output {
-                        with_fractional_scale(states, |fraction_scale| {
-                            fraction_scale
-                                .set_preferred_scale(output.current_scale().fractional_scale());
-                        });
+                        send_preferred_buffer_state(surface, states, &output);
                     }
                 });
                 window.send_frame(output, time, throttle, surface_primary_scanout_output);
+                // hidden tabs are only visible to captures
+                for (tab, _) in mapped
+                    .windows()
+                    .filter(|(tab, _)| tab != &window && !tab.sessions().is_empty())
+                {
+                    tab.send_frame(output, time, Some(Duration::ZERO), |_, _| None);
+                }
                 if let Some(feedback) = window
                     .wl_surface()
                     .and_then(|wl_surface| {
@@ -578,10 +829,20 @@ impl Common {
             .spaces()
             .filter(|w| w.handle != active.handle)
         {
+            let space_captured = !space.screencopy_sessions.is_empty();
             space.mapped().for_each(|mapped| {
                 if space.outputs_for_element(mapped).any(|o| &o == output) {
-                    let window = mapped.active_window();
-                    window.send_frame(output, time, throttle, |_, _| None);
+                    let active = mapped.active_window();
+                    for (window, _) in mapped.windows() {
+                        // captured windows keep drawing at the refresh rate, even if not visible
+                        let captured =
+                            !window.sessions().is_empty() || (space_captured && window == active);
+                        if captured {
+                            window.send_frame(output, time, Some(Duration::ZERO), |_, _| None);
+                        } else if window == active {
+                            window.send_frame(output, time, throttle, |_, _| None);
+                        }
+                    }
                 }
             });
         }
@@ -597,10 +858,7 @@ impl Common {
                         default_primary_scanout_output_compare,
                     );
                     if let Some(output) = primary_scanout_output {
-                        with_fractional_scale(states, |fraction_scale| {
-                            fraction_scale
-                                .set_preferred_scale(output.current_scale().fractional_scale());
-                        });
+                        send_preferred_buffer_state(surface, states, &output);
                     }
                 });
                 send_frames_surface_tree(
@@ -641,10 +899,7 @@ impl Common {
                     default_primary_scanout_output_compare,
                 );
                 if let Some(output) = primary_scanout_output {
-                    with_fractional_scale(states, |fraction_scale| {
-                        fraction_scale
-                            .set_preferred_scale(output.current_scale().fractional_scale());
-                    });
+                    send_preferred_buffer_state(surface, states, &output);
                 }
             });
             layer_surface.send_frame(output, time, throttle, surface_primary_scanout_output);
@@ -666,6 +921,47 @@ impl Common {
                 );
             }
         }
+
+        if let Some(lock_surface) = self
+            .session_lock
+            .as_ref()
+            .and_then(|lock| lock.surfaces.get(output))
+        {
+            // lock surfaces are always only visible on their own output
+            with_surfaces_surface_tree(lock_surface.wl_surface(), |surface, states| {
+                send_preferred_buffer_state(surface, states, output);
+            });
+            send_frames_surface_tree(lock_surface.wl_surface(), output, time, throttle, |_, _| {
+                Some(output.clone())
+            });
+            // they cover the whole output, so are good candidates for direct scanout
+            if let Some(feedback) =
+                source_node_for_surface(lock_surface.wl_surface(), &self.display_handle)
+                    .and_then(|source| dmabuf_feedback(source))
+            {
+                send_dmabuf_feedback_surface_tree(
+                    lock_surface.wl_surface(),
+                    output,
+                    |_, _| Some(output.clone()),
+                    |surface, _| {
+                        select_dmabuf_feedback(
+                            surface,
+                            render_element_states,
+                            &feedback.render_feedback,
+                            &feedback.scanout_feedback,
+                        )
+                    },
+                );
+            }
+        }
+
+        // a refresh cycle of the output clears the fifo barriers of the surfaces shown on it
+        for client in self.fifo_state.clear_barriers(output) {
+            self.event_loop_handle.insert_idle(move |state| {
+                let dh = state.common.display_handle.clone();
+                client_compositor_state(&client).blocker_cleared(state, &dh);
+            });
+        }
     }
 
     pub fn take_presentation_feedback(
@@ -732,6 +1028,7 @@ pub struct Fps {
     pub state: smithay_egui::EguiState,
     pending_frame: Option<PendingFrame>,
     pub frames: VecDeque<Frame>,
+    missed_vblanks: VecDeque<Instant>,
 }
 
 #[derive(Debug)]
@@ -837,6 +1134,32 @@ impl Fps {
         }
     }
 
+    /// Records a frame, that wasn't ready in time for the vblank it was scheduled for
+    pub fn missed_vblank(&mut self) {
+        self.missed_vblanks.push_back(Instant::now());
+        if let Some(first) = self.frames.front() {
+            while self
+                .missed_vblanks
+                .front()
+                .is_some_and(|missed| *missed < first.start)
+            {
+                self.missed_vblanks.pop_front();
+            }
+        }
+        while self.missed_vblanks.len() > Fps::WINDOW_SIZE {
+            self.missed_vblanks.pop_front();
+        }
+    }
+
+    /// Number of missed vblanks during the frames currently tracked
+    pub fn missed_vblanks(&self) -> usize {
+        let first = self.frames.front().map(|frame| frame.start);
+        self.missed_vblanks
+            .iter()
+            .filter(|missed| first.map_or(true, |first| **missed >= first))
+            .count()
+    }
+
     pub fn max_frametime(&self) -> Duration {
         self.frames
             .iter()
@@ -937,10 +1260,27 @@ impl Fps {
             rd: renderdoc::RenderDoc::new().ok(),
             pending_frame: None,
             frames: VecDeque::with_capacity(Fps::WINDOW_SIZE + 1),
+            missed_vblanks: VecDeque::new(),
         }
     }
 }
 
+/// Sends the preferred scale and buffer transform of `output` to `surface`.
+///
+/// Clients matching the transform of a rotated output can have their buffers scanned out
+/// directly, instead of being composited.
+fn send_preferred_buffer_state(surface: &WlSurface, states: &SurfaceData, output: &Output) {
+    with_fractional_scale(states, |fraction_scale| {
+        fraction_scale.set_preferred_scale(output.current_scale().fractional_scale());
+    });
+    send_surface_state(
+        surface,
+        states,
+        output.current_scale().integer_scale(),
+        output.current_transform(),
+    );
+}
+
 pub fn avg_fps<'a>(iter: impl Iterator<Item = &'a Duration>) -> f64 {
     let sum_secs = iter.map(|d| d.as_secs_f64()).sum::<f64>();
     1.0 / (sum_secs / Fps::WINDOW_SIZE as f64)
@@ -6,6 +6,17 @@ use std::process::Command;
 use tracing::{error, warn};
 
 pub fn ready(state: &State) {
+    if booted() {
+        import_environment(state);
+
+        if let Err(err) = notify(false, &[NotifyState::Ready]) {
+            error!(?err, "Failed to notify systemd");
+        }
+    }
+}
+
+/// Tells systemd about the displays of the session, e.g. after Xwayland was restarted
+pub fn import_environment(state: &State) {
     if booted() {
         match Command::new("systemctl")
             .args(["--user", "import-environment", "WAYLAND_DISPLAY", "DISPLAY"])
@@ -28,9 +39,5 @@ pub fn ready(state: &State) {
             ),
             Err(err) => error!(?err, "Failed to run systemctl although booted with systemd",),
         };
-
-        if let Err(err) = notify(false, &[NotifyState::Ready]) {
-            error!(?err, "Failed to notify systemd");
-        }
     }
 }
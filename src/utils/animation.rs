@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::{Duration, Instant};
+
+use keyframe::{
+    ease,
+    functions::{EaseInCubic, EaseInOutCubic, EaseOutCubic, Linear},
+    CanTween,
+};
+
+use crate::config::{AnimationConfig, AnimationCurve, AnimationSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimationKind {
+    WorkspaceSwitch,
+    Overview,
+    Fullscreen,
+    Tiling,
+    WindowMove,
+    Tabs,
+    TabScroll,
+    WindowOpen,
+}
+
+impl AnimationKind {
+    fn settings(self, config: &AnimationConfig) -> AnimationSettings {
+        match self {
+            AnimationKind::WorkspaceSwitch => config.workspace_switch,
+            AnimationKind::Overview => config.overview,
+            AnimationKind::Fullscreen => config.fullscreen,
+            AnimationKind::Tiling => config.tiling,
+            AnimationKind::WindowMove => config.window_move,
+            AnimationKind::Tabs => config.tabs,
+            AnimationKind::TabScroll => config.tab_scroll,
+            AnimationKind::WindowOpen => config.window_open,
+        }
+    }
+
+    /// Configured duration, zero if animations are disabled
+    pub fn duration(self, config: &AnimationConfig) -> Duration {
+        if config.reduced_motion {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(self.settings(config).duration)
+        }
+    }
+
+    pub fn curve(self, config: &AnimationConfig) -> AnimationCurve {
+        self.settings(config).curve
+    }
+}
+
+/// Eases between two values along the given curve, `progress` ranging from `0.0` to `1.0`
+pub fn ease_along<V: CanTween>(curve: AnimationCurve, from: V, to: V, progress: f32) -> V {
+    let progress = progress.clamp(0.0, 1.0);
+    match curve {
+        AnimationCurve::Linear => ease(Linear, from, to, progress),
+        AnimationCurve::EaseInCubic => ease(EaseInCubic, from, to, progress),
+        AnimationCurve::EaseOutCubic => ease(EaseOutCubic, from, to, progress),
+        AnimationCurve::EaseInOutCubic => ease(EaseInOutCubic, from, to, progress),
+    }
+}
+
+/// Linear progress of an animation of the given duration, that started at `start`
+pub fn progress(start: Instant, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return 1.0;
+    }
+    (Instant::now().duration_since(start).as_secs_f32() / duration.as_secs_f32()).min(1.0)
+}
+
+/// An animation of a certain kind started at a point in time,
+/// keeping the duration and curve configured when it was created
+#[derive(Debug, Clone, Copy)]
+pub struct Animation {
+    start: Instant,
+    duration: Duration,
+    curve: AnimationCurve,
+}
+
+impl Animation {
+    pub fn new(kind: AnimationKind, config: &AnimationConfig) -> Animation {
+        Animation::starting_at(kind, Instant::now(), config)
+    }
+
+    pub fn starting_at(kind: AnimationKind, start: Instant, config: &AnimationConfig) -> Animation {
+        Animation {
+            start,
+            duration: kind.duration(config),
+            curve: kind.curve(config),
+        }
+    }
+
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Linear progress from `0.0` to `1.0`
+    pub fn progress(&self) -> f32 {
+        progress(self.start, self.duration)
+    }
+
+    pub fn is_done(&self) -> bool {
+        Instant::now().duration_since(self.start) >= self.duration
+    }
+
+    /// Value between `from` and `to` at the current progress, eased along the configured curve
+    pub fn value<V: CanTween>(&self, from: V, to: V) -> V {
+        ease_along(self.curve, from, to, self.progress())
+    }
+}
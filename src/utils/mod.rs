@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+pub mod animation;
 mod ids;
 pub(crate) use self::ids::id_gen;
 pub mod iced;
@@ -1,13 +1,13 @@
 use std::{cell::RefCell, sync::Mutex, time::Duration};
 
 use crate::{
-    backend::render::cursor::{CursorShape, CursorState},
+    backend::render::cursor::CursorState,
     input::{ActiveOutput, SeatId},
 };
 use smithay::{
     desktop::utils::bbox_from_surface_tree,
     input::{
-        pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus},
+        pointer::{CursorImageAttributes, CursorImageStatus},
         Seat,
     },
     output::Output,
@@ -111,15 +111,12 @@ impl SeatExt for Seat<State> {
                 );
                 Some((buffer_geo, (hotspot.x, hotspot.y).into()))
             }
-            CursorImageStatus::Named(CursorIcon::Default) => {
+            CursorImageStatus::Named(icon) => {
                 let seat_userdata = self.user_data();
                 seat_userdata.insert_if_missing(CursorState::default);
                 let state = seat_userdata.get::<CursorState>().unwrap();
-                let frame = state
-                    .cursors
-                    .get(&CursorShape::Default)
-                    .unwrap()
-                    .get_image(1, Into::<Duration>::into(time).as_millis() as u32);
+                let frame =
+                    state.named_image(icon, 1, Into::<Duration>::into(time).as_millis() as u32);
 
                 Some((
                     Rectangle::from_loc_and_size(
@@ -129,10 +126,6 @@ impl SeatExt for Seat<State> {
                     (frame.xhot as i32, frame.yhot as i32).into(),
                 ))
             }
-            CursorImageStatus::Named(_) => {
-                // TODO: Handle for `cursor_shape_v1` protocol
-                None
-            }
             CursorImageStatus::Hidden => None,
         }
     }
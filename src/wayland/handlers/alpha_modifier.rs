@@ -0,0 +1,5 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{state::State, wayland::protocols::alpha_modifier::delegate_alpha_modifier};
+
+delegate_alpha_modifier!(State);
@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State,
+    wayland::protocols::commit_timing::{
+        delegate_commit_timing, CommitTimingHandler, CommitTimingState,
+    },
+};
+
+impl CommitTimingHandler for State {
+    fn commit_timing_state(&mut self) -> &mut CommitTimingState<State> {
+        &mut self.common.commit_timing_state
+    }
+}
+
+delegate_commit_timing!(State);
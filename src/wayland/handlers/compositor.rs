@@ -188,6 +188,7 @@ impl CompositorHandler for State {
                         let output = seat.active_output();
                         window.on_commit();
                         Shell::map_window(self, &window, &output);
+                        self.update_toplevel_drags();
                     } else {
                         return;
                     }
@@ -229,6 +230,9 @@ impl CompositorHandler for State {
             }
         }
 
+        // commits may change the app id, or acknowledge the window being (un)tiled
+        self.refresh_decoration_rule(surface);
+
         //handle window screencopy sessions
         self.schedule_window_session(surface);
 
@@ -259,6 +263,14 @@ impl CompositorHandler for State {
             }
         }
 
+        if self.common.session_lock.as_ref().map_or(false, |lock| {
+            lock.surfaces
+                .values()
+                .any(|lock_surface| lock_surface.wl_surface() == surface)
+        }) {
+            self.lock_surface_committed();
+        }
+
         let mut scheduled_sessions = self.schedule_workspace_sessions(surface);
 
         // schedule a new render
@@ -0,0 +1,5 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{state::State, wayland::protocols::content_type::delegate_content_type};
+
+delegate_content_type!(State);
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::state::State;
+use smithay::{delegate_cursor_shape, wayland::tablet_manager::TabletSeatHandler};
+
+// shapes requested for the pointer end up in `SeatHandler::cursor_image`,
+// tablet tools are not handled yet.
+impl TabletSeatHandler for State {}
+
+delegate_cursor_shape!(State);
@@ -25,17 +25,13 @@ pub fn get_dnd_icon(seat: &Seat<State>) -> Option<WlSurface> {
 }
 
 impl ClientDndGrabHandler for State {
-    fn started(
-        &mut self,
-        _source: Option<WlDataSource>,
-        icon: Option<WlSurface>,
-        seat: Seat<Self>,
-    ) {
+    fn started(&mut self, source: Option<WlDataSource>, icon: Option<WlSurface>, seat: Seat<Self>) {
         let user_data = seat.user_data();
         user_data.insert_if_missing(|| DnDIcon {
             surface: RefCell::new(None),
         });
         *user_data.get::<DnDIcon>().unwrap().surface.borrow_mut() = icon;
+        self.toplevel_drag_started(&seat, source);
     }
     fn dropped(&mut self, seat: Seat<Self>) {
         seat.user_data()
@@ -44,6 +40,7 @@ impl ClientDndGrabHandler for State {
             .surface
             .borrow_mut()
             .take();
+        self.toplevel_drag_dropped(&seat);
     }
 }
 impl ServerDndGrabHandler for State {}
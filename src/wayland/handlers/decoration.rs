@@ -1,4 +1,6 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+
+use cosmic_comp_config::decoration::DecorationMode;
 
 use smithay::{
     delegate_kde_decoration, delegate_xdg_decoration,
@@ -11,6 +13,7 @@ use smithay::{
         wayland_server::protocol::wl_surface::WlSurface,
     },
     wayland::{
+        compositor::with_states,
         seat::WaylandFocus,
         shell::{
             kde::decoration::{KdeDecorationHandler, KdeDecorationState},
@@ -21,12 +24,57 @@ use smithay::{
 use wayland_backend::protocol::WEnum;
 
 use crate::{
+    config::DecorationRules,
     shell::{CosmicMapped, CosmicSurface},
     state::State,
 };
 
 pub struct PreferredDecorationMode(RefCell<Option<XdgMode>>);
 
+/// Marks windows, whose decoration mode is currently forced by a decoration rule
+#[derive(Default)]
+struct ForcedDecorationMode {
+    forced: Cell<bool>,
+    /// App id and tiling state the rules were last applied with
+    applied_for: RefCell<Option<(String, bool)>>,
+}
+
+impl ForcedDecorationMode {
+    fn is_forced(window: &Window) -> bool {
+        window
+            .user_data()
+            .get::<ForcedDecorationMode>()
+            .map_or(false, |forced| forced.forced.get())
+    }
+}
+
+/// The server decoration object of a surface, which is told about modes forced by a rule
+#[derive(Default)]
+struct KdeDecoration(RefCell<Option<OrgKdeKwinServerDecoration>>);
+
+impl KdeDecoration {
+    fn set(surface: &WlSurface, decoration: Option<OrgKdeKwinServerDecoration>) {
+        with_states(surface, |states| {
+            states.data_map.insert_if_missing(KdeDecoration::default);
+            *states
+                .data_map
+                .get::<KdeDecoration>()
+                .unwrap()
+                .0
+                .borrow_mut() = decoration;
+        });
+    }
+
+    fn get(surface: &WlSurface) -> Option<OrgKdeKwinServerDecoration> {
+        with_states(surface, |states| {
+            states
+                .data_map
+                .get::<KdeDecoration>()
+                .and_then(|decoration| decoration.0.borrow().clone())
+        })
+    }
+}
+
 impl PreferredDecorationMode {
     pub fn is_unset(window: &Window) -> bool {
         window
@@ -75,6 +123,16 @@ impl State {
                 .windows()
                 .find(|(window, _)| window.wl_surface().as_ref() == Some(surface))
             {
+                if ForcedDecorationMode::is_forced(&window) {
+                    let mode = window
+                        .toplevel()
+                        .with_pending_state(|state| state.decoration_mode);
+                    window.toplevel().send_configure();
+                    return match mode {
+                        Some(XdgMode::ServerSide) => KdeMode::Server,
+                        _ => KdeMode::Client,
+                    };
+                }
                 window
                     .toplevel()
                     .with_pending_state(|state| state.decoration_mode = Some(XdgMode::ClientSide));
@@ -90,9 +148,12 @@ impl State {
             .find(|(window, _)| window.wl_surface().as_ref() == Some(surface))
         {
             PreferredDecorationMode::update(&window, Some(mode));
-            window.toplevel().with_pending_state(|state| {
-                state.decoration_mode = Some(mode);
-            });
+            // the request is remembered, if the rule stops to apply
+            if !ForcedDecorationMode::is_forced(&window) {
+                window.toplevel().with_pending_state(|state| {
+                    state.decoration_mode = Some(mode);
+                });
+            }
             window.toplevel().send_configure();
         }
     }
@@ -103,12 +164,118 @@ impl State {
             .find(|(window, _)| window.wl_surface().as_ref() == Some(surface))
         {
             PreferredDecorationMode::update(&window, None);
-            window.toplevel().with_pending_state(|state| {
-                state.decoration_mode = None;
-            });
+            if !ForcedDecorationMode::is_forced(&window) {
+                window.toplevel().with_pending_state(|state| {
+                    state.decoration_mode = None;
+                });
+            }
             window.toplevel().send_configure();
         }
     }
+
+    /// Applies the decoration rules of the config to all windows, after the rules changed.
+    /// Windows no longer matched fall back to the mode they requested.
+    pub fn refresh_decoration_rules(&mut self) {
+        let rules = &self.common.config.decoration_rules;
+        for workspace in self.common.shell.spaces() {
+            for mapped in workspace.mapped().chain(workspace.minimized()) {
+                // stacks always draw their own tabs
+                if mapped.is_stack() {
+                    continue;
+                }
+                let tiled = workspace.is_tiled(mapped);
+                for (surface, _) in mapped.windows() {
+                    if let CosmicSurface::Wayland(window) = &surface {
+                        apply_decoration_rule(rules, window, surface.app_id(), tiled, true);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies the decoration rules to the window of a committed surface, if its app id or
+    /// tiling state changed since they were last applied
+    pub fn refresh_decoration_rule(&mut self, surface: &WlSurface) {
+        let Some(mapped) = self.common.shell.element_for_wl_surface(surface) else {
+            return;
+        };
+        if mapped.is_stack() {
+            return;
+        }
+        let Some(workspace) = self.common.shell.space_for(mapped) else {
+            return;
+        };
+        let tiled = workspace.is_tiled(mapped);
+        let Some((window, _)) = mapped
+            .windows()
+            .find(|(window, _)| window.wl_surface().as_ref() == Some(surface))
+        else {
+            return;
+        };
+        if let CosmicSurface::Wayland(wl_window) = &window {
+            apply_decoration_rule(
+                &self.common.config.decoration_rules,
+                wl_window,
+                window.app_id(),
+                tiled,
+                false,
+            );
+        }
+    }
+}
+
+/// Forces the mode of the first rule matching the window, or restores the mode it requested
+/// once no rule matches anymore. Unless `force` is set, nothing is done if the app id and
+/// tiling state are the same as last time.
+fn apply_decoration_rule(
+    rules: &DecorationRules,
+    window: &Window,
+    app_id: String,
+    tiled: bool,
+    force: bool,
+) {
+    window
+        .user_data()
+        .insert_if_missing(ForcedDecorationMode::default);
+    let rule_state = window.user_data().get::<ForcedDecorationMode>().unwrap();
+    let unchanged = rule_state
+        .applied_for
+        .borrow()
+        .as_ref()
+        .map_or(false, |(applied_app_id, applied_tiled)| {
+            *applied_app_id == app_id && *applied_tiled == tiled
+        });
+    if unchanged && !force {
+        return;
+    }
+
+    let rule = rules.mode(&app_id, tiled);
+    *rule_state.applied_for.borrow_mut() = Some((app_id, tiled));
+    let mode = match rule {
+        Some(mode) => {
+            rule_state.forced.set(true);
+            Some(match mode {
+                DecorationMode::ServerSide => XdgMode::ServerSide,
+                DecorationMode::ClientSide => XdgMode::ClientSide,
+            })
+        }
+        None if rule_state.forced.replace(false) => {
+            PreferredDecorationMode::mode(window).or(Some(XdgMode::ClientSide))
+        }
+        None => return,
+    };
+    let changed = window
+        .toplevel()
+        .with_pending_state(|state| std::mem::replace(&mut state.decoration_mode, mode) != mode);
+    if changed {
+        if let Some(decoration) = KdeDecoration::get(window.toplevel().wl_surface()) {
+            decoration.mode(match mode {
+                Some(XdgMode::ServerSide) => KdeMode::Server,
+                _ => KdeMode::Client,
+            });
+        }
+        window.toplevel().send_configure();
+    }
 }
 
 impl XdgDecorationHandler for State {
@@ -151,6 +318,7 @@ impl KdeDecorationHandler for State {
     }
 
     fn new_decoration(&mut self, surface: &WlSurface, decoration: &OrgKdeKwinServerDecoration) {
+        KdeDecoration::set(surface, Some(decoration.clone()));
         if let Some(mapped) = self.common.shell.element_for_wl_surface(surface) {
             let mode = State::new_decoration(mapped, surface);
             decoration.mode(mode);
@@ -180,6 +348,7 @@ impl KdeDecorationHandler for State {
     }
 
     fn release(&mut self, _decoration: &OrgKdeKwinServerDecoration, surface: &WlSurface) {
+        KdeDecoration::set(surface, None);
         if let Some(mapped) = self.common.shell.element_for_wl_surface(surface) {
             State::unset_mode(mapped, surface)
         }
@@ -32,6 +32,11 @@ impl DmabufHandler for State {
                 .import_dmabuf(&dmabuf, None)
                 .map(|_| ())
                 .map_err(|_| ImportError::Failed),
+            BackendData::Headless(ref mut state) => state
+                .renderer
+                .import_dmabuf(&dmabuf, None)
+                .map(|_| ())
+                .map_err(|_| ImportError::Failed),
             _ => unreachable!("No backend set when importing dmabuf"),
         }
     }
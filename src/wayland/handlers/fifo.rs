@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use smithay::reexports::calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use tracing::warn;
+
+use crate::{
+    state::State,
+    wayland::protocols::fifo::{delegate_fifo, FifoHandler, FifoState},
+};
+
+use super::compositor::client_compositor_state;
+
+/// Interval, at which the barriers of surfaces without a rendering output are cleared.
+/// Matches the throttling of their frame callbacks.
+const OFFSCREEN_INTERVAL: Duration = Duration::from_secs(1);
+
+impl FifoHandler for State {
+    fn fifo_state(&mut self) -> &mut FifoState {
+        &mut self.common.fifo_state
+    }
+}
+
+/// Clears the fifo barriers of surfaces, that aren't visible anywhere or only on outputs that
+/// are turned off. Without any output rendering, nothing else would clear them.
+pub fn init_offscreen_timer(handle: &LoopHandle<'static, State>) {
+    if let Err(err) =
+        handle.insert_source(Timer::from_duration(OFFSCREEN_INTERVAL), |_, _, state| {
+            let idle = &state.common.idle;
+            let clients = state
+                .common
+                .fifo_state
+                .clear_barriers_if(|output| output.map_or(true, |output| idle.is_off(output)));
+            let dh = state.common.display_handle.clone();
+            for client in clients {
+                client_compositor_state(&client).blocker_cleared(state, &dh);
+            }
+            TimeoutAction::ToDuration(OFFSCREEN_INTERVAL)
+        })
+    {
+        warn!(?err, "Failed to start the fifo barrier timer.");
+    }
+}
+
+delegate_fifo!(State);
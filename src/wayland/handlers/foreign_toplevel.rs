@@ -0,0 +1,17 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    shell::CosmicSurface,
+    state::State,
+    wayland::protocols::foreign_toplevel::{
+        delegate_foreign_toplevel, ForeignToplevelHandler, ForeignToplevelState,
+    },
+};
+
+impl ForeignToplevelHandler for State {
+    fn foreign_toplevel_state(&mut self) -> &mut ForeignToplevelState<State, CosmicSurface> {
+        &mut self.common.shell.foreign_toplevel_state
+    }
+}
+
+delegate_foreign_toplevel!(State, CosmicSurface);
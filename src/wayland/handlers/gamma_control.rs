@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::output::Output;
+use tracing::warn;
+
+use crate::{
+    state::{BackendData, State},
+    wayland::protocols::gamma_control::{
+        delegate_gamma_control, GammaControlHandler, GammaControlState,
+    },
+};
+
+impl GammaControlHandler for State {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState {
+        &mut self.common.gamma_control_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        match &mut self.backend {
+            BackendData::Kms(kms) => kms.gamma_size(output),
+            _ => None,
+        }
+    }
+
+    fn set_gamma(&mut self, output: &Output, gamma: Option<[Vec<u16>; 3]>) -> bool {
+        match &mut self.backend {
+            BackendData::Kms(kms) => match kms.set_client_gamma(output, gamma) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!(?err, "Failed to set gamma of output {}.", output.name());
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+delegate_gamma_control!(State);
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State,
+    wayland::protocols::idle_notify::{
+        delegate_idle_notify, IdleNotifierHandler, IdleNotifierState,
+    },
+};
+
+impl IdleNotifierHandler for State {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<State> {
+        &mut self.common.idle_notifier_state
+    }
+
+    fn idle_inhibited(&self) -> bool {
+        State::idle_inhibited(self)
+    }
+}
+
+delegate_idle_notify!(State);
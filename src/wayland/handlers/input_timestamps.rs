@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State,
+    wayland::protocols::input_timestamps::{
+        delegate_input_timestamps, InputTimestampsHandler, InputTimestampsState,
+    },
+};
+
+impl InputTimestampsHandler for State {
+    fn input_timestamps_state(&mut self) -> &mut InputTimestampsState {
+        &mut self.common.input_timestamps_state
+    }
+}
+
+delegate_input_timestamps!(State);
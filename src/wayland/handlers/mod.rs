@@ -1,31 +1,53 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+pub mod alpha_modifier;
 pub mod buffer;
+pub mod commit_timing;
 pub mod compositor;
+pub mod content_type;
+pub mod cursor_shape;
+pub mod data_control;
 pub mod data_device;
 pub mod decoration;
 pub mod dmabuf;
 pub mod drm_lease;
+pub mod fifo;
+pub mod foreign_toplevel;
 pub mod fractional_scale;
+pub mod gamma_control;
+pub mod idle_inhibit;
+pub mod idle_notify;
+pub mod input_timestamps;
 pub mod keyboard_shortcuts_inhibit;
 pub mod layer_shell;
 pub mod output;
 pub mod output_configuration;
+pub mod overlap_notify;
 pub mod pointer_constraints;
 pub mod pointer_gestures;
+pub mod pointer_warp;
 pub mod presentation;
 pub mod primary_selection;
 pub mod relative_pointer;
 pub mod screencopy;
+pub mod screencopy_frame_rate;
 pub mod seat;
 pub mod security_context;
 pub mod selection;
 pub mod session_lock;
 pub mod shm;
+pub mod single_pixel_buffer;
+pub mod system_bell;
+pub mod toplevel_drag;
 pub mod toplevel_info;
 pub mod toplevel_management;
 pub mod viewporter;
+pub mod virtual_keyboard;
 pub mod wl_drm;
 pub mod workspace;
+pub mod xdg_activation;
+pub mod xdg_dialog;
+pub mod xdg_foreign;
 pub mod xdg_shell;
 pub mod xwayland_keyboard_grab;
+pub mod xwayland_shell;
@@ -28,7 +28,7 @@ impl OutputConfigurationHandler for State {
 }
 
 impl State {
-    fn output_configuration(
+    pub fn output_configuration(
         &mut self,
         test_only: bool,
         conf: Vec<(Output, OutputConfiguration)>,
@@ -61,14 +61,17 @@ impl State {
                         Some(ModeConfiguration::Mode(mode)) => {
                             current_config.mode =
                                 ((mode.size.w, mode.size.h), Some(mode.refresh as u32));
+                            current_config.modeline = None;
                         }
                         Some(ModeConfiguration::Custom { size, refresh }) => {
                             current_config.mode = ((size.w, size.h), refresh.map(|x| x as u32));
+                            // timings are generated, if the connector doesn't advertise the mode
+                            current_config.modeline = None;
                         }
                         _ => {}
                     }
                     if let Some(scale) = scale {
-                        current_config.scale = *scale;
+                        current_config.scale = snap_scale(*scale);
                     }
                     if let Some(transform) = transform {
                         current_config.transform = *transform;
@@ -145,4 +148,10 @@ impl State {
     }
 }
 
+/// Rounds a scale to the precision of wp-fractional-scale-v1 (multiples of 1/120),
+/// so clients can render buffers that match the output exactly and need no further scaling.
+fn snap_scale(scale: f64) -> f64 {
+    (scale * 120.0).round().max(1.0) / 120.0
+}
+
 delegate_output_configuration!(State);
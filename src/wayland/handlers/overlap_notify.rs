@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State,
+    wayland::protocols::overlap_notify::{
+        delegate_overlap_notify, OverlapNotifyHandler, OverlapNotifyState,
+    },
+};
+
+impl OverlapNotifyHandler for State {
+    fn overlap_notify_state(&mut self) -> &mut OverlapNotifyState {
+        &mut self.common.shell.overlap_notify_state
+    }
+}
+
+delegate_overlap_notify!(State);
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    shell::focus::target::PointerFocusTarget,
+    utils::prelude::*,
+    wayland::protocols::pointer_warp::{
+        delegate_pointer_warp, PointerWarpHandler, PointerWarpState,
+    },
+};
+use smithay::{
+    reexports::wayland_server::{
+        protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+        Resource,
+    },
+    utils::{Logical, Point, Serial},
+    wayland::{
+        compositor::{get_parent, with_states, SubsurfaceCachedState},
+        pointer_constraints::{with_pointer_constraint, PointerConstraint},
+        seat::WaylandFocus,
+    },
+};
+use std::time::Duration;
+use tracing::trace;
+
+impl PointerWarpHandler for State {
+    fn warp_pointer(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &WlPointer,
+        location: Point<f64, Logical>,
+        _serial: Serial,
+    ) {
+        if pointer.client() != surface.client() {
+            return;
+        }
+        // the pointer focus may be a subsurface of the toplevel asking, or the other way around
+        let (root, offset) = root_and_offset(surface);
+        let same_tree = |target: &PointerFocusTarget| {
+            target
+                .wl_surface()
+                .map_or(false, |target| root_and_offset(&target).0 == root)
+        };
+
+        // only the surface with pointer focus may move it, and not while it is grabbed
+        let Some(seat) = self
+            .common
+            .seats()
+            .find(|seat| {
+                seat.get_pointer()
+                    .and_then(|ptr| ptr.current_focus())
+                    .map_or(false, |target| same_tree(&target))
+            })
+            .cloned()
+        else {
+            trace!(?surface, "Ignoring pointer warp of unfocused surface.");
+            return;
+        };
+        let ptr = seat.get_pointer().unwrap();
+        if ptr.is_grabbed() {
+            return;
+        }
+
+        let Some((target, target_origin)) = self
+            .pointer_target_at(ptr.current_location())
+            .filter(|(target, _)| same_tree(target))
+        else {
+            return;
+        };
+        let target_offset = target
+            .wl_surface()
+            .map_or_else(Point::default, |target| root_and_offset(&target).1);
+        let origin = target_origin - target_offset + offset;
+        let position = origin.to_f64() + location;
+        if self
+            .pointer_target_at(position)
+            .map_or(true, |(target, _)| !same_tree(&target))
+        {
+            trace!(
+                ?surface,
+                ?location,
+                "Ignoring pointer warp outside of surface."
+            );
+            return;
+        }
+
+        // locked pointers stay where they are, confined ones within their region
+        let mut allowed = true;
+        with_pointer_constraint(surface, &ptr, |constraint| match constraint {
+            Some(constraint) if constraint.is_active() => match &*constraint {
+                PointerConstraint::Locked(_) => allowed = false,
+                PointerConstraint::Confined(confined) => {
+                    allowed = confined
+                        .region()
+                        .map_or(true, |region| region.contains(location.to_i32_round()))
+                }
+            },
+            _ => {}
+        });
+        if !allowed {
+            return;
+        }
+
+        let Some(output) = self
+            .common
+            .shell
+            .outputs()
+            .find(|output| output.geometry().to_f64().contains(position))
+            .cloned()
+        else {
+            return;
+        };
+        let time = Into::<Duration>::into(self.common.clock.now());
+        self.pointer_motion_absolute(&seat, &output, position, time);
+    }
+}
+
+/// Root of the surface tree of `surface` and the offset of `surface` within it
+fn root_and_offset(surface: &WlSurface) -> (WlSurface, Point<i32, Logical>) {
+    let mut root = surface.clone();
+    let mut offset = Point::default();
+    while let Some(parent) = get_parent(&root) {
+        offset += with_states(&root, |states| {
+            states
+                .cached_state
+                .current::<SubsurfaceCachedState>()
+                .location
+        });
+        root = parent;
+    }
+    (root, offset)
+}
+
+impl State {
+    /// The pointer target at the global `position` and its location
+    fn pointer_target_at(
+        &mut self,
+        position: Point<f64, Logical>,
+    ) -> Option<(PointerFocusTarget, Point<i32, Logical>)> {
+        let output = self
+            .common
+            .shell
+            .outputs()
+            .find(|output| output.geometry().to_f64().contains(position))
+            .cloned()?;
+        let relative_pos = self.common.shell.map_global_to_space(position, &output);
+        let overview = self.common.shell.overview_mode();
+        let output_geometry = output.geometry();
+        let x11_menu = self.common.x11_menu_grab();
+        let workspace = self.common.shell.workspaces.active_mut(&output);
+        State::surface_under(
+            position,
+            relative_pos,
+            &output,
+            output_geometry,
+            &self.common.shell.override_redirect_windows,
+            x11_menu.as_ref(),
+            self.common.session_lock.as_ref(),
+            overview.0,
+            workspace,
+        )
+    }
+}
+
+delegate_pointer_warp!(State);
@@ -2,35 +2,45 @@ use std::{
     borrow::Borrow,
     cell::RefCell,
     collections::HashSet,
+    iter::once,
     ops::{Deref, DerefMut},
+    time::Duration,
 };
 
 use anyhow::anyhow;
-use calloop::LoopHandle;
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
 use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::{
     FailureReason, InputType,
 };
 use smithay::{
     backend::{
-        allocator::dmabuf::Dmabuf,
+        allocator::{dmabuf::Dmabuf, Fourcc},
         drm::DrmNode,
         egl::EGLDevice,
         renderer::{
             buffer_dimensions, buffer_type,
             damage::{Error as DTError, OutputDamageTracker, RenderOutputResult},
-            element::{surface::WaylandSurfaceRenderElement, AsRenderElements, RenderElement},
+            element::{AsRenderElements, RenderElement},
             gles::{Capability, GlesError, GlesRenderbuffer, GlesRenderer},
             Bind, Blit, BufferType, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
         },
     },
-    desktop::{layer_map_for_output, space::SpaceElement},
+    desktop::{find_popup_root_surface, layer_map_for_output, space::SpaceElement},
+    input::Seat,
     output::{Output, OutputNoMode},
     reexports::wayland_server::{
         protocol::{wl_buffer::WlBuffer, wl_shm::Format as ShmFormat, wl_surface::WlSurface},
         Resource,
     },
-    utils::{IsAlive, Logical, Physical, Rectangle, Scale, Transform},
+    utils::{
+        Buffer as BufferCoords, Clock, IsAlive, Logical, Monotonic, Physical, Rectangle, Scale,
+        Size, Time, Transform,
+    },
     wayland::{
+        compositor::get_parent,
         dmabuf::get_dmabuf,
         seat::WaylandFocus,
         shm::{shm_format_to_fourcc, with_buffer_contents, with_buffer_contents_mut},
@@ -42,17 +52,18 @@ use tracing::warn;
 use crate::{
     backend::render::{
         cursor,
-        element::{AsGlowRenderer, CosmicElement},
+        element::{AsGlowRenderer, CosmicElement, SurfaceRenderElement},
         render_output, render_workspace, CursorMode, CLEAR_COLOR,
     },
     shell::{CosmicMappedRenderElement, CosmicSurface, WorkspaceRenderElement},
     state::{BackendData, ClientState, Common, State},
-    utils::prelude::OutputExt,
+    utils::prelude::{OutputExt, SeatExt},
     wayland::protocols::{
         screencopy::{
             delegate_screencopy, BufferInfo, BufferParams, CursorMode as ScreencopyCursorMode,
             CursorSession, ScreencopyHandler, Session, SessionType,
         },
+        screencopy_frame_rate::FrameThrottle,
         workspace::WorkspaceHandle,
     },
 };
@@ -92,6 +103,9 @@ impl PartialEq<Session> for DropableSession {
 
 pub type SessionDT = RefCell<OutputDamageTracker>;
 
+/// Size of the buffers of cursor sessions, large enough for the usual cursor themes
+const CURSOR_BUFFER_SIZE: (i32, i32) = (64, 64);
+
 impl ScreencopyHandler for State {
     fn capture_output(&mut self, output: Output, session: Session) -> Vec<BufferInfo> {
         let formats = match formats_for_output(&output, &mut self.backend) {
@@ -144,6 +158,15 @@ impl ScreencopyHandler for State {
             }
         };
 
+        // the cursor only enters later with motion otherwise
+        if self.common.shell.active_space(&output).handle == handle {
+            for seat in self.common.seats() {
+                if seat.active_output() == output {
+                    session.cursor_enter(seat, InputType::Pointer);
+                }
+            }
+        }
+
         let workspace = match self.common.shell.space_for_handle_mut(&handle) {
             Some(workspace) => workspace,
             None => {
@@ -194,6 +217,7 @@ impl ScreencopyHandler for State {
             }
             BackendData::Winit(ref mut winit) => winit.backend.renderer(),
             BackendData::X11(ref mut x11) => &mut x11.renderer,
+            BackendData::Headless(ref mut headless) => &mut headless.renderer,
             _ => unreachable!(),
         };
 
@@ -244,6 +268,17 @@ impl ScreencopyHandler for State {
             );
         }
 
+        // the cursor only enters later with motion otherwise
+        if let Some(mapped) = self.common.shell.element_for_surface(&toplevel) {
+            if mapped.active_window() == toplevel {
+                for seat in self.common.seats() {
+                    if mapped.cursor_position(seat).is_some() {
+                        session.cursor_enter(seat, InputType::Pointer);
+                    }
+                }
+            }
+        }
+
         let size = toplevel.geometry().size.to_physical(1);
         session.user_data().insert_if_missing(|| {
             SessionDT::new(OutputDamageTracker::new(size, 1.0, Transform::Normal))
@@ -263,7 +298,13 @@ impl ScreencopyHandler for State {
     }
 
     fn capture_cursor(&mut self, _session: CursorSession) -> Vec<BufferInfo> {
-        unimplemented!("We don't advertise the capture cursor mode")
+        let size = Size::from(CURSOR_BUFFER_SIZE);
+        // the cursor is drawn on the cpu anyway, readback of such a small buffer is cheap
+        vec![BufferInfo::Shm {
+            format: ShmFormat::Abgr8888,
+            size,
+            stride: size.w as u32 * 4,
+        }]
     }
 
     fn buffer_attached(&mut self, session: Session, params: BufferParams, on_damage: bool) {
@@ -330,6 +371,27 @@ impl ScreencopyHandler for State {
         }
 
         if on_damage {
+            if let Some(delay) = session
+                .user_data()
+                .get::<FrameThrottle>()
+                .and_then(FrameThrottle::delay)
+            {
+                // damage keeps accumulating in the session's damage tracker meanwhile
+                let res = self.common.event_loop_handle.insert_source(
+                    Timer::from_duration(delay),
+                    move |_, _, state| {
+                        if session.alive() {
+                            resume_throttled_session(state, session.clone(), params.clone());
+                        }
+                        TimeoutAction::Drop
+                    },
+                );
+                if let Err(err) = res {
+                    warn!(?err, "Failed to delay screencopy frame");
+                }
+                return;
+            }
+
             match session.session_type() {
                 SessionType::Output(output) => {
                     output
@@ -406,10 +468,6 @@ impl ScreencopyHandler for State {
         }
     }
 
-    fn cursor_session_destroyed(&mut self, _session: CursorSession) {
-        unreachable!("We currently don't support cursor sessions");
-    }
-
     fn session_destroyed(&mut self, session: Session) {
         match session.session_type() {
             SessionType::Output(output) => {
@@ -461,6 +519,7 @@ fn formats_for_output(
         }
         BackendData::Winit(ref mut winit) => winit.backend.renderer(),
         BackendData::X11(ref mut x11) => &mut x11.renderer,
+        BackendData::Headless(ref mut headless) => &mut headless.renderer,
         _ => unreachable!(),
     };
 
@@ -537,58 +596,133 @@ fn node_from_params(
     }
 }
 
-fn submit_buffer<R>(
-    session: &Session,
-    buffer: &WlBuffer,
+/// Reads back the given regions of the bound framebuffer into a shm buffer
+fn copy_to_shm<R>(
     renderer: &mut R,
-    transform: Transform,
-    damage: Vec<Rectangle<i32, Physical>>,
+    buffer: &WlBuffer,
+    damage: &[Rectangle<i32, Physical>],
 ) -> Result<(), <R as Renderer>::Error>
 where
     R: ExportMem,
 {
-    if matches!(buffer_type(buffer), Some(BufferType::Shm)) {
-        let buffer_size = buffer_dimensions(buffer).unwrap();
-        with_buffer_contents_mut(buffer, |ptr, len, data| {
-            let offset = data.offset as i32;
-            let width = data.width as i32;
-            let height = data.height as i32;
-            let stride = data.stride as i32;
-            let format = shm_format_to_fourcc(data.format)
-                .expect("We should be able to convert all hardcoded shm screencopy formats");
-
-            // number of bytes per pixel
-            // TODO: compute from data.format
-            let pixelsize = 4i32;
-
-            // ensure consistency, the SHM handler of smithay should ensure this
-            assert!((offset + (height - 1) * stride + width * pixelsize) as usize <= len);
-
-            let mapping = renderer
-                .copy_framebuffer(Rectangle::from_loc_and_size((0, 0), buffer_size), format)?;
+    let buffer_size = buffer_dimensions(buffer).unwrap();
+    with_buffer_contents_mut(buffer, |ptr, len, data| {
+        let offset = data.offset as i32;
+        let width = data.width as i32;
+        let height = data.height as i32;
+        let stride = data.stride as i32;
+        let format = shm_format_to_fourcc(data.format)
+            .expect("We should be able to convert all hardcoded shm screencopy formats");
+
+        // number of bytes per pixel
+        // TODO: compute from data.format
+        let pixelsize = 4i32;
+
+        // ensure consistency, the SHM handler of smithay should ensure this
+        assert!((offset + (height - 1) * stride + width * pixelsize) as usize <= len);
+
+        // the rest of the buffer still holds the frame the damage is relative to,
+        // so only read back what changed
+        let buffer_rect = Rectangle::<i32, BufferCoords>::from_loc_and_size((0, 0), buffer_size);
+        for rect in damage.iter().filter_map(|rect| {
+            Rectangle::<i32, BufferCoords>::from_loc_and_size(
+                (rect.loc.x, rect.loc.y),
+                (rect.size.w, rect.size.h),
+            )
+            .intersection(buffer_rect)
+        }) {
+            let mapping = renderer.copy_framebuffer(rect, format)?;
             let gl_data = renderer.map_texture(&mapping)?;
-            assert!((width * height * pixelsize) as usize <= gl_data.len());
+            let row_len = rect.size.w * pixelsize;
+            assert!((row_len * rect.size.h) as usize <= gl_data.len());
 
-            for i in 0..height {
+            for i in 0..rect.size.h {
                 unsafe {
                     std::ptr::copy_nonoverlapping::<u8>(
-                        gl_data.as_ptr().offset((width * pixelsize * i) as isize),
-                        ptr.offset((offset + stride * i) as isize),
-                        (width * pixelsize) as usize,
+                        gl_data.as_ptr().offset((row_len * i) as isize),
+                        ptr.offset(
+                            (offset + stride * (rect.loc.y + i) + rect.loc.x * pixelsize) as isize,
+                        ),
+                        row_len as usize,
                     );
                 }
             }
-            Ok(())
-        })
-        .unwrap()?;
+        }
+        Ok(())
+    })
+    .unwrap()
+}
+
+fn submit_buffer<R>(
+    session: &Session,
+    buffer: &WlBuffer,
+    renderer: &mut R,
+    transform: Transform,
+    damage: Vec<Rectangle<i32, Physical>>,
+) -> Result<(), <R as Renderer>::Error>
+where
+    R: ExportMem,
+{
+    if matches!(buffer_type(buffer), Some(BufferType::Shm)) {
+        copy_to_shm(renderer, buffer, &damage)?;
     }
 
-    session.commit_buffer(transform, damage, None);
+    // lets encoders pace frames by when they were actually captured
+    let time = Clock::<Monotonic>::new()
+        .ok()
+        .map(|clock| Duration::from(clock.now()));
+    session.commit_buffer(transform, damage, time);
     buffer.release();
+    if let Some(throttle) = session.user_data().get::<FrameThrottle>() {
+        throttle.frame_submitted();
+    }
 
     Ok(())
 }
 
+/// Draws the cursor image of the seat with its top-left corner at the origin of the buffer.
+///
+/// Like the cursor geometry reported to the session, the image is unscaled.
+fn render_cursor_to_buffer<R>(
+    renderer: &mut R,
+    seat: &Seat<State>,
+    buffer: &WlBuffer,
+    time: Time<Monotonic>,
+) -> Result<Rectangle<i32, BufferCoords>, DTError<R>>
+where
+    R: Renderer + ImportAll + ImportMem + ExportMem + Bind<Dmabuf> + Offscreen<GlesRenderbuffer>,
+    <R as Renderer>::TextureId: Clone + 'static,
+{
+    let size = buffer_dimensions(buffer).unwrap();
+    let scale = Scale::from(1.0);
+    let hotspot = cursor::cursor_hotspot(seat, scale, time).unwrap_or_default();
+    let elements = cursor::draw_cursor(renderer, seat, hotspot, scale, time, true);
+
+    if let Ok(dmabuf) = get_dmabuf(buffer) {
+        renderer.bind(dmabuf).map_err(DTError::Rendering)?;
+    } else {
+        let render_buffer =
+            Offscreen::<GlesRenderbuffer>::create_buffer(renderer, Fourcc::Abgr8888, size)
+                .map_err(DTError::Rendering)?;
+        renderer.bind(render_buffer).map_err(DTError::Rendering)?;
+    }
+
+    // the image changes with every frame of an animated cursor, so always draw the full buffer
+    let mut dt = OutputDamageTracker::new((size.w, size.h), scale, Transform::Normal);
+    dt.render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 0.0])?;
+
+    if matches!(buffer_type(buffer), Some(BufferType::Shm)) {
+        copy_to_shm(
+            renderer,
+            buffer,
+            &[Rectangle::from_loc_and_size((0, 0), (size.w, size.h))],
+        )
+        .map_err(DTError::Rendering)?;
+    }
+
+    Ok(Rectangle::from_loc_and_size((0, 0), size))
+}
+
 pub fn render_session<F, R>(
     node: Option<DrmNode>,
     renderer: &mut R,
@@ -598,7 +732,8 @@ pub fn render_session<F, R>(
     render_fn: F,
 ) -> Result<bool, DTError<R>>
 where
-    R: ExportMem,
+    R: Renderer + ImportAll + ImportMem + ExportMem + Bind<Dmabuf> + Offscreen<GlesRenderbuffer>,
+    <R as Renderer>::TextureId: Clone + 'static,
     F: FnOnce(
         Option<&DrmNode>,
         &WlBuffer,
@@ -610,6 +745,32 @@ where
     #[cfg(feature = "debug")]
     puffin::profile_function!();
 
+    // buffers of cursor sessions become ready together with the buffer of their session
+    if let (ScreencopyCursorMode::Captured(cursor_sessions), Ok(clock)) =
+        (session.cursor_mode(), Clock::<Monotonic>::new())
+    {
+        let time = clock.now();
+        for cursor_session in cursor_sessions.iter().filter(|cursor| cursor.alive()) {
+            let Some(params) = cursor_session.buffer_waiting() else {
+                continue;
+            };
+            let Some(seat) = Seat::<State>::from_resource(&cursor_session.seat()) else {
+                cursor_session.failed(FailureReason::InvalidSeat);
+                continue;
+            };
+            match render_cursor_to_buffer(renderer, &seat, &params.buffer, time) {
+                Ok(rect) => {
+                    cursor_session.commit_buffer(Transform::Normal, once(&rect));
+                    params.buffer.release();
+                }
+                Err(err) => {
+                    warn!(?err, "Failed to render cursor for screencopy session");
+                    cursor_session.failed(FailureReason::Unspec);
+                }
+            }
+        }
+    }
+
     let mut dt = session.user_data().get::<SessionDT>().unwrap().borrow_mut();
 
     let res = render_fn(
@@ -689,6 +850,7 @@ pub fn render_output_to_buffer(
                 cursor_mode,
                 None,
                 None,
+                None,
             )
         } else {
             let size = buffer_dimensions(buffer).unwrap();
@@ -710,6 +872,7 @@ pub fn render_output_to_buffer(
                 cursor_mode,
                 None,
                 None,
+                None,
             )
         }
     }
@@ -759,6 +922,17 @@ pub fn render_output_to_buffer(
             },
         )
         .map_err(|err| (FailureReason::Unspec, err.into())),
+        BackendData::Headless(headless) => render_session::<_, _>(
+            node,
+            &mut headless.renderer,
+            session,
+            &params,
+            output.current_transform(),
+            |node, buffer, renderer, dt, age| {
+                render_fn(node, buffer, renderer, dt, age, common, session, output)
+            },
+        )
+        .map_err(|err| (FailureReason::Unspec, err.into())),
         _ => unreachable!(),
     }
 }
@@ -822,6 +996,7 @@ pub fn render_workspace_to_buffer(
                 cursor_mode,
                 None,
                 None,
+                None,
                 true,
             )
         } else {
@@ -846,6 +1021,7 @@ pub fn render_workspace_to_buffer(
                 cursor_mode,
                 None,
                 None,
+                None,
                 true,
             )
         }
@@ -902,13 +1078,26 @@ pub fn render_workspace_to_buffer(
             },
         )
         .map_err(|err| (FailureReason::Unspec, err.into())),
+        BackendData::Headless(headless) => render_session::<_, _>(
+            node,
+            &mut headless.renderer,
+            session,
+            &params,
+            output.current_transform(),
+            |node, buffer, renderer, dt, age| {
+                render_fn(
+                    node, buffer, renderer, dt, age, common, session, output, handle,
+                )
+            },
+        )
+        .map_err(|err| (FailureReason::Unspec, err.into())),
         _ => unreachable!(),
     }
 }
 
 smithay::render_elements! {
-    pub WindowCaptureElement<R> where R: ImportAll;
-    WaylandElement=WaylandSurfaceRenderElement<R>,
+    pub WindowCaptureElement<R> where R: ImportAll + ImportMem;
+    WaylandElement=SurfaceRenderElement<R>,
     CursorElement=cursor::CursorRenderElement<R>,
 }
 
@@ -976,7 +1165,15 @@ pub fn render_window_to_buffer(
                     None
                 }
             } {
-                if session.cursor_mode() == ScreencopyCursorMode::Embedded {
+                if let ScreencopyCursorMode::Captured(_) = session.cursor_mode() {
+                    // windows are rendered on demand, so the position is updated with every frame
+                    if let Some((geometry, offset)) = seat.cursor_geometry(
+                        location.to_buffer(1.0, Transform::Normal, &geometry.size.to_f64()),
+                        common.clock.now(),
+                    ) {
+                        session.cursor_info(seat, InputType::Pointer, geometry, offset);
+                    }
+                } else if session.cursor_mode() == ScreencopyCursorMode::Embedded {
                     elements.extend(
                         cursor::draw_cursor(
                             renderer,
@@ -1063,6 +1260,17 @@ pub fn render_window_to_buffer(
             },
         )
         .map_err(|err| (FailureReason::Unspec, err.into())),
+        BackendData::Headless(headless) => render_session::<_, _>(
+            node,
+            &mut headless.renderer,
+            session,
+            &params,
+            Transform::Normal,
+            |_node, buffer, renderer, dt, age| {
+                render_fn(buffer, renderer, dt, age, session, common, window, geometry)
+            },
+        )
+        .map_err(|err| (FailureReason::Unspec, err.into())),
         _ => unreachable!(),
     }
 }
@@ -1165,14 +1373,26 @@ impl UserdataExt for CosmicSurface {
 
 impl State {
     pub fn schedule_window_session(&mut self, surface: &WlSurface) {
-        if let Some(element) = surface
-            .wl_surface()
-            .and_then(|surface| self.common.shell.element_for_wl_surface(&surface).cloned())
-        {
-            let active = element.active_window();
-            if active.wl_surface().as_ref() == Some(surface) {
-                for (session, params) in active.pending_buffers() {
-                    let window = active.clone();
+        // sessions capture the whole window, so commits of its subsurfaces
+        // (e.g. video players in browsers) and popups produce frames as well
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        if let Some(popup) = self.common.shell.popups.find_popup(&root) {
+            if let Ok(popup_root) = find_popup_root_surface(&popup) {
+                root = popup_root;
+            }
+        }
+
+        if let Some(element) = self.common.shell.element_for_wl_surface(&root).cloned() {
+            // windows in the background of a stack may be captured too
+            if let Some((window, _)) = element
+                .windows()
+                .find(|(window, _)| window.wl_surface().as_ref() == Some(&root))
+            {
+                for (session, params) in window.pending_buffers() {
+                    let window = window.clone();
                     self.common.event_loop_handle.insert_idle(move |state| {
                         if !session.alive() {
                             return;
@@ -1285,6 +1505,46 @@ impl State {
     }
 }
 
+/// Tries to deliver a frame to a session, that was held back by its frame-rate limit.
+///
+/// Any damage accumulated while waiting is sent right away, otherwise the buffer
+/// stays pending until the next damage as usual.
+fn resume_throttled_session(state: &mut State, session: Session, params: BufferParams) {
+    match session.session_type() {
+        SessionType::Output(output) => {
+            output
+                .user_data()
+                .insert_if_missing(PendingScreencopyBuffers::default);
+            state.backend.schedule_render(
+                &state.common.event_loop_handle,
+                &output,
+                Some(vec![(session, params)]),
+            );
+        }
+        SessionType::Workspace(output, handle) => schedule_offscreen_workspace_session(
+            &state.common.event_loop_handle,
+            session,
+            params,
+            output,
+            handle,
+        ),
+        SessionType::Window(window) => {
+            window
+                .user_data()
+                .insert_if_missing(PendingScreencopyBuffers::default);
+            match render_window_to_buffer(state, &session, params.clone(), &window) {
+                Ok(false) => state.common.still_pending(session, params),
+                Ok(true) => {}
+                Err((reason, err)) => {
+                    warn!(?err, "Screencopy session failed");
+                    session.failed(reason);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 pub fn schedule_offscreen_workspace_session(
     event_loop_handle: &LoopHandle<'static, State>,
     session: Session,
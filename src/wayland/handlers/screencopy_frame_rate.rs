@@ -0,0 +1,7 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State, wayland::protocols::screencopy_frame_rate::delegate_screencopy_frame_rate,
+};
+
+delegate_screencopy_frame_rate!(State);
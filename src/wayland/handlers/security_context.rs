@@ -5,7 +5,6 @@ use smithay::{
         SecurityContext, SecurityContextHandler, SecurityContextListenerSource,
     },
 };
-use std::sync::Arc;
 use tracing::warn;
 
 impl SecurityContextHandler for State {
@@ -17,13 +16,21 @@ impl SecurityContextHandler for State {
         self.common
             .event_loop_handle
             .insert_source(source, move |client_stream, _, state| {
-                if let Err(err) = state.common.display_handle.insert_client(
-                    client_stream,
-                    Arc::new(ClientState {
-                        security_context: Some(security_context.clone()),
-                        ..state.new_client_state()
-                    }),
-                ) {
+                let privileged = security_context.app_id.as_ref().map_or(false, |app_id| {
+                    state
+                        .common
+                        .config
+                        .static_conf
+                        .security_context
+                        .privileged_app_ids
+                        .contains(app_id)
+                });
+                let client_state = ClientState {
+                    security_context: Some(security_context.clone()),
+                    privileged,
+                    ..state.new_client_state()
+                };
+                if let Err(err) = state.insert_client(client_stream, client_state) {
                     warn!(?err, "Error adding wayland client");
                 };
             })
@@ -1,38 +1,116 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::state::State;
+use crate::{backend::render::screenshot::PNG_MIME_TYPE, state::State};
 use smithay::{
     input::Seat,
     wayland::selection::{SelectionHandler, SelectionSource, SelectionTarget},
     xwayland::xwm::XwmId,
 };
-use std::os::unix::io::OwnedFd;
+use std::{cell::RefCell, fs::File, io::Write, os::unix::io::OwnedFd, sync::Arc};
 use tracing::warn;
 
-impl SelectionHandler for State {
-    type SelectionUserData = XwmId;
+/// Owner of a selection offered by the compositor itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionData {
+    /// Forwarded from Xwayland
+    Xwayland(XwmId),
+    /// A png image taken by a screenshot action
+    Screenshot(Arc<Vec<u8>>),
+}
 
-    fn new_selection(
+/// Mime types of the wayland selections of a seat, that were offered to Xwayland.
+///
+/// Kept around to offer them again to a freshly started Xwayland. Selections owned by
+/// X11 clients are never offered back, which would take them away from their owner.
+#[derive(Debug, Default)]
+struct XwaylandOffers {
+    clipboard: Option<Vec<String>>,
+    primary: Option<Vec<String>>,
+}
+
+impl State {
+    /// Offers a selection of `seat` to X11 clients, or clears it with `None`
+    pub fn offer_selection_to_xwayland(
         &mut self,
+        seat: &Seat<State>,
         target: SelectionTarget,
-        source: Option<SelectionSource>,
-        _seat: Seat<State>,
+        mime_types: Option<Vec<String>>,
     ) {
+        seat.user_data()
+            .insert_if_missing(|| RefCell::new(XwaylandOffers::default()));
+        {
+            let mut offers = seat
+                .user_data()
+                .get::<RefCell<XwaylandOffers>>()
+                .unwrap()
+                .borrow_mut();
+            match target {
+                SelectionTarget::Clipboard => offers.clipboard = mime_types.clone(),
+                SelectionTarget::Primary => offers.primary = mime_types.clone(),
+            }
+        }
+
+        if let Some(xwm) = self
+            .common
+            .xwayland_state
+            .as_mut()
+            .and_then(|xstate| xstate.xwm.as_mut())
+        {
+            if let Err(err) = xwm.new_selection(target, mime_types) {
+                warn!(?err, ?target, "Failed to update Xwayland selection.");
+            }
+        }
+    }
+
+    /// Forgets the selection of `seat`, that was taken over by an X11 client
+    pub fn selection_taken_by_xwayland(&mut self, seat: &Seat<State>, target: SelectionTarget) {
+        if let Some(offers) = seat.user_data().get::<RefCell<XwaylandOffers>>() {
+            let mut offers = offers.borrow_mut();
+            match target {
+                SelectionTarget::Clipboard => offers.clipboard = None,
+                SelectionTarget::Primary => offers.primary = None,
+            }
+        }
+    }
+
+    /// Offers the current wayland selections to a newly started Xwayland
+    pub fn offer_selections_to_xwayland(&mut self) {
+        let seat = self.common.last_active_seat().clone();
+        let Some(offers) = seat.user_data().get::<RefCell<XwaylandOffers>>() else {
+            return;
+        };
+        let offers = offers.borrow();
         if let Some(xwm) = self
             .common
             .xwayland_state
             .as_mut()
             .and_then(|xstate| xstate.xwm.as_mut())
         {
-            if let Some(source) = &source {
-                if let Err(err) = xwm.new_selection(target, Some(source.mime_types())) {
-                    warn!(?err, "Failed to set Xwayland clipboard selection.");
+            for (target, mime_types) in [
+                (SelectionTarget::Clipboard, &offers.clipboard),
+                (SelectionTarget::Primary, &offers.primary),
+            ] {
+                if let Some(mime_types) = mime_types {
+                    if let Err(err) = xwm.new_selection(target, Some(mime_types.clone())) {
+                        warn!(?err, ?target, "Failed to set Xwayland selection.");
+                    }
                 }
-            } else if let Err(err) = xwm.new_selection(target, None) {
-                warn!(?err, "Failed to clear Xwayland selection.");
             }
         }
     }
+}
+
+impl SelectionHandler for State {
+    type SelectionUserData = SelectionData;
+
+    fn new_selection(
+        &mut self,
+        target: SelectionTarget,
+        source: Option<SelectionSource>,
+        seat: Seat<State>,
+    ) {
+        self.offer_selection_to_xwayland(&seat, target, source.map(|s| s.mime_types()));
+    }
 
     fn send_selection(
         &mut self,
@@ -40,18 +118,37 @@ impl SelectionHandler for State {
         mime_type: String,
         fd: OwnedFd,
         _seat: Seat<State>,
-        _user_data: &Self::SelectionUserData,
+        user_data: &Self::SelectionUserData,
     ) {
-        if let Some(xwm) = self
-            .common
-            .xwayland_state
-            .as_mut()
-            .and_then(|xstate| xstate.xwm.as_mut())
-        {
-            if let Err(err) =
-                xwm.send_selection(target, mime_type, fd, self.common.event_loop_handle.clone())
-            {
-                warn!(?err, "Failed to send selection (X11 -> Wayland).");
+        match user_data {
+            SelectionData::Xwayland(_) => {
+                if let Some(xwm) = self
+                    .common
+                    .xwayland_state
+                    .as_mut()
+                    .and_then(|xstate| xstate.xwm.as_mut())
+                {
+                    if let Err(err) = xwm.send_selection(
+                        target,
+                        mime_type,
+                        fd,
+                        self.common.event_loop_handle.clone(),
+                    ) {
+                        warn!(?err, "Failed to send selection (X11 -> Wayland).");
+                    }
+                }
+            }
+            SelectionData::Screenshot(image) => {
+                if mime_type != PNG_MIME_TYPE {
+                    return;
+                }
+                // the receiving client might read slowly, don't block on it
+                let image = image.clone();
+                std::thread::spawn(move || {
+                    if let Err(err) = File::from(fd).write_all(&image) {
+                        warn!(?err, "Failed to send screenshot to client.");
+                    }
+                });
             }
         }
     }
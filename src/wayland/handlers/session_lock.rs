@@ -1,15 +1,17 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::state::{SessionLock, State};
+use crate::state::{Common, SessionLock, State};
 use smithay::{
     delegate_session_lock,
     output::Output,
-    reexports::wayland_server::protocol::wl_output::WlOutput,
+    reexports::wayland_server::{protocol::wl_output::WlOutput, Resource},
+    utils::{IsAlive, Size},
     wayland::session_lock::{
         surface::LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
     },
 };
 use std::collections::HashMap;
+use tracing::{debug, warn};
 
 impl SessionLockHandler for State {
     fn lock_state(&mut self) -> &mut SessionLockManagerState {
@@ -17,24 +19,106 @@ impl SessionLockHandler for State {
     }
 
     fn lock(&mut self, locker: SessionLocker) {
-        // XXX can there already be a lock?
+        // a locker that crashed may be replaced, a second one is denied by dropping it
+        if let Some(session_lock) = &self.common.session_lock {
+            if session_lock.lock.is_alive() {
+                debug!("Denying session lock, the session is already locked.");
+                return;
+            }
+        }
+        let lock = locker.ext_session_lock().clone();
+        let Some(client) = lock.client() else {
+            return;
+        };
         locker.lock();
         self.common.session_lock = Some(SessionLock {
+            lock,
+            client: client.id(),
             surfaces: HashMap::new(),
-        })
+        });
+        self.schedule_lock_render();
     }
 
     fn unlock(&mut self) {
-        self.common.session_lock = None;
+        // Only a proper unlock reveals the session again, a crashed locker keeps it locked.
+        // Unlocking destroys the lock object, which tells apart the holder of the lock
+        // from a denied locker, once the request was processed.
+        self.common.event_loop_handle.insert_idle(|state| {
+            let dh = &state.common.display_handle;
+            if state
+                .common
+                .session_lock
+                .as_ref()
+                .map_or(false, |session_lock| {
+                    !session_lock.lock.is_alive()
+                        && dh.get_client(session_lock.client.clone()).is_ok()
+                })
+            {
+                state.common.session_lock = None;
+                state.schedule_lock_render();
+            } else {
+                warn!("Ignoring unlock request of a client not holding the session lock.");
+            }
+        });
     }
 
     fn new_surface(&mut self, lock_surface: LockSurface, wl_output: WlOutput) {
-        if let Some(session_lock) = &mut self.common.session_lock {
+        if let Some(session_lock) = self.common.session_lock.as_mut().filter(|session_lock| {
+            lock_surface
+                .wl_surface()
+                .client()
+                .map_or(false, |client| client.id() == session_lock.client)
+        }) {
             if let Some(output) = Output::from_resource(&wl_output) {
+                // let clients pick up the (fractional) scale of the output
+                output.enter(lock_surface.wl_surface());
+                configure_lock_surface(&lock_surface, &output);
                 session_lock.surfaces.insert(output, lock_surface);
             }
         }
     }
 }
 
+impl State {
+    fn schedule_lock_render(&mut self) {
+        for output in self.common.shell.outputs().cloned().collect::<Vec<_>>() {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &output, None);
+        }
+    }
+}
+
+impl Common {
+    /// Keeps the lock surfaces in sync with the outputs.
+    ///
+    /// Surfaces of removed outputs or of a crashed locker are dropped, so their outputs
+    /// fall back to the blank backdrop. Outputs that changed their size reconfigure theirs.
+    pub fn refresh_session_lock(&mut self) {
+        let Some(session_lock) = &mut self.session_lock else {
+            return;
+        };
+        let outputs = self.shell.outputs().cloned().collect::<Vec<_>>();
+        session_lock.surfaces.retain(|output, lock_surface| {
+            outputs.contains(output) && lock_surface.wl_surface().alive()
+        });
+        for (output, lock_surface) in session_lock.surfaces.iter() {
+            configure_lock_surface(lock_surface, output);
+        }
+    }
+}
+
+/// Sizes a lock surface to cover its output, if it doesn't already
+fn configure_lock_surface(lock_surface: &LockSurface, output: &Output) {
+    let size = output.geometry().size;
+    let size = Some(Size::from((size.w as u32, size.h as u32)));
+    let changed = lock_surface.with_pending_state(|states| {
+        let changed = states.size != size;
+        states.size = size;
+        changed
+    });
+    if changed {
+        lock_surface.send_configure();
+    }
+}
+
 delegate_session_lock!(State);
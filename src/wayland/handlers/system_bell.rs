@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use calloop::timer::{TimeoutAction, Timer};
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::Rectangle,
+    wayland::{compositor::get_parent, seat::WaylandFocus},
+};
+use tracing::warn;
+
+use crate::{
+    config::VisualBell,
+    shell::{focus::target::KeyboardFocusTarget, BELL_DURATION},
+    state::State,
+    utils::prelude::*,
+    wayland::protocols::system_bell::{delegate_system_bell, SystemBellHandler},
+};
+
+impl SystemBellHandler for State {
+    fn ring(&mut self, surface: Option<WlSurface>) {
+        let config = self.common.config.static_conf.bell;
+
+        let mapped = match surface {
+            Some(mut root) => {
+                while let Some(parent) = get_parent(&root) {
+                    root = parent;
+                }
+                self.common.shell.element_for_wl_surface(&root).cloned()
+            }
+            None => match self
+                .common
+                .last_active_seat()
+                .get_keyboard()
+                .unwrap()
+                .current_focus()
+            {
+                Some(KeyboardFocusTarget::Element(mapped)) => Some(mapped),
+                _ => None,
+            },
+        };
+
+        if config.urgent {
+            if let Some(mapped) = mapped.as_ref() {
+                self.common.shell.set_urgent(mapped);
+            }
+        }
+
+        let output = mapped
+            .as_ref()
+            .and_then(|mapped| mapped.active_window().wl_surface())
+            .and_then(|surface| {
+                self.common
+                    .shell
+                    .visible_outputs_for_surface(&surface)
+                    .next()
+            })
+            .unwrap_or_else(|| self.common.last_active_seat().active_output());
+        let area = match config.visual {
+            VisualBell::None => return,
+            VisualBell::Output => Rectangle::from_loc_and_size((0, 0), output.geometry().size),
+            VisualBell::Window => {
+                let Some(geometry) = mapped.as_ref().and_then(|mapped| {
+                    let workspace = self.common.shell.active_space(&output);
+                    if workspace.is_fullscreen(mapped) {
+                        Some(Rectangle::from_loc_and_size((0, 0), output.geometry().size))
+                    } else {
+                        workspace.element_geometry(mapped).map(|mut geometry| {
+                            geometry.loc -= output.geometry().loc;
+                            geometry
+                        })
+                    }
+                }) else {
+                    return;
+                };
+                geometry
+            }
+        };
+        self.common.shell.flash(&output, area);
+
+        self.backend
+            .schedule_render(&self.common.event_loop_handle, &output, None);
+        // and once more to clear the flash again
+        if let Err(err) = self.common.event_loop_handle.insert_source(
+            Timer::from_duration(BELL_DURATION + Duration::from_millis(1)),
+            move |_, _, state| {
+                state
+                    .backend
+                    .schedule_render(&state.common.event_loop_handle, &output, None);
+                TimeoutAction::Drop
+            },
+        ) {
+            warn!(?err, "Failed to schedule clearing the visual bell.");
+        }
+    }
+}
+
+delegate_system_bell!(State);
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    shell::{focus::target::KeyboardFocusTarget, grabs::MoveGrabState, Trigger},
+    utils::prelude::*,
+    wayland::protocols::toplevel_drag::{
+        delegate_toplevel_drag, ToplevelDragHandler, ToplevelDragState,
+    },
+};
+use smithay::{
+    desktop::space::SpaceElement, input::Seat, output::Output,
+    reexports::wayland_server::protocol::wl_data_source::WlDataSource,
+};
+use std::{cell::RefCell, collections::HashSet};
+
+const BTN_LEFT: u32 = 0x110;
+
+/// Drag-and-drop operation of a seat, that may move a toplevel along
+#[derive(Debug, Default)]
+pub struct ToplevelDragData {
+    source: Option<WlDataSource>,
+    /// Output the attached toplevel was picked up on, once it follows the pointer
+    output: Option<Output>,
+}
+pub type SeatToplevelDrag = RefCell<ToplevelDragData>;
+
+impl ToplevelDragHandler for State {
+    fn toplevel_drag_state(&mut self) -> &mut ToplevelDragState {
+        &mut self.common.toplevel_drag_state
+    }
+
+    fn toplevel_attached(&mut self, _source: &WlDataSource) {
+        self.update_toplevel_drags();
+    }
+}
+
+impl State {
+    /// Records the data source of a drag-and-drop operation started on `seat`
+    pub fn toplevel_drag_started(&mut self, seat: &Seat<State>, source: Option<WlDataSource>) {
+        seat.user_data()
+            .insert_if_missing(SeatToplevelDrag::default);
+        *seat
+            .user_data()
+            .get::<SeatToplevelDrag>()
+            .unwrap()
+            .borrow_mut() = ToplevelDragData {
+            source,
+            output: None,
+        };
+        self.update_toplevel_drags();
+    }
+
+    /// Detaches toplevels attached to ongoing drag-and-drop operations from the layouts,
+    /// as soon as they are mapped, so they follow the pointer
+    pub fn update_toplevel_drags(&mut self) {
+        for seat in self.common.seats().cloned().collect::<Vec<_>>() {
+            let Some(drag) = seat.user_data().get::<SeatToplevelDrag>() else {
+                continue;
+            };
+            let Some(source) = drag.borrow().source.clone() else {
+                continue;
+            };
+            if drag.borrow().output.is_some() {
+                continue;
+            }
+            let Some((toplevel, offset)) = self.common.toplevel_drag_state.attached(&source)
+            else {
+                continue;
+            };
+            let Some(surface) = self
+                .common
+                .shell
+                .xdg_shell_state
+                .toplevel_surfaces()
+                .iter()
+                .find(|surface| surface.xdg_toplevel() == &toplevel)
+                .map(|surface| surface.wl_surface().clone())
+            else {
+                continue;
+            };
+            // toplevels attached before they are mapped are picked up on map
+            let Some(mapped) = self.common.shell.element_for_wl_surface(&surface).cloned() else {
+                continue;
+            };
+            let Some((window, _)) = mapped
+                .windows()
+                .find(|(window, _)| window.wl_surface().as_ref() == Some(&surface))
+            else {
+                continue;
+            };
+            let Some(workspace) = self.common.shell.space_for_mut(&mapped) else {
+                continue;
+            };
+            let Some(tiled) = workspace.toplevel_drag(
+                &window,
+                &seat,
+                offset,
+                self.common.config.static_conf.active_hint,
+                self.common.config.static_conf.shadows,
+            ) else {
+                continue;
+            };
+            let handle = workspace.handle;
+
+            let output = seat.active_output();
+            mapped.output_enter(&output, mapped.geometry());
+            self.common
+                .shell
+                .toplevel_info_state
+                .toplevel_leave_workspace(&window, &handle);
+            self.common
+                .shell
+                .toplevel_info_state
+                .toplevel_leave_output(&window, &output);
+            if tiled {
+                let button = seat
+                    .get_pointer()
+                    .unwrap()
+                    .grab_start_data()
+                    .map_or(BTN_LEFT, |start_data| start_data.button);
+                self.common.shell.set_overview_mode(
+                    Some(Trigger::Pointer(button)),
+                    self.common.event_loop_handle.clone(),
+                );
+            }
+            drag.borrow_mut().output = Some(output);
+        }
+    }
+
+    /// Ends the drag-and-drop operation of `seat`, placing an attached toplevel at the pointer
+    /// like a move grab would. Dropping it on the tiling layer uses the hovered drop zone.
+    pub fn toplevel_drag_dropped(&mut self, seat: &Seat<State>) {
+        let Some(drag) = seat.user_data().get::<SeatToplevelDrag>() else {
+            return;
+        };
+        let Some(output) = drag.take().output else {
+            return;
+        };
+        let location = seat.get_pointer().unwrap().current_location();
+        if let Some((mapped, _)) =
+            MoveGrabState::drop_window(self, seat, &HashSet::from([output]), location)
+        {
+            Common::set_focus(self, Some(&KeyboardFocusTarget::from(mapped)), seat, None);
+        }
+    }
+}
+
+delegate_toplevel_drag!(State);
@@ -43,7 +43,11 @@ impl Window for CosmicSurface {
     }
 
     fn is_minimized(&self) -> bool {
-        false // TODO
+        CosmicSurface::is_minimized(self)
+    }
+
+    fn is_transient_for(&self, parent: &Self) -> bool {
+        CosmicSurface::is_transient_for(self, parent)
     }
 
     fn user_data(&self) -> &UserDataMap {
@@ -1,9 +1,10 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use smithay::{input::Seat, reexports::wayland_server::DisplayHandle};
+use cosmic_protocols::workspace::v1::server::zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1;
+use smithay::{input::Seat, output::Output, reexports::wayland_server::DisplayHandle};
 
 use crate::{
-    shell::CosmicSurface,
+    shell::{CosmicSurface, Shell, Workspace},
     utils::prelude::*,
     wayland::protocols::{
         toplevel_info::ToplevelInfoHandler,
@@ -21,10 +22,11 @@ impl ToplevelManagementHandler for State {
 
     fn activate(
         &mut self,
-        _dh: &DisplayHandle,
+        dh: &DisplayHandle,
         window: &<Self as ToplevelInfoHandler>::Window,
         seat: Option<Seat<Self>>,
     ) {
+        self.unminimize(dh, window);
         for output in self
             .common
             .shell
@@ -59,6 +61,111 @@ impl ToplevelManagementHandler for State {
     fn close(&mut self, _dh: &DisplayHandle, window: &<Self as ToplevelInfoHandler>::Window) {
         window.close();
     }
+
+    fn fullscreen(
+        &mut self,
+        dh: &DisplayHandle,
+        window: &<Self as ToplevelInfoHandler>::Window,
+        output: Option<Output>,
+    ) {
+        self.unminimize(dh, window);
+        let output = output.unwrap_or_else(|| self.common.last_active_seat().active_output());
+        let evlh = self.common.event_loop_handle.clone();
+        if let Some(workspace) = self.space_for_window(window) {
+            workspace.fullscreen_request(window, &output, evlh);
+        }
+    }
+
+    fn unfullscreen(
+        &mut self,
+        _dh: &DisplayHandle,
+        window: &<Self as ToplevelInfoHandler>::Window,
+    ) {
+        if let Some(workspace) = self.space_for_window(window) {
+            workspace.unfullscreen_request(window);
+        }
+    }
+
+    fn maximize(&mut self, dh: &DisplayHandle, window: &<Self as ToplevelInfoHandler>::Window) {
+        self.unminimize(dh, window);
+        let output = self.common.last_active_seat().active_output();
+        let evlh = self.common.event_loop_handle.clone();
+        if let Some(workspace) = self.space_for_window(window) {
+            workspace.maximize_request(window, &output, evlh);
+        }
+    }
+
+    fn unmaximize(&mut self, _dh: &DisplayHandle, window: &<Self as ToplevelInfoHandler>::Window) {
+        if let Some(workspace) = self.space_for_window(window) {
+            workspace.unmaximize_request(window);
+        }
+    }
+
+    fn minimize(&mut self, _dh: &DisplayHandle, window: &<Self as ToplevelInfoHandler>::Window) {
+        let Some(mapped) = self.common.shell.element_for_surface(window).cloned() else {
+            return;
+        };
+        let seat = self.common.last_active_seat().clone();
+        let Some(workspace) = self.common.shell.space_for_mut(&mapped) else {
+            return;
+        };
+        if workspace.minimize(&mapped) {
+            // hand the focus to the next window of the workspace
+            let next = workspace.focus_stack.get(&seat).last().cloned();
+            Common::set_focus(self, next.map(Into::into).as_ref(), &seat, None);
+        }
+    }
+
+    fn unminimize(&mut self, _dh: &DisplayHandle, window: &<Self as ToplevelInfoHandler>::Window) {
+        let Some(mapped) = self.common.shell.element_for_surface(window).cloned() else {
+            return;
+        };
+        let seat = self.common.last_active_seat().clone();
+        if let Some(workspace) = self.common.shell.space_for_mut(&mapped) {
+            if workspace.unminimize(&mapped, &seat) {
+                Common::set_focus(self, Some(&mapped.into()), &seat, None);
+            }
+        }
+    }
+
+    fn move_to_workspace(
+        &mut self,
+        _dh: &DisplayHandle,
+        window: &<Self as ToplevelInfoHandler>::Window,
+        workspace: ZcosmicWorkspaceHandleV1,
+        output: Output,
+    ) {
+        let Some(mapped) = self.common.shell.element_for_surface(window).cloned() else {
+            return;
+        };
+        let Some(from_handle) = self.common.shell.space_for(&mapped).map(|w| w.handle) else {
+            return;
+        };
+        let Some(to_handle) = self.common.shell.workspace_state.workspace_handle(&workspace) else {
+            return;
+        };
+        let from = self.common.shell.workspace_position(&from_handle, &output);
+        let to = self.common.shell.workspace_position(&to_handle, &output);
+        if let (Some((from_output, from_idx)), Some((to_output, to_idx))) = (from, to) {
+            let seat = self.common.last_active_seat().clone();
+            let _ = Shell::move_window(
+                self,
+                &seat,
+                &mapped,
+                (&from_output, from_idx),
+                (&to_output, to_idx),
+                false,
+                None,
+            );
+        }
+    }
+}
+
+impl State {
+    fn space_for_window(&mut self, window: &CosmicSurface) -> Option<&mut Workspace> {
+        let mapped = self.common.shell.element_for_surface(window).cloned()?;
+        self.common.shell.space_for_mut(&mapped)
+    }
 }
 
 impl ManagementWindow for CosmicSurface {
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use smithay::{
+    delegate_xdg_activation,
+    input::Seat,
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    wayland::{
+        seat::WaylandFocus,
+        xdg_activation::{
+            XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+        },
+    },
+};
+
+use crate::{
+    config::ActivationPolicy, state::State,
+    wayland::protocols::toplevel_management::ToplevelManagementHandler,
+};
+
+/// Tokens used later than this only mark their window urgent, so it doesn't steal the focus,
+/// and are dropped once another token is created
+const ACTIVATION_TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a token was requested in response to user input, recorded when it is created
+struct FromInteraction(bool);
+
+impl XdgActivationHandler for State {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.common.xdg_activation_state
+    }
+
+    fn token_created(&mut self, _token: XdgActivationToken, data: XdgActivationTokenData) -> bool {
+        // clients may never use their tokens
+        self.common
+            .xdg_activation_state
+            .retain_tokens(|_, data| data.timestamp.elapsed() <= ACTIVATION_TOKEN_TIMEOUT);
+
+        // every token is accepted, tokens without interaction only mark their window urgent
+        let interaction = from_interaction(&data);
+        data.user_data
+            .insert_if_missing(|| FromInteraction(interaction));
+        true
+    }
+
+    fn request_activation(
+        &mut self,
+        token: XdgActivationToken,
+        token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        // tokens are single use
+        self.common.xdg_activation_state.remove_token(&token);
+
+        let Some(mapped) = self.common.shell.element_for_wl_surface(&surface).cloned() else {
+            return;
+        };
+        let Some(window) = mapped
+            .windows()
+            .map(|(window, _)| window)
+            .find(|window| window.wl_surface().as_ref() == Some(&surface))
+        else {
+            return;
+        };
+
+        let interaction = token_data
+            .user_data
+            .get::<FromInteraction>()
+            .map_or(false, |from_interaction| from_interaction.0);
+        let focus = match self.common.config.static_conf.activation_policy {
+            ActivationPolicy::Never => false,
+            ActivationPolicy::Interaction => interaction,
+            ActivationPolicy::Always => true,
+        };
+        if !focus || token_data.timestamp.elapsed() > ACTIVATION_TOKEN_TIMEOUT {
+            self.common.shell.set_urgent(&mapped);
+            return;
+        }
+
+        let seat = token_data
+            .serial
+            .and_then(|(_, seat)| Seat::<State>::from_resource(&seat));
+        let dh = self.common.display_handle.clone();
+        ToplevelManagementHandler::activate(self, &dh, &window, seat);
+    }
+}
+
+/// Whether the client requesting the token has the keyboard focus,
+/// and passed the serial of an input event it received since getting it
+fn from_interaction(data: &XdgActivationTokenData) -> bool {
+    let Some((serial, seat)) = &data.serial else {
+        return false;
+    };
+    let Some(keyboard) = Seat::<State>::from_resource(seat).and_then(|seat| seat.get_keyboard())
+    else {
+        return false;
+    };
+    let focused_client = keyboard
+        .current_focus()
+        .and_then(|target| target.wl_surface())
+        .and_then(|surface| surface.client())
+        .map(|client| client.id());
+    focused_client.is_some()
+        && focused_client == data.client_id
+        && keyboard
+            .last_enter()
+            .map_or(false, |enter| serial.is_no_older_than(&enter))
+}
+
+delegate_xdg_activation!(State);
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    shell::{element::CosmicMapped, focus::target::KeyboardFocusTarget, CosmicSurface},
+    utils::prelude::*,
+    wayland::protocols::xdg_dialog::{delegate_xdg_dialog, XdgDialogHandler, XdgDialogState},
+};
+use smithay::{
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel,
+    wayland::{seat::WaylandFocus, shell::xdg::ToplevelSurface},
+};
+
+impl XdgDialogHandler for State {
+    fn xdg_dialog_state(&mut self) -> &mut XdgDialogState {
+        &mut self.common.xdg_dialog_state
+    }
+
+    fn modal_changed(&mut self, toplevel: &XdgToplevel, modal: bool) {
+        if !modal {
+            return;
+        }
+        let Some(surface) = self
+            .common
+            .shell
+            .xdg_shell_state
+            .toplevel_surfaces()
+            .iter()
+            .find(|surface| surface.xdg_toplevel() == toplevel)
+            .cloned()
+        else {
+            return;
+        };
+        self.refocus_parent(&surface);
+    }
+}
+
+impl State {
+    /// Focuses the parent of `surface` again, if it has the focus, which hands it over
+    /// to `surface` in case it is a modal dialog.
+    pub fn refocus_parent(&mut self, surface: &ToplevelSurface) {
+        let Some(parent) = surface
+            .parent()
+            .and_then(|parent| self.common.shell.element_for_wl_surface(&parent))
+            .cloned()
+        else {
+            return;
+        };
+        for seat in self.common.seats().cloned().collect::<Vec<_>>() {
+            let focused = seat.get_keyboard().unwrap().current_focus();
+            if focused == Some(KeyboardFocusTarget::Element(parent.clone())) {
+                Common::set_focus(self, focused.as_ref(), &seat, None);
+            }
+        }
+    }
+}
+
+impl Common {
+    /// The modal dialog, that should receive the keyboard focus instead of `mapped`.
+    ///
+    /// Dialogs of dialogs are followed, so the innermost one gets the focus.
+    pub fn modal_dialog_for(&self, mapped: &CosmicMapped) -> Option<CosmicMapped> {
+        let workspace = self.shell.space_for(mapped)?;
+        let mut dialogs = Vec::new();
+        let mut current = mapped.clone();
+        while let Some(child) = workspace
+            .mapped()
+            .find(|elem| {
+                *elem != &current
+                    && match elem.active_window() {
+                        CosmicSurface::Wayland(window) => {
+                            window.toplevel().parent() == current.active_window().wl_surface()
+                                && self
+                                    .xdg_dialog_state
+                                    .is_modal(window.toplevel().xdg_toplevel())
+                        }
+                        _ => false,
+                    }
+            })
+            .cloned()
+        {
+            // a loop of parents would never end otherwise
+            if &child == mapped || dialogs.contains(&child) {
+                break;
+            }
+            dialogs.push(child.clone());
+            current = child;
+        }
+        dialogs.pop()
+    }
+}
+
+delegate_xdg_dialog!(State);
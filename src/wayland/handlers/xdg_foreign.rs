@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    utils::prelude::*,
+    wayland::protocols::xdg_foreign::{delegate_xdg_foreign, XdgForeignHandler, XdgForeignState},
+};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+
+impl XdgForeignHandler for State {
+    fn xdg_foreign_state(&mut self) -> &mut XdgForeignState {
+        &mut self.common.xdg_foreign_state
+    }
+
+    fn parent_changed(&mut self, surface: &WlSurface) {
+        let Some(toplevel) = self
+            .common
+            .shell
+            .xdg_shell_state
+            .toplevel_surfaces()
+            .iter()
+            .find(|toplevel| toplevel.wl_surface() == surface)
+            .cloned()
+        else {
+            return;
+        };
+        // dialogs of portals are modal over the window of another client
+        self.refocus_parent(&toplevel);
+    }
+}
+
+delegate_xdg_foreign!(State);
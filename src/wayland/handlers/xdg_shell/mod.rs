@@ -1,11 +1,18 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::{shell::CosmicSurface, utils::prelude::*, wayland::protocols::screencopy::SessionType};
+use crate::{
+    shell::{focus::target::KeyboardFocusTarget, CosmicSurface},
+    utils::prelude::*,
+    wayland::protocols::{
+        screencopy::SessionType, toplevel_management::ToplevelManagementHandler,
+        xdg_foreign::XdgForeignHandler,
+    },
+};
 use smithay::{
     delegate_xdg_shell,
     desktop::{
-        find_popup_root_surface, PopupGrab, PopupKeyboardGrab, PopupKind, PopupPointerGrab,
-        PopupUngrabStrategy, Window,
+        find_popup_root_surface, layer_map_for_output, PopupGrab, PopupKeyboardGrab, PopupKind,
+        PopupPointerGrab, PopupUngrabStrategy, Window, WindowSurfaceType,
     },
     input::{pointer::Focus, Seat},
     output::Output,
@@ -13,7 +20,7 @@ use smithay::{
         wayland_protocols::xdg::shell::server::xdg_toplevel,
         wayland_server::protocol::{wl_output::WlOutput, wl_seat::WlSeat},
     },
-    utils::Serial,
+    utils::{Logical, Point, Serial},
     wayland::{
         seat::WaylandFocus,
         shell::xdg::{
@@ -65,11 +72,22 @@ impl XdgShellHandler for State {
     fn grab(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
         let seat = Seat::from_resource(&seat).unwrap();
         let kind = PopupKind::Xdg(surface);
-        if let Some(root) = find_popup_root_surface(&kind)
-            .ok()
-            .and_then(|root| self.common.shell.element_for_wl_surface(&root))
-        {
-            let target = root.clone().into();
+        // popups of panels and other layer surfaces grab just like window popups
+        if let Some(target) = find_popup_root_surface(&kind).ok().and_then(|root| {
+            self.common
+                .shell
+                .element_for_wl_surface(&root)
+                .cloned()
+                .map(KeyboardFocusTarget::from)
+                .or_else(|| {
+                    self.common.shell.outputs().find_map(|o| {
+                        layer_map_for_output(o)
+                            .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                            .cloned()
+                            .map(KeyboardFocusTarget::LayerSurface)
+                    })
+                })
+        }) {
             let ret = self
                 .common
                 .shell
@@ -149,6 +167,32 @@ impl XdgShellHandler for State {
         Shell::resize_request(self, surface.wl_surface(), &seat, serial, edges.into())
     }
 
+    fn show_window_menu(
+        &mut self,
+        surface: ToplevelSurface,
+        seat: WlSeat,
+        serial: Serial,
+        location: Point<i32, Logical>,
+    ) {
+        let seat = Seat::from_resource(&seat).unwrap();
+        if let Some(mapped) = self
+            .common
+            .shell
+            .element_for_wl_surface(surface.wl_surface())
+            .cloned()
+        {
+            if let Some(geometry) = self
+                .common
+                .shell
+                .space_for(&mapped)
+                .and_then(|workspace| workspace.element_geometry(&mapped))
+            {
+                let position = geometry.loc + mapped.active_window_offset() + location;
+                Shell::menu_request(self, surface.wl_surface(), &seat, serial, position)
+            }
+        }
+    }
+
     fn maximize_request(&mut self, surface: ToplevelSurface) {
         let seat = self.common.last_active_seat();
         let output = seat.active_output();
@@ -169,6 +213,22 @@ impl XdgShellHandler for State {
         }
     }
 
+    fn minimize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(mapped) = self
+            .common
+            .shell
+            .element_for_wl_surface(surface.wl_surface())
+            .cloned()
+        {
+            let (window, _) = mapped
+                .windows()
+                .find(|(w, _)| w.wl_surface().as_ref() == Some(surface.wl_surface()))
+                .unwrap();
+            let dh = self.common.display_handle.clone();
+            ToplevelManagementHandler::minimize(self, &dh, &window);
+        }
+    }
+
     fn unmaximize_request(&mut self, surface: ToplevelSurface) {
         if let Some(mapped) = self
             .common
@@ -233,6 +293,14 @@ impl XdgShellHandler for State {
     }
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        let orphans = self
+            .common
+            .xdg_foreign_state
+            .toplevel_destroyed(surface.wl_surface());
+        for orphan in orphans {
+            self.parent_changed(&orphan);
+        }
+
         let outputs = self
             .common
             .shell
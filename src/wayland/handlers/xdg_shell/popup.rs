@@ -136,11 +136,15 @@ fn unconstrain_xdg_popup(
 
 fn unconstrain_layer_popup(surface: &PopupSurface, output: &Output, layer_surface: &LayerSurface) {
     let map = layer_map_for_output(output);
-    let layer_geo = map.layer_geometry(layer_surface).unwrap();
+    let Some(layer_geo) = map.layer_geometry(layer_surface) else {
+        // not arranged yet, the popup will be placed as requested
+        return;
+    };
 
-    // the output_rect represented relative to the parents coordinate system
+    // the output_rect represented relative to the parents coordinate system,
+    // which for nested popups is the geometry of the parent popup
     let mut relative = Rectangle::from_loc_and_size((0, 0), output.geometry().size);
-    relative.loc -= layer_geo.loc;
+    relative.loc -= layer_geo.loc + get_popup_toplevel_coords(surface);
     let geometry = surface.with_pending_state(|state| state.positioner.get_geometry());
     let offset = check_constrained(geometry, relative);
 
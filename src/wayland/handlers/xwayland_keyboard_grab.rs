@@ -2,9 +2,40 @@
 
 use crate::{shell::focus::target::KeyboardFocusTarget, state::State};
 use smithay::{
-    delegate_xwayland_keyboard_grab, reexports::wayland_server::protocol::wl_surface::WlSurface,
-    wayland::xwayland_keyboard_grab::XWaylandKeyboardGrabHandler,
+    delegate_xwayland_keyboard_grab,
+    input::Seat,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Serial, SERIAL_COUNTER},
+    wayland::xwayland_keyboard_grab::{XWaylandKeyboardGrab, XWaylandKeyboardGrabHandler},
 };
+use std::cell::RefCell;
+
+/// Surface of the X11 window, that last grabbed the keyboard of a seat, and the serial
+/// the grab was started with, to tell whether it is still the active one
+#[derive(Debug, Default)]
+struct XwaylandGrabbedSurface(RefCell<Option<(WlSurface, Serial)>>);
+
+/// Whether the X11 window of `surface` grabs the keyboard of `seat`, and receives the
+/// compositor shortcuts as well
+pub fn xwayland_grabs_keyboard(seat: &Seat<State>, surface: &WlSurface) -> bool {
+    let Some(keyboard) = seat.get_keyboard() else {
+        return false;
+    };
+    seat.user_data()
+        .get::<XwaylandGrabbedSurface>()
+        .map_or(false, |grabbed| {
+            let mut grabbed = grabbed.0.borrow_mut();
+            match grabbed.as_ref() {
+                Some((grabbing, serial)) if keyboard.has_grab(*serial) => grabbing == surface,
+                Some(_) => {
+                    // the grab ended or was replaced by another one
+                    *grabbed = None;
+                    false
+                }
+                None => false,
+            }
+        })
+}
 
 impl XWaylandKeyboardGrabHandler for State {
     fn keyboard_focus_for_xsurface(&self, surface: &WlSurface) -> Option<KeyboardFocusTarget> {
@@ -16,5 +47,38 @@ impl XWaylandKeyboardGrabHandler for State {
             .find_map(|x| x.element_for_wl_surface(surface))?;
         Some(KeyboardFocusTarget::Element(element.clone()))
     }
+
+    fn grab(&mut self, surface: WlSurface, seat: Seat<Self>, grab: XWaylandKeyboardGrab<Self>) {
+        let allowed = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .find_map(|x| x.element_for_wl_surface(&surface))
+            .map_or(false, |element| {
+                self.common
+                    .config
+                    .static_conf
+                    .xwayland_keyboard_grab
+                    .contains(&element.active_window().app_id())
+            });
+        if !allowed {
+            return;
+        }
+
+        let Some(keyboard) = seat.get_keyboard() else {
+            return;
+        };
+        let serial = SERIAL_COUNTER.next_serial();
+        seat.user_data()
+            .insert_if_missing(XwaylandGrabbedSurface::default);
+        *seat
+            .user_data()
+            .get::<XwaylandGrabbedSurface>()
+            .unwrap()
+            .0
+            .borrow_mut() = Some((surface, serial));
+        keyboard.set_grab(grab, serial);
+    }
 }
 delegate_xwayland_keyboard_grab!(State);
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    shell::{CosmicSurface, Shell},
+    state::State,
+    utils::prelude::*,
+};
+use smithay::{
+    delegate_xwayland_shell,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::xwayland_shell::{XWaylandShellHandler, XWaylandShellState},
+    xwayland::{xwm::XwmId, X11Surface},
+};
+
+impl XWaylandShellHandler for State {
+    fn xwayland_shell_state(&mut self) -> &mut XWaylandShellState {
+        &mut self.common.xwayland_shell_state
+    }
+
+    fn surface_associated(&mut self, _xwm: XwmId, _wl_surface: WlSurface, surface: X11Surface) {
+        // windows mapped before their surface was associated, are waiting for it
+        if !surface.is_mapped() {
+            return;
+        }
+        if let Some((window, seat)) = self
+            .common
+            .shell
+            .pending_windows
+            .iter()
+            .find(|(window, _)| matches!(window, CosmicSurface::X11(window) if window == &surface))
+            .cloned()
+        {
+            let output = seat.active_output();
+            Shell::map_window(self, &window, &output);
+        }
+    }
+}
+delegate_xwayland_shell!(State);
@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{wp_alpha_modifier_surface_v1, wp_alpha_modifier_v1};
+
+mod generated {
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/alpha-modifier-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/alpha-modifier-v1.xml");
+}
+
+use self::{
+    wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1, wp_alpha_modifier_v1::WpAlphaModifierV1,
+};
+use smithay::{
+    reexports::wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::wl_surface::WlSurface,
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+    wayland::compositor::{with_states, Cacheable, SurfaceData},
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug)]
+pub struct AlphaModifierState {
+    global: GlobalId,
+}
+
+impl AlphaModifierState {
+    pub fn new<D>(dh: &DisplayHandle) -> AlphaModifierState
+    where
+        D: GlobalDispatch<WpAlphaModifierV1, ()>
+            + Dispatch<WpAlphaModifierV1, ()>
+            + Dispatch<WpAlphaModifierSurfaceV1, WlSurface>
+            + 'static,
+    {
+        let global = dh.create_global::<D, WpAlphaModifierV1, _>(1, ());
+        AlphaModifierState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// Double-buffered alpha multiplier of a surface, `u32::MAX` being opaque
+#[derive(Debug, Clone, Copy)]
+pub struct AlphaModifierSurfaceCachedState {
+    pub multiplier: u32,
+}
+
+impl Default for AlphaModifierSurfaceCachedState {
+    fn default() -> Self {
+        AlphaModifierSurfaceCachedState {
+            multiplier: u32::MAX,
+        }
+    }
+}
+
+impl Cacheable for AlphaModifierSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        *self
+    }
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+#[derive(Debug, Default)]
+struct AlphaModifierMarker(AtomicBool);
+
+/// Factor the client asked the alpha of the surface to be multiplied with
+pub fn alpha_multiplier(states: &SurfaceData) -> f32 {
+    let multiplier = states
+        .cached_state
+        .current::<AlphaModifierSurfaceCachedState>()
+        .multiplier;
+    (multiplier as f64 / u32::MAX as f64) as f32
+}
+
+impl<D> GlobalDispatch<WpAlphaModifierV1, (), D> for AlphaModifierState
+where
+    D: GlobalDispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierSurfaceV1, WlSurface>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpAlphaModifierV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpAlphaModifierV1, (), D> for AlphaModifierState
+where
+    D: GlobalDispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierSurfaceV1, WlSurface>
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpAlphaModifierV1,
+        request: wp_alpha_modifier_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_alpha_modifier_v1::Request::GetSurface { id, surface } => {
+                let exists = with_states(&surface, |states| {
+                    states
+                        .data_map
+                        .insert_if_missing_threadsafe(AlphaModifierMarker::default);
+                    states
+                        .data_map
+                        .get::<AlphaModifierMarker>()
+                        .unwrap()
+                        .0
+                        .swap(true, Ordering::SeqCst)
+                });
+                data_init.init(id, surface.clone());
+                if exists {
+                    obj.post_error(
+                        wp_alpha_modifier_v1::Error::AlreadyConstructed,
+                        format!("{:?} already has an alpha modifier object", surface),
+                    );
+                }
+            }
+            wp_alpha_modifier_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<WpAlphaModifierSurfaceV1, WlSurface, D> for AlphaModifierState
+where
+    D: GlobalDispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierV1, ()>
+        + Dispatch<WpAlphaModifierSurfaceV1, WlSurface>
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpAlphaModifierSurfaceV1,
+        request: wp_alpha_modifier_surface_v1::Request,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_alpha_modifier_surface_v1::Request::SetMultiplier { factor } => {
+                if !surface.alive() {
+                    obj.post_error(
+                        wp_alpha_modifier_surface_v1::Error::NoSurface,
+                        "the surface was destroyed",
+                    );
+                    return;
+                }
+                with_states(surface, |states| {
+                    states
+                        .cached_state
+                        .pending::<AlphaModifierSurfaceCachedState>()
+                        .multiplier = factor;
+                });
+            }
+            wp_alpha_modifier_surface_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _resource: &WpAlphaModifierSurfaceV1,
+        surface: &WlSurface,
+    ) {
+        if !surface.alive() {
+            return;
+        }
+        // reverts to opaque with the next commit
+        with_states(surface, |states| {
+            states
+                .cached_state
+                .pending::<AlphaModifierSurfaceCachedState>()
+                .multiplier = u32::MAX;
+            if let Some(marker) = states.data_map.get::<AlphaModifierMarker>() {
+                marker.0.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+macro_rules! delegate_alpha_modifier {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::alpha_modifier::wp_alpha_modifier_v1::WpAlphaModifierV1: ()
+        ] => $crate::wayland::protocols::alpha_modifier::AlphaModifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::alpha_modifier::wp_alpha_modifier_v1::WpAlphaModifierV1: ()
+        ] => $crate::wayland::protocols::alpha_modifier::AlphaModifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::alpha_modifier::wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::protocols::alpha_modifier::AlphaModifierState);
+    };
+}
+pub(crate) use delegate_alpha_modifier;
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{wp_commit_timer_v1, wp_commit_timing_manager_v1};
+
+mod generated {
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/commit-timing-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/commit-timing-v1.xml");
+}
+
+use self::{
+    wp_commit_timer_v1::WpCommitTimerV1, wp_commit_timing_manager_v1::WpCommitTimingManagerV1,
+};
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use smithay::{
+    reexports::wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::wl_surface::WlSurface,
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+    utils::{Clock, Monotonic},
+    wayland::compositor::{
+        add_blocker, add_pre_commit_hook, with_states, Blocker, BlockerState, Cacheable,
+        CompositorHandler,
+    },
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tracing::warn;
+
+/// State of the commit-timing global.
+///
+/// Commits with a timestamp in the future are blocked by a timer, so they are
+/// applied with the first refresh cycle after the requested time.
+#[derive(Debug)]
+pub struct CommitTimingState<D: 'static> {
+    global: GlobalId,
+    loop_handle: LoopHandle<'static, D>,
+}
+
+pub trait CommitTimingHandler: CompositorHandler + Sized {
+    fn commit_timing_state(&mut self) -> &mut CommitTimingState<Self>;
+}
+
+/// Double-buffered target time of a surface, which only applies to a single commit
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CommitTimingSurfaceCachedState {
+    pub timestamp: Option<Duration>,
+}
+
+impl Cacheable for CommitTimingSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        std::mem::take(self)
+    }
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+#[derive(Debug, Default)]
+struct CommitTimerMarker {
+    exists: AtomicBool,
+}
+
+struct CommitTimingBlocker {
+    deadline: Duration,
+}
+
+impl Blocker for CommitTimingBlocker {
+    fn state(&self) -> BlockerState {
+        if now().map_or(true, |now| now >= self.deadline) {
+            BlockerState::Released
+        } else {
+            BlockerState::Pending
+        }
+    }
+}
+
+fn now() -> Option<Duration> {
+    Clock::<Monotonic>::new()
+        .ok()
+        .map(|clock| Duration::from(clock.now()))
+}
+
+impl<D> CommitTimingState<D>
+where
+    D: GlobalDispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimerV1, WlSurface>
+        + CommitTimingHandler
+        + 'static,
+{
+    pub fn new(dh: &DisplayHandle, loop_handle: LoopHandle<'static, D>) -> CommitTimingState<D> {
+        let global = dh.create_global::<D, WpCommitTimingManagerV1, _>(1, ());
+        CommitTimingState {
+            global,
+            loop_handle,
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Blocks the pending commit of `surface` until `deadline`
+    fn block_until(&self, surface: &WlSurface, deadline: Duration, dh: &DisplayHandle) {
+        let Some(delay) = now()
+            .and_then(|now| deadline.checked_sub(now))
+            .filter(|delay| !delay.is_zero())
+        else {
+            return;
+        };
+        let Some(client) = surface.client() else {
+            return;
+        };
+        let dh = dh.clone();
+        match self.loop_handle.insert_source(
+            Timer::from_duration(delay),
+            move |_, _, state: &mut D| {
+                state
+                    .client_compositor_state(&client)
+                    .blocker_cleared(state, &dh);
+                TimeoutAction::Drop
+            },
+        ) {
+            Ok(_) => add_blocker(surface, CommitTimingBlocker { deadline }),
+            Err(err) => warn!(?err, "Failed to start commit timer."),
+        }
+    }
+}
+
+impl<D> GlobalDispatch<WpCommitTimingManagerV1, (), D> for CommitTimingState<D>
+where
+    D: GlobalDispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimerV1, WlSurface>
+        + CommitTimingHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpCommitTimingManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpCommitTimingManagerV1, (), D> for CommitTimingState<D>
+where
+    D: GlobalDispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimerV1, WlSurface>
+        + CommitTimingHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpCommitTimingManagerV1,
+        request: wp_commit_timing_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_commit_timing_manager_v1::Request::GetTimer { id, surface } => {
+                let (new, exists) = with_states(&surface, |states| {
+                    let new = states
+                        .data_map
+                        .insert_if_missing_threadsafe(CommitTimerMarker::default);
+                    let marker = states.data_map.get::<CommitTimerMarker>().unwrap();
+                    (new, marker.exists.swap(true, Ordering::SeqCst))
+                });
+                data_init.init(id, surface.clone());
+                if exists {
+                    obj.post_error(
+                        wp_commit_timing_manager_v1::Error::CommitTimerExists,
+                        format!("{:?} already has a commit timer", surface),
+                    );
+                    return;
+                }
+                // the hook stays around, if the surface gets another timer later
+                if new {
+                    add_pre_commit_hook::<D, _>(&surface, |state, dh, surface| {
+                        let timestamp = with_states(surface, |states| {
+                            states
+                                .cached_state
+                                .pending::<CommitTimingSurfaceCachedState>()
+                                .timestamp
+                        });
+                        if let Some(deadline) = timestamp {
+                            state
+                                .commit_timing_state()
+                                .block_until(surface, deadline, dh);
+                        }
+                    });
+                }
+            }
+            wp_commit_timing_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<WpCommitTimerV1, WlSurface, D> for CommitTimingState<D>
+where
+    D: GlobalDispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimingManagerV1, ()>
+        + Dispatch<WpCommitTimerV1, WlSurface>
+        + CommitTimingHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpCommitTimerV1,
+        request: wp_commit_timer_v1::Request,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_commit_timer_v1::Request::SetTimestamp {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+            } => {
+                if !surface.is_alive() {
+                    obj.post_error(
+                        wp_commit_timer_v1::Error::SurfaceDestroyed,
+                        "the surface was destroyed",
+                    );
+                    return;
+                }
+                if tv_nsec >= 1_000_000_000 {
+                    obj.post_error(
+                        wp_commit_timer_v1::Error::InvalidTimestamp,
+                        format!("invalid tv_nsec {}", tv_nsec),
+                    );
+                    return;
+                }
+                let secs = ((tv_sec_hi as u64) << 32) | tv_sec_lo as u64;
+                let exists = with_states(surface, |states| {
+                    states
+                        .cached_state
+                        .pending::<CommitTimingSurfaceCachedState>()
+                        .timestamp
+                        .replace(Duration::new(secs, tv_nsec))
+                        .is_some()
+                });
+                if exists {
+                    obj.post_error(
+                        wp_commit_timer_v1::Error::TimestampExists,
+                        "the pending commit already has a timestamp",
+                    );
+                }
+            }
+            wp_commit_timer_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _resource: &WpCommitTimerV1,
+        surface: &WlSurface,
+    ) {
+        if !surface.is_alive() {
+            return;
+        }
+        with_states(surface, |states| {
+            if let Some(marker) = states.data_map.get::<CommitTimerMarker>() {
+                marker.exists.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+macro_rules! delegate_commit_timing {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::commit_timing::wp_commit_timing_manager_v1::WpCommitTimingManagerV1: ()
+        ] => $crate::wayland::protocols::commit_timing::CommitTimingState<Self>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::commit_timing::wp_commit_timing_manager_v1::WpCommitTimingManagerV1: ()
+        ] => $crate::wayland::protocols::commit_timing::CommitTimingState<Self>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::commit_timing::wp_commit_timer_v1::WpCommitTimerV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::protocols::commit_timing::CommitTimingState<Self>);
+    };
+}
+pub(crate) use delegate_commit_timing;
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::{
+    reexports::{
+        wayland_protocols::wp::content_type::v1::server::{
+            wp_content_type_manager_v1::{self, WpContentTypeManagerV1},
+            wp_content_type_v1::{self, WpContentTypeV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_surface::WlSurface,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    wayland::compositor::{with_states, Cacheable},
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub use smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_v1::Type as ContentType;
+
+#[derive(Debug)]
+pub struct ContentTypeState {
+    global: GlobalId,
+}
+
+impl ContentTypeState {
+    pub fn new<D>(dh: &DisplayHandle) -> ContentTypeState
+    where
+        D: GlobalDispatch<WpContentTypeManagerV1, ()>
+            + Dispatch<WpContentTypeManagerV1, ()>
+            + Dispatch<WpContentTypeV1, WlSurface>
+            + 'static,
+    {
+        let global = dh.create_global::<D, WpContentTypeManagerV1, _>(1, ());
+        ContentTypeState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// Double-buffered content type of a surface
+#[derive(Debug, Clone, Copy)]
+pub struct ContentTypeSurfaceCachedState {
+    pub content_type: ContentType,
+}
+
+impl Default for ContentTypeSurfaceCachedState {
+    fn default() -> Self {
+        ContentTypeSurfaceCachedState {
+            content_type: ContentType::None,
+        }
+    }
+}
+
+impl Cacheable for ContentTypeSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        *self
+    }
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ContentTypeMarker(AtomicBool);
+
+/// The kind of content the client says the surface shows
+pub fn surface_content_type(surface: &WlSurface) -> ContentType {
+    with_states(surface, |states| {
+        states
+            .cached_state
+            .current::<ContentTypeSurfaceCachedState>()
+            .content_type
+    })
+}
+
+impl<D> GlobalDispatch<WpContentTypeManagerV1, (), D> for ContentTypeState
+where
+    D: GlobalDispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeV1, WlSurface>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpContentTypeManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpContentTypeManagerV1, (), D> for ContentTypeState
+where
+    D: GlobalDispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeV1, WlSurface>
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpContentTypeManagerV1,
+        request: wp_content_type_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_content_type_manager_v1::Request::GetSurfaceContentType { id, surface } => {
+                let exists = with_states(&surface, |states| {
+                    states
+                        .data_map
+                        .insert_if_missing_threadsafe(ContentTypeMarker::default);
+                    states
+                        .data_map
+                        .get::<ContentTypeMarker>()
+                        .unwrap()
+                        .0
+                        .swap(true, Ordering::SeqCst)
+                });
+                data_init.init(id, surface.clone());
+                if exists {
+                    obj.post_error(
+                        wp_content_type_manager_v1::Error::AlreadyConstructed,
+                        format!("{:?} already has a content type object", surface),
+                    );
+                }
+            }
+            wp_content_type_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<WpContentTypeV1, WlSurface, D> for ContentTypeState
+where
+    D: GlobalDispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeV1, WlSurface>
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &WpContentTypeV1,
+        request: wp_content_type_v1::Request,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_content_type_v1::Request::SetContentType { content_type } => {
+                let WEnum::Value(content_type) = content_type else {
+                    return;
+                };
+                with_states(surface, |states| {
+                    states
+                        .cached_state
+                        .pending::<ContentTypeSurfaceCachedState>()
+                        .content_type = content_type;
+                });
+            }
+            wp_content_type_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _resource: &WpContentTypeV1,
+        surface: &WlSurface,
+    ) {
+        if !surface.alive() {
+            return;
+        }
+        // reverts to no content type with the next commit
+        with_states(surface, |states| {
+            states
+                .cached_state
+                .pending::<ContentTypeSurfaceCachedState>()
+                .content_type = ContentType::None;
+            if let Some(marker) = states.data_map.get::<ContentTypeMarker>() {
+                marker.0.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+macro_rules! delegate_content_type {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_manager_v1::WpContentTypeManagerV1: ()
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_manager_v1::WpContentTypeManagerV1: ()
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_v1::WpContentTypeV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+    };
+}
+pub(crate) use delegate_content_type;
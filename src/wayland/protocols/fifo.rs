@@ -0,0 +1,323 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{wp_fifo_manager_v1, wp_fifo_v1};
+
+mod generated {
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/fifo-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/fifo-v1.xml");
+}
+
+use self::{wp_fifo_manager_v1::WpFifoManagerV1, wp_fifo_v1::WpFifoV1};
+use smithay::{
+    desktop::utils::surface_primary_scanout_output,
+    output::Output,
+    reexports::wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::wl_surface::WlSurface,
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+    wayland::compositor::{
+        add_blocker, add_post_commit_hook, add_pre_commit_hook, with_states, Blocker, BlockerState,
+        Cacheable, CompositorHandler,
+    },
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+/// State of the fifo global.
+///
+/// Barriers are numbered in the order of the commits setting them, a commit waiting for
+/// a barrier is blocked until every barrier committed before it was cleared again.
+#[derive(Debug)]
+pub struct FifoState {
+    global: GlobalId,
+    surfaces: Vec<WlSurface>,
+}
+
+pub trait FifoHandler {
+    fn fifo_state(&mut self) -> &mut FifoState;
+}
+
+/// Double-buffered fifo requests of a surface, which only apply to a single commit
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FifoSurfaceCachedState {
+    pub set_barrier: bool,
+    pub wait_barrier: bool,
+}
+
+impl Cacheable for FifoSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        std::mem::take(self)
+    }
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+#[derive(Debug, Default)]
+struct FifoBarriers {
+    exists: AtomicBool,
+    /// Last barrier of a commit, that was received
+    committed: AtomicU64,
+    /// Last barrier of a commit, that was applied
+    applied: AtomicU64,
+    /// Last barrier, that was cleared
+    cleared: Arc<AtomicU64>,
+}
+
+struct FifoBlocker {
+    barrier: u64,
+    cleared: Arc<AtomicU64>,
+}
+
+impl Blocker for FifoBlocker {
+    fn state(&self) -> BlockerState {
+        if self.cleared.load(Ordering::SeqCst) >= self.barrier {
+            BlockerState::Released
+        } else {
+            BlockerState::Pending
+        }
+    }
+}
+
+impl FifoState {
+    pub fn new<D>(dh: &DisplayHandle) -> FifoState
+    where
+        D: GlobalDispatch<WpFifoManagerV1, ()>
+            + Dispatch<WpFifoManagerV1, ()>
+            + Dispatch<WpFifoV1, WlSurface>
+            + FifoHandler
+            + CompositorHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, WpFifoManagerV1, _>(1, ());
+        FifoState {
+            global,
+            surfaces: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Clears the barriers of the surfaces presented by a refresh cycle of `output`, and of
+    /// those not visible anywhere. Returns the clients, whose blocked commits should be
+    /// checked again.
+    pub fn clear_barriers(&self, output: &Output) -> Vec<Client> {
+        self.clear_barriers_if(|primary_output| {
+            primary_output.map_or(true, |primary_output| primary_output == output)
+        })
+    }
+
+    /// Clears the barriers of the surfaces, for whose primary output (`None` if they aren't
+    /// visible anywhere) `filter` returns true. Returns the clients, whose blocked commits
+    /// should be checked again.
+    pub fn clear_barriers_if(&self, filter: impl Fn(Option<&Output>) -> bool) -> Vec<Client> {
+        let mut clients = Vec::new();
+        for surface in self.surfaces.iter().filter(|surface| surface.is_alive()) {
+            let cleared = with_states(surface, |states| {
+                let primary_output = surface_primary_scanout_output(surface, states);
+                if !filter(primary_output.as_ref()) {
+                    return false;
+                }
+                let Some(barriers) = states.data_map.get::<FifoBarriers>() else {
+                    return false;
+                };
+                let applied = barriers.applied.load(Ordering::SeqCst);
+                barriers.cleared.swap(applied, Ordering::SeqCst) != applied
+            });
+            if let Some(client) = cleared.then(|| surface.client()).flatten() {
+                if !clients.contains(&client) {
+                    clients.push(client);
+                }
+            }
+        }
+        clients
+    }
+}
+
+fn add_hooks<D: CompositorHandler + 'static>(surface: &WlSurface) {
+    add_pre_commit_hook::<D, _>(surface, |_state, _dh, surface| {
+        let blocker = with_states(surface, |states| {
+            let pending = *states.cached_state.pending::<FifoSurfaceCachedState>();
+            let barriers = states.data_map.get::<FifoBarriers>().unwrap();
+            let barrier = barriers.committed.load(Ordering::SeqCst);
+            if pending.set_barrier {
+                barriers.committed.fetch_add(1, Ordering::SeqCst);
+            }
+            (pending.wait_barrier && barriers.cleared.load(Ordering::SeqCst) < barrier).then(|| {
+                FifoBlocker {
+                    barrier,
+                    cleared: barriers.cleared.clone(),
+                }
+            })
+        });
+        if let Some(blocker) = blocker {
+            add_blocker(surface, blocker);
+        }
+    });
+    add_post_commit_hook::<D, _>(surface, |_state, _dh, surface| {
+        with_states(surface, |states| {
+            if states
+                .cached_state
+                .current::<FifoSurfaceCachedState>()
+                .set_barrier
+            {
+                let barriers = states.data_map.get::<FifoBarriers>().unwrap();
+                barriers.applied.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+    });
+}
+
+impl<D> GlobalDispatch<WpFifoManagerV1, (), D> for FifoState
+where
+    D: GlobalDispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoV1, WlSurface>
+        + FifoHandler
+        + CompositorHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpFifoManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpFifoManagerV1, (), D> for FifoState
+where
+    D: GlobalDispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoV1, WlSurface>
+        + FifoHandler
+        + CompositorHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &WpFifoManagerV1,
+        request: wp_fifo_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_fifo_manager_v1::Request::GetFifo { id, surface } => {
+                let (new, exists) = with_states(&surface, |states| {
+                    let new = states
+                        .data_map
+                        .insert_if_missing_threadsafe(FifoBarriers::default);
+                    let barriers = states.data_map.get::<FifoBarriers>().unwrap();
+                    (new, barriers.exists.swap(true, Ordering::SeqCst))
+                });
+                data_init.init(id, surface.clone());
+                if exists {
+                    obj.post_error(
+                        wp_fifo_manager_v1::Error::AlreadyExists,
+                        format!("{:?} already has a fifo object", surface),
+                    );
+                    return;
+                }
+                // the hooks stay around, if the surface gets another fifo object later
+                if new {
+                    add_hooks::<D>(&surface);
+                    state.fifo_state().surfaces.push(surface);
+                }
+            }
+            wp_fifo_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<WpFifoV1, WlSurface, D> for FifoState
+where
+    D: GlobalDispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoManagerV1, ()>
+        + Dispatch<WpFifoV1, WlSurface>
+        + FifoHandler
+        + CompositorHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpFifoV1,
+        request: wp_fifo_v1::Request,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        if !surface.is_alive() {
+            if !matches!(request, wp_fifo_v1::Request::Destroy) {
+                obj.post_error(
+                    wp_fifo_v1::Error::SurfaceDestroyed,
+                    "the surface was destroyed",
+                );
+            }
+            return;
+        }
+        match request {
+            wp_fifo_v1::Request::SetBarrier => with_states(surface, |states| {
+                states
+                    .cached_state
+                    .pending::<FifoSurfaceCachedState>()
+                    .set_barrier = true;
+            }),
+            wp_fifo_v1::Request::WaitBarrier => with_states(surface, |states| {
+                states
+                    .cached_state
+                    .pending::<FifoSurfaceCachedState>()
+                    .wait_barrier = true;
+            }),
+            wp_fifo_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, _resource: &WpFifoV1, surface: &WlSurface) {
+        state.fifo_state().surfaces.retain(|s| s.is_alive());
+        if !surface.is_alive() {
+            return;
+        }
+        with_states(surface, |states| {
+            if let Some(barriers) = states.data_map.get::<FifoBarriers>() {
+                barriers.exists.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+macro_rules! delegate_fifo {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::fifo::wp_fifo_manager_v1::WpFifoManagerV1: ()
+        ] => $crate::wayland::protocols::fifo::FifoState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::fifo::wp_fifo_manager_v1::WpFifoManagerV1: ()
+        ] => $crate::wayland::protocols::fifo::FifoState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::fifo::wp_fifo_v1::WpFifoV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::protocols::fifo::FifoState);
+    };
+}
+pub(crate) use delegate_fifo;
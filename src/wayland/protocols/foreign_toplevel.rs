@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::Mutex;
+
+use smithay::{
+    input::Seat,
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::foreign_toplevel::v1::server::{
+            zwlr_foreign_toplevel_handle_v1::{self, State as States, ZwlrForeignToplevelHandleV1},
+            zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::Rectangle,
+};
+
+use super::{
+    toplevel_info::{ToplevelInfoHandler, ToplevelState, Window},
+    toplevel_management::{ManagementWindow, ToplevelManagementHandler},
+};
+
+/// State of the wlr-foreign-toplevel-management global, used by taskbars and docks
+/// not speaking the cosmic toplevel protocols.
+///
+/// Requests on the handles are routed to the [`ToplevelManagementHandler`].
+#[derive(Debug)]
+pub struct ForeignToplevelState<D, W: Window> {
+    dh: DisplayHandle,
+    toplevels: Vec<W>,
+    instances: Vec<ZwlrForeignToplevelManagerV1>,
+    global: GlobalId,
+    _dispatch_data: std::marker::PhantomData<D>,
+}
+
+pub trait ForeignToplevelHandler: ToplevelManagementHandler
+where
+    <Self as ToplevelInfoHandler>::Window: ManagementWindow,
+{
+    fn foreign_toplevel_state(
+        &mut self,
+    ) -> &mut ForeignToplevelState<Self, <Self as ToplevelInfoHandler>::Window>;
+}
+
+pub struct ForeignToplevelGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+/// Handles of a window, one per bound manager
+type ForeignToplevelHandles = Mutex<Vec<ZwlrForeignToplevelHandleV1>>;
+
+pub struct ForeignToplevelHandleStateInner<W: Window> {
+    outputs: Vec<Output>,
+    title: String,
+    app_id: String,
+    states: Vec<States>,
+    parent: Option<ZwlrForeignToplevelHandleV1>,
+    window: W,
+}
+pub type ForeignToplevelHandleState<W> = Mutex<ForeignToplevelHandleStateInner<W>>;
+
+impl<D, W> ForeignToplevelState<D, W>
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>>
+        + ForeignToplevelHandler
+        + ToplevelInfoHandler<Window = W>
+        + 'static,
+    W: ManagementWindow + 'static,
+{
+    pub fn new<F>(dh: &DisplayHandle, client_filter: F) -> ForeignToplevelState<D, W>
+    where
+        F: for<'a> Fn(&'a Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZwlrForeignToplevelManagerV1, _>(
+            3,
+            ForeignToplevelGlobalData {
+                filter: Box::new(client_filter),
+            },
+        );
+        ForeignToplevelState {
+            dh: dh.clone(),
+            toplevels: Vec::new(),
+            instances: Vec::new(),
+            global,
+            _dispatch_data: std::marker::PhantomData,
+        }
+    }
+
+    pub fn new_toplevel(&mut self, toplevel: &W) {
+        toplevel
+            .user_data()
+            .insert_if_missing(ForeignToplevelHandles::default);
+        self.toplevels.push(toplevel.clone());
+        for instance in &self.instances {
+            send_toplevel_to_client::<D, W>(&self.dh, instance, toplevel, &self.toplevels);
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.toplevels.retain(|window| {
+            if window.alive() {
+                true
+            } else {
+                let handles = window
+                    .user_data()
+                    .get::<ForeignToplevelHandles>()
+                    .unwrap()
+                    .lock()
+                    .unwrap();
+                for handle in handles.iter() {
+                    // don't send events to stopped instances
+                    if self
+                        .instances
+                        .iter()
+                        .any(|i| i.id().same_client_as(&handle.id()))
+                    {
+                        handle.closed();
+                    }
+                }
+                false
+            }
+        });
+        for window in &self.toplevels {
+            for instance in &self.instances {
+                send_toplevel_to_client::<D, W>(&self.dh, instance, window, &self.toplevels);
+            }
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D, W> GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData, D>
+    for ForeignToplevelState<D, W>
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>>
+        + ForeignToplevelHandler
+        + ToplevelInfoHandler<Window = W>
+        + 'static,
+    W: ManagementWindow + 'static,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrForeignToplevelManagerV1>,
+        _global_data: &ForeignToplevelGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let instance = data_init.init(resource, ());
+        let toplevels = &state.foreign_toplevel_state().toplevels;
+        for window in toplevels {
+            send_toplevel_to_client::<D, W>(dh, &instance, window, toplevels);
+        }
+        state.foreign_toplevel_state().instances.push(instance);
+    }
+
+    fn can_view(client: Client, global_data: &ForeignToplevelGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D, W> Dispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelState<D, W>
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>>
+        + ForeignToplevelHandler
+        + ToplevelInfoHandler<Window = W>
+        + 'static,
+    W: ManagementWindow + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                state
+                    .foreign_toplevel_state()
+                    .instances
+                    .retain(|i| i != obj);
+                obj.finished();
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrForeignToplevelManagerV1,
+        _data: &(),
+    ) {
+        state
+            .foreign_toplevel_state()
+            .instances
+            .retain(|i| i != resource);
+    }
+}
+
+impl<D, W> Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>, D>
+    for ForeignToplevelState<D, W>
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>>
+        + ForeignToplevelHandler
+        + ToplevelInfoHandler<Window = W>
+        + 'static,
+    W: ManagementWindow + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        data: &ForeignToplevelHandleState<W>,
+        dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let window = data.lock().unwrap().window.clone();
+        if !window.alive() {
+            return;
+        }
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { seat } => {
+                state.activate(dh, &window, Seat::from_resource(&seat));
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Close => {
+                state.close(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetFullscreen { output } => {
+                state.fullscreen(dh, &window, output.as_ref().and_then(Output::from_resource))
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetFullscreen => {
+                state.unfullscreen(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetMaximized => {
+                state.maximize(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMaximized => {
+                state.unmaximize(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetMinimized => {
+                state.minimize(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMinimized => {
+                state.unminimize(dh, &window);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetRectangle {
+                surface,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                // shared with the cosmic protocol, so both end up in `rectangle_for`
+                if let Some(toplevel_state) = window.user_data().get::<ToplevelState>() {
+                    let mut toplevel_state = toplevel_state.lock().unwrap();
+                    if let Some(client) = surface.client() {
+                        if width == 0 && height == 0 {
+                            toplevel_state.rectangles.remove(&client.id());
+                        } else {
+                            toplevel_state.rectangles.insert(
+                                client.id(),
+                                (
+                                    surface,
+                                    Rectangle::from_loc_and_size((x, y), (width, height)),
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrForeignToplevelHandleV1,
+        data: &ForeignToplevelHandleState<W>,
+    ) {
+        let window = data.lock().unwrap().window.clone();
+        if let Some(handles) = window.user_data().get::<ForeignToplevelHandles>() {
+            handles.lock().unwrap().retain(|h| h != resource);
+        }
+    }
+}
+
+fn send_toplevel_to_client<D, W>(
+    dh: &DisplayHandle,
+    manager: &ZwlrForeignToplevelManagerV1,
+    window: &W,
+    toplevels: &[W],
+) where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleState<W>>
+        + ForeignToplevelHandler
+        + ToplevelInfoHandler<Window = W>
+        + 'static,
+    W: ManagementWindow + 'static,
+{
+    let mut handles = window
+        .user_data()
+        .get::<ForeignToplevelHandles>()
+        .unwrap()
+        .lock()
+        .unwrap();
+    let instance = match handles
+        .iter()
+        .find(|i| i.id().same_client_as(&manager.id()))
+    {
+        Some(i) => i,
+        None => {
+            let Ok(client) = dh.get_client(manager.id()) else {
+                return;
+            };
+            let Ok(handle) = client.create_resource::<ZwlrForeignToplevelHandleV1, _, D>(
+                dh,
+                manager.version(),
+                ForeignToplevelHandleState::new(ForeignToplevelHandleStateInner {
+                    outputs: Vec::new(),
+                    title: String::new(),
+                    app_id: String::new(),
+                    states: Vec::new(),
+                    parent: None,
+                    window: window.clone(),
+                }),
+            ) else {
+                return;
+            };
+            manager.toplevel(&handle);
+            handles.push(handle);
+            handles.last().unwrap()
+        }
+    };
+
+    let mut handle_state = instance
+        .data::<ForeignToplevelHandleState<W>>()
+        .unwrap()
+        .lock()
+        .unwrap();
+    let mut changed = false;
+    if handle_state.title != window.title() {
+        handle_state.title = window.title();
+        instance.title(handle_state.title.clone());
+        changed = true;
+    }
+    if handle_state.app_id != window.app_id() {
+        handle_state.app_id = window.app_id();
+        instance.app_id(handle_state.app_id.clone());
+        changed = true;
+    }
+
+    let mut states = Vec::new();
+    if window.is_maximized() {
+        states.push(States::Maximized);
+    }
+    if window.is_minimized() {
+        states.push(States::Minimized);
+    }
+    if window.is_activated() {
+        states.push(States::Activated);
+    }
+    if window.is_fullscreen() {
+        states.push(States::Fullscreen);
+    }
+    if handle_state.states != states {
+        handle_state.states = states.clone();
+        let states: Vec<u8> = {
+            let ratio = std::mem::size_of::<States>() / std::mem::size_of::<u8>();
+            let ptr = states.as_mut_ptr() as *mut u8;
+            let len = states.len() * ratio;
+            let cap = states.capacity() * ratio;
+            std::mem::forget(states);
+            unsafe { Vec::from_raw_parts(ptr, len, cap) }
+        };
+        instance.state(states);
+        changed = true;
+    }
+
+    if instance.version() >= 3 {
+        // a parent without a handle yet is sent on the next refresh
+        let parent = toplevels
+            .iter()
+            .filter(|parent| *parent != window)
+            .find(|parent| window.is_transient_for(parent))
+            .and_then(|parent| {
+                parent
+                    .user_data()
+                    .get::<ForeignToplevelHandles>()?
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|handle| handle.id().same_client_as(&manager.id()))
+                    .cloned()
+            });
+        if handle_state.parent != parent {
+            instance.parent(parent.as_ref());
+            handle_state.parent = parent;
+            changed = true;
+        }
+    }
+
+    // outputs are tracked by the cosmic toplevel-info state
+    let outputs = window
+        .user_data()
+        .get::<ToplevelState>()
+        .map(|state| state.lock().unwrap().outputs.clone())
+        .unwrap_or_default();
+    if let Ok(client) = dh.get_client(instance.id()) {
+        for new_output in outputs.iter().filter(|o| !handle_state.outputs.contains(o)) {
+            for wl_output in new_output.client_outputs(&client) {
+                instance.output_enter(&wl_output);
+            }
+            changed = true;
+        }
+        for old_output in handle_state.outputs.iter().filter(|o| !outputs.contains(o)) {
+            for wl_output in old_output.client_outputs(&client) {
+                instance.output_leave(&wl_output);
+            }
+            changed = true;
+        }
+        handle_state.outputs = outputs;
+    }
+
+    if changed {
+        instance.done();
+    }
+}
+
+macro_rules! delegate_foreign_toplevel {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty, $window: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: $crate::wayland::protocols::foreign_toplevel::ForeignToplevelGlobalData
+        ] => $crate::wayland::protocols::foreign_toplevel::ForeignToplevelState<Self, $window>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::wayland::protocols::foreign_toplevel::ForeignToplevelState<Self, $window>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1: $crate::wayland::protocols::foreign_toplevel::ForeignToplevelHandleState<$window>
+        ] => $crate::wayland::protocols::foreign_toplevel::ForeignToplevelState<Self, $window>);
+    };
+}
+pub(crate) use delegate_foreign_toplevel;
@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::{
+    output::Output,
+    reexports::{
+        nix::fcntl,
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+};
+use std::{
+    fs::File,
+    os::unix::{fs::FileExt, io::AsRawFd},
+};
+
+/// State of the wlr-gamma-control global, tracking which client controls the gamma of which output
+#[derive(Debug)]
+pub struct GammaControlState {
+    global: GlobalId,
+    controls: Vec<(Output, ZwlrGammaControlV1)>,
+}
+
+pub trait GammaControlHandler {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState;
+
+    /// Number of entries per channel of the gamma ramps of an output,
+    /// or `None` if the gamma can't be controlled.
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+    /// Sets the red, green and blue ramps of an output,
+    /// or restores the compositors own gamma if `None`.
+    fn set_gamma(&mut self, output: &Output, gamma: Option<[Vec<u16>; 3]>) -> bool;
+}
+
+pub struct GammaControlGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+impl GammaControlState {
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> GammaControlState
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+            + Dispatch<ZwlrGammaControlManagerV1, ()>
+            + Dispatch<ZwlrGammaControlV1, Option<Output>>
+            + GammaControlHandler
+            + 'static,
+        F: for<'a> Fn(&'a Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZwlrGammaControlManagerV1, _>(
+            1,
+            GammaControlGlobalData {
+                filter: Box::new(client_filter),
+            },
+        );
+
+        GammaControlState {
+            global,
+            controls: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Notifies clients, that they lost control over the gamma of a removed output
+    pub fn output_removed(&mut self, output: &Output) {
+        self.controls.retain(|(o, control)| {
+            if o == output {
+                control.failed();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData, D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, Option<Output>>
+        + GammaControlHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &GammaControlGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, Option<Output>>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let control = data_init.init(id, output.clone());
+
+                let Some(output) = output else {
+                    control.failed();
+                    return;
+                };
+                // only one client may control the gamma of an output at a time
+                if state
+                    .gamma_control_state()
+                    .controls
+                    .iter()
+                    .any(|(o, _)| *o == output)
+                {
+                    control.failed();
+                    return;
+                }
+                let Some(size) = state.gamma_size(&output) else {
+                    control.failed();
+                    return;
+                };
+
+                control.gamma_size(size);
+                state.gamma_control_state().controls.push((output, control));
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, Option<Output>, D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, Option<Output>>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &Option<Output>,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                let Some(output) = data.as_ref() else {
+                    return;
+                };
+                if !state
+                    .gamma_control_state()
+                    .controls
+                    .iter()
+                    .any(|(_, control)| control == obj)
+                {
+                    // the control already failed
+                    return;
+                }
+                let Some(size) = state.gamma_size(output) else {
+                    fail_control(state, obj);
+                    return;
+                };
+
+                let size = size as usize;
+                let mut bytes = vec![0u8; size * 3 * std::mem::size_of::<u16>()];
+                // never wait for the client, the ramps have to be written already
+                let file = File::from(fd);
+                let nonblocking = fcntl::fcntl(file.as_raw_fd(), fcntl::FcntlArg::F_GETFL)
+                    .map(|f| fcntl::OFlag::from_bits_truncate(f) | fcntl::OFlag::O_NONBLOCK)
+                    .and_then(|f| fcntl::fcntl(file.as_raw_fd(), fcntl::FcntlArg::F_SETFL(f)));
+                if nonblocking.is_err()
+                    || !file
+                        .read_at(&mut bytes, 0)
+                        .is_ok_and(|read| read == bytes.len())
+                {
+                    obj.post_error(
+                        zwlr_gamma_control_v1::Error::InvalidGamma,
+                        "Gamma ramps don't match the gamma size",
+                    );
+                    return;
+                }
+                let ramps = bytes
+                    .chunks_exact(2)
+                    .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+                    .collect::<Vec<_>>();
+                let gamma = [
+                    ramps[..size].to_vec(),
+                    ramps[size..size * 2].to_vec(),
+                    ramps[size * 2..].to_vec(),
+                ];
+
+                if !state.set_gamma(output, Some(gamma)) {
+                    fail_control(state, obj);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrGammaControlV1,
+        _data: &Option<Output>,
+    ) {
+        // restore the original gamma, once the controlling client is gone
+        if let Some(output) = remove_control(state, resource) {
+            state.set_gamma(&output, None);
+        }
+    }
+}
+
+fn remove_control<D: GammaControlHandler>(
+    state: &mut D,
+    control: &ZwlrGammaControlV1,
+) -> Option<Output> {
+    let controls = &mut state.gamma_control_state().controls;
+    let idx = controls.iter().position(|(_, c)| c == control)?;
+    Some(controls.remove(idx).0)
+}
+
+fn fail_control<D: GammaControlHandler>(state: &mut D, control: &ZwlrGammaControlV1) {
+    if let Some(output) = remove_control(state, control) {
+        state.set_gamma(&output, None);
+    }
+    control.failed();
+}
+
+macro_rules! delegate_gamma_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::wayland::protocols::gamma_control::GammaControlGlobalData
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: Option<smithay::output::Output>
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+    };
+}
+pub(crate) use delegate_gamma_control;
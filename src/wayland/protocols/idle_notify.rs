@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+use smithay::reexports::{
+    wayland_protocols::ext::idle_notify::v1::server::{
+        ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+        ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+use tracing::warn;
+
+/// State of the ext-idle-notify global.
+///
+/// Every notification has its own timer, which is started again,
+/// whenever input activity ends the idle state of the notification.
+#[derive(Debug)]
+pub struct IdleNotifierState<D: 'static> {
+    global: GlobalId,
+    notifications: Vec<ExtIdleNotificationV1>,
+    last_activity: Instant,
+    loop_handle: LoopHandle<'static, D>,
+}
+
+pub trait IdleNotifierHandler: Sized {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<Self>;
+
+    /// Whether going idle is currently inhibited, which counts as activity
+    fn idle_inhibited(&self) -> bool;
+}
+
+pub struct IdleNotifierGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+#[derive(Debug)]
+pub struct IdleNotificationInner {
+    timeout: Duration,
+    idle: bool,
+    timer: Option<RegistrationToken>,
+}
+pub type IdleNotification = Mutex<IdleNotificationInner>;
+
+impl<D> IdleNotifierState<D>
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, IdleNotifierGlobalData>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, IdleNotification>
+        + IdleNotifierHandler
+        + 'static,
+{
+    pub fn new<F>(
+        dh: &DisplayHandle,
+        loop_handle: LoopHandle<'static, D>,
+        client_filter: F,
+    ) -> IdleNotifierState<D>
+    where
+        F: for<'a> Fn(&'a Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ExtIdleNotifierV1, _>(
+            1,
+            IdleNotifierGlobalData {
+                filter: Box::new(client_filter),
+            },
+        );
+        IdleNotifierState {
+            global,
+            notifications: Vec::new(),
+            last_activity: Instant::now(),
+            loop_handle,
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Records user activity, resuming all notifications that went idle
+    pub fn notify_activity(&mut self) {
+        self.last_activity = Instant::now();
+        for notification in &self.notifications {
+            let data = notification.data::<IdleNotification>().unwrap();
+            let mut data = data.lock().unwrap();
+            if data.idle {
+                data.idle = false;
+                notification.resumed();
+                data.timer = self.start_timer(notification, data.timeout);
+            }
+        }
+    }
+
+    fn start_timer(
+        &self,
+        notification: &ExtIdleNotificationV1,
+        timeout: Duration,
+    ) -> Option<RegistrationToken> {
+        let notification = notification.clone();
+        match self.loop_handle.insert_source(
+            Timer::from_duration(timeout),
+            move |_, _, state: &mut D| {
+                if state.idle_inhibited() {
+                    state.idle_notifier_state().last_activity = Instant::now();
+                    return TimeoutAction::ToDuration(timeout);
+                }
+                // activity in the meantime only moved the deadline
+                let idle_for = state.idle_notifier_state().last_activity.elapsed();
+                if idle_for < timeout {
+                    return TimeoutAction::ToDuration(timeout - idle_for);
+                }
+                if let Some(data) = notification.data::<IdleNotification>() {
+                    let mut data = data.lock().unwrap();
+                    data.idle = true;
+                    data.timer = None;
+                    notification.idled();
+                }
+                TimeoutAction::Drop
+            },
+        ) {
+            Ok(token) => Some(token),
+            Err(err) => {
+                warn!(?err, "Failed to start idle notification timer.");
+                None
+            }
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ExtIdleNotifierV1, IdleNotifierGlobalData, D> for IdleNotifierState<D>
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, IdleNotifierGlobalData>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, IdleNotification>
+        + IdleNotifierHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtIdleNotifierV1>,
+        _global_data: &IdleNotifierGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &IdleNotifierGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotifierV1, (), D> for IdleNotifierState<D>
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, IdleNotifierGlobalData>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, IdleNotification>
+        + IdleNotifierHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ExtIdleNotifierV1,
+        request: ext_idle_notifier_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // activity is tracked across all seats
+            ext_idle_notifier_v1::Request::GetIdleNotification { id, timeout, .. } => {
+                let timeout = Duration::from_millis(timeout as u64);
+                let notification = data_init.init(
+                    id,
+                    IdleNotification::new(IdleNotificationInner {
+                        timeout,
+                        idle: false,
+                        timer: None,
+                    }),
+                );
+                let idle_state = state.idle_notifier_state();
+                let timer = idle_state.start_timer(&notification, timeout);
+                notification
+                    .data::<IdleNotification>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .timer = timer;
+                idle_state.notifications.push(notification);
+            }
+            ext_idle_notifier_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotificationV1, IdleNotification, D> for IdleNotifierState<D>
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, IdleNotifierGlobalData>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, IdleNotification>
+        + IdleNotifierHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ExtIdleNotificationV1,
+        request: ext_idle_notification_v1::Request,
+        _data: &IdleNotification,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_idle_notification_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtIdleNotificationV1,
+        data: &IdleNotification,
+    ) {
+        let idle_state = state.idle_notifier_state();
+        idle_state.notifications.retain(|n| n != resource);
+        if let Some(token) = data.lock().unwrap().timer.take() {
+            idle_state.loop_handle.remove(token);
+        }
+    }
+}
+
+macro_rules! delegate_idle_notify {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: $crate::wayland::protocols::idle_notify::IdleNotifierGlobalData
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState<Self>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: ()
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState<Self>);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notification_v1::ExtIdleNotificationV1: $crate::wayland::protocols::idle_notify::IdleNotification
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState<Self>);
+    };
+}
+pub(crate) use delegate_idle_notify;
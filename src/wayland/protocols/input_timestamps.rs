@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::reexports::{
+    wayland_protocols::wp::input_timestamps::zv1::server::{
+        zwp_input_timestamps_manager_v1::{self, ZwpInputTimestampsManagerV1},
+        zwp_input_timestamps_v1::{self, ZwpInputTimestampsV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::{wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_touch::WlTouch},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+use std::time::Duration;
+
+/// State of the input-timestamps global.
+///
+/// The compositor has to send the timestamps right before the input events they belong to,
+/// which are then associated with the first following event of the subscribed object.
+#[derive(Debug)]
+pub struct InputTimestampsState {
+    global: GlobalId,
+    timestamps: Vec<(ZwpInputTimestampsV1, InputResource)>,
+}
+
+/// Kind of input events, a client can subscribe to the timestamps of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Keyboard,
+    Pointer,
+    Touch,
+}
+
+#[derive(Debug, Clone)]
+enum InputResource {
+    Keyboard(WlKeyboard),
+    Pointer(WlPointer),
+    Touch(WlTouch),
+}
+
+impl InputResource {
+    fn kind(&self) -> InputKind {
+        match self {
+            InputResource::Keyboard(_) => InputKind::Keyboard,
+            InputResource::Pointer(_) => InputKind::Pointer,
+            InputResource::Touch(_) => InputKind::Touch,
+        }
+    }
+
+    fn client(&self) -> Option<Client> {
+        match self {
+            InputResource::Keyboard(keyboard) => keyboard.client(),
+            InputResource::Pointer(pointer) => pointer.client(),
+            InputResource::Touch(touch) => touch.client(),
+        }
+    }
+}
+
+pub trait InputTimestampsHandler {
+    fn input_timestamps_state(&mut self) -> &mut InputTimestampsState;
+}
+
+impl InputTimestampsState {
+    pub fn new<D>(dh: &DisplayHandle) -> InputTimestampsState
+    where
+        D: GlobalDispatch<ZwpInputTimestampsManagerV1, ()>
+            + Dispatch<ZwpInputTimestampsManagerV1, ()>
+            + Dispatch<ZwpInputTimestampsV1, ()>
+            + InputTimestampsHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, ZwpInputTimestampsManagerV1, _>(1, ());
+        InputTimestampsState {
+            global,
+            timestamps: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Sends `time` to the subscriptions of `client` for events of `kind`.
+    ///
+    /// Has to be called right before the event is sent to the client.
+    pub fn send_timestamp(&self, client: &Client, kind: InputKind, time: Duration) {
+        let secs = time.as_secs();
+        for (timestamps, _) in self.timestamps.iter().filter(|(_, resource)| {
+            resource.kind() == kind && resource.client().as_ref() == Some(client)
+        }) {
+            timestamps.timestamp((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwpInputTimestampsManagerV1, (), D> for InputTimestampsState
+where
+    D: GlobalDispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsV1, ()>
+        + InputTimestampsHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpInputTimestampsManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZwpInputTimestampsManagerV1, (), D> for InputTimestampsState
+where
+    D: GlobalDispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsV1, ()>
+        + InputTimestampsHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwpInputTimestampsManagerV1,
+        request: zwp_input_timestamps_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (id, resource) = match request {
+            zwp_input_timestamps_manager_v1::Request::GetKeyboardTimestamps { id, keyboard } => {
+                (id, InputResource::Keyboard(keyboard))
+            }
+            zwp_input_timestamps_manager_v1::Request::GetPointerTimestamps { id, pointer } => {
+                (id, InputResource::Pointer(pointer))
+            }
+            zwp_input_timestamps_manager_v1::Request::GetTouchTimestamps { id, touch } => {
+                (id, InputResource::Touch(touch))
+            }
+            zwp_input_timestamps_manager_v1::Request::Destroy => return,
+            _ => return,
+        };
+        let timestamps = data_init.init(id, ());
+        state
+            .input_timestamps_state()
+            .timestamps
+            .push((timestamps, resource));
+    }
+}
+
+impl<D> Dispatch<ZwpInputTimestampsV1, (), D> for InputTimestampsState
+where
+    D: GlobalDispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsManagerV1, ()>
+        + Dispatch<ZwpInputTimestampsV1, ()>
+        + InputTimestampsHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZwpInputTimestampsV1,
+        request: zwp_input_timestamps_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_input_timestamps_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwpInputTimestampsV1, _data: &()) {
+        state
+            .input_timestamps_state()
+            .timestamps
+            .retain(|(timestamps, _)| timestamps != resource);
+    }
+}
+
+macro_rules! delegate_input_timestamps {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::input_timestamps::zv1::server::zwp_input_timestamps_manager_v1::ZwpInputTimestampsManagerV1: ()
+        ] => $crate::wayland::protocols::input_timestamps::InputTimestampsState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::input_timestamps::zv1::server::zwp_input_timestamps_manager_v1::ZwpInputTimestampsManagerV1: ()
+        ] => $crate::wayland::protocols::input_timestamps::InputTimestampsState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::input_timestamps::zv1::server::zwp_input_timestamps_v1::ZwpInputTimestampsV1: ()
+        ] => $crate::wayland::protocols::input_timestamps::InputTimestampsState);
+    };
+}
+pub(crate) use delegate_input_timestamps;
@@ -1,9 +1,25 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+pub mod alpha_modifier;
+pub mod commit_timing;
+pub mod content_type;
 pub mod drm;
 //pub mod export_dmabuf;
+pub mod fifo;
+pub mod foreign_toplevel;
+pub mod gamma_control;
+pub mod idle_inhibit;
+pub mod idle_notify;
+pub mod input_timestamps;
 pub mod output_configuration;
+pub mod overlap_notify;
+pub mod pointer_warp;
 pub mod screencopy;
+pub mod screencopy_frame_rate;
+pub mod system_bell;
+pub mod toplevel_drag;
 pub mod toplevel_info;
 pub mod toplevel_management;
 pub mod workspace;
+pub mod xdg_dialog;
+pub mod xdg_foreign;
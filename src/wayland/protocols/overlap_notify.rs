@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{zcosmic_overlap_notification_v1, zcosmic_overlap_notify_v1};
+
+mod generated {
+    use cosmic_protocols::toplevel_info::v1::server::*;
+    use smithay::reexports::{
+        wayland_protocols_wlr::layer_shell::v1::server::*,
+        wayland_server::{self, protocol::*},
+    };
+
+    pub mod __interfaces {
+        use cosmic_protocols::toplevel_info::v1::__interfaces::*;
+        use smithay::reexports::{
+            wayland_protocols_wlr::layer_shell::v1::__interfaces::*,
+            wayland_server::protocol::__interfaces::*,
+        };
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!(
+            "resources/protocols/cosmic-overlap-notify-unstable-v1.xml"
+        );
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!(
+        "resources/protocols/cosmic-overlap-notify-unstable-v1.xml"
+    );
+}
+
+use self::{
+    zcosmic_overlap_notification_v1::ZcosmicOverlapNotificationV1,
+    zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1,
+};
+use super::toplevel_info::{toplevel_handle_for_client, Window};
+use cosmic_protocols::toplevel_info::v1::server::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1;
+use smithay::{
+    reexports::{
+        wayland_protocols_wlr::layer_shell::v1::server::zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_surface::WlSurface,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Rectangle},
+    wayland::compositor::with_states,
+};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// State of the overlap-notify global.
+///
+/// Overlaps are computed by the shell, this only keeps track of what every
+/// notification object was already told, so only changes are sent.
+/// Toplevels are referenced by their cosmic-toplevel-info handles, so clients
+/// need to bind that global as well to be told about them.
+#[derive(Debug)]
+pub struct OverlapNotifyState {
+    global: GlobalId,
+    notifications: Vec<OverlapNotification>,
+}
+
+#[derive(Debug)]
+struct OverlapNotification {
+    notification: ZcosmicOverlapNotificationV1,
+    layer_surface: ZwlrLayerSurfaceV1,
+    toplevels: HashMap<ZcosmicToplevelHandleV1, Rectangle<i32, Logical>>,
+    layers: HashMap<String, Rectangle<i32, Logical>>,
+}
+
+/// Another layer surface overlapping an observed one
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerOverlap {
+    /// Identifier unique for the lifetime of the surface
+    pub identifier: String,
+    pub namespace: String,
+    /// Whether the surface has an exclusive zone
+    pub exclusive: bool,
+    /// One of the `zwlr_layer_shell_v1.layer` values
+    pub layer: u32,
+    /// Overlapping area relative to the observed surface
+    pub overlap: Rectangle<i32, Logical>,
+}
+
+struct LayerIdentifier(String);
+
+/// Identifier of a layer surface, unique across clients
+pub fn layer_identifier(surface: &WlSurface) -> String {
+    static NEXT_IDENTIFIER: AtomicU64 = AtomicU64::new(0);
+    with_states(surface, |states| {
+        states.data_map.insert_if_missing_threadsafe(|| {
+            LayerIdentifier(NEXT_IDENTIFIER.fetch_add(1, Ordering::SeqCst).to_string())
+        });
+        states.data_map.get::<LayerIdentifier>().unwrap().0.clone()
+    })
+}
+
+pub trait OverlapNotifyHandler {
+    fn overlap_notify_state(&mut self) -> &mut OverlapNotifyState;
+}
+
+pub struct OverlapNotifyGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+impl OverlapNotifyState {
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> OverlapNotifyState
+    where
+        D: GlobalDispatch<ZcosmicOverlapNotifyV1, OverlapNotifyGlobalData>
+            + Dispatch<ZcosmicOverlapNotifyV1, ()>
+            + Dispatch<ZcosmicOverlapNotificationV1, ()>
+            + OverlapNotifyHandler
+            + 'static,
+        F: for<'a> Fn(&'a Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZcosmicOverlapNotifyV1, _>(
+            1,
+            OverlapNotifyGlobalData {
+                filter: Box::new(client_filter),
+            },
+        );
+        OverlapNotifyState {
+            global,
+            notifications: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Layer surfaces some client asked to be notified about
+    pub fn layer_surfaces(&self) -> Vec<ZwlrLayerSurfaceV1> {
+        let mut surfaces = Vec::new();
+        for notification in &self.notifications {
+            if notification.layer_surface.is_alive()
+                && !surfaces.contains(&notification.layer_surface)
+            {
+                surfaces.push(notification.layer_surface.clone());
+            }
+        }
+        surfaces
+    }
+
+    /// Sets the current overlaps of `layer_surface`, sending the differences to the
+    /// previous ones. Areas are relative to `layer_surface`.
+    pub fn update<W: Window>(
+        &mut self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        toplevels: &[(W, Rectangle<i32, Logical>)],
+        layers: &[LayerOverlap],
+    ) {
+        for notification in self
+            .notifications
+            .iter_mut()
+            .filter(|n| &n.layer_surface == layer_surface)
+        {
+            let object = &notification.notification;
+
+            let mut new_toplevels = HashMap::new();
+            for (window, overlap) in toplevels {
+                if let Some(handle) = toplevel_handle_for_client(window, &object.id()) {
+                    new_toplevels.insert(handle, *overlap);
+                }
+            }
+            for handle in notification.toplevels.keys() {
+                if !new_toplevels.contains_key(handle) && handle.is_alive() {
+                    object.toplevel_leave(handle);
+                }
+            }
+            for (handle, overlap) in &new_toplevels {
+                if notification.toplevels.get(handle) != Some(overlap) {
+                    object.toplevel_enter(
+                        handle,
+                        overlap.loc.x,
+                        overlap.loc.y,
+                        overlap.size.w,
+                        overlap.size.h,
+                    );
+                }
+            }
+            notification.toplevels = new_toplevels;
+
+            for identifier in notification.layers.keys() {
+                if !layers.iter().any(|layer| &layer.identifier == identifier) {
+                    object.layer_leave(identifier.clone());
+                }
+            }
+            for layer in layers {
+                if notification.layers.get(&layer.identifier) != Some(&layer.overlap) {
+                    object.layer_enter(
+                        layer.identifier.clone(),
+                        layer.namespace.clone(),
+                        layer.exclusive as u32,
+                        layer.layer,
+                        layer.overlap.loc.x,
+                        layer.overlap.loc.y,
+                        layer.overlap.size.w,
+                        layer.overlap.size.h,
+                    );
+                }
+            }
+            notification.layers = layers
+                .iter()
+                .map(|layer| (layer.identifier.clone(), layer.overlap))
+                .collect();
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicOverlapNotifyV1, OverlapNotifyGlobalData, D> for OverlapNotifyState
+where
+    D: GlobalDispatch<ZcosmicOverlapNotifyV1, OverlapNotifyGlobalData>
+        + Dispatch<ZcosmicOverlapNotifyV1, ()>
+        + Dispatch<ZcosmicOverlapNotificationV1, ()>
+        + OverlapNotifyHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicOverlapNotifyV1>,
+        _global_data: &OverlapNotifyGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &OverlapNotifyGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicOverlapNotifyV1, (), D> for OverlapNotifyState
+where
+    D: GlobalDispatch<ZcosmicOverlapNotifyV1, OverlapNotifyGlobalData>
+        + Dispatch<ZcosmicOverlapNotifyV1, ()>
+        + Dispatch<ZcosmicOverlapNotificationV1, ()>
+        + OverlapNotifyHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZcosmicOverlapNotifyV1,
+        request: zcosmic_overlap_notify_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_overlap_notify_v1::Request::NotifyOnOverlap {
+                overlap_notification,
+                layer_surface,
+            } => {
+                let notification = data_init.init(overlap_notification, ());
+                // overlaps are sent with the next refresh
+                state
+                    .overlap_notify_state()
+                    .notifications
+                    .push(OverlapNotification {
+                        notification,
+                        layer_surface,
+                        toplevels: HashMap::new(),
+                        layers: HashMap::new(),
+                    });
+            }
+            zcosmic_overlap_notify_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZcosmicOverlapNotificationV1, (), D> for OverlapNotifyState
+where
+    D: GlobalDispatch<ZcosmicOverlapNotifyV1, OverlapNotifyGlobalData>
+        + Dispatch<ZcosmicOverlapNotifyV1, ()>
+        + Dispatch<ZcosmicOverlapNotificationV1, ()>
+        + OverlapNotifyHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZcosmicOverlapNotificationV1,
+        request: zcosmic_overlap_notification_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_overlap_notification_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicOverlapNotificationV1,
+        _data: &(),
+    ) {
+        state
+            .overlap_notify_state()
+            .notifications
+            .retain(|n| &n.notification != resource && n.layer_surface.is_alive());
+    }
+}
+
+macro_rules! delegate_overlap_notify {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::overlap_notify::zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: $crate::wayland::protocols::overlap_notify::OverlapNotifyGlobalData
+        ] => $crate::wayland::protocols::overlap_notify::OverlapNotifyState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::overlap_notify::zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: ()
+        ] => $crate::wayland::protocols::overlap_notify::OverlapNotifyState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::overlap_notify::zcosmic_overlap_notification_v1::ZcosmicOverlapNotificationV1: ()
+        ] => $crate::wayland::protocols::overlap_notify::OverlapNotifyState);
+    };
+}
+pub(crate) use delegate_overlap_notify;
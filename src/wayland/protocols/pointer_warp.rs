@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::wp_pointer_warp_v1;
+
+mod generated {
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/pointer-warp-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/pointer-warp-v1.xml");
+}
+
+use self::wp_pointer_warp_v1::WpPointerWarpV1;
+use smithay::{
+    reexports::wayland_server::{
+        backend::GlobalId,
+        protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+    },
+    utils::{Logical, Point, Serial},
+};
+
+#[derive(Debug)]
+pub struct PointerWarpState {
+    global: GlobalId,
+}
+
+pub trait PointerWarpHandler {
+    /// A client asked to move `pointer` to `location` relative to `surface`.
+    ///
+    /// Whether the request is honored is up to the compositor.
+    fn warp_pointer(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &WlPointer,
+        location: Point<f64, Logical>,
+        serial: Serial,
+    );
+}
+
+impl PointerWarpState {
+    pub fn new<D>(dh: &DisplayHandle) -> PointerWarpState
+    where
+        D: GlobalDispatch<WpPointerWarpV1, ()>
+            + Dispatch<WpPointerWarpV1, ()>
+            + PointerWarpHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, WpPointerWarpV1, _>(1, ());
+        PointerWarpState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<WpPointerWarpV1, (), D> for PointerWarpState
+where
+    D: GlobalDispatch<WpPointerWarpV1, ()>
+        + Dispatch<WpPointerWarpV1, ()>
+        + PointerWarpHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpPointerWarpV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpPointerWarpV1, (), D> for PointerWarpState
+where
+    D: GlobalDispatch<WpPointerWarpV1, ()>
+        + Dispatch<WpPointerWarpV1, ()>
+        + PointerWarpHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &WpPointerWarpV1,
+        request: wp_pointer_warp_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_pointer_warp_v1::Request::WarpPointer {
+                surface,
+                pointer,
+                x,
+                y,
+                serial,
+            } => {
+                state.warp_pointer(&surface, &pointer, (x, y).into(), Serial::from(serial));
+            }
+            wp_pointer_warp_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+macro_rules! delegate_pointer_warp {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::pointer_warp::wp_pointer_warp_v1::WpPointerWarpV1: ()
+        ] => $crate::wayland::protocols::pointer_warp::PointerWarpState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::pointer_warp::wp_pointer_warp_v1::WpPointerWarpV1: ()
+        ] => $crate::wayland::protocols::pointer_warp::PointerWarpState);
+    };
+}
+pub(crate) use delegate_pointer_warp;
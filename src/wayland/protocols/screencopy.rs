@@ -362,6 +362,7 @@ impl Session {
             .aux
             .cursor()
             .sessions()
+            .filter(|session| session.alive())
             .cloned()
             .collect::<Vec<_>>()
             .into_iter()
@@ -402,6 +403,18 @@ impl Session {
         self.data.inner.lock().unwrap().gone = true;
     }
 
+    /// Session of a `zcosmic_screencopy_session_v1` object, unless it captures a cursor
+    pub fn from_resource(resource: &ZcosmicScreencopySessionV1) -> Option<Session> {
+        let data = resource.data::<SessionData>()?;
+        if data.inner.lock().unwrap().is_cursor() {
+            return None;
+        }
+        Some(Session {
+            obj: SessionResource::Alive(resource.clone()),
+            data: data.clone(),
+        })
+    }
+
     pub fn user_data(&self) -> &UserDataMap {
         &self.data.user_data
     }
@@ -797,7 +810,7 @@ where
                         }
                     }
 
-                    let data = Arc::new(SessionDataInner {
+                    let cursor_data = Arc::new(SessionDataInner {
                         inner: Mutex::new(SessionDataInnerInner {
                             gone: false,
                             pending_buffer: None,
@@ -806,12 +819,22 @@ where
                         }),
                         user_data: UserDataMap::new(),
                     });
-                    let session = data_init.init(session, data.clone());
+                    let session = data_init.init(session, cursor_data.clone());
 
                     let cursor_session = CursorSession {
                         obj: SessionResource::Alive(session),
-                        data,
+                        data: cursor_data,
                     };
+                    // the cursor buffers are updated along with the buffers of the parent session
+                    if let AuxData::Normal {
+                        cursor: CursorMode::Captured(sessions),
+                    } = &mut data.inner.lock().unwrap().aux
+                    {
+                        sessions.push(cursor_session.clone());
+                    } else {
+                        cursor_session.failed(FailureReason::Unspec);
+                        return;
+                    }
                     let formats = state.capture_cursor(cursor_session.clone());
                     if !cursor_session.data.inner.lock().unwrap().gone {
                         send_formats(&cursor_session.obj, formats);
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::zcosmic_screencopy_frame_rate_v1;
+
+mod generated {
+    use cosmic_protocols::screencopy::v1::server::*;
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use cosmic_protocols::screencopy::v1::__interfaces::*;
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!(
+            "resources/protocols/cosmic-screencopy-frame-rate-unstable-v1.xml"
+        );
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!(
+        "resources/protocols/cosmic-screencopy-frame-rate-unstable-v1.xml"
+    );
+}
+
+use self::zcosmic_screencopy_frame_rate_v1::ZcosmicScreencopyFrameRateV1;
+use super::screencopy::Session;
+use smithay::reexports::wayland_server::{
+    backend::GlobalId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+};
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+/// State of the screencopy frame-rate global.
+///
+/// The limit is stored with the session, buffers committed too early are held
+/// back until the interval since the previous frame has passed.
+#[derive(Debug)]
+pub struct ScreencopyFrameRateState {
+    global: GlobalId,
+}
+
+impl ScreencopyFrameRateState {
+    pub fn new<D>(dh: &DisplayHandle) -> ScreencopyFrameRateState
+    where
+        D: GlobalDispatch<ZcosmicScreencopyFrameRateV1, ()>
+            + Dispatch<ZcosmicScreencopyFrameRateV1, ()>
+            + 'static,
+    {
+        let global = dh.create_global::<D, ZcosmicScreencopyFrameRateV1, _>(1, ());
+        ScreencopyFrameRateState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// Frame-rate limit of a screencopy session
+#[derive(Debug, Default)]
+pub struct FrameThrottle {
+    interval: Cell<Option<Duration>>,
+    last_frame: Cell<Option<Instant>>,
+}
+
+impl FrameThrottle {
+    /// Time left until the session may receive its next frame
+    pub fn delay(&self) -> Option<Duration> {
+        let next = self.last_frame.get()? + self.interval.get()?;
+        next.checked_duration_since(Instant::now())
+            .filter(|delay| !delay.is_zero())
+    }
+
+    /// Records that a frame was just delivered
+    pub fn frame_submitted(&self) {
+        self.last_frame.set(Some(Instant::now()));
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicScreencopyFrameRateV1, (), D> for ScreencopyFrameRateState
+where
+    D: GlobalDispatch<ZcosmicScreencopyFrameRateV1, ()>
+        + Dispatch<ZcosmicScreencopyFrameRateV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicScreencopyFrameRateV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZcosmicScreencopyFrameRateV1, (), D> for ScreencopyFrameRateState
+where
+    D: GlobalDispatch<ZcosmicScreencopyFrameRateV1, ()>
+        + Dispatch<ZcosmicScreencopyFrameRateV1, ()>
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZcosmicScreencopyFrameRateV1,
+        request: zcosmic_screencopy_frame_rate_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_screencopy_frame_rate_v1::Request::SetMaxFrameRate {
+                session,
+                max_frame_rate,
+            } => {
+                // cursor sessions are delivered together with their parent session
+                let Some(session) = Session::from_resource(&session) else {
+                    return;
+                };
+                session
+                    .user_data()
+                    .insert_if_missing(FrameThrottle::default);
+                session
+                    .user_data()
+                    .get::<FrameThrottle>()
+                    .unwrap()
+                    .interval
+                    .set(
+                        (max_frame_rate > 0)
+                            .then(|| Duration::from_secs_f64(1. / max_frame_rate as f64)),
+                    );
+            }
+            zcosmic_screencopy_frame_rate_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+macro_rules! delegate_screencopy_frame_rate {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::screencopy_frame_rate::zcosmic_screencopy_frame_rate_v1::ZcosmicScreencopyFrameRateV1: ()
+        ] => $crate::wayland::protocols::screencopy_frame_rate::ScreencopyFrameRateState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::screencopy_frame_rate::zcosmic_screencopy_frame_rate_v1::ZcosmicScreencopyFrameRateV1: ()
+        ] => $crate::wayland::protocols::screencopy_frame_rate::ScreencopyFrameRateState);
+    };
+}
+pub(crate) use delegate_screencopy_frame_rate;
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::xdg_system_bell_v1;
+
+mod generated {
+    use smithay::reexports::wayland_server::{self, protocol::*};
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/xdg-system-bell-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/xdg-system-bell-v1.xml");
+}
+
+use self::xdg_system_bell_v1::XdgSystemBellV1;
+use smithay::reexports::wayland_server::{
+    backend::GlobalId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New,
+};
+
+/// State of the system-bell global.
+///
+/// Xwayland forwards the bell of X11 clients through it as well.
+#[derive(Debug)]
+pub struct SystemBellState {
+    global: GlobalId,
+}
+
+pub trait SystemBellHandler {
+    /// A client rang the bell, optionally on behalf of one of its toplevels
+    fn ring(&mut self, surface: Option<WlSurface>);
+}
+
+impl SystemBellState {
+    pub fn new<D>(dh: &DisplayHandle) -> SystemBellState
+    where
+        D: GlobalDispatch<XdgSystemBellV1, ()>
+            + Dispatch<XdgSystemBellV1, ()>
+            + SystemBellHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, XdgSystemBellV1, _>(1, ());
+        SystemBellState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<XdgSystemBellV1, (), D> for SystemBellState
+where
+    D: GlobalDispatch<XdgSystemBellV1, ()>
+        + Dispatch<XdgSystemBellV1, ()>
+        + SystemBellHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<XdgSystemBellV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<XdgSystemBellV1, (), D> for SystemBellState
+where
+    D: GlobalDispatch<XdgSystemBellV1, ()>
+        + Dispatch<XdgSystemBellV1, ()>
+        + SystemBellHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &XdgSystemBellV1,
+        request: xdg_system_bell_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            xdg_system_bell_v1::Request::Ring { surface } => state.ring(surface),
+            xdg_system_bell_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+macro_rules! delegate_system_bell {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::system_bell::xdg_system_bell_v1::XdgSystemBellV1: ()
+        ] => $crate::wayland::protocols::system_bell::SystemBellState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::system_bell::xdg_system_bell_v1::XdgSystemBellV1: ()
+        ] => $crate::wayland::protocols::system_bell::SystemBellState);
+    };
+}
+pub(crate) use delegate_system_bell;
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{xdg_toplevel_drag_manager_v1, xdg_toplevel_drag_v1};
+
+mod generated {
+    use smithay::reexports::{
+        wayland_protocols::xdg::shell::server::*,
+        wayland_server::{self, protocol::*},
+    };
+
+    pub mod __interfaces {
+        use smithay::reexports::{
+            wayland_protocols::xdg::shell::__interfaces::*,
+            wayland_server::protocol::__interfaces::*,
+        };
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/xdg-toplevel-drag-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/xdg-toplevel-drag-v1.xml");
+}
+
+use self::{
+    xdg_toplevel_drag_manager_v1::XdgToplevelDragManagerV1, xdg_toplevel_drag_v1::XdgToplevelDragV1,
+};
+use smithay::{
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel,
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_data_source::WlDataSource,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Point},
+};
+
+/// State of the xdg-toplevel-drag global.
+///
+/// Moving the attached toplevels along with the drag-and-drop operation of their
+/// data source is up to the compositor.
+#[derive(Debug)]
+pub struct ToplevelDragState {
+    global: GlobalId,
+    drags: Vec<ToplevelDrag>,
+}
+
+#[derive(Debug)]
+struct ToplevelDrag {
+    drag: XdgToplevelDragV1,
+    source: WlDataSource,
+    toplevel: Option<(XdgToplevel, Point<i32, Logical>)>,
+}
+
+pub trait ToplevelDragHandler {
+    fn toplevel_drag_state(&mut self) -> &mut ToplevelDragState;
+
+    /// A toplevel was attached to the drag-and-drop operation of `source`
+    fn toplevel_attached(&mut self, source: &WlDataSource);
+}
+
+impl ToplevelDragState {
+    pub fn new<D>(dh: &DisplayHandle) -> ToplevelDragState
+    where
+        D: GlobalDispatch<XdgToplevelDragManagerV1, ()>
+            + Dispatch<XdgToplevelDragManagerV1, ()>
+            + Dispatch<XdgToplevelDragV1, ()>
+            + ToplevelDragHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, XdgToplevelDragManagerV1, _>(1, ());
+        ToplevelDragState {
+            global,
+            drags: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// The toplevel attached to `source` and the pointer offset relative to its geometry
+    pub fn attached(&self, source: &WlDataSource) -> Option<(XdgToplevel, Point<i32, Logical>)> {
+        self.drags
+            .iter()
+            .find(|drag| &drag.source == source)
+            .and_then(|drag| drag.toplevel.clone())
+            .filter(|(toplevel, _)| toplevel.is_alive())
+    }
+}
+
+impl<D> GlobalDispatch<XdgToplevelDragManagerV1, (), D> for ToplevelDragState
+where
+    D: GlobalDispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragV1, ()>
+        + ToplevelDragHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<XdgToplevelDragManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<XdgToplevelDragManagerV1, (), D> for ToplevelDragState
+where
+    D: GlobalDispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragV1, ()>
+        + ToplevelDragHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &XdgToplevelDragManagerV1,
+        request: xdg_toplevel_drag_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            xdg_toplevel_drag_manager_v1::Request::GetXdgToplevelDrag { id, data_source } => {
+                let drag_state = state.toplevel_drag_state();
+                if drag_state
+                    .drags
+                    .iter()
+                    .any(|drag| drag.source == data_source)
+                {
+                    obj.post_error(
+                        xdg_toplevel_drag_manager_v1::Error::InvalidSource,
+                        "data_source already used for a toplevel drag",
+                    );
+                    return;
+                }
+                let drag = data_init.init(id, ());
+                drag_state.drags.push(ToplevelDrag {
+                    drag,
+                    source: data_source,
+                    toplevel: None,
+                });
+            }
+            xdg_toplevel_drag_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<XdgToplevelDragV1, (), D> for ToplevelDragState
+where
+    D: GlobalDispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragManagerV1, ()>
+        + Dispatch<XdgToplevelDragV1, ()>
+        + ToplevelDragHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &XdgToplevelDragV1,
+        request: xdg_toplevel_drag_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            xdg_toplevel_drag_v1::Request::Attach {
+                toplevel,
+                x_offset,
+                y_offset,
+            } => {
+                let Some(drag) = state
+                    .toplevel_drag_state()
+                    .drags
+                    .iter_mut()
+                    .find(|drag| &drag.drag == obj)
+                else {
+                    return;
+                };
+                // a destroyed toplevel is detached implicitly
+                if drag
+                    .toplevel
+                    .as_ref()
+                    .map_or(false, |(attached, _)| attached.is_alive())
+                {
+                    obj.post_error(
+                        xdg_toplevel_drag_v1::Error::ToplevelAttached,
+                        "a toplevel is already attached",
+                    );
+                    return;
+                }
+                drag.toplevel = Some((toplevel, (x_offset, y_offset).into()));
+                let source = drag.source.clone();
+                state.toplevel_attached(&source);
+            }
+            xdg_toplevel_drag_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &XdgToplevelDragV1, _data: &()) {
+        state
+            .toplevel_drag_state()
+            .drags
+            .retain(|drag| &drag.drag != resource);
+    }
+}
+
+macro_rules! delegate_toplevel_drag {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::toplevel_drag::xdg_toplevel_drag_manager_v1::XdgToplevelDragManagerV1: ()
+        ] => $crate::wayland::protocols::toplevel_drag::ToplevelDragState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::toplevel_drag::xdg_toplevel_drag_manager_v1::XdgToplevelDragManagerV1: ()
+        ] => $crate::wayland::protocols::toplevel_drag::ToplevelDragState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::toplevel_drag::xdg_toplevel_drag_v1::XdgToplevelDragV1: ()
+        ] => $crate::wayland::protocols::toplevel_drag::ToplevelDragState);
+    };
+}
+pub(crate) use delegate_toplevel_drag;
@@ -5,7 +5,7 @@ use std::{collections::HashMap, sync::Mutex};
 use smithay::{
     output::Output,
     reexports::wayland_server::{
-        backend::{ClientId, GlobalId},
+        backend::{ClientId, GlobalId, ObjectId},
         protocol::wl_surface::WlSurface,
         Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
     },
@@ -19,13 +19,14 @@ use cosmic_protocols::toplevel_info::v1::server::{
     zcosmic_toplevel_info_v1::{self, ZcosmicToplevelInfoV1},
 };
 
-pub trait Window: IsAlive + Clone + Send {
+pub trait Window: IsAlive + Clone + PartialEq + Send {
     fn title(&self) -> String;
     fn app_id(&self) -> String;
     fn is_activated(&self) -> bool;
     fn is_maximized(&self) -> bool;
     fn is_fullscreen(&self) -> bool;
     fn is_minimized(&self) -> bool;
+    fn is_transient_for(&self, parent: &Self) -> bool;
     fn user_data(&self) -> &UserDataMap;
 }
 
@@ -51,7 +52,7 @@ pub struct ToplevelInfoGlobalData {
 #[derive(Default)]
 pub(super) struct ToplevelStateInner {
     instances: Vec<ZcosmicToplevelHandleV1>,
-    outputs: Vec<Output>,
+    pub(super) outputs: Vec<Output>,
     workspaces: Vec<WorkspaceHandle>,
     pub(super) rectangles: HashMap<ClientId, (WlSurface, Rectangle<i32, Logical>)>,
 }
@@ -441,6 +442,19 @@ pub fn window_from_handle<W: Window + 'static>(handle: ZcosmicToplevelHandleV1)
         .map(|state| state.lock().unwrap().window.clone())
 }
 
+/// Handle of `window` sent to the client owning the object `other`, if any
+pub fn toplevel_handle_for_client<W: Window>(
+    window: &W,
+    other: &ObjectId,
+) -> Option<ZcosmicToplevelHandleV1> {
+    let state = window.user_data().get::<ToplevelState>()?.lock().unwrap();
+    state
+        .instances
+        .iter()
+        .find(|i| i.id().same_client_as(other))
+        .cloned()
+}
+
 macro_rules! delegate_toplevel_info {
     ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty, $window: ty) => {
         smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
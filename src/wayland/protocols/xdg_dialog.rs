@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub use generated::{xdg_dialog_v1, xdg_wm_dialog_v1};
+
+mod generated {
+    use smithay::reexports::{
+        wayland_protocols::xdg::shell::server::*,
+        wayland_server::{self, protocol::*},
+    };
+
+    pub mod __interfaces {
+        use smithay::reexports::{
+            wayland_protocols::xdg::shell::__interfaces::*,
+            wayland_server::protocol::__interfaces::*,
+        };
+        use wayland_backend;
+        wayland_scanner::generate_interfaces!("resources/protocols/xdg-dialog-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("resources/protocols/xdg-dialog-v1.xml");
+}
+
+use self::{xdg_dialog_v1::XdgDialogV1, xdg_wm_dialog_v1::XdgWmDialogV1};
+use smithay::reexports::{
+    wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel,
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+
+/// State of the xdg-dialog global.
+///
+/// The hints only matter for toplevels with a parent, which are floated anyway.
+/// Modal dialogs additionally take the keyboard focus of their parent.
+#[derive(Debug)]
+pub struct XdgDialogState {
+    global: GlobalId,
+    dialogs: Vec<XdgDialog>,
+}
+
+#[derive(Debug)]
+struct XdgDialog {
+    dialog: XdgDialogV1,
+    toplevel: XdgToplevel,
+    modal: bool,
+}
+
+pub trait XdgDialogHandler {
+    fn xdg_dialog_state(&mut self) -> &mut XdgDialogState;
+
+    /// The modal hint of the dialog `toplevel` changed
+    fn modal_changed(&mut self, toplevel: &XdgToplevel, modal: bool);
+}
+
+impl XdgDialogState {
+    pub fn new<D>(dh: &DisplayHandle) -> XdgDialogState
+    where
+        D: GlobalDispatch<XdgWmDialogV1, ()>
+            + Dispatch<XdgWmDialogV1, ()>
+            + Dispatch<XdgDialogV1, XdgToplevel>
+            + XdgDialogHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, XdgWmDialogV1, _>(1, ());
+        XdgDialogState {
+            global,
+            dialogs: Vec::new(),
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Whether the client marked `toplevel` as a modal dialog
+    pub fn is_modal(&self, toplevel: &XdgToplevel) -> bool {
+        self.dialogs
+            .iter()
+            .any(|dialog| &dialog.toplevel == toplevel && dialog.modal)
+    }
+}
+
+impl<D> GlobalDispatch<XdgWmDialogV1, (), D> for XdgDialogState
+where
+    D: GlobalDispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgDialogV1, XdgToplevel>
+        + XdgDialogHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<XdgWmDialogV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<XdgWmDialogV1, (), D> for XdgDialogState
+where
+    D: GlobalDispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgDialogV1, XdgToplevel>
+        + XdgDialogHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &XdgWmDialogV1,
+        request: xdg_wm_dialog_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            xdg_wm_dialog_v1::Request::GetXdgDialog { id, toplevel } => {
+                let dialog_state = state.xdg_dialog_state();
+                let dialog = data_init.init(id, toplevel.clone());
+                if dialog_state
+                    .dialogs
+                    .iter()
+                    .any(|dialog| dialog.toplevel == toplevel)
+                {
+                    obj.post_error(
+                        xdg_wm_dialog_v1::Error::AlreadyUsed,
+                        "xdg_toplevel already has a dialog object",
+                    );
+                    return;
+                }
+                dialog_state.dialogs.push(XdgDialog {
+                    dialog,
+                    toplevel,
+                    modal: false,
+                });
+            }
+            xdg_wm_dialog_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<XdgDialogV1, XdgToplevel, D> for XdgDialogState
+where
+    D: GlobalDispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgWmDialogV1, ()>
+        + Dispatch<XdgDialogV1, XdgToplevel>
+        + XdgDialogHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &XdgDialogV1,
+        request: xdg_dialog_v1::Request,
+        toplevel: &XdgToplevel,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let modal = match request {
+            xdg_dialog_v1::Request::SetModal => true,
+            xdg_dialog_v1::Request::UnsetModal => false,
+            _ => return,
+        };
+        // inert once the toplevel is gone
+        if !toplevel.is_alive() {
+            return;
+        }
+        let Some(dialog) = state
+            .xdg_dialog_state()
+            .dialogs
+            .iter_mut()
+            .find(|dialog| &dialog.dialog == obj)
+        else {
+            return;
+        };
+        if dialog.modal != modal {
+            dialog.modal = modal;
+            state.modal_changed(toplevel, modal);
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &XdgDialogV1, toplevel: &XdgToplevel) {
+        let dialog_state = state.xdg_dialog_state();
+        let was_modal = dialog_state
+            .dialogs
+            .iter()
+            .any(|dialog| &dialog.dialog == resource && dialog.modal);
+        dialog_state
+            .dialogs
+            .retain(|dialog| &dialog.dialog != resource && dialog.toplevel.is_alive());
+        if was_modal && toplevel.is_alive() {
+            state.modal_changed(toplevel, false);
+        }
+    }
+}
+
+macro_rules! delegate_xdg_dialog {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::xdg_dialog::xdg_wm_dialog_v1::XdgWmDialogV1: ()
+        ] => $crate::wayland::protocols::xdg_dialog::XdgDialogState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::xdg_dialog::xdg_wm_dialog_v1::XdgWmDialogV1: ()
+        ] => $crate::wayland::protocols::xdg_dialog::XdgDialogState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland::protocols::xdg_dialog::xdg_dialog_v1::XdgDialogV1: smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel
+        ] => $crate::wayland::protocols::xdg_dialog::XdgDialogState);
+    };
+}
+pub(crate) use delegate_xdg_dialog;
@@ -0,0 +1,610 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::{
+    reexports::{
+        wayland_protocols::xdg::foreign::{
+            zv1::server::{
+                zxdg_exported_v1::{self, ZxdgExportedV1},
+                zxdg_exporter_v1::{self, ZxdgExporterV1},
+                zxdg_imported_v1::{self, ZxdgImportedV1},
+                zxdg_importer_v1::{self, ZxdgImporterV1},
+            },
+            zv2::server::{
+                zxdg_exported_v2::{self, ZxdgExportedV2},
+                zxdg_exporter_v2::{self, ZxdgExporterV2},
+                zxdg_imported_v2::{self, ZxdgImportedV2},
+                zxdg_importer_v2::{self, ZxdgImporterV2},
+            },
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_surface::WlSurface,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    wayland::{
+        compositor::{get_role, with_states},
+        shell::xdg::{XdgToplevelSurfaceData, XDG_TOPLEVEL_ROLE},
+    },
+};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// State of the xdg-foreign globals, in both versions of the protocol.
+///
+/// Importing a handle lets a client set the exported toplevel of another client
+/// as the parent of its own toplevels, which is how portal dialogs get attached
+/// to the window of the sandboxed application, that opened them.
+#[derive(Debug)]
+pub struct XdgForeignState {
+    _exporter_v1: GlobalId,
+    _exporter_v2: GlobalId,
+    _importer_v1: GlobalId,
+    _importer_v2: GlobalId,
+    exports: Vec<Export>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Exported {
+    V1(ZxdgExportedV1),
+    V2(ZxdgExportedV2),
+}
+
+#[derive(Debug, PartialEq)]
+enum Imported {
+    V1(ZxdgImportedV1),
+    V2(ZxdgImportedV2),
+}
+
+#[derive(Debug)]
+struct Export {
+    exported: Exported,
+    handle: String,
+    surface: WlSurface,
+    imports: Vec<Import>,
+}
+
+#[derive(Debug)]
+struct Import {
+    imported: Imported,
+    children: Vec<WlSurface>,
+}
+
+pub trait XdgForeignHandler {
+    fn xdg_foreign_state(&mut self) -> &mut XdgForeignState;
+
+    /// The parent of the toplevel `surface` changed through an imported handle
+    fn parent_changed(&mut self, surface: &WlSurface);
+}
+
+impl XdgForeignState {
+    pub fn new<D>(dh: &DisplayHandle) -> XdgForeignState
+    where
+        D: GlobalDispatch<ZxdgExporterV1, ()>
+            + GlobalDispatch<ZxdgExporterV2, ()>
+            + GlobalDispatch<ZxdgImporterV1, ()>
+            + GlobalDispatch<ZxdgImporterV2, ()>
+            + XdgForeignHandler
+            + 'static,
+    {
+        XdgForeignState {
+            _exporter_v1: dh.create_global::<D, ZxdgExporterV1, _>(1, ()),
+            _exporter_v2: dh.create_global::<D, ZxdgExporterV2, _>(1, ()),
+            _importer_v1: dh.create_global::<D, ZxdgImporterV1, _>(1, ()),
+            _importer_v2: dh.create_global::<D, ZxdgImporterV2, _>(1, ()),
+            exports: Vec::new(),
+        }
+    }
+
+    fn export(&mut self, exported: Exported, surface: WlSurface) -> String {
+        let handle = new_handle();
+        self.exports.push(Export {
+            exported,
+            handle: handle.clone(),
+            surface,
+            imports: Vec::new(),
+        });
+        handle
+    }
+
+    fn import(&mut self, imported: Imported, handle: String) {
+        match self
+            .exports
+            .iter_mut()
+            .find(|export| export.handle == handle && export.surface.is_alive())
+        {
+            Some(export) => export.imports.push(Import {
+                imported,
+                children: Vec::new(),
+            }),
+            // unknown handles are inert right away
+            None => imported.destroyed(),
+        }
+    }
+
+    /// Parents `child` to the toplevel exported under the handle of `imported`
+    fn set_parent_of(&mut self, imported: &Imported, child: &WlSurface) -> bool {
+        let Some((surface, import)) = self.exports.iter_mut().find_map(|export| {
+            let import = export
+                .imports
+                .iter_mut()
+                .find(|import| &import.imported == imported)?;
+            Some((&export.surface, import))
+        }) else {
+            return false;
+        };
+        if !surface.is_alive() {
+            return false;
+        }
+        with_states(child, |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .parent = Some(surface.clone());
+        });
+        import.children.retain(|surface| surface.is_alive());
+        if !import.children.contains(child) {
+            import.children.push(child.clone());
+        }
+        true
+    }
+
+    /// Revokes the export and returns the children, that lost their parent
+    fn unexport(&mut self, exported: &Exported) -> Vec<WlSurface> {
+        let Some(pos) = self
+            .exports
+            .iter()
+            .position(|export| &export.exported == exported)
+        else {
+            return Vec::new();
+        };
+        revoke(self.exports.remove(pos))
+    }
+
+    /// Drops the import and returns the children, that lost their parent
+    fn unimport(&mut self, imported: &Imported) -> Vec<WlSurface> {
+        for export in self.exports.iter_mut() {
+            if let Some(pos) = export
+                .imports
+                .iter()
+                .position(|import| &import.imported == imported)
+            {
+                let import = export.imports.remove(pos);
+                return unparent(import.children, &export.surface);
+            }
+        }
+        Vec::new()
+    }
+
+    /// Revokes all exports of the destroyed toplevel `surface` and returns the children,
+    /// that lost their parent
+    pub fn toplevel_destroyed(&mut self, surface: &WlSurface) -> Vec<WlSurface> {
+        let (revoked, exports) = std::mem::take(&mut self.exports)
+            .into_iter()
+            .partition::<Vec<_>, _>(|export| &export.surface == surface);
+        self.exports = exports;
+        revoked.into_iter().flat_map(revoke).collect()
+    }
+}
+
+impl Imported {
+    fn destroyed(&self) {
+        match self {
+            Imported::V1(imported) => imported.destroyed(),
+            Imported::V2(imported) => imported.destroyed(),
+        }
+    }
+}
+
+/// Tells the importers of `export`, that its handle is gone, and unparents their children
+fn revoke(export: Export) -> Vec<WlSurface> {
+    let mut orphans = Vec::new();
+    for import in export.imports {
+        import.imported.destroyed();
+        orphans.extend(unparent(import.children, &export.surface));
+    }
+    orphans
+}
+
+fn new_handle() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::SeqCst));
+    let first = hasher.finish();
+    hasher.write_u64(first);
+    format!("{:016x}{:016x}", first, hasher.finish())
+}
+
+fn is_toplevel(surface: &WlSurface) -> bool {
+    get_role(surface) == Some(XDG_TOPLEVEL_ROLE)
+}
+
+/// Removes `parent` as the parent of `children`, unless they picked another one since
+fn unparent(children: Vec<WlSurface>, parent: &WlSurface) -> Vec<WlSurface> {
+    children
+        .into_iter()
+        .filter(|child| child.is_alive())
+        .filter(|child| {
+            with_states(child, |states| {
+                let mut attributes = states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap();
+                if attributes.parent.as_ref() == Some(parent) {
+                    attributes.parent = None;
+                    true
+                } else {
+                    false
+                }
+            })
+        })
+        .collect()
+}
+
+impl<D> GlobalDispatch<ZxdgExporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExporterV1, ()> + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgExporterV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgExporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExporterV2, ()> + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgExporterV2>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgImporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImporterV1, ()> + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgImporterV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgImporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImporterV2, ()> + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgImporterV2>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZxdgExporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExportedV1, ()> + XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgExporterV1,
+        request: zxdg_exporter_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exporter_v1::Request::Export { id, surface } => {
+                let exported = data_init.init(id, ());
+                let handle = state
+                    .xdg_foreign_state()
+                    .export(Exported::V1(exported.clone()), surface);
+                exported.handle(handle);
+            }
+            zxdg_exporter_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgExporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExportedV2, ()> + XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZxdgExporterV2,
+        request: zxdg_exporter_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exporter_v2::Request::ExportToplevel { id, surface } => {
+                let exported = data_init.init(id, ());
+                if !is_toplevel(&surface) {
+                    obj.post_error(
+                        zxdg_exporter_v2::Error::InvalidSurface,
+                        "the surface is not an xdg_toplevel",
+                    );
+                    return;
+                }
+                let handle = state
+                    .xdg_foreign_state()
+                    .export(Exported::V2(exported.clone()), surface);
+                exported.handle(handle);
+            }
+            zxdg_exporter_v2::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImportedV1, ()> + XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgImporterV1,
+        request: zxdg_importer_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_importer_v1::Request::Import { id, handle } => {
+                let imported = data_init.init(id, ());
+                state
+                    .xdg_foreign_state()
+                    .import(Imported::V1(imported), handle);
+            }
+            zxdg_importer_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImportedV2, ()> + XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgImporterV2,
+        request: zxdg_importer_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_importer_v2::Request::ImportToplevel { id, handle } => {
+                let imported = data_init.init(id, ());
+                state
+                    .xdg_foreign_state()
+                    .import(Imported::V2(imported), handle);
+            }
+            zxdg_importer_v2::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgExportedV1, (), D> for XdgForeignState
+where
+    D: XdgForeignHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgExportedV1,
+        request: zxdg_exported_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exported_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgExportedV1, _data: &()) {
+        let orphans = state
+            .xdg_foreign_state()
+            .unexport(&Exported::V1(resource.clone()));
+        for surface in orphans {
+            state.parent_changed(&surface);
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgExportedV2, (), D> for XdgForeignState
+where
+    D: XdgForeignHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgExportedV2,
+        request: zxdg_exported_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exported_v2::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgExportedV2, _data: &()) {
+        let orphans = state
+            .xdg_foreign_state()
+            .unexport(&Exported::V2(resource.clone()));
+        for surface in orphans {
+            state.parent_changed(&surface);
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImportedV1, (), D> for XdgForeignState
+where
+    D: XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZxdgImportedV1,
+        request: zxdg_imported_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_imported_v1::Request::SetParentOf { surface } => {
+                // the first version has no error for this
+                if is_toplevel(&surface)
+                    && state
+                        .xdg_foreign_state()
+                        .set_parent_of(&Imported::V1(obj.clone()), &surface)
+                {
+                    state.parent_changed(&surface);
+                }
+            }
+            zxdg_imported_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgImportedV1, _data: &()) {
+        let orphans = state
+            .xdg_foreign_state()
+            .unimport(&Imported::V1(resource.clone()));
+        for surface in orphans {
+            state.parent_changed(&surface);
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImportedV2, (), D> for XdgForeignState
+where
+    D: XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZxdgImportedV2,
+        request: zxdg_imported_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_imported_v2::Request::SetParentOf { surface } => {
+                if !is_toplevel(&surface) {
+                    obj.post_error(
+                        zxdg_imported_v2::Error::InvalidSurface,
+                        "the surface is not an xdg_toplevel",
+                    );
+                    return;
+                }
+                if state
+                    .xdg_foreign_state()
+                    .set_parent_of(&Imported::V2(obj.clone()), &surface)
+                {
+                    state.parent_changed(&surface);
+                }
+            }
+            zxdg_imported_v2::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgImportedV2, _data: &()) {
+        let orphans = state
+            .xdg_foreign_state()
+            .unimport(&Imported::V2(resource.clone()));
+        for surface in orphans {
+            state.parent_changed(&surface);
+        }
+    }
+}
+
+macro_rules! delegate_xdg_foreign {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_exporter_v1::ZxdgExporterV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exporter_v2::ZxdgExporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_importer_v1::ZxdgImporterV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_importer_v2::ZxdgImporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_exporter_v1::ZxdgExporterV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exporter_v2::ZxdgExporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_importer_v1::ZxdgImporterV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_importer_v2::ZxdgImporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_exported_v1::ZxdgExportedV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exported_v2::ZxdgExportedV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_imported_v1::ZxdgImportedV1: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_imported_v2::ZxdgImportedV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+    };
+}
+pub(crate) use delegate_xdg_foreign;
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! XSettings manager and X resources for X11 clients, matching the wayland session.
+//!
+//! X11 clients are scaled by the compositor like any other client with a buffer scale of
+//! one, so the advertised dpi is the unscaled one, unless they render at the output scale.
+
+use anyhow::{Context, Result};
+use smithay::reexports::x11rb::{
+    connection::Connection,
+    protocol::xproto::{
+        AtomEnum, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+        Window, WindowClass,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME,
+};
+use std::sync::mpsc::{channel, Sender};
+use tracing::warn;
+
+const DPI: u32 = 96;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub dark: bool,
+    pub cursor_theme: String,
+    pub cursor_size: u32,
+    pub scale: f64,
+}
+
+impl Settings {
+    fn dpi(&self) -> f64 {
+        DPI as f64 * self.scale
+    }
+
+    /// Cursor size in the pixels of clients rendering at `scale`
+    fn scaled_cursor_size(&self) -> u32 {
+        (self.cursor_size as f64 * self.scale).round() as u32
+    }
+
+    /// Encodes the settings as the value of the `_XSETTINGS_SETTINGS` property
+    fn encode(&self, serial: u32) -> Vec<u8> {
+        let settings = [
+            ("Xft/DPI", Value::Int((self.dpi() * 1024.0).round() as i32)),
+            ("Gtk/CursorThemeName", Value::String(&self.cursor_theme)),
+            (
+                "Gtk/CursorThemeSize",
+                Value::Int(self.scaled_cursor_size() as i32),
+            ),
+            (
+                "Gtk/ApplicationPreferDarkTheme",
+                Value::Int(self.dark as i32),
+            ),
+        ];
+
+        let mut data = Vec::new();
+        // byte order, little endian
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&serial.to_le_bytes());
+        data.extend_from_slice(&(settings.len() as u32).to_le_bytes());
+        for (name, value) in settings {
+            let kind = match value {
+                Value::Int(_) => 0u8,
+                Value::String(_) => 1u8,
+            };
+            data.push(kind);
+            data.push(0);
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            push_padded(&mut data, name.as_bytes());
+            // last-change serial
+            data.extend_from_slice(&serial.to_le_bytes());
+            match value {
+                Value::Int(value) => data.extend_from_slice(&value.to_le_bytes()),
+                Value::String(value) => {
+                    data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    push_padded(&mut data, value.as_bytes());
+                }
+            }
+        }
+        data
+    }
+
+    /// X resources read by Xft and Xcursor, replacing ours in `current`
+    fn resources(&self, current: &str) -> String {
+        let ours = [
+            ("Xft.dpi", (self.dpi().round() as u32).to_string()),
+            ("Xcursor.theme", self.cursor_theme.clone()),
+            ("Xcursor.size", self.scaled_cursor_size().to_string()),
+        ];
+        let mut resources = current
+            .lines()
+            .filter(|line| {
+                !ours
+                    .iter()
+                    .any(|(name, _)| line.split(':').next().map(str::trim) == Some(*name))
+            })
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        for (name, value) in ours {
+            resources.push_str(&format!("{}:\t{}\n", name, value));
+        }
+        resources
+    }
+}
+
+enum Value<'a> {
+    Int(i32),
+    String(&'a str),
+}
+
+fn push_padded(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(bytes);
+    data.resize(data.len() + (4 - bytes.len() % 4) % 4, 0);
+}
+
+/// Provides the settings to the clients of an Xwayland display
+#[derive(Debug)]
+pub struct XSettings {
+    settings: Settings,
+    sender: Sender<Settings>,
+}
+
+impl XSettings {
+    /// Starts a settings manager for `display`.
+    ///
+    /// It runs on its own thread, as requests to Xwayland can block on the compositor.
+    pub fn start(display: u32, settings: Settings) -> XSettings {
+        let (sender, receiver) = channel::<Settings>();
+        let _ = sender.send(settings.clone());
+        std::thread::spawn(move || {
+            let manager = match Manager::new(display) {
+                Ok(manager) => manager,
+                Err(err) => {
+                    warn!(?err, "Failed to start the XSettings manager.");
+                    return;
+                }
+            };
+            for (serial, settings) in receiver.iter().enumerate() {
+                if let Err(err) = manager.set(&settings, serial as u32) {
+                    warn!(?err, "Failed to update XSettings.");
+                    return;
+                }
+            }
+        });
+        XSettings { settings, sender }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Changes the settings, the clients are only updated if they differ
+    pub fn update(&mut self, update: impl FnOnce(&mut Settings)) {
+        let mut settings = self.settings.clone();
+        update(&mut settings);
+        if settings != self.settings {
+            self.settings = settings;
+            let _ = self.sender.send(self.settings.clone());
+        }
+    }
+}
+
+struct Manager {
+    conn: RustConnection,
+    root: Window,
+    window: Window,
+    settings_atom: u32,
+}
+
+impl Manager {
+    fn new(display: u32) -> Result<Manager> {
+        let (conn, screen) = RustConnection::connect(Some(&format!(":{}", display)))
+            .with_context(|| "Failed to connect to Xwayland")?;
+        let root = conn.setup().roots[screen].root;
+        let intern = |name: &str| -> Result<u32> {
+            Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+        };
+        let selection = intern(&format!("_XSETTINGS_S{}", screen))?;
+        let settings_atom = intern("_XSETTINGS_SETTINGS")?;
+        let manager_atom = intern("MANAGER")?;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+        conn.set_selection_owner(window, selection, CURRENT_TIME)?;
+        // let running clients know, that there is a settings manager now
+        let event = ClientMessageEvent::new(
+            32,
+            root,
+            manager_atom,
+            [CURRENT_TIME, selection, window, 0, 0],
+        );
+        conn.send_event(false, root, EventMask::STRUCTURE_NOTIFY, event)?;
+        conn.flush()?;
+
+        Ok(Manager {
+            conn,
+            root,
+            window,
+            settings_atom,
+        })
+    }
+
+    fn set(&self, settings: &Settings, serial: u32) -> Result<()> {
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.window,
+            self.settings_atom,
+            self.settings_atom,
+            &settings.encode(serial),
+        )?;
+
+        let current = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                AtomEnum::RESOURCE_MANAGER,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        let resources = settings.resources(&String::from_utf8_lossy(&current.value));
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            resources.as_bytes(),
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
@@ -1,12 +1,21 @@
-use std::{ffi::OsString, os::unix::io::OwnedFd};
+use std::{
+    ffi::OsString,
+    os::unix::io::OwnedFd,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    backend::render::cursor::{load_cursor_theme, Cursor, CursorShape},
+    backend::render::cursor::{load_cursor_theme, Cursor, CursorShape, CursorState},
     shell::{focus::target::KeyboardFocusTarget, CosmicSurface, Shell},
-    state::State,
+    state::{Common, State},
     utils::prelude::*,
-    wayland::{handlers::screencopy::PendingScreencopyBuffers, protocols::screencopy::SessionType},
+    wayland::{
+        handlers::{screencopy::PendingScreencopyBuffers, selection::SelectionData},
+        protocols::screencopy::SessionType,
+    },
+    xsettings::{Settings, XSettings},
 };
+use cosmic_config::ConfigGet;
 use smithay::{
     backend::drm::DrmNode,
     desktop::space::SpaceElement,
@@ -21,21 +30,76 @@ use smithay::{
             clear_primary_selection, current_primary_selection_userdata,
             request_primary_client_selection, set_primary_selection,
         },
-        SelectionTarget,
+        SelectionHandler, SelectionTarget,
     },
     xwayland::{
-        xwm::{Reorder, XwmId},
+        xwm::{Reorder, WmWindowType, XwmId},
         X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler,
     },
 };
-use tracing::{error, trace, warn};
+use tracing::{error, info, trace, warn};
 
 #[derive(Debug)]
 pub struct XWaylandState {
     pub xwm: Option<X11Wm>,
     pub display: u32,
+    xsettings: Option<XSettings>,
     #[allow(unused)]
     xwayland: XWayland,
+    render_node: Option<DrmNode>,
+    started: Instant,
+}
+
+/// Xwayland dying sooner than this after it was started isn't restarted, as it would keep crashing
+const MIN_UPTIME: Duration = Duration::from_secs(10);
+
+const THEME_MODE_CONFIG: &str = "com.system76.CosmicTheme.Mode";
+
+fn is_dark(config: &cosmic_config::Config) -> bool {
+    config.get::<bool>("is_dark").unwrap_or(true)
+}
+
+/// Settings of X11 clients matching the compositor, with the cursor theme of the last active seat
+fn settings(common: &Common, dark: bool) -> Settings {
+    let (cursor_theme, cursor_size) = common
+        .last_active_seat()
+        .user_data()
+        .get::<CursorState>()
+        .unwrap()
+        .theme();
+    // the highest output scale, if X11 clients render at the output scale
+    let scale = if common.config.static_conf.xwayland_hidpi {
+        common
+            .shell
+            .outputs()
+            .map(|output| output.current_scale().fractional_scale())
+            .fold(1.0, f64::max)
+    } else {
+        1.0
+    };
+    Settings {
+        dark,
+        cursor_theme: cursor_theme.to_string(),
+        cursor_size,
+        scale,
+    }
+}
+
+/// Whether `window` is placed by the client and not managed by the shell.
+///
+/// Besides override-redirect windows, some toolkits open tooltips and menus as regular
+/// windows, which would otherwise be tiled or get decorations.
+pub fn is_unmanaged(window: &X11Surface) -> bool {
+    window.is_override_redirect()
+        || matches!(
+            window.window_type(),
+            Some(
+                WmWindowType::Tooltip
+                    | WmWindowType::Notification
+                    | WmWindowType::PopupMenu
+                    | WmWindowType::DropdownMenu
+            )
+        )
 }
 
 impl State {
@@ -43,7 +107,22 @@ impl State {
         if self.common.xwayland_state.is_some() {
             return;
         }
+        let disabled = matches!(
+            std::env::var("COSMIC_DISABLE_XWAYLAND").map(|val| val.to_lowercase()),
+            Ok(val) if val == "1" || val == "y" || val == "yes" || val == "true"
+        );
+        if disabled || !self.common.config.static_conf.xwayland {
+            info!("Xwayland is disabled, X11 clients won't be able to connect.");
+            return;
+        }
+
+        if self.start_xwayland(render_node, None) {
+            self.watch_theme_mode();
+        }
+    }
 
+    /// Starts Xwayland on `display`, or the next free display if `None`
+    fn start_xwayland(&mut self, render_node: Option<DrmNode>, display: Option<u32>) -> bool {
         let (xwayland, source) = XWayland::new(&self.common.display_handle);
         let token =
             match self
@@ -84,25 +163,28 @@ impl State {
                             );
                         }
 
+                        let dark = cosmic_config::Config::new(THEME_MODE_CONFIG, 1)
+                            .map_or(true, |config| is_dark(&config));
+                        let settings = settings(&data.common, dark);
                         let xwayland_state = data.common.xwayland_state.as_mut().unwrap();
                         xwayland_state.xwm = Some(wm);
+                        xwayland_state.xsettings =
+                            Some(XSettings::start(xwayland_state.display, settings));
+                        // copied before Xwayland was around
+                        data.offer_selections_to_xwayland();
                     }
-                    XWaylandEvent::Exited => {
-                        if let Some(mut xwayland_state) = data.common.xwayland_state.take() {
-                            xwayland_state.xwm = None;
-                        }
-                    }
+                    XWaylandEvent::Exited => data.xwayland_exited(),
                 }) {
                 Ok(token) => token,
                 Err(err) => {
                     error!(?err, "Failed to listen for Xwayland");
-                    return;
+                    return false;
                 }
             };
 
         match xwayland.start(
             self.common.event_loop_handle.clone(),
-            None,
+            display,
             //vec![("WAYLAND_DEBUG", "client")].into_iter(),
             std::iter::empty::<(OsString, OsString)>(),
             true,
@@ -117,13 +199,136 @@ impl State {
                     xwayland,
                     xwm: None,
                     display,
+                    xsettings: None,
+                    render_node,
+                    started: Instant::now(),
                 });
+                true
             }
             Err(err) => {
                 error!(?err, "Failed to start Xwayland.");
                 self.common.event_loop_handle.remove(token);
+                false
+            }
+        }
+    }
+
+    /// Cleans up after Xwayland and restarts it, so X11 clients can connect again
+    fn xwayland_exited(&mut self) {
+        let Some(mut xwayland_state) = self.common.xwayland_state.take() else {
+            return;
+        };
+        if let Some(xwm) = xwayland_state.xwm.take() {
+            // nobody is left to serve its selections
+            self.cleared_selection(xwm.id(), SelectionTarget::Clipboard);
+            self.cleared_selection(xwm.id(), SelectionTarget::Primary);
+
+            // its windows are gone without ever being unmapped
+            let windows = self
+                .common
+                .shell
+                .workspaces
+                .spaces()
+                .flat_map(|workspace| {
+                    workspace.windows().chain(
+                        workspace
+                            .minimized_windows
+                            .iter()
+                            .flat_map(|(mapped, _)| mapped.windows().map(|(window, _)| window)),
+                    )
+                })
+                .filter_map(|window| match window {
+                    CosmicSurface::X11(window) => Some(window),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            for window in windows {
+                self.unmapped_window(xwm.id(), window);
             }
         }
+        self.common.shell.override_redirect_windows.clear();
+        self.common
+            .shell
+            .pending_windows
+            .retain(|(window, _)| !matches!(window, CosmicSurface::X11(_)));
+
+        if xwayland_state.started.elapsed() < MIN_UPTIME {
+            error!("Xwayland exited right after starting, not restarting it.");
+            return;
+        }
+        warn!("Xwayland exited, restarting it.");
+        let XWaylandState {
+            display,
+            render_node,
+            ..
+        } = xwayland_state;
+        // keep the display clients were told about, if it is still free
+        if !self.start_xwayland(render_node, Some(display))
+            && !self.start_xwayland(render_node, None)
+        {
+            return;
+        }
+        if self.common.xwayland_state.as_ref().map(|s| s.display) != Some(display) {
+            crate::session::update_environment(self);
+            #[cfg(feature = "systemd")]
+            crate::systemd::import_environment(self);
+        }
+    }
+}
+
+impl State {
+    /// Keeps the dpi and cursor of X11 clients in sync with the outputs and the seats
+    pub fn refresh_xsettings(&mut self) {
+        let Some(dark) = self
+            .common
+            .xwayland_state
+            .as_ref()
+            .and_then(|xstate| xstate.xsettings.as_ref())
+            .map(|xsettings| xsettings.settings().dark)
+        else {
+            return;
+        };
+        let settings = settings(&self.common, dark);
+        if let Some(xsettings) = self
+            .common
+            .xwayland_state
+            .as_mut()
+            .and_then(|xstate| xstate.xsettings.as_mut())
+        {
+            xsettings.update(|current| *current = settings);
+        }
+    }
+
+    /// Keeps the dark mode hint of X11 clients in sync with the theme
+    fn watch_theme_mode(&mut self) {
+        let source = cosmic_config::Config::new(THEME_MODE_CONFIG, 1)
+            .and_then(|config| cosmic_config::calloop::ConfigWatchSource::new(&config));
+        let source = match source {
+            Ok(source) => source,
+            Err(err) => {
+                warn!(?err, "Failed to watch the theme mode.");
+                return;
+            }
+        };
+        if let Err(err) =
+            self.common
+                .event_loop_handle
+                .insert_source(source, |(config, keys), (), state| {
+                    if !keys.iter().any(|key| key == "is_dark") {
+                        return;
+                    }
+                    if let Some(xsettings) = state
+                        .common
+                        .xwayland_state
+                        .as_mut()
+                        .and_then(|xstate| xstate.xsettings.as_mut())
+                    {
+                        xsettings.update(|settings| settings.dark = is_dark(&config));
+                    }
+                })
+        {
+            warn!(?err, "Failed to watch the theme mode.");
+        }
     }
 }
 
@@ -145,6 +350,19 @@ impl XwmHandler for State {
             warn!(?window, ?err, "Failed to send Xwayland Mapped-Event",);
         }
 
+        if is_unmanaged(&window) {
+            if !self
+                .common
+                .shell
+                .override_redirect_windows
+                .iter()
+                .any(|or| or == &window)
+            {
+                Shell::map_override_redirect(self, window);
+            }
+            return;
+        }
+
         let surface = CosmicSurface::X11(window.clone());
         if self.common.shell.element_for_surface(&surface).is_some() {
             return;
@@ -155,6 +373,11 @@ impl XwmHandler for State {
     }
 
     fn map_window_notify(&mut self, _xwm: XwmId, surface: X11Surface) {
+        // without an associated wl_surface there is nothing to show yet,
+        // this is picked up again by `XWaylandShellHandler::surface_associated`
+        if surface.wl_surface().is_none() {
+            return;
+        }
         if let Some((window, seat)) = self
             .common
             .shell
@@ -189,7 +412,13 @@ impl XwmHandler for State {
 
     fn unmapped_window(&mut self, _xwm: XwmId, window: X11Surface) {
         let surface = CosmicSurface::X11(window.clone());
-        if window.is_override_redirect() {
+        if self
+            .common
+            .shell
+            .override_redirect_windows
+            .iter()
+            .any(|or| or == &window)
+        {
             self.common
                 .shell
                 .override_redirect_windows
@@ -310,18 +539,17 @@ impl XwmHandler for State {
         _geometry: Rectangle<i32, Logical>,
         above: Option<X11Window>,
     ) {
-        if window.is_override_redirect() {
+        if is_unmanaged(&window) {
             if let Some(id) = above {
                 let or_windows = &mut self.common.shell.override_redirect_windows;
+                // ordered bottom to top, the window is now right above its sibling
                 if let Some(own_pos) = or_windows.iter().position(|or| or == &window) {
-                    let compare_pos = or_windows
+                    let this = or_windows.remove(own_pos);
+                    let pos = or_windows
                         .iter()
                         .position(|or| or.window_id() == id)
-                        .unwrap_or(0);
-                    if compare_pos > own_pos {
-                        let this = or_windows.remove(own_pos);
-                        or_windows.insert(compare_pos, this);
-                    }
+                        .map_or(0, |pos| pos + 1);
+                    or_windows.insert(pos, this);
                 }
             }
 
@@ -416,10 +644,21 @@ impl XwmHandler for State {
         mime_type: String,
         fd: OwnedFd,
     ) {
-        let seat = self.common.last_active_seat();
+        let seat = self.common.last_active_seat().clone();
         match selection {
             SelectionTarget::Clipboard => {
-                if let Err(err) = request_data_device_client_selection(seat, mime_type, fd) {
+                if let Some(user_data) = current_data_device_selection_userdata(&seat) {
+                    // offered by the compositor itself, e.g. a screenshot
+                    SelectionHandler::send_selection(
+                        self,
+                        selection,
+                        mime_type,
+                        fd,
+                        seat.clone(),
+                        &user_data,
+                    );
+                } else if let Err(err) = request_data_device_client_selection(&seat, mime_type, fd)
+                {
                     error!(
                         ?err,
                         "Failed to request current wayland clipboard for Xwayland.",
@@ -427,7 +666,16 @@ impl XwmHandler for State {
                 }
             }
             SelectionTarget::Primary => {
-                if let Err(err) = request_primary_client_selection(seat, mime_type, fd) {
+                if let Some(user_data) = current_primary_selection_userdata(&seat) {
+                    SelectionHandler::send_selection(
+                        self,
+                        selection,
+                        mime_type,
+                        fd,
+                        seat.clone(),
+                        &user_data,
+                    );
+                } else if let Err(err) = request_primary_client_selection(&seat, mime_type, fd) {
                     error!(
                         ?err,
                         "Failed to request current wayland primary selection for Xwayland.",
@@ -445,14 +693,21 @@ impl XwmHandler for State {
         trace!(?selection, ?mime_types, "Got Selection from Xwayland",);
 
         if self.common.is_x_focused(xwm) {
-            let seat = self.common.last_active_seat();
+            let seat = self.common.last_active_seat().clone();
+            self.selection_taken_by_xwayland(&seat, selection);
             match selection {
-                SelectionTarget::Clipboard => {
-                    set_data_device_selection(&self.common.display_handle, &seat, mime_types, xwm)
-                }
-                SelectionTarget::Primary => {
-                    set_primary_selection(&self.common.display_handle, &seat, mime_types, xwm)
-                }
+                SelectionTarget::Clipboard => set_data_device_selection(
+                    &self.common.display_handle,
+                    &seat,
+                    mime_types,
+                    SelectionData::Xwayland(xwm),
+                ),
+                SelectionTarget::Primary => set_primary_selection(
+                    &self.common.display_handle,
+                    &seat,
+                    mime_types,
+                    SelectionData::Xwayland(xwm),
+                ),
             }
         }
     }
@@ -461,12 +716,16 @@ impl XwmHandler for State {
         for seat in self.common.seats() {
             match selection {
                 SelectionTarget::Clipboard => {
-                    if current_data_device_selection_userdata(seat).as_deref() == Some(&xwm) {
+                    if current_data_device_selection_userdata(seat).as_deref()
+                        == Some(&SelectionData::Xwayland(xwm))
+                    {
                         clear_data_device_selection(&self.common.display_handle, seat)
                     }
                 }
                 SelectionTarget::Primary => {
-                    if current_primary_selection_userdata(seat).as_deref() == Some(&xwm) {
+                    if current_primary_selection_userdata(seat).as_deref()
+                        == Some(&SelectionData::Xwayland(xwm))
+                    {
                         clear_primary_selection(&self.common.display_handle, seat)
                     }
                 }
@@ -476,6 +735,30 @@ impl XwmHandler for State {
 }
 
 impl Common {
+    /// Menu of an X11 client, that should receive all pointer input, while it is shown.
+    ///
+    /// X11 clients grab the pointer while showing menus and dismiss them on clicks outside,
+    /// which they only see as long as the pointer stays on their surfaces. Only done while
+    /// Xwayland has keyboard focus, so focusing something else always releases the pointer.
+    pub fn x11_menu_grab(&self) -> Option<X11Surface> {
+        let xwm = self.xwayland_state.as_ref()?.xwm.as_ref()?.id();
+        if !self.is_x_focused(xwm) {
+            return None;
+        }
+        self.shell
+            .override_redirect_windows
+            .iter()
+            .rev()
+            .find(|or| {
+                or.wl_surface().is_some()
+                    && matches!(
+                        or.window_type(),
+                        Some(WmWindowType::PopupMenu | WmWindowType::DropdownMenu)
+                    )
+            })
+            .cloned()
+    }
+
     fn is_x_focused(&self, xwm: XwmId) -> bool {
         if let Some(keyboard) = self.last_active_seat().get_keyboard() {
             if let Some(KeyboardFocusTarget::Element(mapped)) = keyboard.current_focus() {